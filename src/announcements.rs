@@ -0,0 +1,75 @@
+use poise::serenity_prelude::{self as serenity, ChannelId, CreateMessage};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{error, info, warn};
+
+use std::sync::Arc;
+
+use crate::{play_history::PlayHistory, playback_events::{FinishReason, PlaybackEvent, StallAction}};
+
+#[derive(Clone, Debug)]
+pub(crate) struct AnnounceConfig {
+    // skips and dropped items get posted here, without it they only end up in the log
+    channel: Option<ChannelId>,
+}
+
+impl AnnounceConfig {
+    pub fn from_env() -> Self {
+        let channel = std::env::var("ANNOUNCE_CHANNEL_ID").ok()
+            .filter(|v| !v.is_empty())
+            .and_then(|v| match v.parse::<u64>() {
+                Ok(id) if id != 0 => Some(ChannelId::new(id)),
+                _ => {
+                    warn!("ignoring invalid ANNOUNCE_CHANNEL_ID {}", v);
+                    None
+                }
+            });
+        AnnounceConfig { channel }
+    }
+}
+
+// audit trail for things that interrupt playback: records skips in the history and tells the channel why
+pub(crate) async fn run_announcements(http: Arc<serenity::Http>, config: AnnounceConfig, history: PlayHistory, mut receiver: broadcast::Receiver<PlaybackEvent>) {
+    loop {
+        let message = match receiver.recv().await {
+            Ok(PlaybackEvent::ItemFinished { item, reason: FinishReason::Skipped, detail, .. }) => {
+                let skips = match history.record_skip(&item, detail.clone()).await {
+                    Ok(s) => s,
+                    Err(e) => {
+                        error!("unable to record skip of {}: {}", item.name(), e);
+                        0
+                    }
+                };
+                info!(item_id = %item.id(), skips, "skipped {}: {}", item.name(), detail.as_deref().unwrap_or("no reason given"));
+                let mut message = format!("\u{23ED} skipped **{}**", item.name());
+                if let Some(reason) = detail {
+                    message.push_str(&format!(": {}", reason));
+                }
+                if skips > 1 {
+                    message.push_str(&format!(" (skipped {} times so far)", skips));
+                }
+                message
+            }
+            Ok(PlaybackEvent::ItemFinished { item, reason: FinishReason::Failed, detail, .. }) => {
+                format!("\u{26A0} dropped **{}**: {}", item.name(), detail.unwrap_or("playback failed".to_string()))
+            }
+            Ok(PlaybackEvent::Stalled { item, stalled_for, action }) => {
+                let action = match action {
+                    StallAction::Restarted => "restarted it",
+                    StallAction::Skipped => "skipped it",
+                };
+                format!("\u{26A0} **{}** froze for {}s, {}", item.name(), stalled_for.as_secs(), action)
+            }
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("announcements fell behind, skipped {} events", skipped);
+                continue
+            }
+            Err(RecvError::Closed) => break,
+        };
+        if let Some(channel) = config.channel {
+            if let Err(e) = channel.send_message(&http, CreateMessage::new().content(message)).await {
+                warn!("unable to post announcement to {}: {}", channel, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{Error, anyhow};
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use tracing::{info, warn};
+
+use std::{collections::HashMap, io::Cursor, path::PathBuf, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use crate::embyclient::{EmbyClient, EmbyImage, EmbySearch};
+
+#[derive(Clone, Debug)]
+pub(crate) struct ArtworkConfig {
+    cache_dir: PathBuf,
+    max_width: u32,
+    // how long a cached image is trusted before we ask emby whether it changed
+    revalidate_after: Duration,
+}
+
+impl ArtworkConfig {
+    pub fn from_env() -> Self {
+        let max_width = std::env::var("ARTWORK_MAX_WIDTH").ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(320);
+        let revalidate_secs = std::env::var("ARTWORK_REVALIDATE_SECS").ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(3600);
+        ArtworkConfig {
+            cache_dir: std::env::var("ARTWORK_CACHE_DIR").map(PathBuf::from).unwrap_or(std::env::temp_dir().join("rustobot5000-artwork")),
+            max_width,
+            revalidate_after: Duration::from_secs(revalidate_secs),
+        }
+    }
+}
+
+// downloads emby artwork once, shrinks it to something embed sized and keeps it on disk next to its etag
+#[derive(Clone)]
+pub(crate) struct ArtworkCache {
+    config: ArtworkConfig,
+    validated: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl ArtworkCache {
+    pub fn new(config: ArtworkConfig) -> Self {
+        ArtworkCache {
+            config,
+            validated: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // returns a jpeg of the requested image type (Primary, Backdrop, Thumb, ...) for an item on the given server
+    pub async fn get(&self, emby_client: &EmbyClient, item_id: &str, image_type: &str) -> Result<Vec<u8>, Error> {
+        if item_id.is_empty() || !item_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(anyhow!("invalid emby item id {}", item_id))
+        }
+        let key = format!("{}_{}_{}_{}", emby_client.name(), item_id, image_type, self.config.max_width);
+        let image_path = self.config.cache_dir.join(format!("{}.jpg", key));
+        let etag_path = self.config.cache_dir.join(format!("{}.etag", key));
+
+        let cached = tokio::fs::read(&image_path).await.ok();
+        let recently_validated = self.validated.lock().unwrap().get(&key)
+            .map(|t| t.elapsed() < self.config.revalidate_after)
+            .unwrap_or(false);
+        if let (Some(data), true) = (&cached, recently_validated) {
+            return Ok(data.clone())
+        }
+
+        let etag = match cached {
+            Some(_) => tokio::fs::read_to_string(&etag_path).await.ok(),
+            None => None,
+        };
+        match emby_client.get_item_image(item_id, image_type, etag.as_deref()).await {
+            Ok(EmbyImage::NotModified) => {
+                self.validated.lock().unwrap().insert(key, Instant::now());
+                cached.ok_or_else(|| anyhow!("emby reported {} as unchanged but it is not cached", image_path.display()))
+            }
+            Ok(EmbyImage::Image { data, etag }) => {
+                let max_width = self.config.max_width;
+                let resized = tokio::task::spawn_blocking(move || resize_to_jpeg(&data, max_width)).await??;
+                tokio::fs::create_dir_all(&self.config.cache_dir).await?;
+                tokio::fs::write(&image_path, &resized).await?;
+                match etag {
+                    Some(etag) => tokio::fs::write(&etag_path, etag).await?,
+                    None => {
+                        let _ = tokio::fs::remove_file(&etag_path).await;
+                    }
+                }
+                info!("cached {} artwork for {} at {}", image_type, item_id, image_path.display());
+                self.validated.lock().unwrap().insert(key, Instant::now());
+                Ok(resized)
+            }
+            // a stale picture is better than none when emby is having a moment
+            Err(e) => match cached {
+                Some(data) => {
+                    warn!("unable to revalidate artwork for {}, using cached copy: {}", item_id, e);
+                    Ok(data)
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
+
+fn resize_to_jpeg(data: &[u8], max_width: u32) -> Result<Vec<u8>, Error> {
+    let mut image = image::load_from_memory(data)?;
+    if image.width() > max_width {
+        image = image.resize(max_width, u32::MAX, FilterType::Lanczos3);
+    }
+    // jpeg has no alpha channel
+    let mut output = Cursor::new(vec![]);
+    DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut output, ImageFormat::Jpeg)?;
+    Ok(output.into_inner())
+}
@@ -0,0 +1,88 @@
+use anyhow::Error;
+use poise::serenity_prelude::{self as serenity, CreateCommand, GuildId};
+use tokio::sync::Mutex as TokioMutex;
+use tracing::info;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::state_store::StateStore;
+
+const REGISTRATIONS_FILE: &str = "registrations.json";
+const GLOBAL_SCOPE: &str = "global";
+
+// commands that are registered globally instead of per guild so they work before any guild is set up
+pub(crate) const GLOBAL_COMMANDS: [&str; 1] = ["rusto_guilds"];
+
+// remembers a hash of the command set last sent to discord per guild so restarts don't re-register everything
+#[derive(Clone)]
+pub(crate) struct CommandRegistry {
+    store: StateStore,
+    hashes: Arc<TokioMutex<BTreeMap<String, u32>>>,
+}
+
+impl CommandRegistry {
+    pub async fn load(store: StateStore) -> Result<Self, Error> {
+        let hashes = store.load::<BTreeMap<String, u32>>(REGISTRATIONS_FILE).await?;
+        Ok(CommandRegistry {
+            store,
+            hashes: Arc::new(TokioMutex::new(hashes)),
+        })
+    }
+
+    // returns true when the commands actually had to be sent to discord
+    pub async fn register_guild<U, E>(&self, http: impl AsRef<serenity::Http>, commands: &[poise::Command<U, E>], guild_id: GuildId, force: bool) -> Result<bool, Error> {
+        let guild_commands = application_commands(commands.iter().filter(|c| !GLOBAL_COMMANDS.contains(&c.name.as_str())));
+        let scope = guild_id.to_string();
+        let hash = hash_commands(&guild_commands)?;
+        if !force && self.is_current(&scope, hash).await {
+            info!("commands for guild {} are up to date, skipping registration", guild_id);
+            return Ok(false)
+        }
+        info!("registering {} commands in guild {}", guild_commands.len(), guild_id);
+        guild_id.set_commands(http, guild_commands).await?;
+        self.remember(scope, Some(hash)).await?;
+        Ok(true)
+    }
+
+    pub async fn register_global<U, E>(&self, http: impl AsRef<serenity::Http>, commands: &[poise::Command<U, E>], force: bool) -> Result<bool, Error> {
+        let global_commands = application_commands(commands.iter().filter(|c| GLOBAL_COMMANDS.contains(&c.name.as_str())));
+        let hash = hash_commands(&global_commands)?;
+        if !force && self.is_current(GLOBAL_SCOPE, hash).await {
+            info!("global commands are up to date, skipping registration");
+            return Ok(false)
+        }
+        info!("registering {} global commands", global_commands.len());
+        serenity::Command::set_global_commands(http, global_commands).await?;
+        self.remember(GLOBAL_SCOPE.to_string(), Some(hash)).await?;
+        Ok(true)
+    }
+
+    pub async fn unregister_guild(&self, http: impl AsRef<serenity::Http>, guild_id: GuildId) -> Result<(), Error> {
+        guild_id.set_commands(http, vec![]).await?;
+        self.remember(guild_id.to_string(), None).await
+    }
+
+    async fn is_current(&self, scope: &str, hash: u32) -> bool {
+        self.hashes.lock().await.get(scope) == Some(&hash)
+    }
+
+    async fn remember(&self, scope: String, hash: Option<u32>) -> Result<(), Error> {
+        let mut hashes = self.hashes.lock().await;
+        match hash {
+            Some(h) => hashes.insert(scope, h),
+            None => hashes.remove(&scope),
+        };
+        self.store.save(REGISTRATIONS_FILE, &*hashes).await
+    }
+}
+
+fn application_commands<'a, U: 'a, E: 'a>(commands: impl Iterator<Item = &'a poise::Command<U, E>>) -> Vec<CreateCommand> {
+    commands
+        .flat_map(|c| c.create_as_slash_command().into_iter().chain(c.create_as_context_menu_command()))
+        .collect()
+}
+
+// the serialized form is exactly what gets sent to discord so any change to names, options or permissions changes the hash
+fn hash_commands(commands: &[CreateCommand]) -> Result<u32, Error> {
+    Ok(crc32fast::hash(&serde_json::to_vec(commands)?))
+}
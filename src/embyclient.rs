@@ -1,17 +1,24 @@
 
-use reqwest::{self, Response};
-use serde::{Deserialize, Deserializer};
+use reqwest::{self, header::{HeaderMap, HeaderValue}, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde::de::{self, Visitor};
 
 use strum::{Display, EnumIter, EnumString};
 use url::Url;
 use anyhow::{Error, anyhow};
-use tracing::{info, error};
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::futures::{stream, Stream, TryStreamExt};
+use tracing::{info, error, warn};
+use std::collections::HashMap;
 use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::{Arc};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex as TokioMutex;
+use uuid::Uuid;
+
+use crate::state_store::StateStore;
 
 
 
@@ -31,21 +38,122 @@ pub(crate) struct EmbyItemData {
     pub(crate) season_num: Option<String>,
     #[serde(default, rename = "UserData")]
     pub(crate) user_data: Option<EmbyItemUserData>,
+    #[serde(default, rename = "Chapters")]
+    pub(crate) chapters: Vec<EmbyChapter>,
+    #[serde(default, rename = "SeriesName")]
+    pub(crate) series_name: Option<String>,
+    #[serde(default, rename = "DateCreated")]
+    date_created: Option<String>,
+    // only filled in when Overview was asked for
+    #[serde(default, rename = "Overview")]
+    pub(crate) overview: Option<String>,
+    #[serde(default, rename = "RunTimeTicks")]
+    run_time_ticks: Option<u64>,
+    #[serde(default, rename = "CommunityRating")]
+    pub(crate) community_rating: Option<f32>,
+    #[serde(default, rename = "PremiereDate")]
+    premiere_date: Option<String>,
+    // e.g. PG-13 or TV-MA, episodes often leave it to their series
+    #[serde(default, rename = "OfficialRating")]
+    pub(crate) official_rating: Option<String>,
+    #[serde(default, rename = "SeriesId")]
+    pub(crate) series_id: Option<String>,
+}
+
+impl EmbyItemData {
+    // when the item was added to the library, only filled in when DateCreated was asked for
+    pub fn created(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(self.date_created.as_deref()?).ok().map(|d| d.with_timezone(&Utc))
+    }
+
+    pub fn runtime(&self) -> Option<Duration> {
+        // emby ticks are 100ns
+        self.run_time_ticks.filter(|t| *t > 0).map(|t| Duration::from_nanos(t.saturating_mul(100)))
+    }
+
+    pub fn premiered(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(self.premiere_date.as_deref()?).ok().map(|d| d.with_timezone(&Utc))
+    }
 }
 
-#[derive(Debug, EnumString, Display, Default, EnumIter)]
+#[derive(Clone, Copy, Debug, EnumString, Display, Default, EnumIter)]
 pub(crate) enum SearchItemType {
     #[default]
     #[strum(ascii_case_insensitive)]
     Series,
     #[strum(ascii_case_insensitive)]
     Movie,
+    // collections, e.g. all movies of a trilogy
+    #[strum(ascii_case_insensitive)]
+    BoxSet,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub(crate) struct EmbyItemUserData {
     #[serde(rename = "Played")]
-    pub(crate) played: bool
+    pub(crate) played: bool,
+    #[serde(default, rename = "PlaybackPositionTicks")]
+    playback_position_ticks: u64,
+    #[serde(default, rename = "IsFavorite")]
+    pub(crate) is_favorite: bool,
+}
+
+impl EmbyItemUserData {
+    // where the user stopped watching, None when they never started or finished it
+    pub fn position(&self) -> Option<Duration> {
+        match self.playback_position_ticks {
+            0 => None,
+            ticks => Some(Duration::from_nanos(ticks.saturating_mul(100))),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct EmbyChapter {
+    #[serde(rename = "StartPositionTicks")]
+    start_ticks: u64,
+    #[serde(rename = "Name")]
+    pub(crate) name: Option<String>,
+    // newer servers mark detected intros and credits (IntroStart, CreditsStart, ...)
+    #[serde(rename = "MarkerType")]
+    pub(crate) marker_type: Option<String>,
+}
+
+impl EmbyChapter {
+    pub fn start(&self) -> Duration {
+        // emby ticks are 100ns
+        Duration::from_nanos(self.start_ticks.saturating_mul(100))
+    }
+}
+
+// result of a conditional image request. NotModified means the etag we sent is still current
+pub(crate) enum EmbyImage {
+    NotModified,
+    Image { data: Vec<u8>, etag: Option<String> },
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbyPlaybackInfo {
+    #[serde(default, rename = "MediaSources")]
+    media_sources: Vec<EmbyMediaSource>,
+    #[serde(rename = "PlaySessionId")]
+    play_session_id: Option<String>,
+    #[serde(rename = "ErrorCode")]
+    error_code: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbyAuthenticationResult {
+    #[serde(rename = "AccessToken")]
+    access_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbyMediaSource {
+    #[serde(rename = "Id")]
+    id: String,
+    #[serde(rename = "TranscodingUrl")]
+    transcoding_url: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -57,7 +165,44 @@ struct EmbySearchResult {
 #[derive(Deserialize, Debug)]
 struct EmbyItemsResult {
     #[serde(default, rename = "Items")]
-    items: Vec<EmbyItemData>
+    items: Vec<EmbyItemData>,
+    #[serde(rename = "TotalRecordCount")]
+    total_record_count: Option<usize>,
+}
+
+// a library as set up on the server, e.g. Movies or TV Shows
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct EmbyLibrary {
+    #[serde(rename = "Name")]
+    pub(crate) name: String,
+    #[serde(rename = "ItemId", deserialize_with = "deserialize_string_or_int")]
+    pub(crate) id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbyScheduledTask {
+    #[serde(rename = "Key")]
+    key: Option<String>,
+    #[serde(rename = "State")]
+    state: Option<String>,
+    #[serde(rename = "CurrentProgressPercentage")]
+    progress: Option<f64>,
+}
+
+// one slice of a library listing plus how many items there are in total
+#[derive(Clone, Debug)]
+pub(crate) struct ItemPage {
+    pub(crate) items: Vec<EmbyItemData>,
+    pub(crate) start: usize,
+    pub(crate) total: usize,
+}
+
+impl ItemPage {
+    // where the next page starts, none once everything was listed
+    pub fn next_start(&self) -> Option<usize> {
+        let end = self.start + self.items.len();
+        (!self.items.is_empty() && end < self.total).then_some(end)
+    }
 }
 
 impl EmbyItemsResult {
@@ -73,67 +218,631 @@ impl EmbyItemsResult {
 }
 
 pub(crate) trait EmbySearch {
-    async fn search_items(&self, item_name: &str, item_type: Vec<SearchItemType>) -> Result<Vec<EmbyItemData>, Error>;
+    async fn search_items(&self, item_name: &str, item_type: Vec<SearchItemType>, genre: Option<&str>) -> Result<Vec<EmbyItemData>, Error>;
     async fn search_series(&self, series_name: &str) -> Result<Vec<EmbyItemData>, Error>;
     async fn search_movies(&self, movie_name: &str) -> Result<Vec<EmbyItemData>, Error>;
     async fn get_seasons_for_series(&self, series_id: &str) -> Result<Vec<EmbyItemData>, Error>;
     async fn get_episodes_for_season(&self, season_id: &str, user: &Option<EmbyItemData>) -> Result<Vec<EmbyItemData>, Error>;
-    async fn get_item_info(&self, episode_id: &str) -> Result<EmbyItemData, Error>;
+    async fn get_item_info(&self, episode_id: &str, user: &Option<EmbyItemData>) -> Result<EmbyItemData, Error>;
+    async fn get_official_rating(&self, item_id: &str) -> Result<Option<String>, Error>;
+    async fn get_next_up(&self, series_id: &str, user: &EmbyItemData) -> Result<Option<EmbyItemData>, Error>;
+    async fn get_resume_items(&self, user: &EmbyItemData) -> Result<Vec<EmbyItemData>, Error>;
+    async fn get_collection_items(&self, collection_id: &str) -> Result<Vec<EmbyItemData>, Error>;
+    async fn get_playlists(&self, user: &EmbyItemData) -> Result<Vec<EmbyItemData>, Error>;
+    async fn get_playlist_items(&self, playlist_id: &str, user: &EmbyItemData) -> Result<Vec<EmbyItemData>, Error>;
+    async fn get_genres(&self) -> Result<Vec<EmbyItemData>, Error>;
+    async fn get_random_in_genre(&self, genre: &str, item_type: SearchItemType, limit: usize) -> Result<Vec<EmbyItemData>, Error>;
+    async fn get_latest(&self, user: &EmbyItemData, limit: usize) -> Result<Vec<EmbyItemData>, Error>;
     async fn get_all_series(&self) -> Result<Vec<EmbyItemData>, Error>;
     async fn get_all_movies(&self) -> Result<Vec<EmbyItemData>, Error>;
+    async fn get_items_page(&self, item_type: SearchItemType, start: usize, limit: usize) -> Result<ItemPage, Error>;
     async fn get_users(&self) -> Result<Vec<EmbyItemData>, Error>;
     async fn get_user_by_id(&self, user_id: String) -> Result<EmbyItemData, Error>;
     async fn user_stop_fn(&self, user_id: String, media_id: String) -> Arc<TokioMutex<Pin<Box<dyn Future<Output = bool> + Send>>>>;
+    async fn set_played(&self, user_id: &str, item_id: &str, played: bool) -> Result<(), Error>;
+    async fn set_favorite(&self, user_id: &str, item_id: &str, favorite: bool) -> Result<(), Error>;
+    async fn get_item_image(&self, item_id: &str, image_type: &str, etag: Option<&str>) -> Result<EmbyImage, Error>;
+    async fn get_hls_stream(&self, item_id: &str, user_id: Option<&str>) -> Result<Url, Error>;
+    async fn get_direct_stream(&self, item_id: &str) -> Result<Url, Error>;
+    async fn get_libraries(&self) -> Result<Vec<EmbyLibrary>, Error>;
+    async fn refresh_library(&self, library_id: Option<&str>) -> Result<(), Error>;
+    async fn get_library_scan(&self) -> Result<Option<f64>, Error>;
+}
+
+const DEVICE_FILE: &str = "emby_device.json";
+const DEFAULT_EMBY_URL: &str = "http://localhost:8096";
+const DEFAULT_PATH_MAP: &str = "/mnt/storage=/mnt/zfspool/storage";
+// big libraries time out when they are listed in one go
+const LIBRARY_PAGE_SIZE: usize = 200;
+
+// jellyfin forked from emby and mostly kept its api, it differs in the path prefix, auth header and a few endpoints
+#[derive(Clone, Copy, Debug, Default, PartialEq, EnumString, Display)]
+pub(crate) enum ServerType {
+    #[default]
+    #[strum(ascii_case_insensitive)]
+    Emby,
+    #[strum(ascii_case_insensitive)]
+    Jellyfin,
+}
+
+impl ServerType {
+    fn api_prefix(self) -> &'static str {
+        match self {
+            ServerType::Emby => "/emby/",
+            ServerType::Jellyfin => "/",
+        }
+    }
+
+    fn users_path(self) -> &'static str {
+        match self {
+            ServerType::Emby => "Users/Query",
+            ServerType::Jellyfin => "Users",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SavedDevice {
+    device_id: Option<String>,
+}
+
+// how the bot shows up in emby's device and session lists
+#[derive(Clone, Debug)]
+pub(crate) struct EmbyIdentity {
+    client: String,
+    device_name: String,
+    device_id: String,
+    version: String,
+}
+
+impl EmbyIdentity {
+    // the device id is generated once and kept in the state dir unless EMBY_DEVICE_ID pins it
+    pub async fn load(store: &StateStore) -> Result<Self, Error> {
+        let env_or = |name: &str, default: &str| std::env::var(name).ok().filter(|v| !v.is_empty()).unwrap_or(default.to_string());
+        let device_id = match std::env::var("EMBY_DEVICE_ID").ok().filter(|v| !v.is_empty()) {
+            Some(id) => id,
+            None => {
+                let mut saved = store.load::<SavedDevice>(DEVICE_FILE).await?;
+                match saved.device_id.clone() {
+                    Some(id) => id,
+                    None => {
+                        let id = Uuid::new_v4().to_string();
+                        info!("generated emby device id {}", id);
+                        saved.device_id = Some(id.clone());
+                        store.save(DEVICE_FILE, &saved).await?;
+                        id
+                    }
+                }
+            }
+        };
+        Ok(EmbyIdentity {
+            client: env_or("EMBY_CLIENT_NAME", "rustobot5000"),
+            device_name: env_or("EMBY_DEVICE_NAME", "rustobot5000"),
+            device_id,
+            version: env_or("EMBY_CLIENT_VERSION", env!("CARGO_PKG_VERSION")),
+        })
+    }
+
+    // there is no token yet while logging in
+    fn headers(&self, api_key: Option<&str>, server: ServerType) -> Result<HeaderMap, Error> {
+        if server == ServerType::Jellyfin {
+            return self.jellyfin_headers(api_key)
+        }
+        // older servers only look at the authorization header, newer ones at the separate headers
+        let authorization = format!(
+            "Emby Client=\"{}\", Device=\"{}\", DeviceId=\"{}\", Version=\"{}\"",
+            self.client, self.device_name, self.device_id, self.version,
+        );
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            ("X-Emby-Token", api_key.unwrap_or_default()),
+            ("X-Emby-Authorization", authorization.as_str()),
+            ("X-Emby-Client", self.client.as_str()),
+            ("X-Emby-Device-Name", self.device_name.as_str()),
+            ("X-Emby-Device-Id", self.device_id.as_str()),
+            ("X-Emby-Client-Version", self.version.as_str()),
+        ] {
+            if value.is_empty() {
+                continue
+            }
+            let mut value = HeaderValue::from_str(value).map_err(|e| anyhow!("invalid value for {}: {}", name, e))?;
+            value.set_sensitive(name == "X-Emby-Token");
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    // jellyfin takes everything including the token in the standard authorization header
+    fn jellyfin_headers(&self, api_key: Option<&str>) -> Result<HeaderMap, Error> {
+        let mut authorization = format!(
+            "MediaBrowser Client=\"{}\", Device=\"{}\", DeviceId=\"{}\", Version=\"{}\"",
+            self.client, self.device_name, self.device_id, self.version,
+        );
+        if let Some(api_key) = api_key {
+            authorization.push_str(&format!(", Token=\"{}\"", api_key));
+        }
+        let mut value = HeaderValue::from_str(&authorization).map_err(|e| anyhow!("invalid value for Authorization: {}", e))?;
+        value.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+        Ok(headers)
+    }
+}
+
+// how queued items get from the media server to gstreamer
+#[derive(Clone, Copy, Debug, Default, PartialEq, EnumString, Display)]
+pub(crate) enum PlayMode {
+    // the file itself through the path mapping, the server transcodes what isn't mounted here
+    #[default]
+    #[strum(ascii_case_insensitive)]
+    Path,
+    // the original file over http, for running the bot away from the media storage
+    #[strum(ascii_case_insensitive)]
+    Stream,
+    // always an hls stream the server transcodes, keeps heavy 4k sources off the bot host
+    #[strum(ascii_case_insensitive)]
+    Transcode,
+}
+
+// what a server side transcode is asked for
+#[derive(Clone, Copy, Debug, Default, PartialEq, EnumString, Display)]
+pub(crate) enum TranscodeQuality {
+    #[strum(ascii_case_insensitive)]
+    Low,
+    #[strum(ascii_case_insensitive)]
+    Medium,
+    #[default]
+    #[strum(ascii_case_insensitive)]
+    High,
+}
+
+impl TranscodeQuality {
+    fn max_bitrate(self) -> u64 {
+        match self {
+            TranscodeQuality::Low => 4_000_000,
+            TranscodeQuality::Medium => 10_000_000,
+            TranscodeQuality::High => 20_000_000,
+        }
+    }
+
+    fn max_width(self) -> u32 {
+        match self {
+            TranscodeQuality::Low => 1280,
+            TranscodeQuality::Medium | TranscodeQuality::High => 1920,
+        }
+    }
+}
+
+// rewrites paths as the media server sees them into where the same files are mounted here
+#[derive(Clone, Debug, Default)]
+struct PathMap {
+    // (server prefix, local prefix), the first match wins
+    mappings: Vec<(String, String)>,
+}
+
+impl PathMap {
+    // from=to pairs separated by ;
+    fn parse(value: &str) -> Result<Self, Error> {
+        let mut mappings = vec![];
+        for entry in value.split(';').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            let (from, to) = entry.split_once('=').ok_or_else(|| anyhow!("path mapping {} has to look like /server/path=/local/path", entry))?;
+            let (from, to) = (from.trim().trim_end_matches(['/', '\\']), to.trim().trim_end_matches('/'));
+            if from.is_empty() || to.is_empty() {
+                return Err(anyhow!("path mapping {} has an empty side", entry))
+            }
+            if !std::path::Path::new(to).exists() {
+                warn!("{} from path mapping {} does not exist here, items under {} will be streamed from the server", to, entry, from);
+            }
+            mappings.push((from.to_string(), to.to_string()));
+        }
+        Ok(PathMap { mappings })
+    }
+
+    fn map(&self, path: &str) -> String {
+        for (from, to) in &self.mappings {
+            // only whole path components count, /mnt/storage shouldn't match /mnt/storage2
+            if let Some(rest) = path.strip_prefix(from.as_str()).filter(|r| r.is_empty() || r.starts_with(['/', '\\'])) {
+                return format!("{}{}", to, rest.replace('\\', "/"))
+            }
+        }
+        path.to_string()
+    }
+}
+
+// a fixed api key, or a user login that gets exchanged for an access token
+#[derive(Clone)]
+enum Credentials {
+    ApiKey(String),
+    Login { username: String, password: String },
+}
+
+// where one media server lives and how to talk to it
+#[derive(Clone)]
+pub(crate) struct ServerConfig {
+    name: String,
+    url: String,
+    credentials: Credentials,
+    server: ServerType,
+    path_map: PathMap,
+    play_mode: PlayMode,
+    transcode_quality: TranscodeQuality,
+}
+
+impl ServerConfig {
+    // EMBY_SERVERS=home,friends reads EMBY_HOME_API_URL, EMBY_HOME_API_TOKEN and EMBY_HOME_SERVER_TYPE per server.
+    // without it the plain EMBY_API_URL, EMBY_API_TOKEN and EMBY_SERVER_TYPE make up a single server.
+    // _USERNAME and _PASSWORD log in as a user instead of using an api key.
+    // _PATH_MAP (e.g. EMBY_PATH_MAP=/mnt/storage=/mnt/zfspool/storage;/data=/media) says where the server's files are mounted here,
+    // _PLAY_MODE=stream plays everything over http instead and _PLAY_MODE=transcode has the server transcode everything
+    // to _TRANSCODE_QUALITY (low 720p/4mbit, medium 1080p/10mbit, high 1080p/20mbit)
+    pub fn from_env() -> Result<Vec<Self>, Error> {
+        let names = std::env::var("EMBY_SERVERS").unwrap_or_default();
+        let names: Vec<&str> = names.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()).collect();
+        if names.is_empty() {
+            return Ok(vec![ServerConfig::load("emby", "EMBY")?])
+        }
+        let mut servers: Vec<ServerConfig> = vec![];
+        for name in names {
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(anyhow!("media server name {} can only have letters, digits and dashes", name))
+            }
+            if servers.iter().any(|s| s.name.eq_ignore_ascii_case(name)) {
+                return Err(anyhow!("media server {} is listed twice in EMBY_SERVERS", name))
+            }
+            servers.push(ServerConfig::load(name, &format!("EMBY_{}", name.to_uppercase().replace('-', "_")))?);
+        }
+        Ok(servers)
+    }
+
+    fn load(name: &str, prefix: &str) -> Result<Self, Error> {
+        let var = |suffix: &str| std::env::var(format!("{}_{}", prefix, suffix)).ok().filter(|v| !v.is_empty());
+        let credentials = match (var("API_TOKEN"), var("USERNAME")) {
+            (Some(api_key), _) => Credentials::ApiKey(api_key),
+            // users without a password are allowed
+            (None, Some(username)) => Credentials::Login { username, password: var("PASSWORD").unwrap_or_default() },
+            (None, None) => return Err(anyhow!("missing {}_API_TOKEN or {}_USERNAME", prefix, prefix)),
+        };
+        Ok(ServerConfig {
+            name: name.to_string(),
+            url: var("API_URL").unwrap_or(DEFAULT_EMBY_URL.to_string()),
+            credentials,
+            server: var("SERVER_TYPE").unwrap_or("emby".to_string()).parse().map_err(|_| anyhow!("{}_SERVER_TYPE has to be emby or jellyfin", prefix))?,
+            // set but empty turns the default mapping off
+            path_map: PathMap::parse(&std::env::var(format!("{}_PATH_MAP", prefix)).unwrap_or(DEFAULT_PATH_MAP.to_string()))
+                .map_err(|e| anyhow!("invalid {}_PATH_MAP: {}", prefix, e))?,
+            play_mode: var("PLAY_MODE").unwrap_or("path".to_string()).parse().map_err(|_| anyhow!("{}_PLAY_MODE has to be path, stream or transcode", prefix))?,
+            transcode_quality: var("TRANSCODE_QUALITY").unwrap_or("high".to_string()).parse().map_err(|_| anyhow!("{}_TRANSCODE_QUALITY has to be low, medium or high", prefix))?,
+        })
+    }
+}
+
+// item lists that rarely change within a session, keyed by request path or listing. EMBY_CACHE_SECS=0 turns it off
+#[derive(Clone)]
+struct ResponseCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<String, CachedItems>>>,
+    // nothing is cached before this, set while a library refresh may still be adding items
+    paused_until: Arc<Mutex<Option<Instant>>>,
+}
+
+// when the list was fetched and what came back
+type CachedItems = (Instant, Vec<EmbyItemData>);
+
+impl ResponseCache {
+    fn from_env() -> Self {
+        let secs = std::env::var("EMBY_CACHE_SECS").ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(300);
+        ResponseCache {
+            ttl: Duration::from_secs(secs),
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            paused_until: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn get(&self, url: &str) -> Option<Vec<EmbyItemData>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(url) {
+            Some((fetched, items)) if fetched.elapsed() < self.ttl => Some(items.clone()),
+            Some(_) => {
+                entries.remove(url);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, url: &str, items: &[EmbyItemData]) {
+        if self.ttl.is_zero() || self.paused_until.lock().unwrap().is_some_and(|until| Instant::now() < until) {
+            return
+        }
+        self.entries.lock().unwrap().insert(url.to_string(), (Instant::now(), items.to_vec()));
+    }
+
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    fn pause(&self, duration: Duration) {
+        *self.paused_until.lock().unwrap() = Some(Instant::now() + duration);
+        self.clear();
+    }
 }
 
 #[derive(Clone)]
 pub(crate) struct EmbyClient {
+    // shown next to results and queue entries when there is more than one server
+    name: String,
     emby_url: Url,
-    api_key: String,
+    http: reqwest::Client,
+    identity: EmbyIdentity,
+    server: ServerType,
+    credentials: Credentials,
+    path_map: PathMap,
+    play_mode: PlayMode,
+    transcode_quality: TranscodeQuality,
+    // access token of the login, fetched on first use and again when the server stops accepting it
+    token: Arc<TokioMutex<Option<String>>>,
+    cache: ResponseCache,
 }
 
 impl EmbyClient {
-    pub(crate) async fn new(emby_url: String, api_key: String) -> Result<Self, Error> {
+    pub(crate) async fn new(config: ServerConfig, identity: EmbyIdentity) -> Result<Self, Error> {
         Ok(EmbyClient {
-            emby_url: Url::parse(emby_url.as_str())?,
-            api_key
+            name: config.name,
+            emby_url: Url::parse(config.url.as_str())?,
+            http: reqwest::Client::new(),
+            identity,
+            server: config.server,
+            credentials: config.credentials,
+            path_map: config.path_map,
+            play_mode: config.play_mode,
+            transcode_quality: config.transcode_quality,
+            token: Arc::new(TokioMutex::new(None)),
+            cache: ResponseCache::from_env(),
         })
     }
 
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn play_mode(&self) -> PlayMode {
+        self.play_mode
+    }
+
+    // where an item's file is mounted here, None when it can't be reached and has to be streamed from the server
+    pub(crate) fn local_path(&self, path: &str) -> Option<String> {
+        let mapped = self.path_map.map(path);
+        match std::path::Path::new(&mapped).exists() {
+            true => Some(mapped),
+            false => None,
+        }
+    }
+
+    fn api_root(&self) -> Result<Url, Error> {
+        Ok(self.emby_url.join(self.server.api_prefix())?)
+    }
+
+    // the whole library of one type, a request per page as the stream gets polled
+    pub(crate) fn item_pages(&self, item_type: SearchItemType) -> impl Stream<Item = Result<Vec<EmbyItemData>, Error>> + '_ {
+        stream::try_unfold(Some(0), move |start| async move {
+            let start = match start {
+                Some(s) => s,
+                None => return Ok(None),
+            };
+            let page = self.get_items_page(item_type, start, LIBRARY_PAGE_SIZE).await?;
+            let next = page.next_start();
+            Ok(Some((page.items, next)))
+        })
+    }
+
+    async fn all_items(&self, item_type: SearchItemType) -> Result<Vec<EmbyItemData>, Error> {
+        let key = format!("all {}", item_type);
+        if let Some(items) = self.cache.get(&key) {
+            return Ok(items)
+        }
+        let items: Vec<EmbyItemData> = self.item_pages(item_type).try_concat().await?;
+        self.cache.insert(&key, &items);
+        Ok(items)
+    }
+
+    // drops every cached list, e.g. after something was marked as watched
+    pub(crate) fn invalidate_cache(&self) {
+        self.cache.clear();
+    }
+
+    // drops the cached lists and stops caching new ones for a while, for changes emby can't report the end of
+    pub(crate) fn pause_cache(&self, duration: Duration) {
+        self.cache.pause(duration);
+    }
+
+    // reports the bot's capabilities which makes emby list it as a device under the configured name
+    pub(crate) async fn register_device(&self) -> Result<(), Error> {
+        let capabilities = serde_json::json!({
+            "PlayableMediaTypes": ["Video", "Audio"],
+            "SupportedCommands": [],
+            "SupportsMediaControl": false,
+        });
+        let resp = self.do_emby_post_json("Sessions/Capabilities/Full", &capabilities).await?;
+        let resp_status = resp.status();
+        if !resp_status.is_success() {
+            let resp_body = resp.bytes().await?;
+            return Err(anyhow!("error registering device {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body)))
+        }
+        info!("registered with {} ({}) as {} ({}) device {}", self.name, self.server, self.identity.client, self.identity.version, self.identity.device_id);
+        Ok(())
+    }
+
+    // the api key, or the token of the login which logs in first when there is none yet
+    async fn access_token(&self) -> Result<String, Error> {
+        let (username, password) = match &self.credentials {
+            Credentials::ApiKey(api_key) => return Ok(api_key.clone()),
+            Credentials::Login { username, password } => (username, password),
+        };
+        // held during the login so concurrent requests wait for it instead of logging in as well
+        let mut token = self.token.lock().await;
+        if let Some(token) = token.as_ref() {
+            return Ok(token.clone())
+        }
+        let req_url = self.api_root()?.join("Users/AuthenticateByName")?;
+        let resp = self.http.post(req_url.clone())
+            .headers(self.identity.headers(None, self.server)?)
+            .json(&serde_json::json!({ "Username": username, "Pw": password }))
+            .send().await
+            .map_err(|e| anyhow!(format!("Error calling {}: {}", req_url, e)))?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if !resp_status.is_success() {
+            return Err(anyhow!("unable to log in to {} as {}: {}", self.name, username, resp_status.as_str()))
+        }
+        let auth = serde_json::from_slice::<EmbyAuthenticationResult>(&resp_body)
+            .map_err(|e| anyhow!(format!("error deserializing login response {}: {}", e, String::from_utf8_lossy(&resp_body))))?;
+        info!("logged in to {} as {}", self.name, username);
+        *token = Some(auth.access_token.clone());
+        Ok(auth.access_token)
+    }
+
+    // sends the request with the current token. when a login token gets rejected mid-session it logs in again and retries once
+    async fn send(&self, request: impl Fn() -> RequestBuilder) -> Result<Response, Error> {
+        let token = self.access_token().await?;
+        let resp = request().headers(self.identity.headers(Some(&token), self.server)?).send().await?;
+        if resp.status() != StatusCode::UNAUTHORIZED || matches!(self.credentials, Credentials::ApiKey(_)) {
+            return Ok(resp)
+        }
+        warn!("{} no longer accepts the access token, logging in again", self.name);
+        {
+            let mut current = self.token.lock().await;
+            // another request may have logged in already
+            if current.as_deref() == Some(token.as_str()) {
+                *current = None;
+            }
+        }
+        let token = self.access_token().await?;
+        Ok(request().headers(self.identity.headers(Some(&token), self.server)?).send().await?)
+    }
+
     async fn do_emby_get(&self, url: &str) -> Result<Response, Error> {
-        let req_url = self.emby_url.join("/emby/")?.join(url)?;
+        let req_url = self.api_root()?.join(url)?;
         info!("doing request against {}", req_url.clone());
-        match reqwest::Client::new().get(req_url.clone()).header("X-Emby-Token", self.api_key.as_str()).send().await {
-            Ok(r) => {
-                Ok(r)
-            }
-            Err(e) => {
-                Err(anyhow!(format!("Error calling {}: {}", req_url.clone(), e)))
+        self.send(|| self.http.get(req_url.clone())).await.map_err(|e| anyhow!(format!("Error calling {}: {}", req_url.clone(), e)))
+    }
+
+    async fn do_emby_get_if_none_match(&self, url: &str, etag: Option<&str>) -> Result<Response, Error> {
+        let req_url = self.api_root()?.join(url)?;
+        info!("doing conditional request against {}", req_url.clone());
+        let request = || {
+            let req = self.http.get(req_url.clone());
+            match etag {
+                Some(etag) => req.header(reqwest::header::IF_NONE_MATCH, etag),
+                None => req,
             }
-        }
+        };
+        self.send(request).await.map_err(|e| anyhow!(format!("Error calling {}: {}", req_url.clone(), e)))
     }
 
     async fn do_emby_post(&self, url: &str) -> Result<Response, Error> {
-        let req_url = self.emby_url.join("/emby/")?.join(url)?;
+        let req_url = self.api_root()?.join(url)?;
         info!("doing post request against {}", req_url.clone());
-        match reqwest::Client::new().post(req_url.clone()).header("X-Emby-Token", self.api_key.as_str()).send().await {
-            Ok(r) => {
-                Ok(r)
-            }
-            Err(e) => {
-                Err(anyhow!(format!("Error calling {}: {}", req_url.clone(), e)))
-            }
+        self.send(|| self.http.post(req_url.clone())).await.map_err(|e| anyhow!(format!("Error calling {}: {}", req_url.clone(), e)))
+    }
+
+    // user item endpoints like PlayedItems and FavoriteItems take a post to set and a delete to clear
+    async fn set_user_flag(&self, url: &str, set: bool) -> Result<(), Error> {
+        let resp = match set {
+            true => self.do_emby_post(url).await?,
+            false => self.do_emby_delete(url).await?,
+        };
+        let resp_status = resp.status();
+        if resp_status.is_success() {
+            Ok(())
+        } else {
+            let resp_body = resp.bytes().await?;
+            Err(anyhow!(format!("error updating {}: {}: {}", url, resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    async fn do_emby_delete(&self, url: &str) -> Result<Response, Error> {
+        let req_url = self.api_root()?.join(url)?;
+        info!("doing delete request against {}", req_url.clone());
+        self.send(|| self.http.delete(req_url.clone())).await.map_err(|e| anyhow!(format!("Error calling {}: {}", req_url.clone(), e)))
+    }
+
+    async fn do_emby_post_json<T: Serialize + ?Sized>(&self, url: &str, body: &T) -> Result<Response, Error> {
+        let req_url = self.api_root()?.join(url)?;
+        info!("doing post request against {}", req_url.clone());
+        self.send(|| self.http.post(req_url.clone()).json(body)).await.map_err(|e| anyhow!(format!("Error calling {}: {}", req_url.clone(), e)))
+    }
+}
+
+// every configured media server, the first one is used where no server is named
+#[derive(Clone)]
+pub(crate) struct EmbyServers {
+    servers: Arc<Vec<Arc<EmbyClient>>>,
+}
+
+impl EmbyServers {
+    pub(crate) async fn connect(configs: Vec<ServerConfig>, identity: EmbyIdentity) -> Result<Self, Error> {
+        let mut servers = vec![];
+        for config in configs {
+            servers.push(Arc::new(EmbyClient::new(config, identity.clone()).await?));
+        }
+        if servers.is_empty() {
+            return Err(anyhow!("no media servers configured"))
+        }
+        Ok(EmbyServers { servers: Arc::new(servers) })
+    }
+
+    pub(crate) fn default_server(&self) -> &EmbyClient {
+        &self.servers[0]
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Result<&EmbyClient, Error> {
+        self.servers.get(index).map(|s| s.as_ref()).ok_or_else(|| anyhow!("no media server {}", index))
+    }
+
+    // saved items from before servers had names belong to the default one
+    pub(crate) fn by_name(&self, name: Option<&str>) -> Option<&EmbyClient> {
+        match name {
+            Some(name) => self.servers.iter().find(|s| s.name.eq_ignore_ascii_case(name)).map(|s| s.as_ref()),
+            None => Some(self.default_server()),
+        }
+    }
+
+    // position of the server in the list, the same rules as by_name
+    pub(crate) fn index_of(&self, name: Option<&str>) -> Option<usize> {
+        match name {
+            Some(name) => self.servers.iter().position(|s| s.name.eq_ignore_ascii_case(name)),
+            None => Some(0),
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &EmbyClient> {
+        self.servers.iter().map(|s| s.as_ref())
+    }
+
+    // adds the server name to a label, with a single server there is nothing to tell apart
+    pub(crate) fn label(&self, server: &EmbyClient, text: &str) -> String {
+        match self.servers.len() {
+            1 => text.to_string(),
+            _ => format!("{} [{}]", text, server.name),
         }
     }
 }
 
 impl EmbySearch for EmbyClient {
-    async fn search_items(&self, item_name: &str, item_types: Vec<SearchItemType>) -> Result<Vec<EmbyItemData>, Error> {
-        if item_name.len() == 0 {
+    // a genre on its own lists everything in it, a name without one searches the whole library
+    async fn search_items(&self, item_name: &str, item_types: Vec<SearchItemType>, genre: Option<&str>) -> Result<Vec<EmbyItemData>, Error> {
+        if item_name.len() == 0 && genre.is_none() {
             return Err(anyhow!("no item types for search passed!"))
         }
         let item_types = item_types.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(",");
-        let url = format!("Items?Recursive=true&IncludeItemTypes={}&SortBy=SortName&SearchTerm={}", item_types, item_name);
+        let mut url = format!("Items?Recursive=true&IncludeItemTypes={}&SortBy=SortName", item_types);
+        if !item_name.is_empty() {
+            url.push_str(&format!("&SearchTerm={}", item_name));
+        }
+        if let Some(genre) = genre {
+            // genres like "Action & Adventure" would break the query otherwise
+            url.push_str(&format!("&Genres={}", url::form_urlencoded::byte_serialize(genre.as_bytes()).collect::<String>()));
+        }
         let resp = self.do_emby_get(&url).await?;
         let resp_status = resp.status();
         let resp_body = resp.bytes().await?;
@@ -152,21 +861,25 @@ impl EmbySearch for EmbyClient {
     }
 
     async fn search_series(&self, series_name: &str) -> Result<Vec<EmbyItemData>, Error> {
-        self.search_items(series_name, vec![SearchItemType::Series]).await
+        self.search_items(series_name, vec![SearchItemType::Series], None).await
     }
 
     async fn search_movies(&self, series_name: &str) -> Result<Vec<EmbyItemData>, Error> {
-        self.search_items(series_name, vec![SearchItemType::Movie]).await
+        self.search_items(series_name, vec![SearchItemType::Movie], None).await
     }
 
     async fn get_seasons_for_series(&self, series_id: &str) -> Result<Vec<EmbyItemData>, Error> {
         let url = format!("Shows/{}/Seasons", series_id);
+        if let Some(items) = self.cache.get(&url) {
+            return Ok(items)
+        }
         let resp = self.do_emby_get(&url).await?;
         let resp_status = resp.status();
         let resp_body = resp.bytes().await?;
         if resp_status.clone().is_success() {
             match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
                 Ok(series) => {
+                    self.cache.insert(&url, &series.items);
                     Ok(series.items)
                 }
                 Err(e) => {
@@ -183,14 +896,19 @@ impl EmbySearch for EmbyClient {
             Some(u) => format!("Users/{}/", u.id),
             None => "".to_string(),
         };
-        let url = format!("{}Items?ParentId={}&Fields=Path&IsMissing=false&SortBy=PremiereDate", url_prefix, season_id);
+        let url = format!("{}Items?ParentId={}&Fields=Path,Chapters&IsMissing=false&SortBy=PremiereDate", url_prefix, season_id);
+        if let Some(items) = self.cache.get(&url) {
+            return Ok(items)
+        }
         let resp = self.do_emby_get(&url).await?;
         let resp_status = resp.status();
         let resp_body = resp.bytes().await?;
         if resp_status.clone().is_success() {
             match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
                 Ok(series) => {
-                    Ok(series.get_sorted_items())
+                    let items = series.get_sorted_items();
+                    self.cache.insert(&url, &items);
+                    Ok(items)
                 }
                 Err(e) => {
                     Err(anyhow!(format!("error deserializing data {}: {}", e, String::from_utf8_lossy(&resp_body))).into())
@@ -201,8 +919,13 @@ impl EmbySearch for EmbyClient {
         }
     }
 
-    async fn get_item_info(&self, item_id: &str) -> Result<EmbyItemData, Error> {
-        let url = format!("Items?Ids={}&Fields=Path&IsMissing=false&SortBy=PremiereDate", item_id);
+    async fn get_item_info(&self, item_id: &str, user: &Option<EmbyItemData>) -> Result<EmbyItemData, Error> {
+        // user data like the saved position only comes back for a user
+        let url_prefix = match user {
+            Some(u) => format!("Users/{}/", u.id),
+            None => "".to_string(),
+        };
+        let url = format!("{}Items?Ids={}&Fields=Path,Chapters,Overview&IsMissing=false&SortBy=PremiereDate", url_prefix, item_id);
         let resp = self.do_emby_get(&url).await?;
         let resp_status = resp.status();
         let resp_body = resp.bytes().await?;
@@ -229,53 +952,214 @@ impl EmbySearch for EmbyClient {
         }
     }
 
-    async fn get_all_series(&self) -> Result<Vec<EmbyItemData>, Error> {
-        let url = "Items?Recursive=true&IncludeItemTypes=Series&SortBy=SortName";
+    // looked up for every episode of a series that is queued, so unlike the item info it is cached
+    async fn get_official_rating(&self, item_id: &str) -> Result<Option<String>, Error> {
+        let url = format!("Items?Ids={}", item_id);
+        if let Some(items) = self.cache.get(&url) {
+            return Ok(items.first().and_then(|i| i.official_rating.clone()))
+        }
         let resp = self.do_emby_get(&url).await?;
         let resp_status = resp.status();
         let resp_body = resp.bytes().await?;
-        if resp_status.clone().is_success() {
+        if resp_status.is_success() {
             match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
-                Ok(series) => {
-                    Ok(series.items)
+                Ok(found) => {
+                    self.cache.insert(&url, &found.items);
+                    Ok(found.items.first().and_then(|i| i.official_rating.clone()))
                 }
-                Err(e) => {
-                    Err(anyhow!(format!("error deserializing data {}: {}", e, String::from_utf8_lossy(&resp_body))).into())
+                Err(e) => Err(anyhow!(format!("error deserializing item {}: {}", e, String::from_utf8_lossy(&resp_body)))),
+            }
+        } else {
+            Err(anyhow!(format!("error getting item {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    // the episode the user should watch next, not cached since it moves along with every watched episode
+    async fn get_next_up(&self, series_id: &str, user: &EmbyItemData) -> Result<Option<EmbyItemData>, Error> {
+        let url = format!("Shows/NextUp?SeriesId={}&UserId={}&Fields=Path,Chapters&Limit=1", series_id, user.id);
+        let resp = self.do_emby_get(&url).await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
+                Ok(next_up) => Ok(next_up.items.into_iter().next()),
+                Err(e) => Err(anyhow!(format!("error deserializing next up {}: {}", e, String::from_utf8_lossy(&resp_body)))),
+            }
+        } else {
+            Err(anyhow!(format!("error getting next up {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    // every genre used by movies and shows in the library
+    async fn get_genres(&self) -> Result<Vec<EmbyItemData>, Error> {
+        let url = "Genres?Recursive=true&IncludeItemTypes=Movie,Series&SortBy=SortName";
+        if let Some(items) = self.cache.get(url) {
+            return Ok(items)
+        }
+        let resp = self.do_emby_get(url).await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
+                Ok(genres) => {
+                    self.cache.insert(url, &genres.items);
+                    Ok(genres.items)
                 }
+                Err(e) => Err(anyhow!(format!("error deserializing genres {}: {}", e, String::from_utf8_lossy(&resp_body)))),
             }
         } else {
-            Err(anyhow!(format!("error getting data {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))).into())
+            Err(anyhow!(format!("error getting genres {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    // a random handful of the genre, a fresh pick on every call so it isn't cached
+    async fn get_random_in_genre(&self, genre: &str, item_type: SearchItemType, limit: usize) -> Result<Vec<EmbyItemData>, Error> {
+        let genre = url::form_urlencoded::byte_serialize(genre.as_bytes()).collect::<String>();
+        let url = format!("Items?Recursive=true&IncludeItemTypes={}&Genres={}&SortBy=Random&Limit={}", item_type, genre, limit);
+        let resp = self.do_emby_get(&url).await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
+                Ok(items) => Ok(items.items),
+                Err(e) => Err(anyhow!(format!("error deserializing genre items {}: {}", e, String::from_utf8_lossy(&resp_body)))),
+            }
+        } else {
+            Err(anyhow!(format!("error getting genre items {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    // everything in a collection in release order
+    async fn get_collection_items(&self, collection_id: &str) -> Result<Vec<EmbyItemData>, Error> {
+        let url = format!("Items?ParentId={}&Fields=Path,Chapters&SortBy=ProductionYear,PremiereDate,SortName", collection_id);
+        if let Some(items) = self.cache.get(&url) {
+            return Ok(items)
+        }
+        let resp = self.do_emby_get(&url).await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
+                Ok(collection) => {
+                    self.cache.insert(&url, &collection.items);
+                    Ok(collection.items)
+                }
+                Err(e) => Err(anyhow!(format!("error deserializing collection {}: {}", e, String::from_utf8_lossy(&resp_body)))),
+            }
+        } else {
+            Err(anyhow!(format!("error getting collection {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
         }
     }
 
+    // playlists change whenever someone edits them so neither of these is cached
+    async fn get_playlists(&self, user: &EmbyItemData) -> Result<Vec<EmbyItemData>, Error> {
+        let url = format!("Users/{}/Items?IncludeItemTypes=Playlist&Recursive=true&SortBy=SortName", user.id);
+        let resp = self.do_emby_get(&url).await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
+                Ok(playlists) => Ok(playlists.items),
+                Err(e) => Err(anyhow!(format!("error deserializing playlists {}: {}", e, String::from_utf8_lossy(&resp_body)))),
+            }
+        } else {
+            Err(anyhow!(format!("error getting playlists {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    // the entries in playlist order
+    async fn get_playlist_items(&self, playlist_id: &str, user: &EmbyItemData) -> Result<Vec<EmbyItemData>, Error> {
+        let url = format!("Playlists/{}/Items?UserId={}&Fields=Path,Chapters", playlist_id, user.id);
+        let resp = self.do_emby_get(&url).await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
+                Ok(playlist) => Ok(playlist.items),
+                Err(e) => Err(anyhow!(format!("error deserializing playlist {}: {}", e, String::from_utf8_lossy(&resp_body)))),
+            }
+        } else {
+            Err(anyhow!(format!("error getting playlist {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    // newest movies and episodes in the libraries the user can see. episodes are listed one by one instead of per series
+    async fn get_latest(&self, user: &EmbyItemData, limit: usize) -> Result<Vec<EmbyItemData>, Error> {
+        let url = format!("Users/{}/Items/Latest?Limit={}&GroupItems=false&IncludeItemTypes=Movie,Episode&Fields=Path,Chapters,DateCreated", user.id, limit);
+        let resp = self.do_emby_get(&url).await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            // a plain list, not wrapped in Items
+            serde_json::from_slice::<Vec<EmbyItemData>>(&resp_body)
+                .map_err(|e| anyhow!(format!("error deserializing latest items {}: {}", e, String::from_utf8_lossy(&resp_body))))
+        } else {
+            Err(anyhow!(format!("error getting latest items {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    // what the user started but didn't finish, most recently watched first
+    async fn get_resume_items(&self, user: &EmbyItemData) -> Result<Vec<EmbyItemData>, Error> {
+        let url = format!("Users/{}/Items/Resume?Fields=Path,Chapters&MediaTypes=Video&Limit=25", user.id);
+        let resp = self.do_emby_get(&url).await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
+                Ok(resume) => Ok(resume.items),
+                Err(e) => Err(anyhow!(format!("error deserializing resume items {}: {}", e, String::from_utf8_lossy(&resp_body)))),
+            }
+        } else {
+            Err(anyhow!(format!("error getting resume items {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    async fn get_all_series(&self) -> Result<Vec<EmbyItemData>, Error> {
+        self.all_items(SearchItemType::Series).await
+    }
+
     async fn get_all_movies(&self) -> Result<Vec<EmbyItemData>, Error> {
-        let url = "Items?Recursive=true&IncludeItemTypes=Movie&SortBy=SortName";
+        self.all_items(SearchItemType::Movie).await
+    }
+
+    async fn get_items_page(&self, item_type: SearchItemType, start: usize, limit: usize) -> Result<ItemPage, Error> {
+        let url = format!("Items?Recursive=true&IncludeItemTypes={}&SortBy=SortName&StartIndex={}&Limit={}", item_type, start, limit);
         let resp = self.do_emby_get(&url).await?;
         let resp_status = resp.status();
         let resp_body = resp.bytes().await?;
-        if resp_status.clone().is_success() {
+        if resp_status.is_success() {
             match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
-                Ok(series) => {
-                    Ok(series.items)
+                Ok(page) => {
+                    let total = page.total_record_count.unwrap_or(start + page.items.len());
+                    Ok(ItemPage { items: page.items, start, total })
                 }
                 Err(e) => {
-                    Err(anyhow!(format!("error deserializing data {}: {}", e, String::from_utf8_lossy(&resp_body))).into())
+                    Err(anyhow!(format!("error deserializing data {}: {}", e, String::from_utf8_lossy(&resp_body))))
                 }
             }
         } else {
-            Err(anyhow!(format!("error getting data {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))).into())
+            Err(anyhow!(format!("error getting data {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
         }
     }
 
     async fn get_users(&self) -> Result<Vec<EmbyItemData>, Error> {
-        let url = "Users/Query";
-        let resp = self.do_emby_get(&url).await?;
+        let url = self.server.users_path();
+        if let Some(items) = self.cache.get(url) {
+            return Ok(items)
+        }
+        let resp = self.do_emby_get(url).await?;
         let resp_status = resp.status();
         let resp_body = resp.bytes().await?;
         if resp_status.clone().is_success() {
-            match serde_json::from_slice::<EmbyItemsResult>(&resp_body) {
-                Ok(series) => {
-                    Ok(series.items)
+            let users = match self.server {
+                ServerType::Emby => serde_json::from_slice::<EmbyItemsResult>(&resp_body).map(|r| r.items),
+                // jellyfin hands back a plain list
+                ServerType::Jellyfin => serde_json::from_slice::<Vec<EmbyItemData>>(&resp_body),
+            };
+            match users {
+                Ok(users) => {
+                    self.cache.insert(url, &users);
+                    Ok(users)
                 }
                 Err(e) => {
                     Err(anyhow!(format!("error deserializing user data {}: {}", e, String::from_utf8_lossy(&resp_body))).into())
@@ -310,9 +1194,159 @@ impl EmbySearch for EmbyClient {
         Arc::new(TokioMutex::new(Box::pin(async move {
                 let url = format!("Users/{user_id}/PlayedItems/{media_id}");
                 let _resp = emby_client.do_emby_post(&url).await;
+                // the cached episode lists still have it as unwatched
+                emby_client.invalidate_cache();
                 true
         }) as Pin<Box<dyn Future<Output = bool> + Send>>))
     }
+
+    // a series or collection takes everything in it along
+    async fn set_played(&self, user_id: &str, item_id: &str, played: bool) -> Result<(), Error> {
+        let result = self.set_user_flag(&format!("Users/{}/PlayedItems/{}", user_id, item_id), played).await;
+        // the cached episode lists still have the old state
+        self.invalidate_cache();
+        result
+    }
+
+    async fn set_favorite(&self, user_id: &str, item_id: &str, favorite: bool) -> Result<(), Error> {
+        self.set_user_flag(&format!("Users/{}/FavoriteItems/{}", user_id, item_id), favorite).await
+    }
+
+    async fn get_item_image(&self, item_id: &str, image_type: &str, etag: Option<&str>) -> Result<EmbyImage, Error> {
+        let url = format!("Items/{}/Images/{}", item_id, image_type);
+        let resp = self.do_emby_get_if_none_match(&url, etag).await?;
+        let resp_status = resp.status();
+        if resp_status == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(EmbyImage::NotModified)
+        }
+        let etag = resp.headers().get(reqwest::header::ETAG).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            Ok(EmbyImage::Image { data: resp_body.to_vec(), etag })
+        } else {
+            Err(anyhow!(format!("error getting {} image for {}: {}", image_type, item_id, resp_status.as_str())))
+        }
+    }
+
+    // the untouched file over http, static=true keeps emby from transcoding it
+    async fn get_direct_stream(&self, item_id: &str) -> Result<Url, Error> {
+        let mut stream_url = self.api_root()?.join(&format!("Videos/{}/stream", item_id))?;
+        // gstreamer can't send our headers so the token goes into the url
+        let token = self.access_token().await?;
+        stream_url.query_pairs_mut()
+            .append_pair("static", "true")
+            .append_pair("DeviceId", &self.identity.device_id)
+            .append_pair("api_key", &token);
+        Ok(stream_url)
+    }
+
+    // asks emby to transcode the item to h264/aac hls at the configured quality. used when the file itself isn't reachable from here
+    async fn get_hls_stream(&self, item_id: &str, user_id: Option<&str>) -> Result<Url, Error> {
+        let max_bitrate = self.transcode_quality.max_bitrate();
+        let mut url = format!("Items/{}/PlaybackInfo?MaxStreamingBitrate={}", item_id, max_bitrate);
+        if let Some(user_id) = user_id {
+            url.push_str(&format!("&UserId={}", user_id));
+        }
+        // no direct play profiles so emby always hands back a transcoding url
+        let profile = serde_json::json!({
+            "DeviceProfile": {
+                "MaxStreamingBitrate": max_bitrate,
+                "DirectPlayProfiles": [],
+                "TranscodingProfiles": [{
+                    "Container": "ts",
+                    "Type": "Video",
+                    "VideoCodec": "h264",
+                    "AudioCodec": "aac",
+                    "Protocol": "hls",
+                    "Context": "Streaming",
+                    "MaxAudioChannels": "2",
+                }],
+            },
+        });
+        let resp = self.do_emby_post_json(&url, &profile).await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if !resp_status.is_success() {
+            return Err(anyhow!(format!("error getting playback info {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+        let info = serde_json::from_slice::<EmbyPlaybackInfo>(&resp_body)
+            .map_err(|e| anyhow!(format!("error deserializing playback info {}: {}", e, String::from_utf8_lossy(&resp_body))))?;
+        if let Some(code) = info.error_code {
+            return Err(anyhow!("emby refused to play {}: {}", item_id, code))
+        }
+        let source = info.media_sources.first().ok_or_else(|| anyhow!("emby returned no media sources for {}", item_id))?;
+        let mut stream_url = match &source.transcoding_url {
+            // transcoding urls are relative to the server root without the api prefix
+            Some(path) => self.api_root()?.join(path.trim_start_matches('/'))?,
+            None => {
+                let mut stream_url = self.api_root()?.join(&format!("Videos/{}/master.m3u8", item_id))?;
+                stream_url.query_pairs_mut()
+                    .append_pair("MediaSourceId", &source.id)
+                    .append_pair("DeviceId", &self.identity.device_id)
+                    .append_pair("VideoCodec", "h264")
+                    .append_pair("AudioCodec", "aac")
+                    .append_pair("MaxStreamingBitrate", &max_bitrate.to_string());
+                if let Some(session) = &info.play_session_id {
+                    stream_url.query_pairs_mut().append_pair("PlaySessionId", session);
+                }
+                stream_url
+            }
+        };
+        // the bitrate alone doesn't stop a 4k source from being transcoded at 4k
+        if !stream_url.query_pairs().any(|(k, _)| k.eq_ignore_ascii_case("MaxWidth")) {
+            stream_url.query_pairs_mut().append_pair("MaxWidth", &self.transcode_quality.max_width().to_string());
+        }
+        // gstreamer fetches the hls segments itself and can't send our headers
+        if !stream_url.query_pairs().any(|(k, _)| k.eq_ignore_ascii_case("api_key")) {
+            let token = self.access_token().await?;
+            stream_url.query_pairs_mut().append_pair("api_key", &token);
+        }
+        Ok(stream_url)
+    }
+
+    async fn get_libraries(&self) -> Result<Vec<EmbyLibrary>, Error> {
+        let resp = self.do_emby_get("Library/VirtualFolders").await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if resp_status.is_success() {
+            serde_json::from_slice::<Vec<EmbyLibrary>>(&resp_body)
+                .map_err(|e| anyhow!(format!("error deserializing libraries {}: {}", e, String::from_utf8_lossy(&resp_body))))
+        } else {
+            Err(anyhow!(format!("error getting libraries {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    // without a library the whole server gets scanned, that runs as the library scan task
+    async fn refresh_library(&self, library_id: Option<&str>) -> Result<(), Error> {
+        let url = match library_id {
+            Some(id) => format!("Items/{}/Refresh?Recursive=true", id),
+            None => "Library/Refresh".to_string(),
+        };
+        let resp = self.do_emby_post(&url).await?;
+        let resp_status = resp.status();
+        if resp_status.is_success() {
+            Ok(())
+        } else {
+            let resp_body = resp.bytes().await?;
+            Err(anyhow!(format!("error starting library refresh {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+    }
+
+    // how far the library scan task got, None when it isn't running
+    async fn get_library_scan(&self) -> Result<Option<f64>, Error> {
+        let resp = self.do_emby_get("ScheduledTasks?IsHidden=false").await?;
+        let resp_status = resp.status();
+        let resp_body = resp.bytes().await?;
+        if !resp_status.is_success() {
+            return Err(anyhow!(format!("error getting scheduled tasks {}: {}", resp_status.as_str(), String::from_utf8_lossy(&resp_body))))
+        }
+        let tasks = serde_json::from_slice::<Vec<EmbyScheduledTask>>(&resp_body)
+            .map_err(|e| anyhow!(format!("error deserializing scheduled tasks {}: {}", e, String::from_utf8_lossy(&resp_body))))?;
+        Ok(tasks.into_iter()
+            .find(|t| t.key.as_deref() == Some("RefreshLibrary"))
+            .filter(|t| t.state.as_deref() == Some("Running"))
+            .map(|t| t.progress.unwrap_or_default()))
+    }
 }
 
 fn deserialize_string_or_int<'de, D>(deserializer: D) -> Result<String, D::Error>
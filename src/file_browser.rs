@@ -0,0 +1,101 @@
+use anyhow::{Error, anyhow};
+use tracing::warn;
+
+use std::path::{Path, PathBuf};
+
+const MEDIA_EXTENSIONS: [&str; 16] = [
+    "mkv", "mp4", "m4v", "avi", "mov", "webm", "ts", "m2ts", "flv", "mpg", "mpeg", "wmv", "mp3", "flac", "ogg", "m4a",
+];
+
+#[derive(Clone, Debug)]
+pub(crate) struct BrowseEntry {
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    pub(crate) is_dir: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct FileBrowser {
+    roots: Vec<PathBuf>,
+}
+
+impl FileBrowser {
+    pub fn from_env() -> Self {
+        let roots = std::env::var("BROWSE_ROOTS").unwrap_or_default()
+            .split(',')
+            .map(|r| r.trim())
+            .filter(|r| !r.is_empty())
+            .filter_map(|r| match Path::new(r).canonicalize() {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    warn!("ignoring browse root {}: {}", r, e);
+                    None
+                }
+            })
+            .collect();
+        FileBrowser { roots }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        !self.roots.is_empty()
+    }
+
+    pub fn roots(&self) -> Vec<BrowseEntry> {
+        self.roots.iter()
+            .map(|r| BrowseEntry {
+                name: r.to_string_lossy().to_string(),
+                path: r.clone(),
+                is_dir: true,
+            })
+            .collect()
+    }
+
+    // only paths inside one of the configured roots may be listed or queued
+    pub fn is_allowed(&self, path: &Path) -> bool {
+        match path.canonicalize() {
+            Ok(p) => self.roots.iter().any(|r| p.starts_with(r)),
+            Err(_) => false,
+        }
+    }
+
+    // the directory one level up, or None when `dir` is already a root and the caller should show the root list
+    pub fn parent_of(&self, dir: &Path) -> Option<PathBuf> {
+        if self.roots.iter().any(|r| r == dir) {
+            return None
+        }
+        dir.parent().map(|p| p.to_path_buf()).filter(|p| self.is_allowed(p))
+    }
+
+    pub async fn list_dir(&self, dir: &Path) -> Result<Vec<BrowseEntry>, Error> {
+        if !self.is_allowed(dir) {
+            return Err(anyhow!("{} is outside of the configured browse roots", dir.display()))
+        }
+        let mut entries = vec![];
+        let mut read_dir = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue
+            }
+            let is_dir = entry.file_type().await?.is_dir();
+            if !is_dir && !is_media_file(&entry.path()) {
+                continue
+            }
+            entries.push(BrowseEntry {
+                name,
+                path: entry.path(),
+                is_dir,
+            });
+        }
+        // folders first, then files, both alphabetical
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())));
+        Ok(entries)
+    }
+}
+
+pub(crate) fn is_media_file(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) => MEDIA_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()),
+        None => false,
+    }
+}
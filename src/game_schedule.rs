@@ -0,0 +1,227 @@
+use anyhow::{Error, anyhow};
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::{api::{ListParams, Patch, PatchParams}, Api, Client as KubeClient};
+use poise::serenity_prelude::{self as serenity, ChannelId, CreateMessage};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex as TokioMutex;
+use tracing::{error, info, warn};
+
+use std::{collections::BTreeMap, str::FromStr, sync::Arc, time::Duration};
+
+use crate::state_store::StateStore;
+
+const OVERRIDES_FILE: &str = "schedule_overrides.json";
+// "18:00-02:00" in the schedule timezone, games without it run all the time
+pub(crate) const ACTIVE_HOURS_ANNOTATION: &str = "rustobot5000.active-hours";
+// replica count to go back to after a scheduled scale down
+const SCALED_FROM_ANNOTATION: &str = "rustobot5000.scaled-down-from";
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+pub(crate) struct ScheduleConfig {
+    channel: Option<ChannelId>,
+    // games get started this long before their window opens so they are ready on time
+    warmup: chrono::Duration,
+    pub(crate) timezone: Tz,
+}
+
+impl ScheduleConfig {
+    pub fn from_env() -> Self {
+        let channel = std::env::var("GAME_SCHEDULE_CHANNEL_ID").ok()
+            .filter(|v| !v.is_empty())
+            .and_then(|v| match v.parse::<u64>() {
+                Ok(id) if id != 0 => Some(ChannelId::new(id)),
+                _ => {
+                    warn!("ignoring invalid GAME_SCHEDULE_CHANNEL_ID {}", v);
+                    None
+                }
+            });
+        let warmup_minutes = std::env::var("GAME_SCHEDULE_WARMUP_MINUTES").ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(10);
+        let timezone = match std::env::var("GAME_SCHEDULE_TZ") {
+            Ok(tz) => Tz::from_str(&tz).unwrap_or_else(|_| {
+                warn!("unknown GAME_SCHEDULE_TZ {}, using UTC", tz);
+                Tz::UTC
+            }),
+            Err(_) => Tz::UTC,
+        };
+        ScheduleConfig {
+            channel,
+            warmup: chrono::Duration::minutes(warmup_minutes.max(0)),
+            timezone,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ActiveHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl ActiveHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // window runs past midnight
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for ActiveHours {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once('-').ok_or(anyhow!("active hours must look like 18:00-02:00, got {}", s))?;
+        let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|e| anyhow!("invalid time {} in active hours: {}", t, e));
+        Ok(ActiveHours { start: parse(start)?, end: parse(end)? })
+    }
+}
+
+impl std::fmt::Display for ActiveHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct ScheduleOverride {
+    pub(crate) running: bool,
+    pub(crate) until: DateTime<Utc>,
+}
+
+// manual holds that win over the active hours until they run out
+#[derive(Clone)]
+pub(crate) struct ScheduleOverrides {
+    store: StateStore,
+    overrides: Arc<TokioMutex<BTreeMap<String, ScheduleOverride>>>,
+}
+
+impl ScheduleOverrides {
+    pub async fn load(store: StateStore) -> Result<Self, Error> {
+        let overrides = store.load::<BTreeMap<String, ScheduleOverride>>(OVERRIDES_FILE).await?;
+        Ok(ScheduleOverrides {
+            store,
+            overrides: Arc::new(TokioMutex::new(overrides)),
+        })
+    }
+
+    pub async fn get(&self, game: &str) -> Option<ScheduleOverride> {
+        self.overrides.lock().await.get(game)
+            .filter(|o| o.until > Utc::now())
+            .cloned()
+    }
+
+    pub async fn set(&self, game: &str, running: bool, duration: chrono::Duration) -> Result<ScheduleOverride, Error> {
+        let mut overrides = self.overrides.lock().await;
+        let hold = ScheduleOverride { running, until: Utc::now() + duration };
+        overrides.insert(game.to_string(), hold.clone());
+        self.store.save(OVERRIDES_FILE, &*overrides).await?;
+        Ok(hold)
+    }
+
+    // returns false when there was nothing to clear
+    pub async fn clear(&self, game: &str) -> Result<bool, Error> {
+        let mut overrides = self.overrides.lock().await;
+        let removed = overrides.remove(game).is_some();
+        if removed {
+            self.store.save(OVERRIDES_FILE, &*overrides).await?;
+        }
+        Ok(removed)
+    }
+}
+
+pub(crate) fn active_hours(deployment: &Deployment) -> Option<Result<ActiveHours, Error>> {
+    deployment.metadata.annotations.as_ref()
+        .and_then(|a| a.get(ACTIVE_HOURS_ANNOTATION))
+        .map(|v| v.parse::<ActiveHours>())
+}
+
+// whether the game should be up right now going by its override or active hours
+pub(crate) async fn should_run(config: &ScheduleConfig, overrides: &ScheduleOverrides, game: &str, hours: ActiveHours) -> bool {
+    if let Some(hold) = overrides.get(game).await {
+        return hold.running
+    }
+    let now = Utc::now().with_timezone(&config.timezone);
+    hours.contains(now.time()) || hours.contains((now + config.warmup).time())
+}
+
+// scales games with active hours to zero outside their window and back up shortly before it opens
+pub(crate) async fn run_schedule(http: Arc<serenity::Http>, config: ScheduleConfig, overrides: ScheduleOverrides) {
+    let client = match KubeClient::try_default().await {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("no kubernetes access, game schedules are disabled: {}", e);
+            return
+        }
+    };
+    let deployments: Api<Deployment> = Api::default_namespaced(client);
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let managed = match deployments.list(&ListParams::default().labels("rustobot5000.managed=true")).await {
+            Ok(list) => list.items,
+            Err(e) => {
+                error!("unable to list games for the schedule: {}", e);
+                continue
+            }
+        };
+        for deployment in managed {
+            let game = deployment.metadata.name.clone().unwrap_or_default();
+            let hours = match active_hours(&deployment) {
+                Some(Ok(hours)) => hours,
+                Some(Err(e)) => {
+                    warn!("ignoring schedule of {}: {}", game, e);
+                    continue
+                }
+                None => continue,
+            };
+            let replicas = deployment.spec.as_ref().and_then(|s| s.replicas).unwrap_or(1);
+            let running = should_run(&config, &overrides, &game, hours).await;
+            let message = if running && replicas == 0 {
+                let scale_to = deployment.metadata.annotations.as_ref()
+                    .and_then(|a| a.get(SCALED_FROM_ANNOTATION))
+                    .and_then(|v| v.parse::<i32>().ok())
+                    .unwrap_or(1)
+                    .max(1);
+                match scale(&deployments, &game, scale_to, None).await {
+                    Ok(_) => format!("Starting {game} for its active hours {hours}"),
+                    Err(e) => {
+                        error!("unable to start {}: {}", game, e);
+                        continue
+                    }
+                }
+            } else if !running && replicas > 0 {
+                match scale(&deployments, &game, 0, Some(replicas)).await {
+                    Ok(_) => format!("Stopping {game} outside its active hours {hours}"),
+                    Err(e) => {
+                        error!("unable to stop {}: {}", game, e);
+                        continue
+                    }
+                }
+            } else {
+                continue
+            };
+            info!("{}", message);
+            if let Some(channel) = config.channel {
+                if let Err(e) = channel.send_message(&http, CreateMessage::new().content(message)).await {
+                    warn!("unable to post schedule notice to {}: {}", channel, e);
+                }
+            }
+        }
+    }
+}
+
+async fn scale(deployments: &Api<Deployment>, game: &str, replicas: i32, scaled_from: Option<i32>) -> Result<(), Error> {
+    let mut patch = serde_json::json!({ "spec": { "replicas": replicas } });
+    if let Some(from) = scaled_from {
+        patch["metadata"] = serde_json::json!({ "annotations": { SCALED_FROM_ANNOTATION: from.to_string() } });
+    }
+    deployments.patch(game, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    Ok(())
+}
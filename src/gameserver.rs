@@ -1,10 +1,32 @@
-use crate::{BotError, Context, Error};
-use poise::{serenity_prelude::CreateAttachment, CreateReply};
-use kube::{ api::{ListParams, LogParams}, Api, Client as KubeClient};
-use k8s_openapi::api::{apps::v1::Deployment, core::v1::Pod};
+use crate::{game_schedule::{self, ScheduleConfig}, BotError, Context, Error};
+use poise::{serenity_prelude::{Attachment, CreateAttachment, CreateMessage, EditMessage}, CreateReply};
+use kube::{ api::{AttachParams, ListParams, LogParams, ObjectMeta, Patch, PatchParams, PostParams}, Api, Client as KubeClient, Resource};
+use k8s_openapi::{api::{apps::v1::Deployment, autoscaling::v2::{HorizontalPodAutoscaler, MetricTarget, MetricValueStatus}, batch::v1::{CronJob, Job}, core::v1::Pod}, apimachinery::pkg::apis::meta::v1::Status};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{info, error, warn};
 
-#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR", subcommands("list", "restart", "status", "logs"), subcommand_required)]
+use std::{future::Future, io::Read, path::Path, time::{Duration, Instant}};
+
+// discord rejects anything bigger on servers without boosts
+const MAX_DOWNLOAD_BYTES: usize = 10 * 1024 * 1024;
+// headers and padding tar adds around the files, anything past this can't fit into an attachment anyway
+const TAR_OVERHEAD_BYTES: u64 = 64 * 1024;
+// deployment annotations that opt a game into uploads, both comma separated
+const UPLOAD_DIRS_ANNOTATION: &str = "rustobot5000.upload-dirs";
+const UPLOAD_TYPES_ANNOTATION: &str = "rustobot5000.upload-types";
+const DEFAULT_UPLOAD_TYPES: &[&str] = &["zip", "jar", "json", "toml", "yaml", "yml", "cfg", "ini", "properties", "txt"];
+const MAX_UPLOAD_BYTES: u32 = 25 * 1024 * 1024;
+// suspended cronjobs with this label set to the game name are the tasks run_job can start
+const JOB_TEMPLATE_LABEL: &str = "rustobot5000.game";
+// the task name, falls back to the cronjob name
+const JOB_TASK_ANNOTATION: &str = "rustobot5000.task";
+// deployment annotations bounding what autoscale may set, games without them can't be changed from discord
+const SCALE_MIN_ANNOTATION: &str = "rustobot5000.scale-min";
+const SCALE_MAX_ANNOTATION: &str = "rustobot5000.scale-max";
+const JOB_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const JOB_WAIT_LIMIT: Duration = Duration::from_secs(60 * 60);
+
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR", subcommands("list", "restart", "status", "logs", "download", "upload", "run_job", "autoscale", "schedule", "hold", "release"), subcommand_required)]
 pub(crate) async fn rusto_gameadmin(_: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
@@ -102,12 +124,15 @@ async fn status(
                 warn!("ready_replicas not found for {game}");
                 0
             });
-            let pods = get_deployment_pods(kclient, game.clone()).await?;
+            let pods = get_deployment_pods(kclient.clone(), game.clone()).await?;
             ctx.say(format!("{ready_replicas}/{total_replicas} ready for game {game}")).await?;
             for pod in pods {
                 let pod_status = pod.status.expect("pod has no status somehow").phase.unwrap_or("unknown".to_string());
                 ctx.say(format!("Pod in status: {pod_status} ")).await?;
             }
+            if let Some(hpa) = get_autoscaler(kclient, &game).await? {
+                ctx.say(format_autoscaler(&hpa)).await?;
+            }
             Ok(())
         },
         Err(e) => {
@@ -152,6 +177,466 @@ async fn logs(
     }
 }
 
+/// download a file from a game, like a save file or crash dump
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn download(
+    ctx: Context<'_>,
+    #[description = "Game to download from"] game: String,
+    #[description = "Path of the file or folder in the game container"] path: String,
+) -> Result<(), Error> {
+    validate_game_name(ctx, game.clone()).await?;
+    ctx.defer().await?;
+    match ctx.data().get_kube_client().await {
+        Ok(kclient) => {
+            let pod_name = match get_deployment_pods(kclient.clone(), game.clone()).await?.into_iter().find_map(|p| p.metadata.name) {
+                Some(name) => name,
+                None => return Err(Box::new(BotError::new(&format!("{game} has no running pods")))),
+            };
+            let pod_client: Api<Pod> = Api::default_namespaced(kclient);
+            info!("copying {path} out of {pod_name}");
+            let archive = copy_from_pod(&pod_client, &pod_name, &path).await?;
+            let (file_name, contents) = unpack_download(&path, archive)?;
+            if contents.len() > MAX_DOWNLOAD_BYTES {
+                return Err(Box::new(BotError::new(&format!("{file_name} is {} bytes, too big to attach", contents.len()))))
+            }
+            ctx.send(CreateReply::default().attachment(CreateAttachment::bytes(contents, file_name))).await?;
+            Ok(())
+        }
+        Err(e) => {
+            error!("Error getting client {e}");
+            Err(e)
+        }
+    }
+}
+
+// same as kubectl cp: tar the path up inside the container and stream it back over exec
+async fn copy_from_pod(
+    pod_client: &Api<Pod>,
+    pod_name: &str,
+    path: &str,
+) -> Result<Vec<u8>, Error> {
+    let path = Path::new(path);
+    let (parent, name) = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => (parent.to_string_lossy().to_string(), name.to_string_lossy().to_string()),
+        _ => return Err(Box::new(BotError::new(&format!("{} is not a file path", path.display())))),
+    };
+    let parent = if parent.is_empty() { ".".to_string() } else { parent };
+    let attach_params = AttachParams::default().stdin(false).stdout(true).stderr(true);
+    let mut process = pod_client.exec(pod_name, vec!["tar", "cf", "-", "-C", &parent, &name], &attach_params).await?;
+    let status = process.take_status();
+    let (stdout, mut stderr) = match (process.stdout(), process.stderr()) {
+        (Some(stdout), Some(stderr)) => (stdout, stderr),
+        _ => return Err(Box::new(BotError::new("exec did not attach stdout and stderr"))),
+    };
+    let mut archive = Vec::new();
+    let mut errors = String::new();
+    let limit = MAX_DOWNLOAD_BYTES as u64 + TAR_OVERHEAD_BYTES;
+    let read_archive = async {
+        stdout.take(limit + 1).read_to_end(&mut archive).await?;
+        if archive.len() as u64 > limit {
+            return Err(std::io::Error::other(format!("{} is bigger than {MAX_DOWNLOAD_BYTES} bytes, too big to attach", path.display())))
+        }
+        Ok(())
+    };
+    // both have to be drained together or tar blocks on a full pipe, stops early once the archive is too big
+    tokio::try_join!(read_archive, stderr.read_to_string(&mut errors))?;
+    check_exec_status(status, &format!("copy {}", path.display()), &errors).await?;
+    Ok(archive)
+}
+
+// waits for the command in the pod to exit and turns a failure into an error with whatever it printed
+async fn check_exec_status(
+    status: Option<impl Future<Output = Option<Status>>>,
+    action: &str,
+    errors: &str,
+) -> Result<(), Error> {
+    let status = match status {
+        Some(s) => s.await,
+        None => None,
+    };
+    if status.as_ref().and_then(|s| s.status.as_deref()) != Some("Success") {
+        let message = status.and_then(|s| s.message).unwrap_or_default();
+        return Err(Box::new(BotError::new(&format!("unable to {action}: {} {}", message, errors.trim()))))
+    }
+    Ok(())
+}
+
+// single files get attached as they are, folders stay a tar
+fn unpack_download(path: &str, archive: Vec<u8>) -> Result<(String, Vec<u8>), Error> {
+    let file_name = Path::new(path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or("download".to_string());
+    let mut tar = tar::Archive::new(archive.as_slice());
+    let mut entries = tar.entries()?;
+    if let Some(entry) = entries.next() {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            if entries.next().is_none() {
+                return Ok((file_name, contents))
+            }
+        }
+    }
+    Ok((format!("{file_name}.tar"), archive))
+}
+
+/// upload a file into a game, like a mod or config file
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn upload(
+    ctx: Context<'_>,
+    #[description = "Game to upload to"] game: String,
+    #[description = "File to upload"] file: Attachment,
+    #[description = "Folder to put the file in, defaults to the first allowed one"] folder: Option<String>,
+) -> Result<(), Error> {
+    validate_game_name(ctx, game.clone()).await?;
+    let kclient = ctx.data().get_kube_client().await?;
+    let deployment = Api::<Deployment>::default_namespaced(kclient.clone()).get(&game).await?;
+    let annotations = deployment.metadata.annotations.unwrap_or_default();
+    let allowed_dirs = annotation_list(annotations.get(UPLOAD_DIRS_ANNOTATION));
+    let allowed_types = match annotations.get(UPLOAD_TYPES_ANNOTATION) {
+        Some(types) => annotation_list(Some(types)),
+        None => DEFAULT_UPLOAD_TYPES.iter().map(|t| t.to_string()).collect(),
+    };
+    let folder = match folder {
+        Some(f) if allowed_dirs.contains(&f) => f,
+        Some(f) => return Err(Box::new(BotError::new(&format!("{f} is not an upload folder for {game}, allowed: {}", allowed_dirs.join(", "))))),
+        None => match allowed_dirs.first() {
+            Some(f) => f.clone(),
+            None => return Err(Box::new(BotError::new(&format!("{game} does not accept uploads")))),
+        },
+    };
+    // the name ends up in the tar so anything that could point outside the folder is refused
+    if file.filename.starts_with('.') || file.filename.contains(['/', '\\']) {
+        return Err(Box::new(BotError::new(&format!("{} is not an allowed file name", file.filename))))
+    }
+    let extension = Path::new(&file.filename).extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if !allowed_types.contains(&extension) {
+        return Err(Box::new(BotError::new(&format!("{} files can't be uploaded to {game}, allowed: {}", extension, allowed_types.join(", ")))))
+    }
+    if file.size > MAX_UPLOAD_BYTES {
+        return Err(Box::new(BotError::new(&format!("{} is {} bytes, the limit is {MAX_UPLOAD_BYTES}", file.filename, file.size))))
+    }
+    ctx.defer().await?;
+    let contents = file.download().await?;
+    let pod_name = match get_deployment_pods(kclient.clone(), game.clone()).await?.into_iter().find_map(|p| p.metadata.name) {
+        Some(name) => name,
+        None => return Err(Box::new(BotError::new(&format!("{game} has no running pods")))),
+    };
+    let pod_client: Api<Pod> = Api::default_namespaced(kclient);
+    info!(user = %ctx.author().name, user_id = %ctx.author().id, game, pod = pod_name, folder, size = contents.len(), "uploading {}", file.filename);
+    match copy_to_pod(&pod_client, &pod_name, &folder, &file.filename, &contents).await {
+        Ok(_) => {
+            info!(user = %ctx.author().name, game, "uploaded {} to {folder}", file.filename);
+            ctx.say(format!("Uploaded {} to {folder} on {game}", file.filename)).await?;
+            Ok(())
+        }
+        Err(e) => {
+            error!(user = %ctx.author().name, game, "upload of {} to {folder} failed: {e}", file.filename);
+            Err(e)
+        }
+    }
+}
+
+fn annotation_list(value: Option<&String>) -> Vec<String> {
+    value.map(|v| v.split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect())
+        .unwrap_or_default()
+}
+
+// reverse of copy_from_pod, the file is wrapped in a tar and extracted into the folder
+async fn copy_to_pod(
+    pod_client: &Api<Pod>,
+    pod_name: &str,
+    folder: &str,
+    file_name: &str,
+    contents: &[u8],
+) -> Result<(), Error> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.append_data(&mut header, file_name, contents)?;
+    let mut archive = builder.into_inner()?;
+    // tar reads whole 10k records and exec has no way to close stdin, so pad out the last one
+    archive.resize(archive.len().div_ceil(10240) * 10240, 0);
+
+    let attach_params = AttachParams::default().stdin(true).stdout(false).stderr(true);
+    let mut process = pod_client.exec(pod_name, vec!["tar", "xf", "-", "-C", folder], &attach_params).await?;
+    let status = process.take_status();
+    let (mut stdin, mut stderr) = match (process.stdin(), process.stderr()) {
+        (Some(stdin), Some(stderr)) => (stdin, stderr),
+        _ => return Err(Box::new(BotError::new("exec did not attach stdin and stderr"))),
+    };
+    let mut errors = String::new();
+    let write = async {
+        stdin.write_all(&archive).await?;
+        // make sure everything is flushed before waiting on tar
+        stdin.shutdown().await
+    };
+    let (written, err) = tokio::join!(write, stderr.read_to_string(&mut errors));
+    written?;
+    err?;
+    check_exec_status(status, &format!("extract {file_name} into {folder}"), &errors).await
+}
+
+/// run one of the predefined maintenance tasks of a game
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn run_job(
+    ctx: Context<'_>,
+    #[description = "Game to run the task for"] game: String,
+    #[description = "Task to run"] task: String,
+) -> Result<(), Error> {
+    validate_game_name(ctx, game.clone()).await?;
+    let kclient = ctx.data().get_kube_client().await?;
+    let templates = get_job_templates(kclient.clone(), &game).await?;
+    let template = match templates.iter().find(|(name, _)| *name == task) {
+        Some((_, template)) => template,
+        None => {
+            let available = templates.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+            return Err(Box::new(BotError::new(&format!("{task} is not a task for {game}, available: {}", available.join(", ")))))
+        }
+    };
+    let job_client: Api<Job> = Api::default_namespaced(kclient.clone());
+    let job = job_client.create(&PostParams::default(), &job_from_template(template)).await?;
+    let job_name = job.metadata.name.unwrap_or_default();
+    info!(user = %ctx.author().name, game, task, "started job {job_name}");
+    ctx.say(format!("Started {task} for {game} as {job_name}")).await?;
+    // the interaction token is gone after 15 minutes, so progress and logs go out as plain channel messages
+    let channel = ctx.channel_id();
+    let mut progress = channel.send_message(ctx.http(), CreateMessage::new().content(format!("Waiting on {task} for {game}"))).await?;
+
+    let started = Instant::now();
+    let outcome = loop {
+        tokio::time::sleep(JOB_POLL_INTERVAL).await;
+        let status = job_client.get_status(&job_name).await?.status.unwrap_or_default();
+        let condition = |kind: &str| status.conditions.iter().flatten().any(|c| c.type_ == kind && c.status == "True");
+        if condition("Complete") {
+            break "finished"
+        }
+        if condition("Failed") {
+            break "failed"
+        }
+        if started.elapsed() > JOB_WAIT_LIMIT {
+            break "is still running, stopped waiting on it"
+        }
+        let content = format!(
+            "{task} for {game} running for {}s ({} active, {} failed attempts)",
+            started.elapsed().as_secs(), status.active.unwrap_or(0), status.failed.unwrap_or(0),
+        );
+        if let Err(e) = progress.edit(ctx.http(), EditMessage::new().content(content)).await {
+            warn!("unable to update progress of job {job_name}: {e}");
+        }
+    };
+    info!(game, task, "job {job_name} {outcome}");
+    let content = format!("{task} for {game} {outcome} after {}s", started.elapsed().as_secs());
+    if let Err(e) = progress.edit(ctx.http(), EditMessage::new().content(content)).await {
+        warn!("unable to update progress of job {job_name}: {e}");
+    }
+
+    let pod_client: Api<Pod> = Api::default_namespaced(kclient);
+    let pods = pod_client.list(&ListParams::default().labels(&format!("job-name={job_name}"))).await?;
+    for pod in pods {
+        let pod_name = pod.metadata.name.unwrap_or_default();
+        match pod_client.logs(&pod_name, &LogParams::default()).await {
+            Ok(pod_logs) => {
+                let attachment_logs = CreateAttachment::bytes(pod_logs.as_bytes(), format!("{pod_name}.log"));
+                if let Err(e) = channel.send_message(ctx.http(), CreateMessage::new().add_file(attachment_logs)).await {
+                    warn!("unable to post logs for {pod_name}: {e}");
+                }
+            }
+            Err(e) => warn!("unable to get logs for {pod_name}: {e}"),
+        }
+    }
+    Ok(())
+}
+
+async fn get_job_templates(
+    client: KubeClient,
+    game: &str,
+) -> Result<Vec<(String, CronJob)>, Error> {
+    let cron_client: Api<CronJob> = Api::default_namespaced(client);
+    let lp = ListParams::default().labels(&format!("{JOB_TEMPLATE_LABEL}={game}"));
+    let templates = cron_client.list(&lp).await?.items.into_iter()
+        .map(|cron| {
+            let task = cron.metadata.annotations.as_ref()
+                .and_then(|a| a.get(JOB_TASK_ANNOTATION).cloned())
+                .or(cron.metadata.name.clone())
+                .unwrap_or_default();
+            (task, cron)
+        })
+        .collect();
+    Ok(templates)
+}
+
+// what kubectl create job --from=cronjob does, the cronjob owns the job so it gets cleaned up with it
+fn job_from_template(template: &CronJob) -> Job {
+    let job_template = template.spec.as_ref().map(|s| s.job_template.clone()).unwrap_or_default();
+    let template_meta = job_template.metadata.unwrap_or_default();
+    let mut annotations = template_meta.annotations.unwrap_or_default();
+    annotations.insert("cronjob.kubernetes.io/instantiate".to_string(), "manual".to_string());
+    Job {
+        metadata: ObjectMeta {
+            generate_name: Some(format!("{}-manual-", template.metadata.name.as_deref().unwrap_or("job"))),
+            labels: template_meta.labels,
+            annotations: Some(annotations),
+            owner_references: template.controller_owner_ref(&()).map(|r| vec![r]),
+            ..ObjectMeta::default()
+        },
+        spec: job_template.spec,
+        status: None,
+    }
+}
+
+/// change the replica range of a game's autoscaler
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn autoscale(
+    ctx: Context<'_>,
+    #[description = "Game to change"] game: String,
+    #[description = "Fewest replicas to keep running"] min: Option<i32>,
+    #[description = "Most replicas to scale up to"] max: Option<i32>,
+) -> Result<(), Error> {
+    validate_game_name(ctx, game.clone()).await?;
+    let kclient = ctx.data().get_kube_client().await?;
+    let hpa = match get_autoscaler(kclient.clone(), &game).await? {
+        Some(hpa) => hpa,
+        None => return Err(Box::new(BotError::new(&format!("{game} has no autoscaler")))),
+    };
+    let spec = hpa.spec.clone().unwrap_or_default();
+    let min = min.or(spec.min_replicas).unwrap_or(1);
+    let max = max.unwrap_or(spec.max_replicas);
+    let deployment = Api::<Deployment>::default_namespaced(kclient.clone()).get(&game).await?;
+    let annotations = deployment.metadata.annotations.unwrap_or_default();
+    let bound = |key: &str| annotations.get(key).and_then(|v| v.trim().parse::<i32>().ok());
+    let (floor, ceiling) = match (bound(SCALE_MIN_ANNOTATION), bound(SCALE_MAX_ANNOTATION)) {
+        (Some(floor), Some(ceiling)) => (floor, ceiling),
+        _ => return Err(Box::new(BotError::new(&format!("{game} has no scaling bounds configured")))),
+    };
+    if min > max || min < floor || max > ceiling {
+        return Err(Box::new(BotError::new(&format!("{game} can scale between {floor} and {ceiling} replicas, {min}-{max} is not allowed"))))
+    }
+    let hpa_name = hpa.metadata.name.unwrap_or_default();
+    let patch = serde_json::json!({ "spec": { "minReplicas": min, "maxReplicas": max } });
+    let hpa_client: Api<HorizontalPodAutoscaler> = Api::default_namespaced(kclient);
+    let hpa = hpa_client.patch(&hpa_name, &PatchParams::default(), &Patch::Merge(&patch)).await?;
+    info!(user = %ctx.author().name, game, min, max, "changed autoscaler {hpa_name}");
+    ctx.say(format_autoscaler(&hpa)).await?;
+    Ok(())
+}
+
+// the autoscaler targeting the game's deployment, if it has one
+async fn get_autoscaler(
+    client: KubeClient,
+    game: &str,
+) -> Result<Option<HorizontalPodAutoscaler>, Error> {
+    let hpa_client: Api<HorizontalPodAutoscaler> = Api::default_namespaced(client);
+    let hpa = hpa_client.list(&ListParams::default()).await?.items.into_iter()
+        .find(|hpa| hpa.spec.as_ref()
+            .map(|s| s.scale_target_ref.kind == "Deployment" && s.scale_target_ref.name == game)
+            .unwrap_or(false));
+    Ok(hpa)
+}
+
+fn format_autoscaler(hpa: &HorizontalPodAutoscaler) -> String {
+    let spec = hpa.spec.clone().unwrap_or_default();
+    let status = hpa.status.clone().unwrap_or_default();
+    let mut lines = vec![format!(
+        "Autoscaler: {} replicas (wants {}), range {}-{}",
+        status.current_replicas.unwrap_or(0), status.desired_replicas, spec.min_replicas.unwrap_or(1), spec.max_replicas,
+    )];
+    let current = status.current_metrics.unwrap_or_default();
+    for metric in spec.metrics.unwrap_or_default() {
+        match metric.resource {
+            Some(resource) => {
+                let value = current.iter()
+                    .filter_map(|m| m.resource.as_ref())
+                    .find(|m| m.name == resource.name)
+                    .map(|m| format_metric_value(&m.current))
+                    .unwrap_or("unknown".to_string());
+                lines.push(format!("{}: {} (target {})", resource.name, value, format_metric_target(&resource.target)));
+            }
+            None => lines.push(format!("{} metric", metric.type_)),
+        }
+    }
+    lines.join("\n")
+}
+
+fn format_metric_value(value: &MetricValueStatus) -> String {
+    match (value.average_utilization, &value.average_value, &value.value) {
+        (Some(utilization), _, _) => format!("{utilization}%"),
+        (_, Some(average), _) => average.0.clone(),
+        (_, _, Some(value)) => value.0.clone(),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn format_metric_target(target: &MetricTarget) -> String {
+    match (target.average_utilization, &target.average_value, &target.value) {
+        (Some(utilization), _, _) => format!("{utilization}%"),
+        (_, Some(average), _) => average.0.clone(),
+        (_, _, Some(value)) => value.0.clone(),
+        _ => target.type_.clone(),
+    }
+}
+
+/// show the active hours of a game and any manual hold on it
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn schedule(
+    ctx: Context<'_>,
+    #[description = "Game to show the schedule for"] game: String,
+) -> Result<(), Error> {
+    validate_game_name(ctx, game.clone()).await?;
+    let deployment = ctx.data().get_deployment_client().await?.get(&game).await?;
+    let config = ScheduleConfig::from_env();
+    let hours = match game_schedule::active_hours(&deployment) {
+        Some(Ok(hours)) => hours,
+        Some(Err(e)) => return Err(Box::new(BotError::new(&format!("{game} has an invalid schedule: {e}")))),
+        None => {
+            ctx.say(format!("{game} has no active hours and runs all the time")).await?;
+            return Ok(())
+        }
+    };
+    let overrides = &ctx.data().schedule_overrides;
+    let running = game_schedule::should_run(&config, overrides, &game, hours).await;
+    let mut response = format!("{game} is active {hours} ({}), should be {} now", config.timezone, if running { "running" } else { "stopped" });
+    if let Some(hold) = overrides.get(&game).await {
+        response.push_str(&format!("\nHeld {} until <t:{}:t>", if hold.running { "running" } else { "stopped" }, hold.until.timestamp()));
+    }
+    ctx.say(response).await?;
+    Ok(())
+}
+
+/// keep a game running or stopped regardless of its active hours
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn hold(
+    ctx: Context<'_>,
+    #[description = "Game to hold"] game: String,
+    #[description = "Keep it running, otherwise keep it stopped"] running: bool,
+    #[description = "How many hours to hold it for"] #[min = 1] #[max = 72] hours: u32,
+) -> Result<(), Error> {
+    validate_game_name(ctx, game.clone()).await?;
+    let hold = ctx.data().schedule_overrides.set(&game, running, chrono::Duration::hours(hours.into())).await?;
+    info!(user = %ctx.author().name, game, running, hours, "schedule hold set");
+    ctx.say(format!("Holding {game} {} until <t:{}:t>, applied within a minute", if running { "running" } else { "stopped" }, hold.until.timestamp())).await?;
+    Ok(())
+}
+
+/// hand a held game back to its active hours
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn release(
+    ctx: Context<'_>,
+    #[description = "Game to release"] game: String,
+) -> Result<(), Error> {
+    validate_game_name(ctx, game.clone()).await?;
+    if ctx.data().schedule_overrides.clear(&game).await? {
+        info!(user = %ctx.author().name, game, "schedule hold released");
+        ctx.say(format!("{game} follows its active hours again")).await?;
+    } else {
+        ctx.say(format!("{game} was not held")).await?;
+    }
+    Ok(())
+}
+
 async fn get_valid_deployments(
     api: Api<Deployment>
 ) -> Result<Vec<String>, Error> {
@@ -1,26 +1,57 @@
 extern crate gstreamer as gst;
+extern crate gstreamer_app as gst_app;
 extern crate gstreamer_audio as gst_audio;
 extern crate gstreamer_pbutils as gst_pbutils;
 extern crate gstreamer_video as gst_video;
 
-use gst::{glib, MessageView, Pipeline};
+use gst::{glib, Pipeline};
 use anyhow::{Error, anyhow};
 use derive_more::{Display, Error};
-use poise::serenity_prelude::futures::StreamExt;
 
 use tokio::{sync::Mutex as TokioMutex};
+use rand::seq::SliceRandom;
 use url::Url;
 
-use std::{collections::VecDeque, fmt::Debug, future::{Future}, path::Path, pin::Pin, sync::{Arc, Mutex}};
+use std::{collections::VecDeque, fmt::Debug, future::{Future}, path::{Path, PathBuf}, pin::Pin, str::FromStr, sync::{mpsc, atomic::{AtomicU32, AtomicU64, Ordering}, Arc, Mutex}, time::{Duration, Instant, SystemTime}};
 use gst_pbutils::{prelude::*, ElementPropertiesMapItem};
 
 
 use uuid::Uuid;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+use crate::{file_browser::is_media_file, media_inspect, playback_events::{FinishReason, PlaybackEvent, PlaybackEvents, StallAction}, source_resolver::SourceResolver, trim_analysis::{TrimAnalyser, TrimOffsets}};
 
+const MAX_LIVE_RECONNECTS: u32 = 5;
+const DEFAULT_ERROR_RETRIES: u32 = 2;
+const MAX_BUS_LOG_ENTRIES: usize = 50;
+const SEEK_END_MARGIN: Duration = Duration::from_secs(5);
+const SEEK_SETTLE_SECS: u64 = 2;
+const SNAPSHOT_TIMEOUT_SECS: u64 = 5;
+// going back a chapter restarts the current one unless it started less than this ago
+const CHAPTER_RESTART_WINDOW: Duration = Duration::from_secs(5);
+// after this long without another stall the watchdog goes back to trying a restart before skipping
+const STALL_RECOVERY_RESET: Duration = Duration::from_secs(300);
+const LIVE_RECONNECT_BASE_DELAY: Duration = Duration::from_secs(2);
+// a live item that stayed up this long is considered healthy again and gets a fresh set of reconnect attempts
+const LIVE_STABLE_AFTER: Duration = Duration::from_secs(60);
+const OUTPUT_RECONNECT_BASE_DELAY: Duration = Duration::from_secs(2);
+const OUTPUT_RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+const MAX_OUTPUT_RECONNECTS: u32 = 10;
+// application message posted when a handed over item starts coming out of the source
+pub(crate) const GAPLESS_SWITCH_MESSAGE: &str = "gapless-switch";
 
 
+
+
+// an add that was turned down by the queue limits, the message is meant for whoever tried to add
+#[derive(Debug, Display, Error)]
+pub(crate) enum QueueLimitError {
+    #[display(fmt = "the queue is full, it already has {} items", _0)]
+    QueueFull(#[error(not(source))] usize),
+    #[display(fmt = "you already have {} items queued, wait for some of them to play first", _0)]
+    UserLimit(#[error(not(source))] usize),
+}
+
 #[derive(Debug, Display, Error)]
 #[display(fmt = "Received error from {src}: {error} (debug: {debug:?})")]
 struct ErrorMessage {
@@ -37,24 +68,159 @@ fn get_value_or_error<T>(option: Option<T>, error: &str) -> Result<T, Error> {
     option.ok_or_else(|| anyhow!("{}", error))
 }
 
+// callback run when an item stops playing, e.g. to mark it watched in emby
+pub(crate) type StopFn = Arc<TokioMutex<Pin<Box<dyn Future<Output = bool> + Send>>>>;
+
+// extra per item settings for add_uri. everything is optional so callers only set what they care about
+#[derive(Clone, Debug, Default)]
+pub(crate) struct QueueItemOptions {
+    // force the item to be treated as live (or not). when unset it is detected from the uri
+    pub(crate) live: Option<bool>,
+    pub(crate) http: HttpSourceOptions,
+    // called with a json payload once the item is done playing. falls back to the configured default
+    pub(crate) webhook: Option<Url>,
+    // overrides the configured frame rate cap for this item
+    pub(crate) max_framerate: Option<u32>,
+    // path or url of an external subtitle file, local files get their sidecar picked up without it
+    pub(crate) subtitles: Option<String>,
+    // chapters known ahead of time (e.g. from emby), used instead of the toc of the media
+    pub(crate) chapters: Vec<Chapter>,
+    // start this far in, e.g. to pick up where playback was interrupted
+    pub(crate) start_at: Option<Duration>,
+    // whoever queued it, counts towards their queue limit
+    pub(crate) requested_by: Option<Requester>,
+    pub(crate) watched_mark: Option<WatchedMark>,
+    // emby item it was queued from, for looking up its artwork
+    pub(crate) emby_item: Option<EmbyItemRef>,
+    // remux h.264 sources into the output as they are instead of re-encoding them
+    pub(crate) copy: bool,
+}
+
+// discord user that queued an item
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Requester {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+}
+
+// emby user and item the stop callback marks as watched, kept so the callback can be rebuilt after a restart
+#[derive(Clone, Debug)]
+pub(crate) struct WatchedMark {
+    // name of the media server, None for the default one
+    pub(crate) server: Option<String>,
+    pub(crate) user_id: String,
+    pub(crate) item_id: String,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct EmbyItemRef {
+    // name of the media server, None for the default one
+    pub(crate) server: Option<String>,
+    pub(crate) item_id: String,
+}
+
+// settings handed to http source elements (souphttpsrc) when the item is loaded
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HttpSourceOptions {
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) username: Option<String>,
+    pub(crate) password: Option<String>,
+}
+
+impl HttpSourceOptions {
+    pub fn is_set(&self) -> bool {
+        !self.headers.is_empty() || self.username.is_some() || self.password.is_some()
+    }
+
+    // pull basic auth credentials out of the url so they end up in user-id/user-pw instead of the logs
+    fn take_url_credentials(&mut self, uri: &mut Url) {
+        if !uri.username().is_empty() && self.username.is_none() {
+            let username = percent_encoding::percent_decode_str(uri.username()).decode_utf8_lossy().to_string();
+            self.username = Some(username);
+        }
+        if let Some(password) = uri.password() {
+            if self.password.is_none() {
+                self.password = Some(percent_encoding::percent_decode_str(password).decode_utf8_lossy().to_string());
+            }
+        }
+        let _ = uri.set_username("");
+        let _ = uri.set_password(None);
+    }
+
+    pub fn apply(&self, source: &gst::Element) {
+        if !self.headers.is_empty() {
+            if source.has_property("extra-headers", None) {
+                let mut extra_headers = gst::Structure::builder("extra-headers");
+                for (name, value) in &self.headers {
+                    extra_headers = extra_headers.field(name.as_str(), value.as_str());
+                }
+                source.set_property("extra-headers", extra_headers.build());
+            } else {
+                warn!("source {} does not support extra headers, ignoring them", source.name());
+            }
+        }
+        if let Some(username) = &self.username {
+            if source.has_property("user-id", None) {
+                source.set_property("user-id", username);
+            }
+        }
+        if let Some(password) = &self.password {
+            if source.has_property("user-pw", None) {
+                source.set_property("user-pw", password);
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct QueueItem {
     display_name: String,
     uri: Url,
-    stop_fn: Option<Arc<TokioMutex<Pin<Box<dyn Future<Output = bool> + Send>>>>>,
+    stop_fn: Option<StopFn>,
     id: Uuid,
+    live: bool,
+    http: HttpSourceOptions,
+    webhook: Option<Url>,
+    max_framerate: Option<u32>,
+    subtitles: Option<Url>,
+    chapters: Vec<Chapter>,
+    start_at: Option<Duration>,
+    requested_by: Option<Requester>,
+    watched_mark: Option<WatchedMark>,
+    emby_item: Option<EmbyItemRef>,
+    bumper: bool,
+    copy: bool,
+    // why the streams of a copy item can't go into the output as they are, looked at when it was queued
+    copy_blocker: Option<String>,
 }
 
 impl QueueItem {
-    pub fn new(display_name: String, uri: Url, stop_fn: Option<Arc<TokioMutex<Pin<Box<dyn Future<Output = bool> + Send>>>>>) -> Self {
+    pub fn new(display_name: String, uri: Url, stop_fn: Option<StopFn>) -> Self {
         QueueItem {
             display_name: display_name,
             uri: uri,
             id: Uuid::new_v4(),
             stop_fn: stop_fn,
+            live: false,
+            http: HttpSourceOptions::default(),
+            webhook: None,
+            max_framerate: None,
+            subtitles: None,
+            chapters: vec![],
+            start_at: None,
+            requested_by: None,
+            watched_mark: None,
+            emby_item: None,
+            bumper: false,
+            copy: false,
+            copy_blocker: None,
         }
     }
 
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
     pub fn name(&self) -> String {
         self.display_name.clone()
     }
@@ -67,6 +233,55 @@ impl QueueItem {
         self.id.clone()
     }
 
+    pub fn http_options(&self) -> HttpSourceOptions {
+        self.http.clone()
+    }
+
+    pub fn webhook(&self) -> Option<Url> {
+        self.webhook.clone()
+    }
+
+    pub fn subtitles(&self) -> Option<Url> {
+        self.subtitles.clone()
+    }
+
+    pub fn max_framerate(&self) -> Option<u32> {
+        self.max_framerate
+    }
+
+    pub fn chapters(&self) -> Vec<Chapter> {
+        self.chapters.clone()
+    }
+
+    pub fn requested_by(&self) -> Option<Requester> {
+        self.requested_by.clone()
+    }
+
+    pub fn watched_mark(&self) -> Option<WatchedMark> {
+        self.watched_mark.clone()
+    }
+
+    pub fn emby_item(&self) -> Option<EmbyItemRef> {
+        self.emby_item.clone()
+    }
+
+    pub fn is_bumper(&self) -> bool {
+        self.bumper
+    }
+
+    pub fn copy(&self) -> bool {
+        self.copy
+    }
+
+    // a copy to queue again that starts from the beginning and counts as its own item
+    fn requeued(&self) -> QueueItem {
+        QueueItem {
+            id: Uuid::new_v4(),
+            start_at: None,
+            ..self.clone()
+        }
+    }
+
 
     pub async fn run_stop_fn(&self) -> bool {
         match &self.stop_fn {
@@ -81,64 +296,2365 @@ impl QueueItem {
     
 }
 
-pub(crate) struct PlayQueue {
-    pipeline: gst::Pipeline,
-    uris: VecDeque<QueueItem>,
-    current_item: Option<QueueItem>,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum TrackKind {
+    Audio,
+    Subtitle,
 }
 
-impl PlayQueue {
-    pub fn new(rtmp_host: &str) -> Result<Self, Error> {
-        let pipeline = get_rtmp_pipeline(rtmp_host)?;
-        // Initialize and add necessary elements to the pipeline
+impl TrackKind {
+    fn stream_type(self) -> gst::StreamType {
+        match self {
+            TrackKind::Audio => gst::StreamType::AUDIO,
+            TrackKind::Subtitle => gst::StreamType::TEXT,
+        }
+    }
+}
 
-        Ok(
-            Self {
-               pipeline,
-               uris: VecDeque::new(),
-               current_item: None,
+#[derive(Clone, Debug)]
+pub(crate) struct TrackInfo {
+    pub(crate) id: String,
+    pub(crate) kind: TrackKind,
+    pub(crate) language: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) codec: Option<String>,
+    pub(crate) selected: bool,
+}
+
+#[derive(Default)]
+struct SelectionState {
+    collection: Option<gst::StreamCollection>,
+    selected: Vec<String>,
+    // decodebin3 lists the streams of the subtitle uri after the main ones
+    prefer_last_subtitle: bool,
+}
+
+// streams the current source offers and the ones it is decoding, kept up to date from the bus
+#[derive(Clone, Default)]
+pub(crate) struct StreamSelection(Arc<Mutex<SelectionState>>);
+
+impl StreamSelection {
+    pub fn reset(&self, external_subtitles: bool) {
+        *self.0.lock().unwrap() = SelectionState {
+            prefer_last_subtitle: external_subtitles,
+            ..SelectionState::default()
+        };
+    }
+
+    // remember a new collection and pick one video, audio and subtitle stream, preferring the ones the file marks as default
+    pub fn default_selection(&self, collection: gst::StreamCollection) -> Vec<String> {
+        let mut state = self.0.lock().unwrap();
+        let mut picked = vec![];
+        for stream_type in [gst::StreamType::VIDEO, gst::StreamType::AUDIO, gst::StreamType::TEXT] {
+            let candidates: Vec<gst::Stream> = collection.iter().filter(|s| s.stream_type().contains(stream_type)).collect();
+            let stream = if stream_type == gst::StreamType::TEXT && state.prefer_last_subtitle {
+                candidates.last()
+            } else {
+                candidates.iter()
+                    .find(|s| s.stream_flags().contains(gst::StreamFlags::SELECT))
+                    .or(candidates.first())
+            };
+            if let Some(id) = stream.and_then(|s| s.stream_id()) {
+                picked.push(id.to_string());
             }
-        )
+        }
+        state.collection = Some(collection);
+        picked
     }
 
-    pub async fn add_eos_watch(play_queue: &Arc<tokio::sync::Mutex<Self>>) {
-        let pipeline = {
-            let playqueue = play_queue.lock().await;
-            playqueue.pipeline.clone()
+    // audio and subtitle streams of the current source in the order the demuxer reported them
+    pub fn tracks(&self) -> Vec<TrackInfo> {
+        let state = self.0.lock().unwrap();
+        let collection = match &state.collection {
+            Some(c) => c,
+            None => return vec![],
         };
+        collection.iter()
+            .filter_map(|stream| {
+                let kind = [TrackKind::Audio, TrackKind::Subtitle].into_iter()
+                    .find(|k| stream.stream_type().contains(k.stream_type()))?;
+                let id = stream.stream_id()?.to_string();
+                let tags = stream.tags();
+                let tag_codec = tags.as_ref().and_then(|t| match kind {
+                    TrackKind::Audio => t.get::<gst::tags::AudioCodec>().map(|v| v.get().to_string()),
+                    TrackKind::Subtitle => t.get::<gst::tags::SubtitleCodec>().map(|v| v.get().to_string()),
+                });
+                let codec = tag_codec.or_else(|| stream.caps().map(|c| gst_pbutils::pb_utils_get_codec_description(&c).to_string()));
+                let selected = state.selected.contains(&id);
+                Some(TrackInfo {
+                    id,
+                    kind,
+                    language: tags.as_ref().and_then(|t| t.get::<gst::tags::LanguageCode>()).map(|v| v.get().to_string()),
+                    title: tags.as_ref().and_then(|t| t.get::<gst::tags::Title>()).map(|v| v.get().to_string()),
+                    codec,
+                    selected,
+                })
+            })
+            .collect()
+    }
+
+    // the current selection with the stream of one kind swapped for another, or dropped without one
+    pub fn with_track(&self, kind: TrackKind, stream_id: Option<&str>) -> Vec<String> {
+        let state = self.0.lock().unwrap();
+        let is_kind = |id: &String| state.collection.as_ref()
+            .and_then(|c| c.iter().find(|s| s.stream_id().as_deref() == Some(id.as_str())))
+            .map(|s| s.stream_type().contains(kind.stream_type()))
+            .unwrap_or(false);
+        let mut selected: Vec<String> = state.selected.iter().filter(|id| !is_kind(id)).cloned().collect();
+        if let Some(id) = stream_id {
+            selected.push(id.to_string());
+        }
+        selected
+    }
+
+    pub fn set_selected(&self, stream_ids: Vec<String>) {
+        let mut state = self.0.lock().unwrap();
+        let total = state.collection.as_ref().map(|c| c.len()).unwrap_or_default();
+        info!("decoding {} of {} streams: {}", stream_ids.len(), total, stream_ids.join(", "));
+        state.selected = stream_ids;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Chapter {
+    pub(crate) title: Option<String>,
+    pub(crate) start: Duration,
+}
 
-        let bus = pipeline.bus().unwrap();
-        let playqueue_clone = Arc::clone(play_queue);
+// where a newly added item goes in the queue
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum QueuePosition {
+    #[default]
+    Last,
+    // right after the current item
+    Next,
+}
 
-        let mut messages = bus.stream();
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum RepeatMode {
+    #[default]
+    Off,
+    // play the current item again instead of moving on
+    One,
+    // finished items go back to the end of the queue
+    Queue,
+}
 
-        while let Some(msg) = messages.next().await {
-            match msg.view() {
-                MessageView::Eos(..) => {
-                    match playqueue_clone.lock().await.skip_video().await {
-                        Ok(_) => (),
-                        Err(e) => error!("{}", e)
-                    };
-                    ()
-                },
-                _ => (),
+impl RepeatMode {
+    pub fn next(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Queue,
+            RepeatMode::Queue => RepeatMode::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RepeatMode::Off => "off",
+            RepeatMode::One => "repeat one",
+            RepeatMode::Queue => "repeat queue",
+        }
+    }
+
+    // whether an item that finished this way gets queued again
+    fn requeues(self, reason: FinishReason) -> bool {
+        match self {
+            RepeatMode::Off => false,
+            RepeatMode::One => reason == FinishReason::Completed,
+            RepeatMode::Queue => matches!(reason, FinishReason::Completed | FinishReason::Skipped),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ChapterJump {
+    Previous,
+    Next,
+    // position in the chapter list
+    Index(usize),
+}
+
+// chapters from the toc of the current source, kept up to date from the bus
+#[derive(Clone, Default)]
+pub(crate) struct ChapterList(Arc<Mutex<Vec<Chapter>>>);
+
+impl ChapterList {
+    pub fn reset(&self) {
+        self.0.lock().unwrap().clear();
+    }
+
+    pub fn update(&self, toc: &gst::TocRef) {
+        let mut chapters = vec![];
+        collect_toc_chapters(&toc.entries(), &mut chapters);
+        chapters.sort_by_key(|c| c.start);
+        chapters.dedup_by_key(|c| c.start);
+        info!("media has {} chapters", chapters.len());
+        *self.0.lock().unwrap() = chapters;
+    }
+
+    pub fn chapters(&self) -> Vec<Chapter> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+// chapters can sit below editions or be nested in other chapters, flatten all of them
+fn collect_toc_chapters(entries: &[gst::TocEntry], chapters: &mut Vec<Chapter>) {
+    for entry in entries {
+        if entry.entry_type() == gst::TocEntryType::Chapter {
+            if let Some((start, _)) = entry.start_stop_times() {
+                chapters.push(Chapter {
+                    title: entry.tags().and_then(|t| t.get::<gst::tags::Title>().map(|v| v.get().to_string())),
+                    start: Duration::from_nanos(start.max(0) as u64),
+                });
             }
         }
+        collect_toc_chapters(&entry.sub_entries(), chapters);
     }
+}
 
-    // Function to add a URI to the queue
-    pub fn add_uri(&mut self, uri: String, display_name: String, stop_fn: Option<Arc<TokioMutex<Pin<Box<dyn Future<Output = bool> + Send>>>>>) -> Result<QueueItem, Error> {
-        let queue_uri: String;
-        if uri.starts_with("/") {
-            let path = Path::new(&uri);
-            queue_uri = Url::from_file_path(path).unwrap().to_string();
-        } else {
-            queue_uri = uri;
+#[derive(Clone, Debug)]
+pub(crate) struct BusLogEntry {
+    pub(crate) received: Instant,
+    pub(crate) level: String,
+    pub(crate) source: String,
+    pub(crate) message: String,
+}
+
+// bounded history of interesting bus messages so problems can be looked at after the fact
+#[derive(Clone, Default)]
+pub(crate) struct BusLog(Arc<Mutex<VecDeque<BusLogEntry>>>);
+
+impl BusLog {
+    pub fn push(&self, level: &str, source: String, message: String) {
+        let mut entries = self.0.lock().unwrap();
+        if entries.len() >= MAX_BUS_LOG_ENTRIES {
+            entries.pop_front();
         }
-        let queue_item = QueueItem::new(display_name, Url::parse(&queue_uri).unwrap(), stop_fn);
-        self.uris.push_back(queue_item.clone());
-        Ok(queue_item)
+        entries.push_back(BusLogEntry {
+            received: Instant::now(),
+            level: level.to_string(),
+            source,
+            message,
+        });
+    }
+
+    pub fn entries(&self) -> Vec<BusLogEntry> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+pub(crate) fn is_unsupported_media_error(error: &glib::Error) -> bool {
+    error.matches(gst::StreamError::CodecNotFound)
+        || error.matches(gst::StreamError::TypeNotFound)
+        || error.matches(gst::CoreError::MissingPlugin)
+}
+
+// missing-plugin messages carry a `type` (decoder, element, ...) and a `detail` with the caps or element name
+pub(crate) fn describe_missing_plugin(structure: &gst::StructureRef) -> String {
+    let plugin_type = structure.get::<String>("type").unwrap_or("plugin".to_string());
+    let detail = match structure.get::<gst::Caps>("detail") {
+        Ok(caps) => caps.structure(0).map(|s| s.name().to_string()).unwrap_or(caps.to_string()),
+        Err(_) => structure.get::<String>("detail").unwrap_or("unknown".to_string()),
+    };
+    let name = structure.get::<String>("name").unwrap_or(detail.clone());
+    format!("codec {} not supported (missing {}), {}", name, plugin_type, missing_plugin_hint(&detail))
+}
+
+pub(crate) fn message_source_name(msg: &gst::Message) -> String {
+    msg.src().map(|s| s.path_string().to_string()).unwrap_or("<unknown>".to_string())
+}
+
+// the main sink is called sink and the ones added at runtime output_<n>
+pub(crate) fn is_output_message(msg: &gst::Message) -> bool {
+    let mut object = msg.src().cloned();
+    while let Some(o) = object {
+        let name = o.name();
+        if name == "sink" || (name.starts_with("output_") && !name.ends_with("_queue")) {
+            return true
+        }
+        object = o.parent();
+    }
+    false
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SeekResult {
+    pub(crate) position: Duration,
+    pub(crate) duration: Option<Duration>,
+    // the requested offset would have gone past the start or the end of the item
+    pub(crate) clamped: bool,
+}
+
+impl SeekResult {
+    pub fn remaining(&self) -> Option<Duration> {
+        self.duration.map(|d| d.saturating_sub(self.position))
+    }
+}
+
+// what goes out while there is nothing to play so viewers stay connected. STANDBY_SOURCE unset tears the stream down instead
+#[derive(Clone, Debug, Default)]
+pub(crate) enum StandbySource {
+    #[default]
+    Off,
+    // videotestsrc pattern
+    TestPattern(String),
+    Image(String),
+    // looped for as long as the standby lasts
+    Clip(String),
+}
+
+impl StandbySource {
+    pub fn from_env() -> Self {
+        let uri = std::env::var("STANDBY_URI").ok().filter(|v| !v.is_empty()).map(|v| standby_uri(&v));
+        match (std::env::var("STANDBY_SOURCE").unwrap_or_default().to_lowercase().as_str(), uri) {
+            ("", _) => StandbySource::Off,
+            ("testsrc", _) => StandbySource::TestPattern(std::env::var("STANDBY_PATTERN").ok().filter(|v| !v.is_empty()).unwrap_or("smpte".to_string())),
+            ("image", Some(uri)) => StandbySource::Image(uri),
+            ("clip", Some(uri)) => StandbySource::Clip(uri),
+            ("image" | "clip", None) => {
+                warn!("STANDBY_SOURCE needs STANDBY_URI for images and clips, standby is off");
+                StandbySource::Off
+            }
+            (other, _) => {
+                warn!("unknown STANDBY_SOURCE {}, expected testsrc, image or clip", other);
+                StandbySource::Off
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, StandbySource::Off)
+    }
+}
+
+// plain paths are turned into file uris
+fn standby_uri(value: &str) -> String {
+    match Url::parse(value) {
+        Ok(u) if u.scheme().len() > 1 => u.to_string(),
+        _ => std::path::absolute(value).ok()
+            .and_then(|p| Url::from_file_path(p).ok())
+            .map(|u| u.to_string())
+            .unwrap_or(value.to_string()),
+    }
+}
+
+// how often an item that errors out gets restarted before it is skipped. PIPELINE_ERROR_RETRIES=0 skips right away
+#[derive(Clone, Debug)]
+pub(crate) struct RecoveryConfig {
+    max_retries: u32,
+}
+
+impl RecoveryConfig {
+    pub fn from_env() -> Self {
+        RecoveryConfig {
+            max_retries: std::env::var("PIPELINE_ERROR_RETRIES").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_ERROR_RETRIES),
+        }
+    }
+}
+
+// a break between items so people have time to grab a drink. INTERMISSION_SECS unset or 0 turns it off
+#[derive(Clone, Debug, Default)]
+pub(crate) struct IntermissionConfig {
+    gap: Option<Duration>,
+}
+
+impl IntermissionConfig {
+    pub fn from_env() -> Self {
+        let gap = std::env::var("INTERMISSION_SECS").ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|s| *s > 0)
+            .map(Duration::from_secs);
+        IntermissionConfig { gap }
+    }
+}
+
+// short clips played before queued items like a channel ident. BUMPER_DIR holds the clips or BUMPER_URIS lists them
+// comma separated, BUMPER_EVERY=n plays one before every nth item
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BumperConfig {
+    clips: Vec<Url>,
+    every: usize,
+}
+
+impl BumperConfig {
+    pub fn from_env() -> Self {
+        let mut clips = std::env::var("BUMPER_URIS").unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|u| !u.is_empty())
+            .filter_map(|u| match path_or_url(u) {
+                Ok(url) => Some(url),
+                Err(e) => {
+                    warn!("ignoring bumper {}: {}", u, e);
+                    None
+                }
+            })
+            .collect::<Vec<Url>>();
+        if let Ok(dir) = std::env::var("BUMPER_DIR") {
+            match std::fs::read_dir(&dir) {
+                Ok(entries) => {
+                    let mut paths = entries.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| is_media_file(p)).collect::<Vec<PathBuf>>();
+                    paths.sort();
+                    clips.extend(paths.into_iter().filter_map(|p| Url::from_file_path(p).ok()));
+                }
+                Err(e) => warn!("unable to read BUMPER_DIR {}: {}", dir, e),
+            }
+        }
+        if !clips.is_empty() {
+            info!("playing bumpers from {} clips", clips.len());
+        }
+        BumperConfig {
+            clips,
+            every: std::env::var("BUMPER_EVERY").ok().and_then(|v| v.parse().ok()).filter(|n| *n > 0).unwrap_or(1),
+        }
+    }
+
+    fn pick(&self) -> Option<QueueItem> {
+        let uri = self.clips.choose(&mut rand::thread_rng())?;
+        let name = uri.path_segments().and_then(|mut s| s.next_back()).unwrap_or("bumper").to_string();
+        Some(QueueItem { bumper: true, ..QueueItem::new(name, uri.clone(), None) })
+    }
+}
+
+// how long the name of a new item stays on the video. TITLE_OVERLAY_SECS=0 leaves the overlay out of the pipeline
+#[derive(Clone, Debug)]
+pub(crate) struct TitleOverlayConfig {
+    duration: Option<Duration>,
+}
+
+impl TitleOverlayConfig {
+    pub fn from_env() -> Self {
+        let secs = std::env::var("TITLE_OVERLAY_SECS").ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(8);
+        TitleOverlayConfig {
+            duration: Some(secs).filter(|s| *s > 0).map(Duration::from_secs),
+        }
+    }
+}
+
+// elapsed and remaining time of the current item in the corner of the video. CLOCK_OVERLAY=off leaves it out
+// of the pipeline, hidden (the default) keeps it ready to be turned on at runtime and shown starts with it on
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum ClockOverlayConfig {
+    Off,
+    #[default]
+    Hidden,
+    Shown,
+}
+
+impl ClockOverlayConfig {
+    pub fn from_env() -> Self {
+        let value = std::env::var("CLOCK_OVERLAY").unwrap_or_default();
+        match value.as_str() {
+            "" => ClockOverlayConfig::default(),
+            v => v.parse().unwrap_or_else(|e| {
+                warn!("{}, leaving the clock hidden", e);
+                ClockOverlayConfig::default()
+            }),
+        }
+    }
+
+    pub fn enabled(self) -> bool {
+        self != ClockOverlayConfig::Off
     }
+}
+
+impl FromStr for ClockOverlayConfig {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(ClockOverlayConfig::Off),
+            "hidden" => Ok(ClockOverlayConfig::Hidden),
+            "shown" | "on" => Ok(ClockOverlayConfig::Shown),
+            other => Err(anyhow!("unknown clock overlay mode {}, expected off, hidden or shown", other)),
+        }
+    }
+}
+
+// the title currently on the video, hidden again once its time is up
+#[derive(Clone, Debug)]
+pub(crate) struct TitleOverlay {
+    pub(crate) id: Uuid,
+    hides_at: Instant,
+}
+
+impl TitleOverlay {
+    pub fn remaining(&self) -> Duration {
+        self.hides_at.saturating_duration_since(Instant::now())
+    }
+}
+
+// fade to black and silence at the end of an item and back in at the start of the next one.
+// TRANSITION_SECS unset or 0 turns it off, TRANSITION_EFFECT picks fade (both), video or audio
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TransitionConfig {
+    pub(crate) duration: Option<Duration>,
+    video: bool,
+    audio: bool,
+}
+
+impl TransitionConfig {
+    pub fn from_env() -> Self {
+        let duration = std::env::var("TRANSITION_SECS").ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|s| *s > 0.0)
+            .map(Duration::from_secs_f64);
+        let effect = std::env::var("TRANSITION_EFFECT").unwrap_or("fade".to_string()).to_lowercase();
+        let (video, audio) = match effect.as_str() {
+            "video" => (true, false),
+            "audio" => (false, true),
+            "fade" => (true, true),
+            other => {
+                warn!("unknown TRANSITION_EFFECT {}, fading both video and audio", other);
+                (true, true)
+            }
+        };
+        TransitionConfig { duration, video, audio }
+    }
+
+    fn fades_video(&self) -> bool {
+        self.duration.is_some() && self.video
+    }
+
+    fn fades_audio(&self) -> bool {
+        self.duration.is_some() && self.audio
+    }
+}
+
+// the gap after an item finished, the next one starts once it is over or when someone starts it early
+#[derive(Clone)]
+pub(crate) struct Intermission {
+    pub(crate) id: Uuid,
+    pub(crate) next: QueueItem,
+    pub(crate) ends_at: SystemTime,
+}
+
+impl Intermission {
+    pub fn remaining(&self) -> Duration {
+        self.ends_at.duration_since(SystemTime::now()).unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct FallbackConfig {
+    enabled: bool,
+    // slate shown while the real source is down, black when unset. images work too
+    fallback_uri: Option<String>,
+    // how long the source may stall before the slate goes up and a restart is tried
+    timeout: Duration,
+    // how long to keep retrying before the item fails for real
+    retry_timeout: Duration,
+    // local paths that are network mounts and can drop out like http does
+    network_paths: Vec<PathBuf>,
+}
+
+// unset or 0 means no limit
+#[derive(Clone, Debug, Default)]
+pub(crate) struct QueueLimits {
+    max_length: Option<usize>,
+    max_per_user: Option<usize>,
+}
+
+impl QueueLimits {
+    pub fn from_env() -> Self {
+        let limit = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<usize>().ok()).filter(|l| *l > 0);
+        QueueLimits {
+            max_length: limit("QUEUE_MAX_LENGTH"),
+            max_per_user: limit("QUEUE_MAX_PER_USER"),
+        }
+    }
+
+    fn check(&self, queued: &VecDeque<QueueItem>, item: &QueueItem) -> Result<(), QueueLimitError> {
+        if let Some(max) = self.max_length {
+            if queued.len() >= max {
+                return Err(QueueLimitError::QueueFull(queued.len()))
+            }
+        }
+        if let (Some(max), Some(requester)) = (self.max_per_user, &item.requested_by) {
+            let pending = queued.iter().filter(|i| i.requested_by.as_ref().map(|r| r.id) == Some(requester.id)).count();
+            if pending >= max {
+                return Err(QueueLimitError::UserLimit(pending))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FallbackConfig {
+    pub fn from_env() -> Self {
+        let secs_or = |name: &str, default: u64| Duration::from_secs(std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default));
+        FallbackConfig {
+            enabled: std::env::var("SOURCE_FALLBACK").map(|v| v == "1" || v == "true").unwrap_or(false),
+            fallback_uri: std::env::var("SOURCE_FALLBACK_URI").ok().filter(|v| !v.is_empty()),
+            timeout: secs_or("SOURCE_FALLBACK_TIMEOUT_SECS", 5),
+            retry_timeout: secs_or("SOURCE_RETRY_TIMEOUT_SECS", 120),
+            network_paths: std::env::var("SOURCE_NETWORK_PATHS").unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+        }
+    }
+
+    // fallbacksrc can't seek, show subtitles or send custom http settings so only sources that can actually drop out use it
+    fn applies_to(&self, item: &QueueItem, play_uri: &Url) -> bool {
+        if !self.enabled || item.http.is_set() || item.subtitles.is_some() {
+            return false
+        }
+        match play_uri.scheme() {
+            "http" | "https" => true,
+            "file" => play_uri.to_file_path()
+                .map(|path| self.network_paths.iter().any(|p| path.starts_with(p)))
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RateControl {
+    // constant bitrate, what most rtmp relays want
+    Cbr,
+    // constant quality, bitrate floats around the quantizer
+    Crf,
+    // first pass of a two-pass encode, only useful to collect stats
+    FirstPass,
+}
+
+impl RateControl {
+    // values of the x264enc pass enum
+    fn x264_pass(&self) -> i32 {
+        match self {
+            RateControl::Cbr => 0,
+            RateControl::Crf => 5,
+            RateControl::FirstPass => 17,
+        }
+    }
+}
+
+impl FromStr for RateControl {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cbr" => Ok(RateControl::Cbr),
+            "crf" => Ok(RateControl::Crf),
+            "pass1" | "two-pass" => Ok(RateControl::FirstPass),
+            other => Err(anyhow!("unknown rate control mode {}, expected cbr, crf or pass1", other)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum VideoCodec {
+    H264,
+    Vp9,
+    Av1,
+}
+
+impl VideoCodec {
+    fn caps(&self) -> gst::Caps {
+        let media_type = match self {
+            VideoCodec::H264 => "video/x-h264",
+            VideoCodec::Vp9 => "video/x-vp9",
+            VideoCodec::Av1 => "video/x-av1",
+        };
+        gst_video::VideoCapsBuilder::for_encoding(media_type).build()
+    }
+
+    fn default_encoder(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "x264enc",
+            VideoCodec::Vp9 => "vp9enc",
+            VideoCodec::Av1 => "svtav1enc",
+        }
+    }
+}
+
+impl FromStr for VideoCodec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "h264" | "avc" => Ok(VideoCodec::H264),
+            "vp9" => Ok(VideoCodec::Vp9),
+            "av1" => Ok(VideoCodec::Av1),
+            other => Err(anyhow!("unknown video codec {}, expected h264, vp9 or av1", other)),
+        }
+    }
+}
+
+// about two seconds of 20ms chunks
+const VOICE_TAP_CHUNKS: usize = 100;
+
+// where the video gets encoded. hardware backends are probed at startup and fall back to software
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum EncoderBackend {
+    Software,
+    Nvenc,
+    Vaapi,
+    Qsv,
+}
+
+impl EncoderBackend {
+    const HARDWARE: [EncoderBackend; 3] = [EncoderBackend::Nvenc, EncoderBackend::Vaapi, EncoderBackend::Qsv];
+
+    // none when the backend can't encode the codec
+    fn encoder(self, codec: VideoCodec) -> Option<&'static str> {
+        match (self, codec) {
+            (EncoderBackend::Software, codec) => Some(codec.default_encoder()),
+            (EncoderBackend::Nvenc, VideoCodec::H264) => Some("nvh264enc"),
+            (EncoderBackend::Nvenc, VideoCodec::Vp9) => None,
+            (EncoderBackend::Nvenc, VideoCodec::Av1) => Some("nvav1enc"),
+            (EncoderBackend::Vaapi, VideoCodec::H264) => Some("vah264enc"),
+            (EncoderBackend::Vaapi, VideoCodec::Vp9) => Some("vavp9enc"),
+            (EncoderBackend::Vaapi, VideoCodec::Av1) => Some("vaav1enc"),
+            (EncoderBackend::Qsv, VideoCodec::H264) => Some("qsvh264enc"),
+            (EncoderBackend::Qsv, VideoCodec::Vp9) => Some("qsvvp9enc"),
+            (EncoderBackend::Qsv, VideoCodec::Av1) => Some("qsvav1enc"),
+        }
+    }
+}
+
+impl FromStr for EncoderBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "software" | "cpu" => Ok(EncoderBackend::Software),
+            "nvenc" | "nvidia" => Ok(EncoderBackend::Nvenc),
+            "vaapi" | "va" => Ok(EncoderBackend::Vaapi),
+            "qsv" | "intel" => Ok(EncoderBackend::Qsv),
+            other => Err(anyhow!("unknown encoder backend {}, expected auto, software, nvenc, vaapi or qsv", other)),
+        }
+    }
+}
+
+// an encoder element only tells whether the device behind it works once it opens it
+fn probe_encoder(factory: &str) -> Result<(), Error> {
+    let element = gst::ElementFactory::make(factory).build()?;
+    let result = element.set_state(gst::State::Ready);
+    let _ = element.set_state(gst::State::Null);
+    result?;
+    Ok(())
+}
+
+// pull a branch off a tee while no buffer is in flight on it so the other branches keep going.
+// the first element is the one linked to the tee
+fn detach_tee_branch(pipeline: &Pipeline, elements: Vec<gst::Element>) -> Result<(), Error> {
+    let first = get_value_or_error(elements.first(), "no elements to detach")?;
+    let tee_pad = get_value_or_error(first.static_pad("sink").and_then(|p| p.peer()), "branch is not linked to a tee")?;
+    let tee = get_value_or_error(tee_pad.parent_element(), "tee pad has no element")?;
+    let pipeline = pipeline.clone();
+    tee_pad.add_probe(gst::PadProbeType::IDLE, move |pad, _| {
+        if let Some(peer) = pad.peer() {
+            let _ = pad.unlink(&peer);
+        }
+        tee.release_request_pad(pad);
+        for element in &elements {
+            let _ = element.set_state(gst::State::Null);
+            let _ = pipeline.remove(element);
+        }
+        gst::PadProbeReturn::Remove
+    });
+    Ok(())
+}
+
+// container caps and the muxer properties that go with them
+type ContainerProfile = (gst::Caps, Vec<(&'static str, bool)>);
+
+// where the encoded stream goes. picked from the scheme and extension of the output uri
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum OutputTarget {
+    Rtmp(String),
+    Srt(String),
+    Udp(String),
+    Hls(PathBuf),
+    File(PathBuf),
+}
+
+impl OutputTarget {
+    pub fn parse(uri: &str) -> Result<Self, Error> {
+        if uri.starts_with('/') {
+            return Ok(OutputTarget::from_path(PathBuf::from(uri)))
+        }
+        let url = Url::parse(uri)?;
+        match url.scheme() {
+            "rtmp" | "rtmps" => Ok(OutputTarget::Rtmp(uri.to_string())),
+            "srt" => Ok(OutputTarget::Srt(uri.to_string())),
+            "udp" if url.host_str().is_some() && url.port().is_some() => Ok(OutputTarget::Udp(uri.to_string())),
+            "udp" => Err(anyhow!("udp outputs need a host and port, e.g. udp://239.0.0.1:5000")),
+            "file" => url.to_file_path()
+                .map(OutputTarget::from_path)
+                .map_err(|_| anyhow!("{} is not a valid file path", uri)),
+            other => Err(anyhow!("unsupported output scheme {}", other)),
+        }
+    }
+
+    // playlist.m3u8 and its segments inside the directory
+    pub fn hls_dir(dir: &str) -> Self {
+        OutputTarget::Hls(PathBuf::from(dir).join("playlist.m3u8"))
+    }
+
+    fn from_path(path: PathBuf) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("m3u8") => OutputTarget::Hls(path),
+            _ => OutputTarget::File(path),
+        }
+    }
+
+    fn extension(&self) -> Option<String> {
+        match self {
+            OutputTarget::File(path) => path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()),
+            _ => None,
+        }
+    }
+
+    // container caps plus the muxer properties it needs to survive the pipeline being torn down between items.
+    // rtmp carries flv, srt and udp get mpeg-ts for h264 and matroska for the other codecs
+    fn container(&self, codec: VideoCodec) -> Result<ContainerProfile, Error> {
+        let matroska = || (gst::Caps::builder("video/x-matroska").build(), vec![("streamable", true)]);
+        let mpegts = || (gst::Caps::builder("video/mpegts").field("systemstream", true).build(), vec![]);
+        match (self, self.extension().as_deref()) {
+            (OutputTarget::Rtmp(_), _) if codec != VideoCodec::H264 => Err(anyhow!("rtmp output only supports h264")),
+            (OutputTarget::Rtmp(_), _) => Ok((gst::Caps::builder("video/x-flv").build(), vec![("streamable", true)])),
+            (OutputTarget::Hls(_), _) if codec != VideoCodec::H264 => Err(anyhow!("hls output only supports h264")),
+            (OutputTarget::Hls(_), _) => Ok(mpegts()),
+            (OutputTarget::Srt(_), _) | (OutputTarget::Udp(_), _) if codec == VideoCodec::H264 => Ok(mpegts()),
+            (OutputTarget::Srt(_), _) | (OutputTarget::Udp(_), _) => Ok(matroska()),
+            (OutputTarget::File(_), Some("webm")) if codec == VideoCodec::H264 => Err(anyhow!("webm files need vp9 or av1")),
+            (OutputTarget::File(_), Some("webm")) => Ok((gst::Caps::builder("video/webm").build(), vec![("streamable", true)])),
+            (OutputTarget::File(_), Some("ts")) if codec == VideoCodec::H264 => Ok(mpegts()),
+            (OutputTarget::File(_), _) => Ok(matroska()),
+        }
+    }
+
+    fn audio_caps(&self, codec: VideoCodec) -> gst::Caps {
+        match self.container(codec).map(|(caps, _)| caps.structure(0).map(|s| s.name().to_string())) {
+            Ok(Some(name)) if name == "video/x-matroska" || name == "video/webm" => {
+                gst_audio::AudioCapsBuilder::for_encoding("audio/x-opus").channels(2).build()
+            }
+            _ => gst_audio::AudioCapsBuilder::for_encoding("audio/mpeg").channels(2).rate_range(1000..100000)
+                .field("mpegversion", 1).field("layer", 3).build(),
+        }
+    }
+
+    // where the output goes without anything secret, rtmp paths usually carry the stream key
+    pub fn label(&self) -> String {
+        match self {
+            OutputTarget::Rtmp(location) => match Url::parse(location) {
+                Ok(url) => format!("{}://{}", url.scheme(), url.host_str().unwrap_or_default()),
+                Err(_) => "rtmp".to_string(),
+            },
+            OutputTarget::Srt(uri) => uri.split('?').next().unwrap_or_default().to_string(),
+            OutputTarget::Udp(uri) => uri.clone(),
+            OutputTarget::Hls(path) | OutputTarget::File(path) => path.display().to_string(),
+        }
+    }
+
+    fn make_sink(&self, name: &str) -> Result<gst::Element, Error> {
+        let sink = match self {
+            OutputTarget::Rtmp(location) => gst::ElementFactory::make("rtmpsink").name(name).property("location", location).build()?,
+            OutputTarget::Srt(uri) => gst::ElementFactory::make("srtsink").name(name).property("uri", uri).build()?,
+            OutputTarget::Udp(uri) => {
+                let url = Url::parse(uri)?;
+                gst::ElementFactory::make("udpsink")
+                    .name(name)
+                    .property("host", url.host_str().unwrap_or_default())
+                    .property("port", url.port().unwrap_or_default() as i32)
+                    .build()?
+            }
+            OutputTarget::Hls(playlist) => {
+                let settings = HlsSettings::from_env();
+                if let Some(dir) = playlist.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                let segments = playlist.with_file_name("segment%05d.ts");
+                let sink = gst::ElementFactory::make("hlssink")
+                    .name(name)
+                    .property("playlist-location", playlist.to_string_lossy().to_string())
+                    .property("location", segments.to_string_lossy().to_string())
+                    .property("target-duration", settings.segment_secs)
+                    .property("playlist-length", settings.playlist_length)
+                    // a few more segments than the playlist lists so slow clients don't 404 on one that just dropped off
+                    .property("max-files", settings.playlist_length + 3)
+                    .build()?;
+                if let Some(root) = settings.playlist_root {
+                    sink.set_property("playlist-root", root);
+                }
+                sink
+            }
+            OutputTarget::File(path) => gst::ElementFactory::make("filesink").name(name).property("location", path.to_string_lossy().to_string()).build()?,
+        };
+        Ok(sink)
+    }
+
+    // files get a new name per item, the pipeline restarts between items and would truncate the last one
+    fn item_file(&self) -> Option<PathBuf> {
+        let path = match self {
+            OutputTarget::File(p) => p,
+            _ => return None,
+        };
+        let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("recording".to_string());
+        let started = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let name = match path.extension() {
+            Some(ext) => format!("{}-{}.{}", stem, started, ext.to_string_lossy()),
+            None => format!("{}-{}", stem, started),
+        };
+        Some(path.with_file_name(name))
+    }
+
+    fn required_elements(&self, encoder: &EncoderConfig) -> Vec<(String, &'static str)> {
+        let mut elements = vec![(encoder.encoder_element(), element_package(&encoder.encoder_element()))];
+        let sink = match self {
+            OutputTarget::Rtmp(_) => "rtmpsink",
+            OutputTarget::Srt(_) => "srtsink",
+            OutputTarget::Udp(_) => "udpsink",
+            OutputTarget::Hls(_) => "hlssink",
+            OutputTarget::File(_) => "filesink",
+        };
+        elements.push((sink.to_string(), element_package(sink)));
+        if let Ok((caps, _)) = self.container(encoder.codec) {
+            let muxer = match caps.structure(0).map(|s| s.name().as_str()) {
+                Some("video/x-flv") => "flvmux",
+                Some("video/mpegts") => "mpegtsmux",
+                Some("video/webm") => "webmmux",
+                _ => "matroskamux",
+            };
+            elements.push((muxer.to_string(), element_package(muxer)));
+        }
+        let audio_encoder = match self.audio_caps(encoder.codec).structure(0).map(|s| s.name().as_str()) {
+            Some("audio/x-opus") => "opusenc",
+            _ => "lamemp3enc",
+        };
+        elements.push((audio_encoder.to_string(), element_package(audio_encoder)));
+        if encoder.watermark.is_some() {
+            elements.push(("gdkpixbufoverlay".to_string(), element_package("gdkpixbufoverlay")));
+        }
+        if encoder.deinterlace != DeinterlaceMode::Off {
+            elements.push(("deinterlace".to_string(), element_package("deinterlace")));
+        }
+        // copy items go through a parser instead of the encoder
+        if matches!(self, OutputTarget::Rtmp(_)) {
+            elements.push(("h264parse".to_string(), element_package("h264parse")));
+        }
+        elements
+    }
+}
+
+#[derive(Clone, Debug)]
+struct HlsSettings {
+    segment_secs: u32,
+    playlist_length: u32,
+    // url prefix for the segment names in the playlist when they are served from somewhere else
+    playlist_root: Option<String>,
+}
+
+impl HlsSettings {
+    fn from_env() -> Self {
+        let env_or = |name: &str, default: u32| std::env::var(name).ok().and_then(|v| v.parse::<u32>().ok()).unwrap_or(default);
+        HlsSettings {
+            segment_secs: env_or("HLS_SEGMENT_SECS", 4).max(1),
+            playlist_length: env_or("HLS_PLAYLIST_LENGTH", 6).max(1),
+            playlist_root: std::env::var("HLS_PLAYLIST_ROOT").ok().filter(|v| !v.is_empty()),
+        }
+    }
+}
+
+fn element_package(factory: &str) -> &'static str {
+    match factory {
+        "x264enc" => "gstreamer1.0-plugins-ugly",
+        "vp9enc" | "lamemp3enc" | "flvmux" | "matroskamux" | "webmmux" | "gdkpixbufoverlay" | "deinterlace" | "udpsink" => "gstreamer1.0-plugins-good",
+        "filesink" => "gstreamer1.0 core",
+        "opusenc" => "gstreamer1.0-plugins-base",
+        _ => "gstreamer1.0-plugins-bad",
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct EncoderConfig {
+    codec: VideoCodec,
+    // overrides the encoder element picked from the backend
+    video_encoder: Option<String>,
+    // backends to try in order, the first one that works is used
+    backends: Vec<EncoderBackend>,
+    backend: EncoderBackend,
+    rate_control: RateControl,
+    // kbit/s, the target for cbr and the cap otherwise
+    bitrate: u32,
+    quantizer: u32,
+    // output runs at a constant rate of at most this so the keyframe interval can be given in seconds.
+    // faster sources get frames dropped, slower ones get frames repeated
+    max_framerate: u32,
+    keyframe_secs: u32,
+    bframes: u32,
+    // output is scaled down to fit inside these, keeping its aspect ratio
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    // fixed output size so every item comes out the same no matter the source. wins over the max size
+    resolution: Option<OutputResolution>,
+    letterbox: bool,
+    // kbit/s, the audio encoder picks its own when unset
+    audio_bitrate: Option<u32>,
+    // x264 speed-preset and tune, left at the x264enc defaults when unset
+    preset: Option<X264Preset>,
+    tune: Option<X264Tune>,
+    // logo drawn on top of every frame
+    watermark: Option<Watermark>,
+    deinterlace: DeinterlaceMode,
+    // convert hdr sources to bt.709 sdr, the relays only carry sdr
+    tonemap: bool,
+    // custom surround to stereo mix, audioconvert's own mix is used when unset
+    downmix: Option<Downmix>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct OutputResolution {
+    width: u32,
+    height: u32,
+}
+
+impl FromStr for OutputResolution {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s.to_ascii_lowercase().split_once('x')
+            .map(|(w, h)| (w.trim().parse::<u32>(), h.trim().parse::<u32>()))
+            .ok_or(anyhow!("resolution must look like 1280x720, got {}", s))?;
+        Ok(OutputResolution { width: width?, height: height? })
+    }
+}
+
+// how loud each surround channel ends up in both stereo channels. a center gain above the default 0.707 keeps dialogue audible
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Downmix {
+    center: f32,
+    surround: f32,
+    lfe: f32,
+}
+
+impl Downmix {
+    // audioconvert mix-matrix with a row per stereo channel and a column per input channel
+    fn matrix(&self, positions: &[gst_audio::AudioChannelPosition]) -> gst::Array {
+        use gst_audio::AudioChannelPosition as P;
+        let gains = positions.iter().map(|position| match position {
+            P::FrontLeft | P::FrontLeftOfCenter | P::WideLeft => (1.0, 0.0),
+            P::FrontRight | P::FrontRightOfCenter | P::WideRight => (0.0, 1.0),
+            P::FrontCenter | P::Mono => (self.center, self.center),
+            P::Lfe1 | P::Lfe2 => (self.lfe, self.lfe),
+            P::RearLeft | P::SideLeft | P::SurroundLeft => (self.surround, 0.0),
+            P::RearRight | P::SideRight | P::SurroundRight => (0.0, self.surround),
+            _ => (self.surround * 0.707, self.surround * 0.707),
+        }).collect::<Vec<(f32, f32)>>();
+        // scale down so a full scale signal on every channel can't clip
+        let loudest = gains.iter().map(|g| g.0).sum::<f32>().max(gains.iter().map(|g| g.1).sum::<f32>()).max(1.0);
+        let row = |channel: fn(&(f32, f32)) -> f32| gst::Array::new(gains.iter().map(|g| channel(g) / loudest));
+        gst::Array::new([row(|g| g.0), row(|g| g.1)])
+    }
+}
+
+// auto only touches sources whose caps say they are interlaced, always also catches rips that are flagged wrong
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DeinterlaceMode {
+    Off,
+    Auto,
+    Always,
+}
+
+impl DeinterlaceMode {
+    // value of the deinterlace element's mode property
+    fn element_mode(self) -> Option<&'static str> {
+        match self {
+            DeinterlaceMode::Off => None,
+            DeinterlaceMode::Auto => Some("auto"),
+            DeinterlaceMode::Always => Some("interlaced"),
+        }
+    }
+}
+
+impl FromStr for DeinterlaceMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(DeinterlaceMode::Off),
+            "auto" => Ok(DeinterlaceMode::Auto),
+            "always" | "on" => Ok(DeinterlaceMode::Always),
+            other => Err(anyhow!("unknown deinterlace mode {}, expected off, auto or always", other)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum WatermarkCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl FromStr for WatermarkCorner {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "top-left" => Ok(WatermarkCorner::TopLeft),
+            "top-right" => Ok(WatermarkCorner::TopRight),
+            "bottom-left" => Ok(WatermarkCorner::BottomLeft),
+            "bottom-right" => Ok(WatermarkCorner::BottomRight),
+            other => Err(anyhow!("unknown corner {}, expected top-left, top-right, bottom-left or bottom-right", other)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Watermark {
+    path: PathBuf,
+    corner: WatermarkCorner,
+    // pixels between the image and the edges of the video
+    margin: u32,
+    alpha: f64,
+}
+
+impl Watermark {
+    fn element(&self) -> Result<gst::Element, Error> {
+        // negative offsets count from the right and bottom edge, so they can't be 0 there
+        let margin = self.margin.max(1) as i32;
+        let (x, y) = match self.corner {
+            WatermarkCorner::TopLeft => (margin, margin),
+            WatermarkCorner::TopRight => (-margin, margin),
+            WatermarkCorner::BottomLeft => (margin, -margin),
+            WatermarkCorner::BottomRight => (-margin, -margin),
+        };
+        Ok(gst::ElementFactory::make("gdkpixbufoverlay")
+            .name("watermark")
+            .property("location", self.path.to_string_lossy().to_string())
+            .property("offset-x", x)
+            .property("offset-y", y)
+            .property("alpha", self.alpha)
+            .build()?)
+    }
+}
+
+const X264_PRESETS: [&str; 10] = ["ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow", "placebo"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct X264Preset(u32);
+
+impl FromStr for X264Preset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the x264enc speed-preset enum starts with "none" at 0
+        X264_PRESETS.iter()
+            .position(|p| p.eq_ignore_ascii_case(s))
+            .map(|i| X264Preset(i as u32 + 1))
+            .ok_or(anyhow!("unknown x264 preset {}, expected one of {}", s, X264_PRESETS.join(", ")))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct X264Tune(u32);
+
+impl FromStr for X264Tune {
+    type Err = Error;
+
+    // comma separated values of the x264enc tune flags
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut flags = 0;
+        for tune in s.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            flags |= match tune.to_ascii_lowercase().as_str() {
+                "stillimage" => 1,
+                "fastdecode" => 2,
+                "zerolatency" => 4,
+                other => return Err(anyhow!("unknown x264 tune {}, expected stillimage, fastdecode or zerolatency", other)),
+            };
+        }
+        Ok(X264Tune(flags))
+    }
+}
+
+// unset variables get the default, set ones have to parse
+fn env_or<T: FromStr>(name: &str, default: T) -> Result<T, Error> where T::Err: std::fmt::Display {
+    match std::env::var(name) {
+        Ok(v) if !v.is_empty() => v.trim().parse().map_err(|e| anyhow!("invalid {} {}: {}", name, v, e)),
+        _ => Ok(default),
+    }
+}
+
+fn env_opt<T: FromStr>(name: &str) -> Result<Option<T>, Error> where T::Err: std::fmt::Display {
+    match std::env::var(name) {
+        Ok(v) if !v.is_empty() => v.trim().parse().map(Some).map_err(|e| anyhow!("invalid {} {}: {}", name, v, e)),
+        _ => Ok(None),
+    }
+}
+
+impl EncoderConfig {
+    // bad values stop startup instead of quietly streaming with settings nobody asked for
+    pub fn from_env() -> Result<Self, Error> {
+        let backends = match std::env::var("ENCODER_BACKEND") {
+            Ok(v) if v.eq_ignore_ascii_case("auto") => EncoderBackend::HARDWARE.to_vec(),
+            _ => match env_or("ENCODER_BACKEND", EncoderBackend::Software)? {
+                EncoderBackend::Software => vec![],
+                backend => vec![backend],
+            },
+        };
+        let config = EncoderConfig {
+            codec: env_or("OUTPUT_VIDEO_CODEC", VideoCodec::H264)?,
+            video_encoder: std::env::var("OUTPUT_VIDEO_ENCODER").ok().filter(|v| !v.is_empty()),
+            backends,
+            backend: EncoderBackend::Software,
+            rate_control: env_or("ENCODER_RATE_CONTROL", RateControl::Crf)?,
+            bitrate: env_or("ENCODER_BITRATE", 3000)?,
+            quantizer: env_or("ENCODER_QUANTIZER", 21)?,
+            max_framerate: env_or("ENCODER_MAX_FRAMERATE", 30)?,
+            keyframe_secs: env_or("ENCODER_KEYFRAME_SECS", 2)?,
+            bframes: env_or("ENCODER_BFRAMES", 0)?,
+            max_width: env_opt("ENCODER_MAX_WIDTH")?,
+            max_height: env_opt("ENCODER_MAX_HEIGHT")?,
+            resolution: env_opt("OUTPUT_RESOLUTION")?,
+            letterbox: match std::env::var("OUTPUT_SCALING").unwrap_or_default().to_ascii_lowercase().as_str() {
+                "" | "letterbox" => true,
+                "stretch" => false,
+                other => return Err(anyhow!("unknown OUTPUT_SCALING {}, expected letterbox or stretch", other)),
+            },
+            audio_bitrate: env_opt("ENCODER_AUDIO_BITRATE")?,
+            preset: env_opt("ENCODER_PRESET")?,
+            tune: env_opt("ENCODER_TUNE")?,
+            watermark: match env_opt::<PathBuf>("WATERMARK_PATH")? {
+                Some(path) => Some(Watermark {
+                    path,
+                    corner: env_or("WATERMARK_POSITION", WatermarkCorner::TopRight)?,
+                    margin: env_or("WATERMARK_MARGIN", 20)?,
+                    alpha: env_or("WATERMARK_ALPHA", 1.0)?,
+                }),
+                None => None,
+            },
+            deinterlace: env_or("DEINTERLACE", DeinterlaceMode::Off)?,
+            tonemap: env_or("HDR_TONEMAP", true)?,
+            downmix: match env_opt::<f32>("DOWNMIX_CENTER_GAIN")? {
+                Some(center) => Some(Downmix {
+                    center,
+                    surround: env_or("DOWNMIX_SURROUND_GAIN", 0.707)?,
+                    lfe: env_or("DOWNMIX_LFE_GAIN", 0.0)?,
+                }),
+                None => None,
+            },
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.bitrate == 0 {
+            return Err(anyhow!("ENCODER_BITRATE must be above 0"))
+        }
+        if self.quantizer > 63 {
+            return Err(anyhow!("ENCODER_QUANTIZER must be between 0 and 63"))
+        }
+        if !(1..=120).contains(&self.max_framerate) {
+            return Err(anyhow!("ENCODER_MAX_FRAMERATE must be between 1 and 120"))
+        }
+        if self.keyframe_secs == 0 {
+            return Err(anyhow!("ENCODER_KEYFRAME_SECS must be above 0"))
+        }
+        // most encoders want even dimensions for 4:2:0 video
+        let (width, height) = (self.resolution.map(|r| r.width), self.resolution.map(|r| r.height));
+        for (name, value) in [("ENCODER_MAX_WIDTH", self.max_width), ("ENCODER_MAX_HEIGHT", self.max_height), ("OUTPUT_RESOLUTION width", width), ("OUTPUT_RESOLUTION height", height)] {
+            if let Some(v) = value.filter(|v| *v < 16 || v % 2 != 0) {
+                return Err(anyhow!("{} must be an even number of at least 16, got {}", name, v))
+            }
+        }
+        if let Some(bitrate) = self.audio_bitrate.filter(|b| !(8..=320).contains(b)) {
+            return Err(anyhow!("ENCODER_AUDIO_BITRATE must be between 8 and 320 kbit/s, got {}", bitrate))
+        }
+        if let Some(watermark) = &self.watermark {
+            if !watermark.path.is_file() {
+                return Err(anyhow!("WATERMARK_PATH {} is not a file", watermark.path.display()))
+            }
+            if !(0.0..=1.0).contains(&watermark.alpha) {
+                return Err(anyhow!("WATERMARK_ALPHA must be between 0 and 1, got {}", watermark.alpha))
+            }
+        }
+        if let Some(downmix) = &self.downmix {
+            for (name, gain) in [("DOWNMIX_CENTER_GAIN", downmix.center), ("DOWNMIX_SURROUND_GAIN", downmix.surround), ("DOWNMIX_LFE_GAIN", downmix.lfe)] {
+                if !(0.0..=2.0).contains(&gain) {
+                    return Err(anyhow!("{} must be between 0 and 2, got {}", name, gain))
+                }
+            }
+        }
+        if (self.preset.is_some() || self.tune.is_some()) && self.encoder_element() != "x264enc" {
+            warn!("ENCODER_PRESET and ENCODER_TUNE only apply to x264enc, not {}", self.encoder_element());
+        }
+        Ok(())
+    }
+
+    // caps for the filter behind videoscale, open ended when no size is configured
+    fn scale_caps(&self) -> gst::Caps {
+        if let Some(resolution) = self.resolution {
+            // square pixels make videoscale pad with borders instead of changing the pixel aspect ratio
+            return gst_video::VideoCapsBuilder::new()
+                .width(resolution.width as i32)
+                .height(resolution.height as i32)
+                .pixel_aspect_ratio(gst::Fraction::new(1, 1))
+                .build()
+        }
+        let mut caps = gst_video::VideoCapsBuilder::new();
+        if let Some(width) = self.max_width {
+            caps = caps.width_range(16..=width as i32);
+        }
+        if let Some(height) = self.max_height {
+            caps = caps.height_range(16..=height as i32);
+        }
+        caps.build()
+    }
+
+    fn audio_properties(&self, audio_caps: &gst::Caps) -> Option<gst_pbutils::ElementProperties> {
+        let bitrate = self.audio_bitrate? as i32;
+        let item = match audio_caps.structure(0).map(|s| s.name().as_str()) {
+            // opus takes bit/s, lame kbit/s and only honours it when targeting bitrate instead of quality
+            Some("audio/x-opus") => ElementPropertiesMapItem::builder("opusenc").field("bitrate", bitrate * 1000),
+            _ => ElementPropertiesMapItem::builder("lamemp3enc").field("target", 1).field("bitrate", bitrate).field("cbr", true),
+        };
+        Some(gst_pbutils::ElementProperties::builder_map().item(item.build()).build())
+    }
+
+    // settle on the first configured backend whose encoder works on this machine. needs gstreamer initialised
+    pub fn with_available_backend(&self) -> Self {
+        let mut config = self.clone();
+        if config.video_encoder.is_some() {
+            return config
+        }
+        for backend in &self.backends {
+            let factory = match backend.encoder(self.codec) {
+                Some(f) => f,
+                None => {
+                    info!("{:?} can't encode {:?}", backend, self.codec);
+                    continue
+                }
+            };
+            match probe_encoder(factory) {
+                Ok(_) => {
+                    info!("encoding {:?} with {} ({:?})", self.codec, factory, backend);
+                    config.backend = *backend;
+                    return config
+                }
+                Err(e) => warn!("{:?} encoder {} is not usable: {}", backend, factory, e),
+            }
+        }
+        if !self.backends.is_empty() {
+            warn!("no hardware encoder available, falling back to {}", self.codec.default_encoder());
+        }
+        config.backend = EncoderBackend::Software;
+        config
+    }
+
+    fn keyframe_interval(&self, framerate: u32) -> u32 {
+        framerate * self.keyframe_secs
+    }
+
+    // name of the encoder property holding the keyframe distance in frames
+    fn keyframe_property(&self) -> Option<&'static str> {
+        match self.encoder_element().as_str() {
+            "x264enc" => Some("key-int-max"),
+            "vp9enc" | "av1enc" => Some("keyframe-max-dist"),
+            "svtav1enc" => Some("intra-period-length"),
+            "nvh264enc" | "nvav1enc" | "qsvh264enc" | "qsvvp9enc" | "qsvav1enc" => Some("gop-size"),
+            "vah264enc" | "vavp9enc" | "vaav1enc" => Some("key-int-max"),
+            _ => None,
+        }
+    }
+
+    fn encoder_element(&self) -> String {
+        self.video_encoder.clone()
+            .or(self.backend.encoder(self.codec).map(str::to_string))
+            .unwrap_or(self.codec.default_encoder().to_string())
+    }
+
+    // each encoder names its rate control knobs differently. unknown elements keep their defaults
+    fn element_properties(&self) -> gst_pbutils::ElementProperties {
+        let element = self.encoder_element();
+        let item = ElementPropertiesMapItem::builder(&element);
+        let item = match element.as_str() {
+            "x264enc" => {
+                let item = item
+                    .field("pass", self.rate_control.x264_pass())
+                    .field("quantizer", self.quantizer)
+                    .field("bitrate", self.bitrate)
+                    .field("key-int-max", self.keyframe_interval(self.max_framerate))
+                    .field("bframes", self.bframes);
+                let item = match self.preset {
+                    Some(preset) => item.field("speed-preset", preset.0),
+                    None => item,
+                };
+                match self.tune {
+                    Some(tune) => item.field("tune", tune.0),
+                    None => item,
+                }
+            }
+            "vp9enc" | "av1enc" => {
+                // libvpx and libaom share the end-usage enum, vpx wants bits and aom kbits
+                let end_usage = match self.rate_control {
+                    RateControl::Cbr => 1,
+                    RateControl::Crf => 2,
+                    RateControl::FirstPass => 0,
+                };
+                let target_bitrate = if element == "vp9enc" { self.bitrate as i32 * 1000 } else { self.bitrate as i32 };
+                let item = item
+                    .field("end-usage", end_usage)
+                    .field("target-bitrate", target_bitrate)
+                    .field("cq-level", self.quantizer as i32)
+                    .field("keyframe-max-dist", self.keyframe_interval(self.max_framerate) as i32);
+                if element == "vp9enc" {
+                    // realtime deadline, the good deadline can't keep up with a live stream
+                    item.field("deadline", 1i64)
+                } else {
+                    item
+                }
+            }
+            "svtav1enc" => {
+                let item = item.field("intra-period-length", self.keyframe_interval(self.max_framerate) as i32);
+                match self.rate_control {
+                    RateControl::Crf => item.field("crf", self.quantizer),
+                    _ => item.field("target-bitrate", self.bitrate),
+                }
+            }
+            "nvh264enc" | "nvav1enc" => {
+                // values of the nvenc rc-mode enum
+                let item = item
+                    .field("gop-size", self.keyframe_interval(self.max_framerate) as i32)
+                    .field("bitrate", self.bitrate);
+                match self.rate_control {
+                    RateControl::Cbr => item.field("rc-mode", 2),
+                    _ => item.field("rc-mode", 1)
+                        .field("max-bitrate", self.bitrate)
+                        .field("const-quality", self.quantizer as f64),
+                }
+            }
+            "vah264enc" | "vavp9enc" | "vaav1enc" => {
+                // values of the va rate-control enum
+                let item = item
+                    .field("key-int-max", self.keyframe_interval(self.max_framerate))
+                    .field("bitrate", self.bitrate);
+                match self.rate_control {
+                    RateControl::Cbr => item.field("rate-control", 2),
+                    _ if element == "vah264enc" => item.field("rate-control", 16)
+                        .field("qpi", self.quantizer)
+                        .field("qpp", self.quantizer)
+                        .field("qpb", self.quantizer),
+                    _ => item.field("rate-control", 16).field("qp", self.quantizer),
+                }
+            }
+            "qsvh264enc" | "qsvvp9enc" | "qsvav1enc" => {
+                // values of the mfx rate-control enum
+                let item = item
+                    .field("gop-size", self.keyframe_interval(self.max_framerate))
+                    .field("bitrate", self.bitrate);
+                match self.rate_control {
+                    RateControl::Cbr => item.field("rate-control", 1),
+                    _ => item.field("rate-control", 3)
+                        .field("qp-i", self.quantizer)
+                        .field("qp-p", self.quantizer),
+                }
+            }
+            other => {
+                info!("no rate control mapping for {}, using its defaults", other);
+                item
+            }
+        };
+        gst_pbutils::ElementProperties::builder_map().item(item.build()).build()
+    }
+}
+
+// the next item as uridecodebin3 gets it when the current one is about to finish
+struct PreparedItem {
+    id: Uuid,
+    // none when the item was looked at but has to start the slow way
+    play_uri: Option<Url>,
+    http: HttpSourceOptions,
+    // after this the url may have expired, the item starts the slow way and gets resolved again
+    fresh_until: Option<SystemTime>,
+}
+
+impl PreparedItem {
+    fn is_stale(&self) -> bool {
+        self.fresh_until.is_some_and(|t| SystemTime::now() >= t)
+    }
+}
+
+#[derive(Default)]
+struct HandoverState {
+    prepared: Option<PreparedItem>,
+    // given to the source, it takes over once its streams start
+    handed_over: Option<Uuid>,
+    group: Option<gst::GroupId>,
+}
+
+// lets uridecodebin3 follow on with the next item without the pipeline going to null in between
+#[derive(Clone, Default)]
+struct GaplessHandover(Arc<Mutex<HandoverState>>);
+
+impl GaplessHandover {
+    // the item that is either waiting for the source or already with it
+    fn pending(&self) -> Option<Uuid> {
+        let state = self.0.lock().unwrap();
+        state.handed_over.or(state.prepared.as_ref().map(|p| p.id))
+    }
+
+    fn prepare(&self, item: Option<PreparedItem>) {
+        self.0.lock().unwrap().prepared = item;
+    }
+
+    // prepared a while ago and its url may be gone by now
+    fn is_stale(&self) -> bool {
+        self.0.lock().unwrap().prepared.as_ref().is_some_and(|p| p.is_stale())
+    }
+
+    // the pipeline is starting over so whatever was handed to the old source is gone
+    fn reset(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.prepared = None;
+        state.handed_over = None;
+    }
+
+    // called from the streaming thread, the uri has to be set before the signal returns
+    fn about_to_finish(&self, source: &gst::Element, source_http_options: &Mutex<HttpSourceOptions>) {
+        let mut state = self.0.lock().unwrap();
+        let (id, play_uri, http) = match state.prepared.take() {
+            Some(prepared) if prepared.is_stale() => {
+                warn!("url for the next item expired before it came up, starting it the normal way");
+                return
+            }
+            Some(PreparedItem { id, play_uri: Some(play_uri), http, .. }) => (id, play_uri, http),
+            _ => return,
+        };
+        info!("handing {} to the source ahead of time", SourceResolver::without_credentials(&play_uri));
+        *source_http_options.lock().unwrap() = http;
+        source.set_property("uri", play_uri.as_str());
+        state.handed_over = Some(id);
+    }
+
+    // returns the handed over item once its streams show up
+    fn stream_started(&self, group: Option<gst::GroupId>) -> Option<Uuid> {
+        let mut state = self.0.lock().unwrap();
+        if group.is_none() || state.group == group {
+            return None
+        }
+        state.group = group;
+        state.handed_over.take()
+    }
+}
+
+// counters fed by the pipeline itself, read back for health reports
+#[derive(Clone, Debug)]
+pub(crate) struct StreamStats {
+    bytes_sent: Arc<AtomicU64>,
+    frames_encoded: Arc<AtomicU64>,
+    dropped_frames: Arc<AtomicU64>,
+    buffering_percent: Arc<AtomicU32>,
+    // the frame that went to the encoder last, for snapshots
+    last_frame: Arc<Mutex<Option<gst::Buffer>>>,
+}
+
+impl Default for StreamStats {
+    fn default() -> Self {
+        StreamStats {
+            bytes_sent: Arc::new(AtomicU64::new(0)),
+            frames_encoded: Arc::new(AtomicU64::new(0)),
+            dropped_frames: Arc::new(AtomicU64::new(0)),
+            buffering_percent: Arc::new(AtomicU32::new(100)),
+            last_frame: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl StreamStats {
+    pub fn record_dropped_frame(&self) {
+        self.dropped_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // returns the previous level so callers can tell when buffering starts or ends
+    pub fn record_buffering(&self, percent: i32) -> u32 {
+        self.buffering_percent.swap(percent.clamp(0, 100) as u32, Ordering::Relaxed)
+    }
+
+    // a new source starts out not buffering, whatever the last one was doing
+    fn reset_buffering(&self) {
+        self.buffering_percent.store(100, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct StreamHealth {
+    pub(crate) item: Option<QueueItem>,
+    pub(crate) playing: bool,
+    // how long the stream has been going across items, not just the current one
+    pub(crate) uptime: Option<Duration>,
+    // running totals, rates are up to whoever samples them
+    pub(crate) bytes_sent: u64,
+    pub(crate) dropped_frames: u64,
+    pub(crate) buffering_percent: u32,
+    pub(crate) video_buffer: Option<Duration>,
+    pub(crate) audio_buffer: Option<Duration>,
+    pub(crate) position: Option<Duration>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct QueueLevel {
+    pub(crate) name: String,
+    pub(crate) time: Duration,
+    pub(crate) max_time: Duration,
+    pub(crate) buffers: u32,
+    pub(crate) bytes: u32,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct PipelineLatency {
+    pub(crate) live: bool,
+    pub(crate) min: Duration,
+    pub(crate) max: Option<Duration>,
+}
+
+// what the elements themselves report, for debugging stream quality
+#[derive(Clone, Debug)]
+pub(crate) struct PipelineStats {
+    pub(crate) state: String,
+    pub(crate) encoder: String,
+    // kbit/s the encoder was configured with
+    pub(crate) target_bitrate: u32,
+    // the rate videorate holds the output at, ENCODER_MAX_FRAMERATE or the item's own cap
+    pub(crate) framerate: Option<u32>,
+    pub(crate) bytes_sent: u64,
+    pub(crate) frames_encoded: u64,
+    pub(crate) dropped_frames: u64,
+    // none when the pipeline can't answer a latency query, e.g. while it isn't playing
+    pub(crate) latency: Option<PipelineLatency>,
+    pub(crate) queues: Vec<QueueLevel>,
+}
+
+// last position the watchdog saw and when it first saw it
+struct ProgressMark {
+    position: gst::ClockTime,
+    since: Instant,
+}
+
+pub(crate) struct PlayQueue {
+    pipeline: gst::Pipeline,
+    uris: VecDeque<QueueItem>,
+    current_item: Option<QueueItem>,
+    resolver: SourceResolver,
+    trimmer: TrimAnalyser,
+    live_reconnects: u32,
+    live_started: Option<Instant>,
+    recovery: RecoveryConfig,
+    // restarts of the current item after pipeline errors
+    error_retries: u32,
+    // reconnects after the relay dropped us, reset once the stream stays up for a while
+    output_reconnects: u32,
+    output_connected_at: Option<Instant>,
+    // read by the source-setup callback so each item can carry its own http settings
+    source_http_options: Arc<Mutex<HttpSourceOptions>>,
+    bus_log: BusLog,
+    events: PlaybackEvents,
+    progress: Option<ProgressMark>,
+    stall_recoveries: u32,
+    last_stall_recovery: Option<Instant>,
+    stats: StreamStats,
+    handover: GaplessHandover,
+    streaming_since: Option<Instant>,
+    output: OutputTarget,
+    fallback: FallbackConfig,
+    stream_selection: StreamSelection,
+    toc_chapters: ChapterList,
+    repeat: RepeatMode,
+    limits: QueueLimits,
+    intermission_config: IntermissionConfig,
+    intermission: Option<Intermission>,
+    transition: TransitionConfig,
+    title_config: TitleOverlayConfig,
+    clock_config: ClockOverlayConfig,
+    title: Option<TitleOverlay>,
+    bumpers: BumperConfig,
+    // queued items started so far, bumpers go by it
+    items_started: usize,
+    // a bumper already ran in front of the item that is up next
+    bumper_played: bool,
+    standby: StandbySource,
+    // the standby source is on stream instead of an item
+    in_standby: bool,
+    encoder: EncoderConfig,
+    // outputs added at runtime next to the main one, keyed by their sink name
+    extra_outputs: Vec<(String, OutputTarget)>,
+    next_output_id: u32,
+}
+
+impl PlayQueue {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(output: &OutputTarget, encoder: &EncoderConfig, fallback: FallbackConfig, limits: QueueLimits, intermission_config: IntermissionConfig, transition: TransitionConfig, title_config: TitleOverlayConfig, clock_config: ClockOverlayConfig, bumpers: BumperConfig, standby: StandbySource, recovery: RecoveryConfig, resolver: SourceResolver, trimmer: TrimAnalyser) -> Result<Self, Error> {
+        gst::init()?;
+        let encoder = &encoder.with_available_backend();
+        let source_http_options = Arc::new(Mutex::new(HttpSourceOptions::default()));
+        let stats = StreamStats::default();
+        let handover = GaplessHandover::default();
+        let pipeline = get_output_pipeline(output, encoder, &transition, &title_config, clock_config, Arc::clone(&source_http_options), &stats, &handover)?;
+        let mut fallback = fallback;
+        if fallback.enabled && gst::ElementFactory::find("fallbacksrc").is_none() {
+            warn!("SOURCE_FALLBACK is set but fallbacksrc is missing, install gst-plugin-fallbackswitch from gst-plugins-rs");
+            fallback.enabled = false;
+        }
+        // Initialize and add necessary elements to the pipeline
+
+        Ok(
+            Self {
+               pipeline,
+               uris: VecDeque::new(),
+               current_item: None,
+               resolver,
+               trimmer,
+               live_reconnects: 0,
+               live_started: None,
+               recovery,
+               error_retries: 0,
+               output_reconnects: 0,
+               output_connected_at: None,
+               source_http_options,
+               bus_log: BusLog::default(),
+               events: PlaybackEvents::new(),
+               progress: None,
+               stall_recoveries: 0,
+               last_stall_recovery: None,
+               stats,
+               handover,
+               streaming_since: None,
+               output: output.clone(),
+               fallback,
+               stream_selection: StreamSelection::default(),
+               toc_chapters: ChapterList::default(),
+               repeat: RepeatMode::default(),
+               limits,
+               intermission_config,
+               intermission: None,
+               transition,
+               title_config,
+               title: None,
+               clock_config,
+               bumpers,
+               items_started: 0,
+               bumper_played: false,
+               standby,
+               in_standby: false,
+               encoder: encoder.clone(),
+               extra_outputs: vec![],
+               next_output_id: 1,
+            }
+        )
+    }
+
+    pub fn pipeline(&self) -> gst::Pipeline {
+        self.pipeline.clone()
+    }
+
+    pub fn bus_log(&self) -> BusLog {
+        self.bus_log.clone()
+    }
+
+    pub fn resolver(&self) -> SourceResolver {
+        self.resolver.clone()
+    }
+
+    pub fn trimmer(&self) -> TrimAnalyser {
+        self.trimmer.clone()
+    }
+
+    pub fn transition(&self) -> TransitionConfig {
+        self.transition.clone()
+    }
+
+    pub fn clock_config(&self) -> ClockOverlayConfig {
+        self.clock_config
+    }
+
+    pub fn events(&self) -> PlaybackEvents {
+        self.events.clone()
+    }
+
+    pub fn stream_selection(&self) -> StreamSelection {
+        self.stream_selection.clone()
+    }
+
+    pub fn toc_chapters(&self) -> ChapterList {
+        self.toc_chapters.clone()
+    }
+
+    pub fn chapters(&self) -> Vec<Chapter> {
+        match &self.current_item {
+            Some(item) if !item.chapters.is_empty() => item.chapters.clone(),
+            Some(_) => self.toc_chapters.chapters(),
+            None => vec![],
+        }
+    }
+
+    fn position(&self) -> Option<Duration> {
+        let position = self.pipeline.by_name("src")?.query_position::<gst::ClockTime>()?;
+        Some(Duration::from_nanos(position.nseconds()))
+    }
+
+    // index of the chapter the current position is in
+    pub fn current_chapter(&self, chapters: &[Chapter]) -> Option<usize> {
+        let position = self.position()?;
+        chapters.iter().rposition(|c| c.start <= position)
+    }
+
+    pub async fn seek_chapter(&mut self, jump: ChapterJump) -> Result<(usize, Chapter, SeekResult), Error> {
+        if self.current_item.as_ref().map(|i| i.is_live()).unwrap_or(false) {
+            return Err(anyhow!("cannot seek in a live stream"))
+        }
+        let chapters = self.chapters();
+        if chapters.is_empty() {
+            return Err(anyhow!("the current item has no chapters"))
+        }
+        let current = self.current_chapter(&chapters);
+        let index = match jump {
+            ChapterJump::Next => current.map(|i| i + 1).unwrap_or(0),
+            ChapterJump::Previous => match (current, self.position()) {
+                (Some(i), Some(position)) if position.saturating_sub(chapters[i].start) > CHAPTER_RESTART_WINDOW => i,
+                (Some(i), _) => i.saturating_sub(1),
+                (None, _) => 0,
+            },
+            ChapterJump::Index(i) => i,
+        };
+        let chapter = get_value_or_error(chapters.get(index).cloned(), &format!("there is no chapter {}, the item has {}", index + 1, chapters.len()))?;
+        let pipeline = self.pipeline.clone();
+        let start = chapter.start;
+        let result = tokio::task::spawn_blocking(move || seek_pipeline_to(&pipeline, start)).await??;
+        self.progress = None;
+        Ok((index, chapter, result))
+    }
+
+    pub fn audio_tracks(&self) -> Vec<TrackInfo> {
+        self.stream_selection.tracks().into_iter().filter(|t| t.kind == TrackKind::Audio).collect()
+    }
+
+    // index is the position in audio_tracks
+    pub fn set_audio_track(&self, index: usize) -> Result<TrackInfo, Error> {
+        let track = get_value_or_error(self.audio_tracks().into_iter().nth(index), &format!("there is no audio track {}", index + 1))?;
+        self.switch_track(TrackKind::Audio, Some(&track.id))?;
+        Ok(track)
+    }
+
+    pub fn list_subtitle_tracks(&self) -> Vec<TrackInfo> {
+        self.stream_selection.tracks().into_iter().filter(|t| t.kind == TrackKind::Subtitle).collect()
+    }
+
+    pub fn subtitles_enabled(&self) -> bool {
+        self.pipeline.by_name("suboverlay").map(|o| !o.property::<bool>("silent")).unwrap_or(false)
+    }
+
+    // index is the position in list_subtitle_tracks, none hides subtitles until a track is picked again
+    pub fn set_subtitle_track(&self, index: Option<usize>) -> Result<Option<TrackInfo>, Error> {
+        let suboverlay = get_value_or_error(self.pipeline.by_name("suboverlay"), "unable to get subtitle overlay from pipeline")?;
+        let index = match index {
+            Some(i) => i,
+            None => {
+                suboverlay.set_property("silent", true);
+                return Ok(None)
+            }
+        };
+        let track = get_value_or_error(self.list_subtitle_tracks().into_iter().nth(index), &format!("there is no subtitle track {}", index + 1))?;
+        self.switch_track(TrackKind::Subtitle, Some(&track.id))?;
+        suboverlay.set_property("silent", false);
+        Ok(Some(track))
+    }
+
+    fn switch_track(&self, kind: TrackKind, stream_id: Option<&str>) -> Result<(), Error> {
+        // fallbacksrc decodes a fixed set of streams on its own
+        if source_factory(&self.pipeline).as_deref() != Some("uridecodebin3") {
+            return Err(anyhow!("tracks can't be switched on this source"))
+        }
+        select_streams(&self.pipeline, &self.stream_selection.with_track(kind, stream_id))
+    }
+
+    // the main output first, then the ones added at runtime
+    pub fn outputs(&self) -> Vec<(String, String)> {
+        std::iter::once(("main".to_string(), self.output.label()))
+            .chain(self.extra_outputs.iter().map(|(name, target)| (name.clone(), target.label())))
+            .collect()
+    }
+
+    // push the encoded stream to another place as well. every output gets the same muxed stream
+    pub fn add_output(&mut self, target: OutputTarget) -> Result<String, Error> {
+        let (main_caps, _) = self.output.container(self.encoder.codec)?;
+        if !matches!(target, OutputTarget::File(_)) {
+            let (caps, _) = target.container(self.encoder.codec)?;
+            if caps != main_caps {
+                return Err(anyhow!("{} needs {} but the stream is muxed as {}", target.label(), caps, main_caps))
+            }
+        }
+        let name = format!("output_{}", self.next_output_id);
+        let tee = get_value_or_error(self.pipeline.by_name("output_tee"), "unable to get output tee from pipeline")?;
+        let queue = gst::ElementFactory::make("queue").name(format!("{}_queue", name)).build()?;
+        let sink = target.make_sink(&name)?;
+        if let (Some(path), true) = (target.item_file(), self.current_item.is_some()) {
+            sink.set_property("location", path.to_string_lossy().to_string());
+        }
+        self.pipeline.add_many([&queue, &sink])?;
+        gst::Element::link_many([&queue, &sink])?;
+        let tee_pad = get_value_or_error(tee.request_pad_simple("src_%u"), "unable to get a tee pad")?;
+        let queue_pad = get_value_or_error(queue.static_pad("sink"), "unable to get output queue pad")?;
+        tee_pad.link(&queue_pad)?;
+        queue.sync_state_with_parent()?;
+        sink.sync_state_with_parent()?;
+        info!("added output {} to {}", name, target.label());
+        self.next_output_id += 1;
+        self.extra_outputs.push((name.clone(), target));
+        Ok(name)
+    }
+
+    pub fn remove_output(&mut self, name: &str) -> Result<(), Error> {
+        let position = get_value_or_error(self.extra_outputs.iter().position(|(n, _)| n == name), &format!("there is no output {}", name))?;
+        let queue = get_value_or_error(self.pipeline.by_name(&format!("{}_queue", name)), "unable to get output queue from pipeline")?;
+        let sink = get_value_or_error(self.pipeline.by_name(name), "unable to get output sink from pipeline")?;
+        detach_tee_branch(&self.pipeline, vec![queue, sink])?;
+        let (_, target) = self.extra_outputs.remove(position);
+        info!("removed output {} to {}", name, target.label());
+        Ok(())
+    }
+
+    // raw audio of whatever is playing as interleaved 48kHz stereo f32 chunks, for voice channels.
+    // chunks get dropped instead of holding up the stream when the receiver falls behind
+    pub fn attach_audio_tap(&mut self) -> Result<mpsc::Receiver<Vec<u8>>, Error> {
+        self.detach_audio_tap()?;
+        let tee = get_value_or_error(self.pipeline.by_name("audio_tee"), "unable to get audio tee from pipeline")?;
+        let queue = gst::ElementFactory::make("queue").name("voice_queue").property_from_str("leaky", "downstream").build()?;
+        let convert = gst::ElementFactory::make("audioconvert").name("voice_convert").build()?;
+        let resample = gst::ElementFactory::make("audioresample").name("voice_resample").build()?;
+        let caps = gst_audio::AudioCapsBuilder::new_interleaved()
+            .format(gst_audio::AudioFormat::F32le)
+            .rate(48000)
+            .channels(2)
+            .build();
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(VOICE_TAP_CHUNKS);
+        let appsink = gst_app::AppSink::builder()
+            .name("voice_sink")
+            .caps(&caps)
+            // the main output already paces the pipeline
+            .sync(false)
+            .callbacks(gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |appsink| {
+                    let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    if let Some(map) = sample.buffer().and_then(|b| b.map_readable().ok()) {
+                        let _ = sender.try_send(map.as_slice().to_vec());
+                    }
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build())
+            .build();
+        let elements = [&queue, &convert, &resample, appsink.upcast_ref()];
+        self.pipeline.add_many(elements)?;
+        gst::Element::link_many(elements)?;
+        let tee_pad = get_value_or_error(tee.request_pad_simple("src_%u"), "unable to get a tee pad")?;
+        tee_pad.link(&get_value_or_error(queue.static_pad("sink"), "unable to get voice queue pad")?)?;
+        for element in elements {
+            element.sync_state_with_parent()?;
+        }
+        info!("attached audio tap");
+        Ok(receiver)
+    }
+
+    pub fn detach_audio_tap(&mut self) -> Result<(), Error> {
+        let elements: Vec<gst::Element> = ["voice_queue", "voice_convert", "voice_resample", "voice_sink"].iter()
+            .filter_map(|name| self.pipeline.by_name(name))
+            .collect();
+        if elements.is_empty() {
+            return Ok(())
+        }
+        detach_tee_branch(&self.pipeline, elements)?;
+        info!("detached audio tap");
+        Ok(())
+    }
+
+    pub fn stats(&self) -> StreamStats {
+        self.stats.clone()
+    }
+
+    pub fn stream_health(&self) -> StreamHealth {
+        let queue_level = |name: &str| self.pipeline.by_name(name)
+            .map(|q| Duration::from_nanos(q.property::<u64>("current-level-time")));
+        StreamHealth {
+            item: self.current_item.clone(),
+            playing: self.pipeline.current_state() == gst::State::Playing,
+            uptime: self.streaming_since.map(|s| s.elapsed()),
+            bytes_sent: self.stats.bytes_sent.load(Ordering::Relaxed),
+            dropped_frames: self.stats.dropped_frames.load(Ordering::Relaxed),
+            buffering_percent: self.stats.buffering_percent.load(Ordering::Relaxed),
+            video_buffer: queue_level("video_queue"),
+            audio_buffer: queue_level("audio_queue"),
+            position: self.position(),
+        }
+    }
+
+    pub fn pipeline_stats(&self) -> PipelineStats {
+        let state = match self.pipeline.state(gst::ClockTime::ZERO) {
+            (_, current, gst::State::VoidPending) => format!("{:?}", current),
+            (_, current, pending) => format!("{:?} (going to {:?})", current, pending),
+        };
+        let mut query = gst::query::Latency::new();
+        let latency = self.pipeline.query(&mut query).then(|| {
+            let (live, min, max) = query.result();
+            PipelineLatency { live, min: min.into(), max: max.map(Duration::from) }
+        });
+        let mut queues = self.pipeline.children().into_iter()
+            .filter(|e| e.factory().is_some_and(|f| f.name() == "queue"))
+            .map(|q| QueueLevel {
+                name: q.name().to_string(),
+                time: Duration::from_nanos(q.property::<u64>("current-level-time")),
+                max_time: Duration::from_nanos(q.property::<u64>("max-size-time")),
+                buffers: q.property("current-level-buffers"),
+                bytes: q.property("current-level-bytes"),
+            })
+            .collect::<Vec<QueueLevel>>();
+        queues.sort_by(|a, b| a.name.cmp(&b.name));
+        PipelineStats {
+            state,
+            encoder: self.encoder.encoder_element(),
+            target_bitrate: self.encoder.bitrate,
+            framerate: output_framerate(&self.pipeline),
+            bytes_sent: self.stats.bytes_sent.load(Ordering::Relaxed),
+            frames_encoded: self.stats.frames_encoded.load(Ordering::Relaxed),
+            dropped_frames: self.stats.dropped_frames.load(Ordering::Relaxed),
+            latency,
+            queues,
+        }
+    }
+
+    // called periodically by the watchdog. a pipeline that claims to be playing but doesn't move is wedged
+    pub async fn check_progress(&mut self, stall_timeout: Duration) -> Result<(), Error> {
+        let item = match &self.current_item {
+            Some(i) if self.pipeline.current_state() == gst::State::Playing => i.clone(),
+            _ => {
+                self.progress = None;
+                return Ok(())
+            }
+        };
+        let position = self.pipeline.query_position::<gst::ClockTime>().unwrap_or(gst::ClockTime::ZERO);
+        let stalled_for = match &self.progress {
+            Some(mark) if mark.position == position => mark.since.elapsed(),
+            _ => {
+                self.progress = Some(ProgressMark { position, since: Instant::now() });
+                return Ok(())
+            }
+        };
+        if stalled_for < stall_timeout {
+            return Ok(())
+        }
+        if self.last_stall_recovery.map(|t| t.elapsed() >= STALL_RECOVERY_RESET).unwrap_or(false) {
+            self.stall_recoveries = 0;
+        }
+        let action = if self.stall_recoveries == 0 {
+            self.stall_recoveries += 1;
+            self.last_stall_recovery = Some(Instant::now());
+            if item.is_live() {
+                self.reconnect_current_item(&item.id()).await?;
+            } else {
+                // a flushing seek to where we got stuck is usually enough to kick a wedged demuxer
+                self.pipeline.seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT, position)?;
+            }
+            StallAction::Restarted
+        } else {
+            self.advance(FinishReason::Failed, Some(format!("stalled for {}s", stalled_for.as_secs()))).await?;
+            StallAction::Skipped
+        };
+        self.progress = None;
+        let message = format!("{} stalled at {} for {}s, {:?}", item.name(), position, stalled_for.as_secs(), action);
+        self.bus_log.push("watchdog", "playqueue".to_string(), message);
+        self.events.emit(PlaybackEvent::Stalled { item, stalled_for, action });
+        Ok(())
+    }
+
+    // live items never end on their own. an eos means the stream dropped so hand back how long to wait before reconnecting
+    pub async fn handle_end_of_item(&mut self) -> Result<Option<Duration>, Error> {
+        if self.in_standby {
+            // only a standby clip ends, start it over
+            self.pipeline.seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT, gst::ClockTime::ZERO)?;
+            return Ok(None)
+        }
+        if let Some(delay) = self.live_reconnect_delay() {
+            info!("live stream dropped, reconnecting in {}s", delay.as_secs());
+            return Ok(Some(delay))
+        }
+        match self.intermission_config.gap {
+            Some(gap) if !self.uris.is_empty() => self.start_intermission(gap).await?,
+            _ => self.advance(FinishReason::Completed, None).await?,
+        }
+        Ok(None)
+    }
+
+    // finish the item but hold off on the next one. the actor starts it once the gap is over
+    async fn start_intermission(&mut self, gap: Duration) -> Result<(), Error> {
+        self.finish_current_item(FinishReason::Completed, None).await?;
+        if let Err(e) = self.enter_standby() {
+            warn!("unable to show the standby source during the intermission: {}", e);
+        }
+        // repeat one may have put the item back so only look at what is up next now
+        let next = match self.uris.front() {
+            Some(i) => i.clone(),
+            None => return self.start_playback().await.map(|_| ()),
+        };
+        info!("intermission for {}s before {}", gap.as_secs(), next.name());
+        let intermission = Intermission { id: Uuid::new_v4(), next, ends_at: SystemTime::now() + gap };
+        self.events.emit(PlaybackEvent::IntermissionStarted { intermission: intermission.clone() });
+        self.intermission = Some(intermission);
+        Ok(())
+    }
+
+    pub fn intermission(&self) -> Option<Intermission> {
+        self.intermission.clone()
+    }
+
+    // called when the gap is over. does nothing if playback was started, stopped or skipped in the meantime
+    pub async fn end_intermission(&mut self, id: &Uuid) -> Result<(), Error> {
+        if self.intermission.as_ref().map(|i| i.id) != Some(*id) {
+            return Ok(())
+        }
+        self.start_playback().await.map(|_| ())
+    }
+
+    pub async fn handle_pipeline_error(&mut self, unsupported_media: bool, reason: String) -> Result<Option<Duration>, Error> {
+        if self.in_standby {
+            warn!("standby source failed, stopping the stream: {}", reason);
+            self.leave_standby()?;
+            return Ok(None)
+        }
+        if unsupported_media {
+            let item_name = self.current_item.as_ref().map(|i| i.name()).unwrap_or("<unknown>".to_string());
+            let skip_message = format!("skipping {}: {}", item_name, reason);
+            error!("{}", skip_message);
+            self.bus_log.push("skipped", "playqueue".to_string(), skip_message);
+            self.advance(FinishReason::Failed, Some(reason)).await?;
+            return Ok(None)
+        }
+        let item = match &self.current_item {
+            Some(i) if i.is_live() => return self.handle_end_of_item().await,
+            Some(i) => i.clone(),
+            None => return Ok(None),
+        };
+        if self.error_retries >= self.recovery.max_retries {
+            let skip_message = format!("skipping {} after {} restarts: {}", item.name(), self.error_retries, reason);
+            error!("{}", skip_message);
+            self.bus_log.push("skipped", "playqueue".to_string(), skip_message);
+            self.advance(FinishReason::Failed, Some(reason)).await?;
+            return Ok(None)
+        }
+        self.error_retries += 1;
+        let restart_message = format!("restarting {} (attempt {} of {}): {}", item.name(), self.error_retries, self.recovery.max_retries, reason);
+        warn!("{}", restart_message);
+        self.bus_log.push("restarted", "playqueue".to_string(), restart_message);
+        if let Err(e) = self.restart_current_item().await {
+            error!("unable to restart {}, skipping it: {}", item.name(), e);
+            self.advance(FinishReason::Failed, Some(reason)).await?;
+        }
+        Ok(None)
+    }
+
+    // the output went away, not the item. stop everything and have the actor try again after the returned delay
+    pub fn handle_output_error(&mut self, reason: String) -> Result<Option<Duration>, Error> {
+        if self.output_connected_at.map(|t| t.elapsed() >= LIVE_STABLE_AFTER).unwrap_or(false) {
+            self.output_reconnects = 0;
+        }
+        self.remember_position();
+        stop_pipeline(&self.pipeline)?;
+        if self.output_reconnects >= MAX_OUTPUT_RECONNECTS {
+            let message = format!("giving up on the output after {} reconnect attempts: {}", self.output_reconnects, reason);
+            error!("{}", message);
+            self.bus_log.push("output", "playqueue".to_string(), message);
+            self.output_reconnects = 0;
+            return Ok(None)
+        }
+        let delay = (OUTPUT_RECONNECT_BASE_DELAY * 2u32.pow(self.output_reconnects)).min(OUTPUT_RECONNECT_MAX_DELAY);
+        self.output_reconnects += 1;
+        let message = format!("output failed, reconnecting in {}s (attempt {} of {}): {}", delay.as_secs(), self.output_reconnects, MAX_OUTPUT_RECONNECTS, reason);
+        warn!("{}", message);
+        self.bus_log.push("output", "playqueue".to_string(), message);
+        Ok(Some(delay))
+    }
+
+    // does nothing when playback was stopped or started again in the meantime
+    pub async fn reconnect_output(&mut self) -> Result<(), Error> {
+        if self.pipeline.current_state() != gst::State::Null {
+            return Ok(())
+        }
+        if self.in_standby {
+            self.in_standby = false;
+            self.enter_standby()?;
+        } else if self.current_item.is_some() {
+            self.start_current_item().await?;
+        } else {
+            return Ok(())
+        }
+        info!("output reconnected");
+        self.output_connected_at = Some(Instant::now());
+        Ok(())
+    }
+
+    // so a restart picks up close to where the item was
+    fn remember_position(&mut self) {
+        let position = self.position();
+        if let (Some(item), Some(position)) = (self.current_item.as_mut(), position.filter(|p| !p.is_zero())) {
+            if !item.is_live() {
+                item.start_at = Some(position);
+            }
+        }
+    }
+
+    // build a fresh source for the current item and carry on from where it broke
+    async fn restart_current_item(&mut self) -> Result<(), Error> {
+        self.remember_position();
+        stop_pipeline(&self.pipeline)?;
+        self.start_current_item().await
+    }
+
+    async fn start_current_item(&mut self) -> Result<(), Error> {
+        let item = match &self.current_item {
+            Some(i) => i.clone(),
+            None => return Ok(()),
+        };
+        // whatever state the failed decoder was in goes away with it
+        let kind = source_kind(&self.pipeline);
+        swap_source(&self.pipeline, kind, &self.fallback, &self.standby, Arc::clone(&self.source_http_options), &self.handover)?;
+        self.resolver.invalidate(&item.uri());
+        let play_uri = self.resolver.playable_uri(&item.uri()).await?;
+        let kind = self.item_source_kind(&item, &play_uri);
+        self.set_item_source(&item, &play_uri, kind)?;
+        start_pipeline(&self.pipeline)?;
+        self.progress = None;
+        self.apply_trim().await;
+        Ok(())
+    }
+
+    fn live_reconnect_delay(&mut self) -> Option<Duration> {
+        if !self.current_item.as_ref().map(|i| i.is_live()).unwrap_or(false) {
+            return None
+        }
+        if let Some(started) = self.live_started {
+            if started.elapsed() >= LIVE_STABLE_AFTER {
+                self.live_reconnects = 0;
+            }
+        }
+        if self.live_reconnects >= MAX_LIVE_RECONNECTS {
+            warn!("giving up on live stream after {} reconnect attempts", self.live_reconnects);
+            return None
+        }
+        let delay = LIVE_RECONNECT_BASE_DELAY * 2u32.pow(self.live_reconnects);
+        self.live_reconnects += 1;
+        Some(delay)
+    }
+
+    pub async fn reconnect_current_item(&mut self, item_id: &Uuid) -> Result<(), Error> {
+        let item = match &self.current_item {
+            Some(i) if i.id() == *item_id => i.clone(),
+            // someone stopped or skipped while we were waiting to reconnect
+            _ => return Ok(()),
+        };
+        stop_pipeline(&self.pipeline)?;
+        self.resolver.invalidate(&item.uri());
+        let play_uri = self.resolver.playable_uri(&item.uri()).await?;
+        let kind = self.item_source_kind(&item, &play_uri);
+        self.set_item_source(&item, &play_uri, kind)?;
+        start_pipeline(&self.pipeline)?;
+        self.live_started = Some(Instant::now());
+        Ok(())
+    }
+
+    // build a queue item from a uri, resolving web links on the way. this can be slow so it doesn't touch the queue itself
+    pub async fn prepare_item(resolver: &SourceResolver, uri: String, display_name: String, stop_fn: Option<StopFn>, options: QueueItemOptions) -> Result<QueueItem, Error> {
+        let mut queue_url = path_or_url(&uri)?;
+        let subtitles = match &options.subtitles {
+            Some(s) => Some(path_or_url(s)?),
+            None => queue_url.to_file_path().ok().and_then(|p| find_sidecar_subtitles(&p)),
+        };
+        let mut http = options.http;
+        http.take_url_credentials(&mut queue_url);
+        let mut display_name = display_name;
+        let mut live = SourceResolver::is_live_uri(&queue_url);
+        if SourceResolver::is_web_video(&queue_url) {
+            // resolve up front so bad links fail on add instead of when they come up in the queue
+            let resolved = resolver.resolve(&queue_url).await?;
+            if let Some(title) = resolved.title {
+                display_name = title;
+            }
+            live = live || resolved.is_live;
+        }
+        let mut queue_item = QueueItem::new(display_name, queue_url, stop_fn);
+        queue_item.live = options.live.unwrap_or(live);
+        queue_item.http = http;
+        queue_item.webhook = options.webhook;
+        queue_item.max_framerate = options.max_framerate;
+        if let Some(subtitles) = &subtitles {
+            info!("using subtitles {} for {}", subtitles, queue_item.name());
+        }
+        queue_item.subtitles = subtitles;
+        queue_item.chapters = options.chapters;
+        queue_item.start_at = options.start_at;
+        queue_item.requested_by = options.requested_by;
+        queue_item.watched_mark = options.watched_mark;
+        queue_item.emby_item = options.emby_item;
+        queue_item.copy = options.copy;
+        if queue_item.copy {
+            // the discoverer can take a while so it runs here rather than when the item comes up in the actor
+            queue_item.copy_blocker = match resolver.playable_uri(&queue_item.uri).await {
+                Ok(play_uri) => match media_inspect::inspect(play_uri, queue_item.http.clone()).await {
+                    Ok(info) => info.remux_blocker(),
+                    Err(e) => Some(format!("unable to inspect it: {}", e)),
+                },
+                Err(e) => Some(format!("unable to resolve it: {}", e)),
+            };
+        }
+        Ok(queue_item)
+    }
+
+    // Function to add a URI to the queue
+    pub fn enqueue_item(&mut self, queue_item: QueueItem, position: QueuePosition) -> Result<QueueItem, Error> {
+        self.limits.check(&self.uris, &queue_item)?;
+        match position {
+            QueuePosition::Last => self.uris.push_back(queue_item.clone()),
+            QueuePosition::Next => self.uris.push_front(queue_item.clone()),
+        }
+        Ok(queue_item)
+    }
+
+    pub fn repeat_mode(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    pub fn set_repeat_mode(&mut self, mode: RepeatMode) {
+        info!("repeat mode is now {}", mode.label());
+        self.repeat = mode;
+    }
+
+    // new_index past the end moves the item to the back of the queue
+    pub fn move_item(&mut self, id: &Uuid, new_index: usize) -> Result<usize, Error> {
+        let index = get_value_or_error(self.uris.iter().position(|u| u.id == *id), "item is not in the queue")?;
+        let item = get_value_or_error(self.uris.remove(index), "item is not in the queue")?;
+        let new_index = new_index.min(self.uris.len());
+        self.uris.insert(new_index, item);
+        Ok(new_index)
+    }
+
+    // drop everything that is still to come, the current item keeps playing. returns how many items were dropped
+    pub fn clear(&mut self) -> usize {
+        let count = self.uris.len();
+        self.uris.clear();
+        count
+    }
+
+    // randomise what is still to come, the current item keeps playing
+    pub fn shuffle(&mut self) -> usize {
+        self.uris.make_contiguous().shuffle(&mut rand::thread_rng());
+        self.uris.len()
+    }
+
 
     // Function to remove a URI from the queue
     pub fn remove_uri(&mut self, id: &Uuid) -> Result<(), Error> {
@@ -154,14 +2670,62 @@ impl PlayQueue {
         self.current_item.clone()
     }
 
-    fn queue_next_item(&mut self) -> Result<Option<QueueItem>, Error> {
+    fn bumper_due(&self) -> bool {
+        !self.bumper_played
+            && self.items_started.is_multiple_of(self.bumpers.every.max(1))
+            && self.uris.front().is_some_and(|i| !i.bumper)
+    }
+
+    fn count_started(&mut self, item: &QueueItem) {
+        self.bumper_played = item.bumper;
+        if !item.bumper {
+            self.items_started += 1;
+        }
+    }
+
+    // a bumper that can't be played is dropped instead of holding up the queue
+    fn requeue_failed(&mut self, item: QueueItem) {
+        match item.bumper {
+            true => self.bumper_played = true,
+            false => self.uris.push_front(item),
+        }
+    }
+
+    async fn queue_next_item(&mut self) -> Result<Option<QueueItem>, Error> {
+        if self.bumper_due() {
+            if let Some(bumper) = self.bumpers.pick() {
+                info!("playing bumper {} first", bumper.name());
+                self.uris.push_front(bumper);
+            }
+        }
         if let Some(uri) = self.uris.pop_front() {
-            match set_source_uri(&self.pipeline, uri.uri().as_str()) {
+            // the handle and the prefetcher resolve what is up next so this normally comes out of the cache.
+            // only an item that got in front in the meantime still waits on yt-dlp or the media server here
+            let resolved = match self.resolver.cached_playable_uri(&uri.uri()) {
+                Some(u) => Ok(u),
+                None => self.resolver.playable_uri(&uri.uri()).await,
+            };
+            let play_uri = match resolved {
+                Ok(u) => u,
+                Err(e) => {
+                    self.requeue_failed(uri);
+                    error!("Failed to resolve item {}", e);
+                    return Err(anyhow!("failed to resolve item: {}", e))
+                }
+            };
+            let kind = self.item_source_kind(&uri, &play_uri);
+            match self.set_item_source(&uri, &play_uri, kind) {
                 Ok(_) => {
+                    self.live_reconnects = 0;
+                    self.error_retries = 0;
+                    self.live_started = if uri.is_live() { Some(Instant::now()) } else { None };
+                    self.progress = None;
+                    self.stall_recoveries = 0;
+                    self.count_started(&uri);
                     self.current_item = Some(uri)
                 },
                 Err(e) => {
-                    self.uris.push_front(uri);
+                    self.requeue_failed(uri);
                     error!("Failed to queue item {}", e);
                     return Err(anyhow!("failed to queue item: {}", e))
                 }
@@ -172,16 +2736,208 @@ impl PlayQueue {
         Ok(self.current_item.clone())
     }
 
+    // puts the name of the current item on the video for a while, the actor hides it again
+    pub fn show_title(&mut self) -> Result<(), Error> {
+        let duration = self.title_config.duration.ok_or(anyhow!("the title overlay is turned off"))?;
+        let item = self.current_item.as_ref().ok_or(anyhow!("nothing is playing"))?;
+        let overlay = get_value_or_error(self.pipeline.by_name("title_overlay"), "unable to get title overlay from pipeline")?;
+        let text = match item.requested_by() {
+            Some(requester) => format!("Now playing: {}\nrequested by {}", item.name(), requester.name),
+            None => format!("Now playing: {}", item.name()),
+        };
+        overlay.set_property("text", text);
+        overlay.set_property("silent", false);
+        self.title = Some(TitleOverlay { id: Uuid::new_v4(), hides_at: Instant::now() + duration });
+        Ok(())
+    }
+
+    fn show_title_on_start(&mut self) {
+        if self.title_config.duration.is_none() || self.current_item.as_ref().is_some_and(|i| i.bumper) {
+            return
+        }
+        if let Err(e) = self.show_title() {
+            warn!("unable to show the title overlay: {}", e);
+        }
+    }
+
+    pub fn title_overlay(&self) -> Option<TitleOverlay> {
+        self.title.clone()
+    }
+
+    // does nothing when the title was shown again in the meantime
+    pub fn hide_title(&mut self, id: &Uuid) {
+        if self.title.as_ref().map(|t| t.id) != Some(*id) {
+            return
+        }
+        self.title = None;
+        if let Some(overlay) = self.pipeline.by_name("title_overlay") {
+            overlay.set_property("silent", true);
+        }
+    }
+
+    // turns the clock on or off, flips it without a choice. returns whether it's on now
+    pub fn show_clock(&mut self, shown: Option<bool>) -> Result<bool, Error> {
+        if !self.clock_config.enabled() {
+            return Err(anyhow!("the clock overlay is turned off"));
+        }
+        let overlay = get_value_or_error(self.pipeline.by_name("clock_overlay"), "unable to get clock overlay from pipeline")?;
+        let shown = shown.unwrap_or(overlay.property::<bool>("silent"));
+        overlay.set_property("silent", !shown);
+        Ok(shown)
+    }
+
+    // anything that needs the pipeline stopped to change has to start the slow way
+    fn gapless_candidate(&self) -> Option<&QueueItem> {
+        let current = self.current_item.as_ref()?;
+        let next = self.uris.front()?;
+        let records_items = std::iter::once(&self.output)
+            .chain(self.extra_outputs.iter().map(|(_, target)| target))
+            .any(|target| target.item_file().is_some());
+        let eligible = !current.is_live() && !next.is_live()
+            && current.subtitles.is_none() && next.subtitles.is_none()
+            && current.max_framerate == next.max_framerate
+            && !next.copy
+            && !records_items
+            // repeat one puts the current item back in front once it finishes
+            && self.repeat != RepeatMode::One
+            && self.intermission_config.gap.is_none()
+            // bumpers get put in front when the next item starts the normal way
+            && !self.bumper_due()
+            && source_kind(&self.pipeline) == SourceKind::Decode;
+        eligible.then_some(next)
+    }
+
+    // give the source what is up next so it can switch to it as soon as the current item runs out.
+    // the prefetcher resolves it outside the actor, until then there is nothing to hand over
+    pub fn prepare_next_item(&mut self) {
+        let next = match self.gapless_candidate() {
+            Some(i) => i.clone(),
+            None => return self.handover.prepare(None),
+        };
+        if self.handover.pending() == Some(next.id) && !self.handover.is_stale() {
+            return
+        }
+        let play_uri = match self.resolver.cached_playable_uri(&next.uri()) {
+            Some(u) => Some(u).filter(|u| !self.fallback.applies_to(&next, u)),
+            None => return self.handover.prepare(None),
+        };
+        if play_uri.is_some() {
+            info!("{} is ready to follow without a gap", next.name());
+        }
+        let fresh_until = self.resolver.fresh_until(&next.uri());
+        self.handover.prepare(Some(PreparedItem { id: next.id, play_uri, http: next.http.clone(), fresh_until }));
+    }
+
+    // the source moved on to the handed over item by itself, catch up with it
+    pub async fn handle_gapless_switch(&mut self, id: &Uuid) -> Result<(), Error> {
+        let index = match self.uris.iter().position(|i| i.id == *id) {
+            Some(i) => i,
+            None => {
+                warn!("handed over item {} was taken off the queue, starting over with what is next", id);
+                return self.advance(FinishReason::Skipped, Some("removed from the queue".to_string())).await
+            }
+        };
+        self.retire_current_item(FinishReason::Completed, None, None);
+        let item = self.uris.remove(index).unwrap();
+        info!("{} took over without a gap", item.name());
+        self.live_reconnects = 0;
+        self.error_retries = 0;
+        self.live_started = None;
+        self.progress = None;
+        self.stall_recoveries = 0;
+        self.stream_selection.reset(false);
+        self.toc_chapters.reset();
+        self.stats.reset_buffering();
+        self.count_started(&item);
+        self.current_item = Some(item.clone());
+        self.events.emit(PlaybackEvent::ItemStarted { item });
+        self.show_title_on_start();
+        self.apply_trim().await;
+        Ok(())
+    }
+
+    // copy items skip decoding when the output is flv and their streams fit into it, anything else gets transcoded
+    fn item_source_kind(&self, item: &QueueItem, play_uri: &Url) -> SourceKind {
+        if self.fallback.applies_to(item, play_uri) {
+            return SourceKind::Fallback
+        }
+        if !item.copy {
+            return SourceKind::Decode
+        }
+        let blocker = if !matches!(self.output, OutputTarget::Rtmp(_)) {
+            Some(format!("{} isn't muxed as flv", self.output.label()))
+        } else if item.subtitles.is_some() {
+            Some("subtitles have to be drawn onto the video".to_string())
+        } else if self.pipeline.by_name("voice_sink").is_some() {
+            Some("voice channels need the decoded audio".to_string())
+        } else {
+            item.copy_blocker.clone()
+        };
+        match blocker {
+            Some(reason) => {
+                info!("transcoding {} instead of copying it, {}", item.name(), reason);
+                SourceKind::Decode
+            }
+            None => {
+                info!("copying {} into the output without re-encoding", item.name());
+                SourceKind::Remux
+            }
+        }
+    }
+
+    fn set_item_source(&self, item: &QueueItem, play_uri: &Url, kind: SourceKind) -> Result<(), Error> {
+        self.handover.reset();
+        *self.source_http_options.lock().unwrap() = item.http.clone();
+        replace_source(&self.pipeline, kind, &self.fallback, &self.standby, Arc::clone(&self.source_http_options), &self.handover)?;
+        self.stream_selection.reset(item.subtitles.is_some());
+        self.toc_chapters.reset();
+        self.stats.reset_buffering();
+        set_output_framerate(&self.pipeline, &self.encoder, item.max_framerate.unwrap_or(self.encoder.max_framerate).max(1))?;
+        let outputs = std::iter::once(("sink", &self.output)).chain(self.extra_outputs.iter().map(|(name, target)| (name.as_str(), target)));
+        for (name, target) in outputs {
+            if let (Some(path), Some(sink)) = (target.item_file(), self.pipeline.by_name(name)) {
+                info!("recording {} to {}", item.name(), path.display());
+                sink.set_property("location", path.to_string_lossy().to_string());
+            }
+        }
+        set_subtitle_uri(&self.pipeline, item.subtitles.as_ref())?;
+        set_source_uri(&self.pipeline, play_uri.as_str())
+    }
+
     // Function to start playback
     pub async fn start_playback(&mut self) -> Result<Option<QueueItem>, Error> {
+        // starting by hand cuts the intermission short
+        self.intermission = None;
+        let was_standby = self.in_standby;
+        if was_standby {
+            self.leave_standby()?;
+        }
         match self.pipeline.current_state() {
             gst::State::Null => {
-                match self.queue_next_item() {
+                match self.queue_next_item().await {
                     Ok(i) => {
                         start_pipeline(&self.pipeline)?;
+                        self.apply_trim().await;
+                        self.streaming_since.get_or_insert_with(Instant::now);
+                        if let Some(item) = &i {
+                            self.events.emit(PlaybackEvent::ItemStarted { item: item.clone() });
+                            self.show_title_on_start();
+                        }
                         return Ok(i)
                     }
-                    Err(e) =>  return Err(e)
+                    Err(e) => {
+                        // running dry mid stream is the end of the session, a failed play on an empty queue isn't
+                        let was_streaming = self.streaming_since.take().is_some();
+                        if was_streaming && self.uris.is_empty() {
+                            self.events.emit(PlaybackEvent::QueueFinished);
+                        }
+                        if was_streaming || was_standby {
+                            if let Err(e) = self.enter_standby() {
+                                warn!("unable to switch to the standby source: {}", e);
+                            }
+                        }
+                        return Err(e)
+                    }
                 }
             }
             gst::State::Paused => {
@@ -193,15 +2949,59 @@ impl PlayQueue {
         Ok(self.current_item.clone())
     }
 
+    // with a standby source the first stop switches to it and a second one takes the stream down
     pub async fn stop_playback(&mut self) -> Result<(), Error> {
+        self.streaming_since = None;
+        self.intermission = None;
+        if self.in_standby {
+            return self.leave_standby()
+        }
+        let was_playing = self.current_item.is_some();
+        self.finish_current_item(FinishReason::Stopped, None).await?;
+        if was_playing {
+            self.enter_standby()?;
+        }
+        Ok(())
+    }
+
+    // keep the stream going with the standby source. does nothing when there is none configured
+    fn enter_standby(&mut self) -> Result<(), Error> {
+        if !self.standby.is_enabled() || self.in_standby {
+            return Ok(())
+        }
+        info!("nothing to play, switching to the standby source");
+        stop_pipeline(&self.pipeline)?;
+        self.handover.reset();
+        self.stream_selection.reset(false);
+        self.toc_chapters.reset();
+        set_output_framerate(&self.pipeline, &self.encoder, self.encoder.max_framerate.max(1))?;
+        match &self.standby {
+            StandbySource::Clip(uri) => {
+                replace_source(&self.pipeline, SourceKind::Decode, &self.fallback, &self.standby, Arc::clone(&self.source_http_options), &self.handover)?;
+                *self.source_http_options.lock().unwrap() = HttpSourceOptions::default();
+                set_subtitle_uri(&self.pipeline, None)?;
+                set_source_uri(&self.pipeline, uri)?;
+            }
+            _ => replace_source(&self.pipeline, SourceKind::Standby, &self.fallback, &self.standby, Arc::clone(&self.source_http_options), &self.handover)?,
+        }
+        // the standby bin has no uri for start_pipeline to look at
+        self.pipeline.set_state(gst::State::Playing)?;
+        self.in_standby = true;
+        Ok(())
+    }
+
+    fn leave_standby(&mut self) -> Result<(), Error> {
+        self.in_standby = false;
+        stop_pipeline(&self.pipeline)
+    }
+
+    async fn finish_current_item(&mut self, reason: FinishReason, detail: Option<String>) -> Result<(), Error> {
         match self.pipeline.current_state() {
             gst::State::Playing|gst::State::Paused|gst::State::Ready => {
+                // after a gapless switch the pipeline position runs on across items, the source has the item's own
+                let position = self.position();
                 stop_pipeline(&self.pipeline)?;
-                match &self.current_item {
-                    Some(i) => {i.run_stop_fn().await; ()},
-                    None => (),
-                }
-                self.current_item = None;
+                self.retire_current_item(reason, detail, position);
             }
             _ => {
             }
@@ -210,6 +3010,48 @@ impl PlayQueue {
         Ok(())
     }
 
+    fn retire_current_item(&mut self, reason: FinishReason, detail: Option<String>, position: Option<Duration>) {
+        if let Some(id) = self.title.as_ref().map(|t| t.id) {
+            self.hide_title(&id);
+        }
+        if let Some(i) = self.current_item.take() {
+            if self.repeat.requeues(reason) && !i.bumper {
+                match self.repeat {
+                    RepeatMode::One => self.uris.push_front(i.requeued()),
+                    _ => self.uris.push_back(i.requeued()),
+                }
+            }
+            self.events.emit(PlaybackEvent::ItemFinished { item: i.clone(), reason, detail, position });
+            // the stop callback talks to emby so don't hold up the queue waiting on it
+            tokio::spawn(async move {
+                i.run_stop_fn().await;
+            });
+        }
+    }
+
+    // skip leading and stop before trailing dead air found by the trim analysis
+    async fn apply_trim(&self) {
+        let item = match &self.current_item {
+            Some(i) => i.clone(),
+            None => return,
+        };
+        let mut offsets = self.trimmer.offsets(&item.uri()).unwrap_or_default();
+        if let Some(start_at) = item.start_at {
+            // the leading trim only matters when it goes past where we are resuming
+            offsets.start = Some(offsets.start.map_or(start_at, |s| s.max(start_at)));
+        }
+        if offsets.is_empty() {
+            return
+        }
+        info!("trimming {} to {:?}", item.name(), offsets);
+        let pipeline = self.pipeline.clone();
+        match tokio::task::spawn_blocking(move || trim_pipeline(&pipeline, offsets)).await {
+            Ok(Ok(_)) => (),
+            Ok(Err(e)) => warn!("unable to trim {}: {}", item.name(), e),
+            Err(e) => warn!("unable to trim {}: {}", item.name(), e),
+        }
+    }
+
     pub async fn pause_playback(&mut self) -> Result<(), Error> {
         match self.pipeline.current_state() {
             gst::State::Playing => {
@@ -223,8 +3065,13 @@ impl PlayQueue {
         Ok(())
     }
 
-    pub async fn skip_video(&mut self) -> Result<(), Error> {
-        match self.stop_playback().await {
+    pub async fn skip_video(&mut self, reason: Option<String>) -> Result<(), Error> {
+        self.advance(FinishReason::Skipped, reason).await
+    }
+
+    // finish the current item and move on to the next one
+    async fn advance(&mut self, reason: FinishReason, detail: Option<String>) -> Result<(), Error> {
+        match self.finish_current_item(reason, detail).await {
             Ok(_) => {
             }
             Err(e) => {
@@ -235,63 +3082,83 @@ impl PlayQueue {
         Ok(())
     }
 
-    pub async fn seek_video(&mut self, seek_seconds: i64) -> Result<u64, Error> {
-        match seek_pipeline(&self.pipeline, seek_seconds) {
-            Ok(pos) => {
-                Ok(pos)
-            }
-            Err(e) => {
-                Err(e)
-            }
+    pub async fn seek_video(&mut self, seek_seconds: i64) -> Result<SeekResult, Error> {
+        if self.current_item.as_ref().map(|i| i.is_live()).unwrap_or(false) {
+            return Err(anyhow!("cannot seek in a live stream"))
         }
+        // waiting for the seek to settle blocks so keep it off the runtime threads
+        let pipeline = self.pipeline.clone();
+        let result = tokio::task::spawn_blocking(move || seek_pipeline(&pipeline, seek_seconds)).await??;
+        self.progress = None;
+        Ok(result)
+    }
+
+    pub fn recent_bus_messages(&self) -> Vec<BusLogEntry> {
+        self.bus_log.entries()
+    }
+
+    // the frame that is on the stream right now, as it went into the encoder
+    pub fn last_frame(&self) -> Result<gst::Sample, Error> {
+        let buffer = self.stats.last_frame.lock().unwrap().clone().ok_or(anyhow!("no video has gone out yet"))?;
+        let filter = get_value_or_error(self.pipeline.by_name("framerate_filter"), "unable to get framerate filter from pipeline")?;
+        let caps = get_value_or_error(filter.static_pad("src").and_then(|p| p.current_caps()), "the video output has no caps yet")?;
+        Ok(gst::Sample::builder().buffer(&buffer).caps(&caps).build())
+    }
+
+    // graphviz dump of the whole pipeline, handy for debugging caps negotiation remotely.
+    // element properties are left out, they carry the stream key, emby api keys and http credentials
+    pub fn pipeline_dot(&self) -> String {
+        let details = gst::DebugGraphDetails::MEDIA_TYPE | gst::DebugGraphDetails::CAPS_DETAILS | gst::DebugGraphDetails::STATES;
+        self.pipeline.debug_to_dot_data(details).to_string()
     }
 
     // More functions for controlling playback and handling EOS, etc.
 }
 
 
-fn configure_encodebin_rtmp(encodebin: &gst::Element) {
-    // To tell the encodebin what we want it to produce, we create an EncodingProfile
-    // https://gstreamer.freedesktop.org/data/doc/gstreamer/head/gst-plugins-base-libs/html/GstEncodingProfile.html
-    // This profile consists of information about the contained audio and video formats
-    // as well as the container format we want everything to be combined into.
+// To tell the encodebin what we want it to produce, we create an EncodingProfile
+// https://gstreamer.freedesktop.org/data/doc/gstreamer/head/gst-plugins-base-libs/html/GstEncodingProfile.html
+// This profile consists of information about the audio and video formats as well as the container
+// they are combined into, which comes from the output (flv for rtmp, mpeg-ts or matroska for the rest)
+fn configure_encodebin(encodebin: &gst::Element, encoder: &EncoderConfig, output: &OutputTarget) -> Result<(), Error> {
+    let (container_caps, container_fields) = output.container(encoder.codec)?;
+    let audio_profile = audio_encoding_profile(&output.audio_caps(encoder.codec), encoder);
+
+    info!("encoding for {} with {:?}", output.label(), encoder);
+    let video_profile = video_encoding_profile(encoder);
+
+    let mut container_profile = gst_pbutils::EncodingContainerProfile::builder(&container_caps)
+        .name("container")
+        .add_profile(video_profile)
+        .add_profile(audio_profile);
+    if !container_fields.is_empty() {
+        let mut container_props = gst_pbutils::ElementProperties::builder_general();
+        for (name, value) in container_fields {
+            container_props = container_props.field(name, value);
+        }
+        container_profile = container_profile.element_properties(container_props.build());
+    }
 
-    let audiocaps = gst_audio::AudioCapsBuilder::for_encoding("audio/mpeg").channels(2).rate_range(1000..100000)
-        .field("mpegversion", 1).field("layer", 3).build();
-    let audio_profile =
-        gst_pbutils::EncodingAudioProfile::builder(&audiocaps)
-            .presence(0)
-            .build();
+    encodebin.set_property("profile", container_profile.build());
+    Ok(())
+}
 
-    
-    let encoder_props = gst_pbutils::ElementProperties::builder_map().item(
-        ElementPropertiesMapItem::builder("x264enc")
-            .field("pass", 5)
-            .field("quantizer", 21)
-            .field("bitrate", 3000)
-            .build()
-    ).build();
-    let videocaps = gst_video::VideoCapsBuilder::for_encoding("video/x-h264").build();
-    let video_profile =
-        gst_pbutils::EncodingVideoProfile::builder(&videocaps)
-            .presence(0)
-            .variable_framerate(true)
-            .element_properties(encoder_props)
-            .preset_name("x264enc")
-            .build();
-    
-    let contianer_props = gst_pbutils::ElementProperties::builder_general().field("streamable", true).build();
-    let container_profile = gst_pbutils::EncodingContainerProfile::builder(
-        &gst::Caps::builder("video/x-flv").build(),
-    )
-    .name("container")
-    .add_profile(video_profile)
-    .add_profile(audio_profile)
-    .element_properties(contianer_props)
-    .build();
+fn audio_encoding_profile(audio_caps: &gst::Caps, encoder: &EncoderConfig) -> gst_pbutils::EncodingAudioProfile {
+    let profile = gst_pbutils::EncodingAudioProfile::builder(audio_caps).presence(0);
+    match encoder.audio_properties(audio_caps) {
+        Some(properties) => profile.element_properties(properties).build(),
+        None => profile.build(),
+    }
+}
 
-    // Finally, apply the EncodingProfile onto our encodebin element.
-    encodebin.set_property("profile", &container_profile);
+fn video_encoding_profile(encoder: &EncoderConfig) -> gst_pbutils::EncodingVideoProfile {
+    // the frame rate is fixed by our own videorate in front of encodebin so it can change per item
+    gst_pbutils::EncodingVideoProfile::builder(&encoder.codec.caps())
+        .presence(0)
+        .variable_framerate(true)
+        .element_properties(encoder.element_properties())
+        .preset_name(&encoder.encoder_element())
+        .build()
 }
 
 fn get_string_property(element: &gst::Element, property_name: &str) -> Result<String, Error> {
@@ -313,35 +3180,66 @@ pub(crate) fn start_pipeline(pipeline: &Pipeline) -> Result<String, Error> {
     Ok(set_uri)
 }
 
-pub(crate) fn seek_pipeline(pipeline: &Pipeline, seek_seconds: i64) -> Result<u64, Error> {
+pub(crate) fn seek_pipeline(pipeline: &Pipeline, seek_seconds: i64) -> Result<SeekResult, Error> {
+    seek_pipeline_with(pipeline, |current| current + seek_seconds as i128 * 1_000_000_000)
+}
+
+pub(crate) fn seek_pipeline_to(pipeline: &Pipeline, position: Duration) -> Result<SeekResult, Error> {
+    seek_pipeline_with(pipeline, |_| position.as_nanos() as i128)
+}
+
+// requested gets the current position and returns where to go, both in nanoseconds
+fn seek_pipeline_with(pipeline: &Pipeline, requested: impl FnOnce(i128) -> i128) -> Result<SeekResult, Error> {
     if pipeline.current_state() != gst::State::Playing {
         return Err(anyhow!("cannot seek on non-playing stream"))
     }
     let src_element = get_value_or_error(pipeline.by_name("src"), "unable to get source element from pipeline")?;
 
     let current_pos_ct = get_value_or_error(src_element.query_position::<gst::ClockTime>(), "unable to get current position")?;
-    let max_pos_ct = get_value_or_error(src_element.query_duration::<gst::ClockTime>(), "unable to get max position")?;
+    // some sources (progressive http without a content length) don't know how long they are
+    let duration_ct = src_element.query_duration::<gst::ClockTime>().filter(|d| d.nseconds() > 0);
     info!("current position {}s", current_pos_ct.seconds());
-    let new_pos = if seek_seconds.is_negative() {
-        if current_pos_ct.seconds() > seek_seconds.wrapping_abs() as u64 {
-            current_pos_ct.seconds() - seek_seconds.wrapping_abs() as u64
-        } else {
-            0
-        }
-    } else {
-        let next_pos = current_pos_ct.seconds() + seek_seconds.wrapping_abs() as u64;
-        if next_pos >= max_pos_ct.seconds() && max_pos_ct.seconds() > 0 {
-            max_pos_ct.seconds()
-        } else {
-            next_pos
-        }
-    };
+    let requested = requested(current_pos_ct.nseconds() as i128);
+    // stop a little short of the end so the item finishes by itself instead of jumping straight into the next one
+    let max_pos = duration_ct
+        .map(|d| d.nseconds().saturating_sub(SEEK_END_MARGIN.as_nanos() as u64) as i128)
+        .unwrap_or(i128::MAX);
+    let new_pos = requested.clamp(0, max_pos);
     let seek_flags = gst::SeekFlags::FLUSH;
-    info!("setting position to {}", new_pos);
-
-    src_element.seek_simple(seek_flags, gst::ClockTime::from_seconds(new_pos))?;
+    info!("setting position to {}ns (requested {}ns)", new_pos, requested);
+
+    src_element.seek_simple(seek_flags, gst::ClockTime::from_nseconds(new_pos as u64))?;
+    // a flushing seek prerolls again. wait for that so the position we report is the real one
+    let _ = pipeline.state(gst::ClockTime::from_seconds(SEEK_SETTLE_SECS));
+    let actual_pos = src_element.query_position::<gst::ClockTime>().map(|p| p.nseconds()).unwrap_or(new_pos as u64);
+
+    Ok(SeekResult {
+        position: Duration::from_nanos(actual_pos),
+        duration: duration_ct.map(|d| Duration::from_nanos(d.nseconds())),
+        clamped: new_pos != requested,
+    })
+}
 
-    return Ok(new_pos)
+// the stop position sticks around for later seeks so the end trim also holds when someone seeks
+pub(crate) fn trim_pipeline(pipeline: &Pipeline, offsets: TrimOffsets) -> Result<(), Error> {
+    // the pipeline has to be prerolled before it accepts a seek
+    let (state_result, _, _) = pipeline.state(gst::ClockTime::from_seconds(SEEK_SETTLE_SECS * 5));
+    state_result?;
+    let src_element = get_value_or_error(pipeline.by_name("src"), "unable to get source element from pipeline")?;
+    let start = offsets.start.unwrap_or_default();
+    let (stop_type, stop) = match offsets.end {
+        Some(end) => (gst::SeekType::Set, Some(gst::ClockTime::from_nseconds(end.as_nanos() as u64))),
+        None => (gst::SeekType::None, gst::ClockTime::NONE),
+    };
+    src_element.seek(
+        1.0,
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+        gst::SeekType::Set,
+        Some(gst::ClockTime::from_nseconds(start.as_nanos() as u64)),
+        stop_type,
+        stop,
+    )?;
+    Ok(())
 }
 
 pub(crate) fn stop_pipeline(pipeline: &Pipeline) -> Result<(), Error> {
@@ -358,90 +3256,611 @@ pub(crate) fn pause_pipeline(pipeline: &Pipeline) -> Result<(), Error> {
     Ok(())
 }
 
+fn path_or_url(uri: &str) -> Result<Url, Error> {
+    if uri.starts_with("/") {
+        return Url::from_file_path(uri).map_err(|_| anyhow!("invalid path {}", uri))
+    }
+    Ok(Url::parse(uri)?)
+}
+
+const SUBTITLE_EXTENSIONS: [&str; 4] = ["srt", "ass", "ssa", "vtt"];
+
+// subtitle files next to a video named like it, e.g. movie.srt or movie.en.srt for movie.mkv
+fn find_sidecar_subtitles(video: &Path) -> Option<Url> {
+    let stem = video.file_stem()?.to_string_lossy().to_string();
+    let mut candidates: Vec<PathBuf> = std::fs::read_dir(video.parent()?).ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+            SUBTITLE_EXTENSIONS.contains(&extension.as_str()) && name.starts_with(&format!("{}.", stem))
+        })
+        .collect();
+    // the plain name wins over language tagged ones
+    candidates.sort_by_key(|p| (p.file_name().map(|n| n.len()).unwrap_or_default(), p.clone()));
+    candidates.first().and_then(|p| Url::from_file_path(p).ok())
+}
+
+fn set_subtitle_uri(pipeline: &Pipeline, subtitles: Option<&Url>) -> Result<(), Error> {
+    let src_element = get_value_or_error(pipeline.by_name("src"), "unable to get source element from pipeline")?;
+    if src_element.has_property("suburi", None) {
+        src_element.set_property("suburi", subtitles.map(|s| s.to_string()));
+    }
+    Ok(())
+}
+
 pub(crate) fn set_source_uri(pipeline: &Pipeline, uri_path: &str) -> Result<(), Error> {
     let src_element = get_value_or_error(pipeline.by_name("src"), "unable to get source element from pipeline")?;
     src_element.set_property_from_str("uri", uri_path);
-    info!("set url to {}", uri_path);
+    match Url::parse(uri_path) {
+        Ok(url) => info!("set url to {}", SourceResolver::without_credentials(&url)),
+        Err(_) => info!("set url to {}", uri_path),
+    }
+    Ok(())
+}
+
+// elements the pipeline can't work without along with the package that ships them
+const REQUIRED_ELEMENTS: [(&str, &str); 8] = [
+    ("queue", "gstreamer1.0 core"),
+    ("videoconvert", "gstreamer1.0-plugins-base"),
+    ("videoscale", "gstreamer1.0-plugins-base"),
+    ("audioconvert", "gstreamer1.0-plugins-base"),
+    ("audioresample", "gstreamer1.0-plugins-base"),
+    ("subtitleoverlay", "gstreamer1.0-plugins-base"),
+    ("encodebin", "gstreamer1.0-plugins-base"),
+    ("uridecodebin3", "gstreamer1.0-plugins-base"),
+];
+
+fn check_required_elements(output: &OutputTarget, encoder: &EncoderConfig) -> Result<(), Error> {
+    let output_elements = output.required_elements(encoder);
+    let missing = REQUIRED_ELEMENTS.iter()
+        .map(|(factory, package)| (factory.to_string(), *package))
+        .chain(output_elements)
+        .filter(|(factory, _)| gst::ElementFactory::find(factory).is_none())
+        .map(|(factory, package)| format!("{} (install {})", factory, package))
+        .collect::<Vec<String>>();
+    if !missing.is_empty() {
+        return Err(anyhow!("missing gstreamer elements: {}", missing.join(", ")))
+    }
     Ok(())
 }
 
-pub(crate) fn get_rtmp_pipeline(rtmp_host: &str) -> Result<Pipeline, Error>  {
+// best guess at which package provides a decoder for the given caps
+pub(crate) fn missing_plugin_hint(caps_name: &str) -> String {
+    let package = match caps_name {
+        "video/x-h264" | "video/x-h265" | "video/mpeg" | "audio/x-ac3" | "audio/x-eac3" | "audio/x-dts" | "audio/x-true-hd" | "audio/x-wma" | "video/x-wmv" => "gstreamer1.0-libav",
+        "video/x-vp8" | "video/x-vp9" | "audio/x-flac" | "audio/x-opus" => "gstreamer1.0-plugins-good",
+        "video/x-av1" | "subpicture/x-pgs" | "subpicture/x-dvd" | "subpicture/x-dvb" | "application/x-ass" | "application/x-ssa" => "gstreamer1.0-plugins-bad",
+        _ => "gstreamer1.0-libav or gstreamer1.0-plugins-bad",
+    };
+    format!("install {} to play {}", package, caps_name)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn get_output_pipeline(output: &OutputTarget, encoder: &EncoderConfig, transition: &TransitionConfig, title_config: &TitleOverlayConfig, clock_config: ClockOverlayConfig, source_http_options: Arc<Mutex<HttpSourceOptions>>, stats: &StreamStats, handover: &GaplessHandover) -> Result<Pipeline, Error>  {
 
     gst::init()?;
+    check_required_elements(output, encoder)?;
 
-    let audio_queue = gst::ElementFactory::make("queue").build()?;
+    let audio_queue = gst::ElementFactory::make("queue").name("audio_queue").build()?;
 
-    let video_queue = gst::ElementFactory::make("queue").build()?;
+    let video_queue = gst::ElementFactory::make("queue").name("video_queue").build()?;
     let video_convert = gst::ElementFactory::make("videoconvert").build()?;
-    let video_scale = gst::ElementFactory::make("videoscale").build()?;
+    let video_scale = gst::ElementFactory::make("videoscale")
+        .property("add-borders", encoder.letterbox)
+        .build()?;
+    let scale_filter = gst::ElementFactory::make("capsfilter")
+        .name("scale_filter")
+        .property("caps", encoder.scale_caps())
+        .build()?;
+    let video_rate = gst::ElementFactory::make("videorate").build()?;
+    let framerate_filter = gst::ElementFactory::make("capsfilter")
+        .name("framerate_filter")
+        .property("caps", framerate_caps(encoder.max_framerate))
+        .build()?;
     let audio_convert = gst::ElementFactory::make("audioconvert").build()?;
     let audio_resample = gst::ElementFactory::make("audioresample").build()?;
-    let suboverlay = gst::ElementFactory::make("subtitleoverlay").build()?;
-
-    let encodebin = gst::ElementFactory::make("encodebin").build()?;
-    let sink = gst::ElementFactory::make("rtmpsink").property("location", &rtmp_host).build()?;
+    // decoded audio is split here so it can also go to voice channels
+    let audio_tee = gst::ElementFactory::make("tee").name("audio_tee").property("allow-not-linked", true).build()?;
+    let audio_encode_queue = gst::ElementFactory::make("queue").name("audio_encode_queue").build()?;
+    let suboverlay = gst::ElementFactory::make("subtitleoverlay").name("suboverlay").build()?;
+
+    let encodebin = gst::ElementFactory::make("encodebin").name("encodebin").build()?;
+    // extra outputs hang off the tee, it keeps running when none of them are linked
+    let output_tee = gst::ElementFactory::make("tee").name("output_tee").property("allow-not-linked", true).build()?;
+    let sink_queue = gst::ElementFactory::make("queue").name("sink_queue").build()?;
+    let sink = output.make_sink("sink")?;
+    // the faders only go in when transitions are on, a pad level of 1 passes everything through
+    let video_fade = match transition.fades_video() {
+        true => Some(gst::ElementFactory::make("compositor").name("fade_video").property_from_str("background", "black").build()?),
+        false => None,
+    };
+    let title_overlay = match title_config.duration {
+        Some(_) => Some(gst::ElementFactory::make("textoverlay")
+            .name("title_overlay")
+            .property("silent", true)
+            .property("shaded-background", true)
+            .property("font-desc", "Sans Bold 20")
+            .property_from_str("valignment", "top")
+            .property_from_str("halignment", "left")
+            .build()?),
+        None => None,
+    };
+    // the text is kept up to date by the actor's clock task
+    let clock_overlay = match clock_config.enabled() {
+        true => Some(gst::ElementFactory::make("textoverlay")
+            .name("clock_overlay")
+            .property("silent", clock_config != ClockOverlayConfig::Shown)
+            .property("shaded-background", true)
+            .property("font-desc", "Sans 14")
+            .property_from_str("valignment", "bottom")
+            .property_from_str("halignment", "right")
+            .build()?),
+        false => None,
+    };
+    let watermark = encoder.watermark.as_ref().map(Watermark::element).transpose()?;
+    let sdr_filter = match encoder.tonemap {
+        true => {
+            // remap the transfer function and primaries too, only changing the matrix leaves pq and hlg washed out
+            video_convert.set_property_from_str("gamma-mode", "remap");
+            video_convert.set_property_from_str("primaries-mode", "fast");
+            Some(gst::ElementFactory::make("capsfilter")
+                .name("sdr_filter")
+                .property("caps", gst_video::VideoCapsBuilder::new().field("colorimetry", "bt709").build())
+                .build()?)
+        }
+        false => None,
+    };
+    // the mix matrix only has rows for two channels
+    let stereo_filter = match encoder.downmix {
+        Some(_) => Some(gst::ElementFactory::make("capsfilter")
+            .name("stereo_filter")
+            .property("caps", gst_audio::AudioCapsBuilder::new().channels(2).build())
+            .build()?),
+        None => None,
+    };
+    let deinterlace = match encoder.deinterlace.element_mode() {
+        Some(mode) => Some(gst::ElementFactory::make("deinterlace").name("deinterlace").property_from_str("mode", mode).build()?),
+        None => None,
+    };
+    let audio_fade = match transition.fades_audio() {
+        true => Some(gst::ElementFactory::make("audiomixer").name("fade_audio").build()?),
+        false => None,
+    };
+    // copy items go from the source straight into their own muxer, it takes the encoder's place in front of the output tee
+    let remux = match output {
+        OutputTarget::Rtmp(_) => Some((
+            gst::ElementFactory::make("h264parse").name("copy_video_parse").build()?,
+            gst::ElementFactory::make("queue").name("copy_video_queue").build()?,
+            gst::ElementFactory::make("queue").name("copy_audio_queue").build()?,
+            gst::ElementFactory::make("flvmux").name("copy_mux").property("streamable", true).build()?,
+        )),
+        _ => None,
+    };
 
 
     let pipeline = gst::Pipeline::default();
-    pipeline.add_many([&encodebin, &sink])?;
+    pipeline.add_many([&encodebin, &output_tee, &sink_queue, &sink])?;
     pipeline.add_many([&video_queue, &audio_queue])?;
-    pipeline.add_many([&video_convert, &video_scale, &audio_convert, &audio_resample])?;
+    pipeline.add_many([&video_convert, &video_scale, &scale_filter, &video_rate, &framerate_filter, &audio_convert, &audio_resample, &audio_tee, &audio_encode_queue])?;
     pipeline.add(&suboverlay)?;
+    for optional in stereo_filter.iter().chain(sdr_filter.iter()).chain(deinterlace.iter()).chain(video_fade.iter()).chain(watermark.iter()).chain(title_overlay.iter()).chain(clock_overlay.iter()).chain(audio_fade.iter()) {
+        pipeline.add(optional)?;
+    }
 
-    gst::Element::link_many([&encodebin, &sink])?;
-    gst::Element::link_many([&suboverlay, &video_queue, &video_convert, &video_scale])?;
-    gst::Element::link_many([&audio_queue, &audio_convert, &audio_resample])?;
+    gst::Element::link_many([&encodebin, &output_tee, &sink_queue, &sink])?;
+    if let Some((video_parse, copy_video_queue, copy_audio_queue, copy_mux)) = &remux {
+        pipeline.add_many([video_parse, copy_video_queue, copy_audio_queue, copy_mux])?;
+        video_parse.link(copy_video_queue)?;
+        // flvmux would hand out whichever pad fits first for a queue with any caps
+        for (queue, pad) in [(copy_video_queue, "video"), (copy_audio_queue, "audio")] {
+            let mux_pad = get_value_or_error(copy_mux.request_pad_simple(pad), "unable to get a pad of the remux muxer")?;
+            get_value_or_error(queue.static_pad("src"), "unable to get src pad of a remux queue")?.link(&mux_pad)?;
+        }
+    }
 
-    configure_encodebin_rtmp(&encodebin);
+    // count what actually leaves for the relay so health reports can show the real bitrate
+    let bytes_sent = Arc::clone(&stats.bytes_sent);
+    let sink_pad = get_value_or_error(sink.static_pad("sink"), "unable to get sink pad of the output")?;
+    sink_pad.add_probe(gst::PadProbeType::BUFFER | gst::PadProbeType::BUFFER_LIST, move |_, info| {
+        let size = match &info.data {
+            Some(gst::PadProbeData::Buffer(buffer)) => buffer.size(),
+            Some(gst::PadProbeData::BufferList(list)) => list.calculate_size(),
+            _ => 0,
+        };
+        bytes_sent.fetch_add(size as u64, Ordering::Relaxed);
+        gst::PadProbeReturn::Ok
+    });
+    // every frame that makes it past the rate filter goes to the encoder
+    let frames_encoded = Arc::clone(&stats.frames_encoded);
+    let last_frame = Arc::clone(&stats.last_frame);
+    let encode_pad = get_value_or_error(framerate_filter.static_pad("src"), "unable to get src pad of the framerate filter")?;
+    encode_pad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+        frames_encoded.fetch_add(1, Ordering::Relaxed);
+        if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
+            *last_frame.lock().unwrap() = Some(buffer.clone());
+        }
+        gst::PadProbeReturn::Ok
+    });
+    let video_chain = [&suboverlay, &video_queue, &video_convert].into_iter()
+        .chain(sdr_filter.as_ref())
+        .chain(deinterlace.as_ref())
+        .chain(video_fade.as_ref())
+        .chain([&video_scale, &scale_filter])
+        // after scaling so the logo and text come out the same size whatever the source resolution
+        .chain(watermark.as_ref())
+        .chain(title_overlay.as_ref())
+        .chain(clock_overlay.as_ref())
+        .chain([&video_rate, &framerate_filter]);
+    gst::Element::link_many(video_chain)?;
+    let audio_chain = [&audio_queue, &audio_convert].into_iter()
+        .chain(stereo_filter.as_ref())
+        .chain([&audio_resample])
+        .chain(audio_fade.as_ref())
+        .chain([&audio_tee, &audio_encode_queue]);
+    gst::Element::link_many(audio_chain)?;
+
+    configure_encodebin(&encodebin, encoder, output)?;
 
     let sink_audio_encode_pad = get_value_or_error(encodebin.request_pad_simple("audio_%u"), "unable to get audio sink from encodebin")?;
     let sink_video_encode_pad = get_value_or_error(encodebin.request_pad_simple("video_%u"), "unable to get video sink from encodebin")?;
 
     // link the end of the chain to the encoder
-    audio_resample.static_pad("src").unwrap().link(&sink_audio_encode_pad)?;
-    video_scale.static_pad("src").unwrap().link(&sink_video_encode_pad)?;
+    audio_encode_queue.static_pad("src").unwrap().link(&sink_audio_encode_pad)?;
+    framerate_filter.static_pad("src").unwrap().link(&sink_video_encode_pad)?;
+
+    // the matrix depends on the channel layout so it gets set again for every new set of caps
+    if let Some(downmix) = encoder.downmix {
+        let convert_sink = get_value_or_error(audio_convert.static_pad("sink"), "unable to get sink pad of the audio converter")?;
+        convert_sink.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |pad, info| {
+            let caps = match &info.data {
+                Some(gst::PadProbeData::Event(event)) => match event.view() {
+                    gst::EventView::Caps(c) => c.caps_owned(),
+                    _ => return gst::PadProbeReturn::Ok,
+                },
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            let matrix = match gst_audio::AudioInfo::from_caps(&caps) {
+                Ok(info) if info.channels() > 2 => {
+                    info!("downmixing {} channels to stereo", info.channels());
+                    downmix.matrix(info.positions().unwrap_or_default())
+                }
+                _ => gst::Array::default(),
+            };
+            if let Some(convert) = pad.parent_element() {
+                convert.set_property("mix-matrix", matrix);
+            }
+            gst::PadProbeReturn::Ok
+        });
+    }
 
-    let video_sink_real = get_value_or_error(suboverlay.static_pad("video_sink"), "failed to get video sink for uridecode")?;
-    let subtitle_sink_real = get_value_or_error(suboverlay.static_pad("subtitle_sink"), "filed to get subtitle sink for uridecode")?;
-    let audio_sink_real = get_value_or_error(audio_queue.static_pad("sink"), "failed to get audio sink for uridecode")?;
+    if encoder.tonemap {
+        let convert_sink = get_value_or_error(video_convert.static_pad("sink"), "unable to get sink pad of the video converter")?;
+        convert_sink.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, |_, info| {
+            if let Some(gst::PadProbeData::Event(event)) = &info.data {
+                if let gst::EventView::Caps(c) = event.view() {
+                    if is_hdr(c.caps()) {
+                        info!("hdr source, tone mapping to bt.709: {}", c.caps());
+                    }
+                }
+            }
+            gst::PadProbeReturn::Ok
+        });
+    }
+
+    // a new group of streams coming out of the source is where a handed over item takes over
+    let video_sink = get_value_or_error(suboverlay.static_pad("video_sink"), "unable to get video sink of the subtitle overlay")?;
+    let audio_sink = get_value_or_error(audio_queue.static_pad("sink"), "unable to get audio sink of the audio queue")?;
+    for pad in [video_sink, audio_sink] {
+        let handover = handover.clone();
+        pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |pad, info| {
+            let group = match &info.data {
+                Some(gst::PadProbeData::Event(event)) => match event.view() {
+                    gst::EventView::StreamStart(s) => s.group_id(),
+                    _ => return gst::PadProbeReturn::Ok,
+                },
+                _ => return gst::PadProbeReturn::Ok,
+            };
+            if let (Some(id), Some(element)) = (handover.stream_started(group), pad.parent_element()) {
+                let structure = gst::Structure::builder(GAPLESS_SWITCH_MESSAGE).field("item", id.to_string()).build();
+                if element.post_message(gst::message::Application::builder(structure).src(&element).build()).is_err() {
+                    warn!("unable to tell the play queue that the next item took over");
+                }
+            }
+            gst::PadProbeReturn::Ok
+        });
+    }
 
-    let uridecode = gst::ElementFactory::make("uridecodebin")
+    let uridecode = make_uridecodebin(source_http_options, handover.clone())?;
+    pipeline.add(&uridecode)?;
+    link_source_pads(&pipeline, &uridecode)?;
+
+    Ok(pipeline)
+}
+ 
+// uridecodebin3 only decodes the streams picked through select-streams and keeps its output pads when the pick changes
+fn make_uridecodebin(source_http_options: Arc<Mutex<HttpSourceOptions>>, handover: GaplessHandover) -> Result<gst::Element, Error> {
+    let uridecode = gst::ElementFactory::make("uridecodebin3")
         .name("src")
-        .property("force-sw-decoders", true)
         .property("use-buffering", true)
         .property("buffer-size", 10 * 1024 * 1024)
         .build()?;
+    if uridecode.has_property("force-sw-decoders", None) {
+        uridecode.set_property("force-sw-decoders", true);
+    }
 
-    pipeline.add(&uridecode)?;
+    let http_options = Arc::clone(&source_http_options);
+    uridecode.connect("source-setup", false, move |values| {
+        match values.get(1).map(|v| v.get::<gst::Element>()) {
+            Some(Ok(source)) => http_options.lock().unwrap().apply(&source),
+            _ => warn!("source-setup fired without a source element"),
+        }
+        None
+    });
+    uridecode.connect("about-to-finish", false, move |values| {
+        match values.first().map(|v| v.get::<gst::Element>()) {
+            Some(Ok(uridecode)) => handover.about_to_finish(&uridecode, &source_http_options),
+            _ => warn!("about-to-finish fired without the source element"),
+        }
+        None
+    });
+    Ok(uridecode)
+}
+
+fn make_fallbacksrc(config: &FallbackConfig) -> Result<gst::Element, Error> {
+    let nanos = |d: Duration| d.as_nanos() as u64;
+    let fallbacksrc = gst::ElementFactory::make("fallbacksrc")
+        .name("src")
+        .property("timeout", nanos(config.timeout))
+        .property("restart-timeout", nanos(config.timeout))
+        .property("retry-timeout", nanos(config.retry_timeout))
+        .build()?;
+    if let Some(fallback_uri) = &config.fallback_uri {
+        fallbacksrc.set_property("fallback-uri", fallback_uri);
+    }
+    Ok(fallbacksrc)
+}
 
+// live test pattern or a frozen image, both with silence since the encoder always wants audio
+fn make_standby_source(standby: &StandbySource) -> Result<gst::Element, Error> {
+    let video = match standby {
+        StandbySource::TestPattern(pattern) => format!("videotestsrc is-live=true pattern={}", pattern),
+        StandbySource::Image(uri) => format!("uridecodebin uri=\"{}\" ! videoconvert ! imagefreeze is-live=true", uri),
+        _ => return Err(anyhow!("{:?} is not a standby source that needs its own element", standby)),
+    };
+    let description = format!("{} ! queue name=standby_video audiotestsrc is-live=true wave=silence ! queue name=standby_audio", video);
+    let bin = gst::parse::bin_from_description(&description, false)?;
+    bin.set_property("name", "src");
+    // link_source_pads goes by these pad names
+    for (queue, name) in [("standby_video", "video"), ("standby_audio", "audio")] {
+        let queue = get_value_or_error(bin.by_name(queue), "unable to get standby queue")?;
+        let target = get_value_or_error(queue.static_pad("src"), "unable to get standby queue pad")?;
+        bin.add_pad(&gst::GhostPad::builder_with_target(&target)?.name(name).build())?;
+    }
+    Ok(bin.upcast())
+}
 
-    uridecode.connect_pad_added(move |_src, src_pad| {
-        let pad_caps = src_pad.current_caps().unwrap();
-        let pad_struct = pad_caps.structure(0).unwrap();
-        let pad_type = pad_struct.name();
-        if pad_type.starts_with("video/x-raw") {
-            if video_sink_real.is_linked() {
-                info!("video sink is already linked!");
-                return;
-            }
-            src_pad.link(&video_sink_real).unwrap();
+// which element sits at the head of the pipeline as "src". remux is a uridecodebin3 that stops at the parsers
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SourceKind {
+    Decode,
+    Remux,
+    Fallback,
+    Standby,
+}
+
+fn source_kind(pipeline: &Pipeline) -> SourceKind {
+    match source_factory(pipeline).as_deref() {
+        Some("fallbacksrc") => SourceKind::Fallback,
+        Some("uridecodebin3") if remuxing(pipeline) => SourceKind::Remux,
+        Some("uridecodebin3") => SourceKind::Decode,
+        _ => SourceKind::Standby,
+    }
+}
+
+fn remuxing(pipeline: &Pipeline) -> bool {
+    pipeline.by_name("output_tee")
+        .and_then(|tee| tee.static_pad("sink"))
+        .and_then(|pad| pad.peer())
+        .and_then(|peer| peer.parent_element())
+        .is_some_and(|element| element.name() == "copy_mux")
+}
+
+// the output tee gets its flv from the encoder or, for copy items, from the remux muxer
+fn set_output_branch(pipeline: &Pipeline, remux: bool) -> Result<(), Error> {
+    let name = if remux { "copy_mux" } else { "encodebin" };
+    let branch = get_value_or_error(pipeline.by_name(name), &format!("unable to get {} from pipeline", name))?;
+    let branch_pad = get_value_or_error(branch.static_pad("src"), &format!("unable to get src pad of {}", name))?;
+    let tee_pad = get_value_or_error(pipeline.by_name("output_tee").and_then(|t| t.static_pad("sink")), "unable to get sink pad of the output tee")?;
+    match tee_pad.peer() {
+        Some(peer) if peer == branch_pad => return Ok(()),
+        Some(peer) => peer.unlink(&tee_pad)?,
+        None => (),
+    }
+    branch_pad.link(&tee_pad)?;
+    Ok(())
+}
+
+// swap the source element between uridecodebin, fallbacksrc and the standby source. only valid while the pipeline is stopped
+fn replace_source(pipeline: &Pipeline, kind: SourceKind, config: &FallbackConfig, standby: &StandbySource, source_http_options: Arc<Mutex<HttpSourceOptions>>, handover: &GaplessHandover) -> Result<(), Error> {
+    if source_kind(pipeline) == kind {
+        return Ok(())
+    }
+    info!("switching source to {:?}", kind);
+    swap_source(pipeline, kind, config, standby, source_http_options, handover)
+}
+
+// put a new source element of the given kind in place of the current one
+fn swap_source(pipeline: &Pipeline, kind: SourceKind, config: &FallbackConfig, standby: &StandbySource, source_http_options: Arc<Mutex<HttpSourceOptions>>, handover: &GaplessHandover) -> Result<(), Error> {
+    let current = get_value_or_error(pipeline.by_name("src"), "unable to get source element from pipeline")?;
+    current.set_state(gst::State::Null)?;
+    // removing the element unlinks its pads so the sinks are free for the new one
+    pipeline.remove(&current)?;
+    let source = match kind {
+        SourceKind::Fallback => make_fallbacksrc(config)?,
+        SourceKind::Decode => make_uridecodebin(source_http_options, handover.clone())?,
+        SourceKind::Remux => {
+            let uridecode = make_uridecodebin(source_http_options, handover.clone())?;
+            uridecode.set_property("caps", gst::Caps::from_str("video/x-h264; audio/mpeg")?);
+            uridecode
         }
-        if pad_type.starts_with("audio/x-raw") {
-            if audio_sink_real.is_linked() {
-                info!("audio sink is already linked!");
-                return;
-            }
-            src_pad.link(&audio_sink_real).unwrap();
+        SourceKind::Standby => make_standby_source(standby)?,
+    };
+    pipeline.add(&source)?;
+    match kind {
+        SourceKind::Remux => link_remux_pads(pipeline, &source)?,
+        _ => link_source_pads(pipeline, &source)?,
+    }
+    set_output_branch(pipeline, kind == SourceKind::Remux)
+}
+
+// the parsed streams of a copy item go to the remux branch, anything besides the video and audio is left alone
+fn link_remux_pads(pipeline: &Pipeline, source: &gst::Element) -> Result<(), Error> {
+    let video_parse = get_value_or_error(pipeline.by_name("copy_video_parse"), "unable to get remux video parser from pipeline")?;
+    let audio_queue = get_value_or_error(pipeline.by_name("copy_audio_queue"), "unable to get remux audio queue from pipeline")?;
+    let video_sink = get_value_or_error(video_parse.static_pad("sink"), "unable to get sink pad of the remux video parser")?;
+    let audio_sink = get_value_or_error(audio_queue.static_pad("sink"), "unable to get sink pad of the remux audio queue")?;
+    source.connect_pad_added(move |_src, src_pad| {
+        let stream_type = src_pad.stream().map(|s| s.stream_type()).unwrap_or(gst::StreamType::empty());
+        let (sink_name, sink_pad) = if stream_type.contains(gst::StreamType::VIDEO) {
+            ("video", &video_sink)
+        } else if stream_type.contains(gst::StreamType::AUDIO) {
+            ("audio", &audio_sink)
+        } else {
+            return;
+        };
+        if sink_pad.is_linked() {
+            info!("remux {} sink is already linked!", sink_name);
+            return;
         }
-        if pad_type.starts_with("text/x-raw") {
-            if subtitle_sink_real.is_linked() {
-                info!("subtitle sink is already linked!");
-                return;
-            }
-            src_pad.link(&subtitle_sink_real).unwrap();
+        if let Err(e) = src_pad.link(sink_pad) {
+            error!("unable to link {} pad for remuxing: {}", sink_name, e);
         }
     });
+    Ok(())
+}
 
-    Ok(pipeline)
+fn link_source_pads(pipeline: &Pipeline, source: &gst::Element) -> Result<(), Error> {
+    let suboverlay = get_value_or_error(pipeline.by_name("suboverlay"), "unable to get subtitle overlay from pipeline")?;
+    let audio_queue = get_value_or_error(pipeline.by_name("audio_queue"), "unable to get audio queue from pipeline")?;
+    let video_sink_real = get_value_or_error(suboverlay.static_pad("video_sink"), "failed to get video sink for uridecode")?;
+    let subtitle_sink_real = get_value_or_error(suboverlay.static_pad("subtitle_sink"), "filed to get subtitle sink for uridecode")?;
+    let audio_sink_real = get_value_or_error(audio_queue.static_pad("sink"), "failed to get audio sink for uridecode")?;
+
+    let link_pad = move |src_pad: &gst::Pad| {
+        // caps are not always fixed yet when the pad shows up so fall back to querying them
+        let pad_caps = match src_pad.current_caps() {
+            Some(c) => c,
+            None => src_pad.query_caps(None),
+        };
+        // decodebin3 pads carry their stream and fallbacksrc names its pads after the stream type, neither may have caps yet
+        let stream_type = src_pad.stream().map(|s| s.stream_type()).unwrap_or(gst::StreamType::empty());
+        let pad_name = src_pad.name();
+        let pad_type = if stream_type.contains(gst::StreamType::VIDEO) || pad_name == "video" {
+            "video/x-raw".to_string()
+        } else if stream_type.contains(gst::StreamType::AUDIO) || pad_name == "audio" {
+            "audio/x-raw".to_string()
+        } else if stream_type.contains(gst::StreamType::TEXT) {
+            "text/x-raw".to_string()
+        } else {
+            match pad_caps.structure(0) {
+                Some(s) => s.name().to_string(),
+                None => {
+                    warn!("pad {} has no caps, ignoring it", src_pad.name());
+                    return;
+                }
+            }
+        };
+        let (sink_name, sink_pad) = if pad_type.starts_with("video/x-raw") {
+            ("video", &video_sink_real)
+        } else if pad_type.starts_with("audio/x-raw") {
+            ("audio", &audio_sink_real)
+        } else if pad_type.starts_with("text/x-raw") {
+            ("subtitle", &subtitle_sink_real)
+        } else {
+            // the decoder bin hands out undecoded pads when no decoder is installed for the stream
+            warn!("no decoder for {} stream, ignoring it. {}", pad_type, missing_plugin_hint(&pad_type));
+            return;
+        };
+        if sink_pad.is_linked() {
+            info!("{} sink is already linked!", sink_name);
+            return;
+        }
+        if let Err(e) = src_pad.link(sink_pad) {
+            error!("unable to link {} pad with caps {}: {}", sink_name, pad_caps, e);
+        }
+    };
+
+    // depending on the version fallbacksrc has its pads from the start instead of adding them later
+    for src_pad in source.src_pads() {
+        link_pad(&src_pad);
+    }
+    source.connect_pad_added(move |_src, src_pad| link_pad(src_pad));
+    Ok(())
+}
+
+fn source_factory(pipeline: &Pipeline) -> Option<String> {
+    pipeline.by_name("src").and_then(|s| s.factory()).map(|f| f.name().to_string())
+}
+
+// ask the source to decode only these streams. fallbacksrc does its own selection so it is left alone
+pub(crate) fn select_streams(pipeline: &Pipeline, stream_ids: &[String]) -> Result<(), Error> {
+    if source_factory(pipeline).as_deref() != Some("uridecodebin3") || stream_ids.is_empty() {
+        return Ok(())
+    }
+    let src_element = get_value_or_error(pipeline.by_name("src"), "unable to get source element from pipeline")?;
+    let ids: Vec<&str> = stream_ids.iter().map(String::as_str).collect();
+    let event = gst::event::SelectStreams::new(&ids);
+    if !src_element.send_event(event) {
+        return Err(anyhow!("source refused to select streams {}", stream_ids.join(", ")))
+    }
+    Ok(())
+}
+
+// blocks until the frame is converted so keep it off the runtime threads
+pub(crate) fn sample_to_png(sample: &gst::Sample) -> Result<Vec<u8>, Error> {
+    let png = gst_video::convert_sample(sample, &gst::Caps::builder("image/png").build(), gst::ClockTime::from_seconds(SNAPSHOT_TIMEOUT_SECS))?;
+    let buffer = get_value_or_error(png.buffer(), "converted snapshot has no data")?;
+    let map = buffer.map_readable()?;
+    Ok(map.as_slice().to_vec())
+}
+
+// pq (hdr10) and hlg are the transfer functions hdr video comes with
+fn is_hdr(caps: &gst::CapsRef) -> bool {
+    match gst_video::VideoInfo::from_caps(caps) {
+        Ok(info) => matches!(info.colorimetry().transfer(), gst_video::VideoTransferFunction::Smpte2084 | gst_video::VideoTransferFunction::AribStdB67),
+        Err(_) => false,
+    }
+}
+
+fn framerate_caps(framerate: u32) -> gst::Caps {
+    gst_video::VideoCapsBuilder::new()
+        .framerate(gst::Fraction::new(framerate as i32, 1))
+        .build()
+}
+
+fn output_framerate(pipeline: &Pipeline) -> Option<u32> {
+    let caps = pipeline.by_name("framerate_filter")?.property::<gst::Caps>("caps");
+    let framerate = caps.structure(0)?.get::<gst::Fraction>("framerate").ok()?;
+    (framerate.denom() > 0).then(|| (framerate.numer() / framerate.denom()) as u32)
+}
+
+// change the output frame rate and keep the keyframe interval in seconds. only valid while the pipeline is stopped
+pub(crate) fn set_output_framerate(pipeline: &Pipeline, encoder: &EncoderConfig, framerate: u32) -> Result<(), Error> {
+    let filter = get_value_or_error(pipeline.by_name("framerate_filter"), "unable to get framerate filter from pipeline")?;
+    filter.set_property("caps", framerate_caps(framerate));
+    let property = match encoder.keyframe_property() {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    let encodebin = get_value_or_error(pipeline.by_name("encodebin"), "unable to get encodebin from pipeline")?;
+    let encodebin = encodebin.downcast::<gst::Bin>().map_err(|_| anyhow!("encodebin is not a bin"))?;
+    let encoder_element = encoder.encoder_element();
+    let video_encoder = encodebin.iterate_recurse().into_iter()
+        .filter_map(Result::ok)
+        .find(|e| e.factory().map(|f| f.name() == encoder_element.as_str()).unwrap_or(false));
+    if let Some(video_encoder) = video_encoder {
+        video_encoder.set_property_from_str(property, &encoder.keyframe_interval(framerate).to_string());
+    }
+    Ok(())
 }
- 
\ No newline at end of file
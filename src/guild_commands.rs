@@ -0,0 +1,244 @@
+use crate::{bot_error, parental_rating::ParentalLimit, Context, Error};
+use poise::serenity_prelude::{self as serenity, GuildId, Mentionable};
+
+#[poise::command(slash_command, owners_only, subcommands("list", "add", "remove", "sync"), subcommand_required)]
+pub(crate) async fn rusto_guilds(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+fn parse_guild_id(guild_id: &str) -> Result<GuildId, Error> {
+    match guild_id.trim().parse::<u64>() {
+        Ok(id) if id != 0 => Ok(GuildId::new(id)),
+        _ => Err(bot_error(&format!("{} is not a valid guild id", guild_id))),
+    }
+}
+
+/// list the guilds the bot registers its commands in
+#[poise::command(slash_command, owners_only)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guilds = ctx.data().guild_config.guilds().await;
+    let message = if guilds.is_empty() {
+        "no guilds configured".to_string()
+    } else {
+        guilds.iter()
+            .map(|(id, settings)| format!("{} {}", id, settings.name.clone().unwrap_or_default()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    ctx.send(poise::CreateReply::default().content(message).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// register the bot commands in a guild and remember it
+#[poise::command(slash_command, owners_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "id of the guild, defaults to the current one"] guild_id: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = match guild_id {
+        Some(g) => parse_guild_id(&g)?,
+        None => ctx.guild_id().ok_or_else(|| bot_error("not in a guild, pass a guild id"))?,
+    };
+    // fails when the bot isn't a member which is exactly when registering would fail too
+    let guild = guild_id.to_partial_guild(ctx).await.map_err(|e| bot_error(&format!("unable to look up guild {}: {}", guild_id, e)))?;
+    ctx.data().command_registry.register_guild(ctx.http(), &ctx.framework().options().commands, guild_id, true).await?;
+    let added = ctx.data().guild_config.add_guild(guild_id, Some(guild.name.clone())).await?;
+    let message = if added {
+        format!("added {} and registered commands", guild.name)
+    } else {
+        format!("{} was already configured, re-registered commands", guild.name)
+    };
+    ctx.send(poise::CreateReply::default().content(message).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// unregister the bot commands from a guild and forget it
+#[poise::command(slash_command, owners_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "id of the guild, defaults to the current one"] guild_id: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = match guild_id {
+        Some(g) => parse_guild_id(&g)?,
+        None => ctx.guild_id().ok_or_else(|| bot_error("not in a guild, pass a guild id"))?,
+    };
+    if !ctx.data().guild_config.remove_guild(guild_id).await? {
+        return Err(bot_error(&format!("guild {} is not configured", guild_id)))
+    }
+    ctx.data().command_registry.unregister_guild(ctx.http(), guild_id).await?;
+    ctx.send(poise::CreateReply::default().content(format!("removed guild {} and unregistered its commands", guild_id)).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// register commands in every configured guild where they changed since the last registration
+#[poise::command(slash_command, owners_only)]
+async fn sync(
+    ctx: Context<'_>,
+    #[description = "register even when nothing changed"] force: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let force = force.unwrap_or(false);
+    let registry = &ctx.data().command_registry;
+    let commands = &ctx.framework().options().commands;
+    let mut registered = 0;
+    let mut skipped = 0;
+    if registry.register_global(ctx.http(), commands, force).await? {
+        registered += 1;
+    } else {
+        skipped += 1;
+    }
+    for guild_id in ctx.data().guild_config.guild_ids().await {
+        if registry.register_guild(ctx.http(), commands, guild_id, force).await? {
+            registered += 1;
+        } else {
+            skipped += 1;
+        }
+    }
+    ctx.send(poise::CreateReply::default().content(format!("registered commands in {} scopes, {} were already up to date", registered, skipped)).ephemeral(true)).await?;
+    Ok(())
+}
+
+// commands that can never be restricted so nobody locks themselves out of fixing a bad binding
+const UNRESTRICTABLE_COMMANDS: [&str; 3] = ["help", "rusto_guilds", "rusto_channels"];
+
+// global command check. refuses commands used outside the channels they are bound to and points at the right ones
+pub(crate) async fn check_channel(ctx: Context<'_>) -> Result<bool, Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(g) => g,
+        None => return Ok(true),
+    };
+    let command = ctx.command().qualified_name.split(' ').next().unwrap_or_default().to_string();
+    if UNRESTRICTABLE_COMMANDS.contains(&command.as_str()) {
+        return Ok(true)
+    }
+    let allowed = match ctx.data().guild_config.allowed_channels(guild_id, &command).await {
+        Some(channels) => channels,
+        None => return Ok(true),
+    };
+    // threads count as their parent channel
+    let mut channel_id = ctx.channel_id();
+    if !allowed.contains(&channel_id) {
+        if let Ok(serenity::Channel::Guild(channel)) = channel_id.to_channel(ctx).await {
+            if channel.thread_metadata.is_some() {
+                channel_id = channel.parent_id.unwrap_or(channel_id);
+            }
+        }
+    }
+    if allowed.contains(&channel_id) {
+        return Ok(true)
+    }
+    let channels = allowed.iter().map(|c| c.mention().to_string()).collect::<Vec<String>>().join(", ");
+    ctx.send(poise::CreateReply::default().content(format!("`/{}` can only be used in {}", command, channels)).ephemeral(true)).await?;
+    Ok(false)
+}
+
+#[poise::command(slash_command, guild_only, default_member_permissions = "ADMINISTRATOR", subcommands("bind", "unbind", "bindings"), subcommand_required)]
+pub(crate) async fn rusto_channels(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+fn validate_command_name(ctx: Context<'_>, command: &str) -> Result<String, Error> {
+    let command = command.trim().trim_start_matches('/').to_string();
+    if UNRESTRICTABLE_COMMANDS.contains(&command.as_str()) {
+        return Err(bot_error(&format!("{} can not be restricted", command)))
+    }
+    if !ctx.framework().options().commands.iter().any(|c| c.name == command) {
+        return Err(bot_error(&format!("unknown command {}", command)))
+    }
+    Ok(command)
+}
+
+/// only allow a command group in the given channel (can be used several times for more channels)
+#[poise::command(slash_command, guild_only, default_member_permissions = "ADMINISTRATOR")]
+async fn bind(
+    ctx: Context<'_>,
+    #[description = "top level command e.g. rusto_video"] command: String,
+    #[description = "channel the command is allowed in"]
+    #[channel_types("Text", "Voice", "Stage")] channel: serenity::GuildChannel,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or_else(|| bot_error("not in a guild"))?;
+    let command = validate_command_name(ctx, &command)?;
+    ctx.data().guild_config.restrict_command(guild_id, &command, channel.id).await?;
+    ctx.send(poise::CreateReply::default().content(format!("`/{}` is now allowed in {}", command, channel.id.mention())).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// remove a channel binding, or all of them for a command when no channel is given
+#[poise::command(slash_command, guild_only, default_member_permissions = "ADMINISTRATOR")]
+async fn unbind(
+    ctx: Context<'_>,
+    #[description = "top level command e.g. rusto_video"] command: String,
+    #[description = "channel to remove, leave empty to allow the command everywhere again"] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or_else(|| bot_error("not in a guild"))?;
+    let command = validate_command_name(ctx, &command)?;
+    ctx.data().guild_config.unrestrict_command(guild_id, &command, channel.as_ref().map(|c| c.id)).await?;
+    let message = match channel {
+        Some(c) => format!("`/{}` is no longer allowed in {}", command, c.id.mention()),
+        None => format!("`/{}` can be used in any channel again", command),
+    };
+    ctx.send(poise::CreateReply::default().content(message).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// show which commands are bound to which channels
+#[poise::command(slash_command, guild_only, default_member_permissions = "ADMINISTRATOR")]
+async fn bindings(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or_else(|| bot_error("not in a guild"))?;
+    let restrictions = ctx.data().guild_config.channel_restrictions(guild_id).await;
+    let message = if restrictions.is_empty() {
+        "all commands can be used in every channel".to_string()
+    } else {
+        restrictions.iter()
+            .map(|(command, channels)| format!("`/{}`: {}", command, channels.iter().map(|c| c.mention().to_string()).collect::<Vec<String>>().join(", ")))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    ctx.send(poise::CreateReply::default().content(message).ephemeral(true)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, default_member_permissions = "ADMINISTRATOR", subcommands("limit", "unlimit", "show_limit"), subcommand_required)]
+pub(crate) async fn rusto_rating(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// hide and refuse emby items rated above this in the current server
+#[poise::command(slash_command, guild_only, default_member_permissions = "ADMINISTRATOR")]
+async fn limit(
+    ctx: Context<'_>,
+    #[description = "highest allowed rating e.g. PG-13, TV-14 or an age like 12"] rating: String,
+    #[description = "allow items without a rating, off by default"] allow_unrated: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or_else(|| bot_error("not in a guild"))?;
+    let limit = ParentalLimit::new(&rating, allow_unrated.unwrap_or(false)).map_err(|e| bot_error(&e.to_string()))?;
+    let message = match limit.allow_unrated {
+        true => format!("emby items rated above {} are now hidden, unrated ones are allowed", limit.max_rating),
+        false => format!("emby items rated above {} or without a rating are now hidden", limit.max_rating),
+    };
+    ctx.data().guild_config.set_parental_limit(guild_id, Some(limit)).await?;
+    ctx.send(poise::CreateReply::default().content(message).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// allow emby items of any rating again
+#[poise::command(slash_command, guild_only, default_member_permissions = "ADMINISTRATOR")]
+async fn unlimit(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or_else(|| bot_error("not in a guild"))?;
+    ctx.data().guild_config.set_parental_limit(guild_id, None).await?;
+    ctx.send(poise::CreateReply::default().content("emby items of any rating are allowed again").ephemeral(true)).await?;
+    Ok(())
+}
+
+/// show the rating limit of the current server
+#[poise::command(slash_command, guild_only, default_member_permissions = "ADMINISTRATOR", rename = "show")]
+async fn show_limit(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or_else(|| bot_error("not in a guild"))?;
+    let message = match ctx.data().guild_config.parental_limit(guild_id).await {
+        Some(l) if l.allow_unrated => format!("emby items up to {} and unrated ones are allowed", l.max_rating),
+        Some(l) => format!("emby items up to {} are allowed, unrated ones are hidden", l.max_rating),
+        None => "emby items of any rating are allowed".to_string(),
+    };
+    ctx.send(poise::CreateReply::default().content(message).ephemeral(true)).await?;
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use anyhow::{Error, anyhow};
+use poise::serenity_prelude::{ChannelId, GuildId};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex as TokioMutex;
+use tracing::{info, warn};
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{parental_rating::ParentalLimit, state_store::StateStore};
+
+const GUILDS_FILE: &str = "guilds.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub(crate) struct GuildSettings {
+    #[serde(default)]
+    pub(crate) name: Option<String>,
+    // top level command name -> channels it may be used in. commands without an entry work everywhere
+    #[serde(default)]
+    pub(crate) channel_restrictions: BTreeMap<String, Vec<u64>>,
+    // emby items rated above this can't be found or queued from the guild
+    #[serde(default)]
+    pub(crate) parental_limit: Option<ParentalLimit>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct GuildConfigFile {
+    #[serde(default)]
+    guilds: BTreeMap<u64, GuildSettings>,
+}
+
+// the guilds the bot registers its commands in. editable at runtime and saved to the state dir
+#[derive(Clone)]
+pub(crate) struct GuildConfig {
+    store: StateStore,
+    guilds: Arc<TokioMutex<BTreeMap<u64, GuildSettings>>>,
+}
+
+impl GuildConfig {
+    // DISCORD_SERVER_IDS only seeds the list the first time, after that the saved config wins even when it is empty
+    pub async fn load(store: StateStore) -> Result<Self, Error> {
+        let seed = !store.exists(GUILDS_FILE).await?;
+        let mut config = store.load::<GuildConfigFile>(GUILDS_FILE).await?;
+        // GuildId can't hold a zero
+        if config.guilds.remove(&0).is_some() {
+            warn!("ignoring guild id 0 in {}", GUILDS_FILE);
+        }
+        if seed {
+            for guild_id in std::env::var("DISCORD_SERVER_IDS").unwrap_or_default().split(',').map(|g| g.trim()).filter(|g| !g.is_empty()) {
+                match guild_id.parse::<u64>() {
+                    Ok(id) if id != 0 => {
+                        config.guilds.insert(id, GuildSettings::default());
+                    }
+                    _ => warn!("ignoring invalid guild id {}", guild_id),
+                }
+            }
+            if !config.guilds.is_empty() {
+                info!("seeding guild config with {} guilds from DISCORD_SERVER_IDS", config.guilds.len());
+                store.save(GUILDS_FILE, &config).await?;
+            }
+        }
+        if config.guilds.is_empty() {
+            warn!("no guilds configured, use the rusto_guilds command to add one");
+        }
+        Ok(GuildConfig {
+            store,
+            guilds: Arc::new(TokioMutex::new(config.guilds)),
+        })
+    }
+
+    pub async fn guild_ids(&self) -> Vec<GuildId> {
+        self.guilds.lock().await.keys().map(|id| GuildId::new(*id)).collect()
+    }
+
+    pub async fn guilds(&self) -> Vec<(GuildId, GuildSettings)> {
+        self.guilds.lock().await.iter().map(|(id, settings)| (GuildId::new(*id), settings.clone())).collect()
+    }
+
+    // returns false when the guild was already configured
+    pub async fn add_guild(&self, guild_id: GuildId, name: Option<String>) -> Result<bool, Error> {
+        let mut guilds = self.guilds.lock().await;
+        let added = !guilds.contains_key(&guild_id.get());
+        guilds.entry(guild_id.get()).or_default().name = name;
+        self.persist(&guilds).await?;
+        Ok(added)
+    }
+
+    // returns false when the guild was not configured
+    pub async fn remove_guild(&self, guild_id: GuildId) -> Result<bool, Error> {
+        let mut guilds = self.guilds.lock().await;
+        let removed = guilds.remove(&guild_id.get()).is_some();
+        if removed {
+            self.persist(&guilds).await?;
+        }
+        Ok(removed)
+    }
+
+    // None when the command may be used in any channel of the guild
+    pub async fn allowed_channels(&self, guild_id: GuildId, command: &str) -> Option<Vec<ChannelId>> {
+        let guilds = self.guilds.lock().await;
+        let channels = guilds.get(&guild_id.get())?.channel_restrictions.get(command)?;
+        if channels.is_empty() {
+            return None
+        }
+        Some(channels.iter().map(|c| ChannelId::new(*c)).collect())
+    }
+
+    pub async fn channel_restrictions(&self, guild_id: GuildId) -> BTreeMap<String, Vec<ChannelId>> {
+        match self.guilds.lock().await.get(&guild_id.get()) {
+            Some(settings) => settings.channel_restrictions.iter()
+                .map(|(command, channels)| (command.clone(), channels.iter().map(|c| ChannelId::new(*c)).collect()))
+                .collect(),
+            None => BTreeMap::new(),
+        }
+    }
+
+    pub async fn restrict_command(&self, guild_id: GuildId, command: &str, channel_id: ChannelId) -> Result<(), Error> {
+        let mut guilds = self.guilds.lock().await;
+        let settings = guilds.get_mut(&guild_id.get()).ok_or_else(|| anyhow!("guild {} is not configured", guild_id))?;
+        let channels = settings.channel_restrictions.entry(command.to_string()).or_default();
+        if !channels.contains(&channel_id.get()) {
+            channels.push(channel_id.get());
+        }
+        self.persist(&guilds).await
+    }
+
+    // drops a single channel or, without one, the whole restriction for the command
+    pub async fn unrestrict_command(&self, guild_id: GuildId, command: &str, channel_id: Option<ChannelId>) -> Result<(), Error> {
+        let mut guilds = self.guilds.lock().await;
+        let settings = guilds.get_mut(&guild_id.get()).ok_or_else(|| anyhow!("guild {} is not configured", guild_id))?;
+        match channel_id {
+            Some(channel_id) => {
+                if let Some(channels) = settings.channel_restrictions.get_mut(command) {
+                    channels.retain(|c| *c != channel_id.get());
+                    if channels.is_empty() {
+                        settings.channel_restrictions.remove(command);
+                    }
+                }
+            }
+            None => {
+                settings.channel_restrictions.remove(command);
+            }
+        }
+        self.persist(&guilds).await
+    }
+
+    pub async fn parental_limit(&self, guild_id: GuildId) -> Option<ParentalLimit> {
+        self.guilds.lock().await.get(&guild_id.get())?.parental_limit.clone()
+    }
+
+    // None lifts the limit
+    pub async fn set_parental_limit(&self, guild_id: GuildId, limit: Option<ParentalLimit>) -> Result<(), Error> {
+        let mut guilds = self.guilds.lock().await;
+        let settings = guilds.get_mut(&guild_id.get()).ok_or_else(|| anyhow!("guild {} is not configured", guild_id))?;
+        settings.parental_limit = limit;
+        self.persist(&guilds).await
+    }
+
+    async fn persist(&self, guilds: &BTreeMap<u64, GuildSettings>) -> Result<(), Error> {
+        self.store.save(GUILDS_FILE, &GuildConfigFile { guilds: guilds.clone() }).await
+    }
+}
@@ -0,0 +1,103 @@
+use poise::serenity_prelude::{self as serenity, ChannelId, CreateMessage, EditMessage, MessageId};
+use tracing::{info, warn};
+
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use crate::{gstreamer::StreamHealth, play_queue_actor::PlayQueueHandle, video_commands::format_timestamp};
+
+#[derive(Clone, Debug)]
+pub(crate) struct HealthReportConfig {
+    // reports are off unless a channel is set
+    channel: Option<ChannelId>,
+    interval: Duration,
+}
+
+impl HealthReportConfig {
+    pub fn from_env() -> Self {
+        let channel = std::env::var("HEALTH_REPORT_CHANNEL_ID").ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|id| *id != 0)
+            .map(ChannelId::new);
+        let minutes = std::env::var("HEALTH_REPORT_MINUTES").ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|m| *m > 0)
+            .unwrap_or(10);
+        HealthReportConfig {
+            channel,
+            interval: Duration::from_secs(minutes * 60),
+        }
+    }
+}
+
+// previous sample so the bitrate covers the time between two reports
+struct Sample {
+    taken: Instant,
+    bytes_sent: u64,
+    dropped_frames: u64,
+}
+
+// keeps one message per stream up to date instead of filling the channel during long marathons
+pub(crate) async fn run_health_reports(http: Arc<serenity::Http>, config: HealthReportConfig, play_queue: PlayQueueHandle) {
+    let channel = match config.channel {
+        Some(c) => c,
+        None => return,
+    };
+    info!("posting stream health to {} every {}s", channel, config.interval.as_secs());
+    let mut interval = tokio::time::interval(config.interval);
+    let mut message: Option<MessageId> = None;
+    let mut last_sample: Option<Sample> = None;
+    loop {
+        interval.tick().await;
+        let health = match play_queue.stream_health().await {
+            Ok(h) => h,
+            Err(e) => {
+                warn!("unable to get stream health: {}", e);
+                continue
+            }
+        };
+        if health.uptime.is_none() {
+            // stream is over, the next one gets a fresh message
+            message = None;
+            last_sample = None;
+            continue
+        }
+        let now = Instant::now();
+        let content = format_health(&health, last_sample.as_ref(), now);
+        last_sample = Some(Sample { taken: now, bytes_sent: health.bytes_sent, dropped_frames: health.dropped_frames });
+        let edited = match message {
+            Some(id) => channel.edit_message(&http, id, EditMessage::new().content(content.clone())).await.is_ok(),
+            None => false,
+        };
+        if edited {
+            continue
+        }
+        // first report of this stream or the old message got deleted
+        match channel.send_message(&http, CreateMessage::new().content(content)).await {
+            Ok(m) => message = Some(m.id),
+            Err(e) => warn!("unable to post stream health to {}: {}", channel, e),
+        }
+    }
+}
+
+fn format_health(health: &StreamHealth, last: Option<&Sample>, now: Instant) -> String {
+    let (bitrate, dropped) = match last {
+        Some(last) => {
+            let elapsed = now.duration_since(last.taken).as_secs_f64().max(1.0);
+            let kbps = health.bytes_sent.saturating_sub(last.bytes_sent) as f64 * 8.0 / 1000.0 / elapsed;
+            (format!("{:.0} kbps", kbps), health.dropped_frames.saturating_sub(last.dropped_frames))
+        }
+        None => ("measuring".to_string(), health.dropped_frames),
+    };
+    let buffer = |level: Option<Duration>| level.map(|l| format!("{:.1}s", l.as_secs_f64())).unwrap_or("-".to_string());
+    let lines = [
+        format!("**stream health** ({})", if health.playing { "playing" } else { "paused" }),
+        format!("now: {}", health.item.as_ref().map(|i| i.name()).unwrap_or("nothing".to_string())),
+        format!("uptime: {}", health.uptime.map(format_timestamp).unwrap_or("-".to_string())),
+        format!("bitrate: {}", bitrate),
+        format!("dropped frames: {} since last report, {} total", dropped, health.dropped_frames),
+        format!("buffer: video {}, audio {}, source {}%", buffer(health.video_buffer), buffer(health.audio_buffer), health.buffering_percent),
+        // the rtmp relay doesn't tell us who is watching
+        "viewers: unknown".to_string(),
+    ];
+    lines.join("\n")
+}
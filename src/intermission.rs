@@ -0,0 +1,105 @@
+use poise::serenity_prelude::{self as serenity, ChannelId, CreateMessage, EditMessage, Message};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use std::time::UNIX_EPOCH;
+
+use crate::{gstreamer::Intermission, play_queue_actor::PlayQueueHandle, playback_events::{FinishReason, PlaybackEvent}};
+
+#[derive(Clone, Debug)]
+pub(crate) struct IntermissionCardConfig {
+    // channel the countdown card goes to, the intermission still happens without it
+    channel: Option<ChannelId>,
+}
+
+impl IntermissionCardConfig {
+    pub fn from_env() -> Self {
+        IntermissionCardConfig {
+            channel: std::env::var("INTERMISSION_CHANNEL_ID").ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|id| *id != 0)
+                .map(ChannelId::new),
+        }
+    }
+}
+
+fn card(intermission: &Intermission) -> CreateMessage {
+    let ends_at = intermission.ends_at.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    CreateMessage::new()
+        .content(format!("\u{2615} Intermission, **{}** starts <t:{}:R>", intermission.next.name(), ends_at))
+        .components(vec![serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new("intermission_start_now")
+                .style(serenity::ButtonStyle::Primary)
+                .label("start now")
+                .emoji('\u{25B6}'),
+        ])])
+}
+
+// posts a countdown for every intermission. admins can use its button to start the next item right away
+pub(crate) async fn run_intermission_cards(ctx: serenity::Context, config: IntermissionCardConfig, pipeline: PlayQueueHandle) {
+    let channel = match config.channel {
+        Some(c) => c,
+        None => return,
+    };
+    info!("posting intermission cards to {}", channel);
+    let mut events = pipeline.subscribe();
+    // the card of the intermission that is running right now
+    let mut current: Option<Message> = None;
+    loop {
+        let card_id = current.as_ref().map(|m| m.id);
+        let clicks = async {
+            match card_id {
+                Some(id) => serenity::ComponentInteractionCollector::new(&ctx).message_id(id).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(PlaybackEvent::IntermissionStarted { intermission }) => {
+                    close_card(&ctx, current.take(), "Intermission is over").await;
+                    match channel.send_message(&ctx, card(&intermission)).await {
+                        Ok(m) => current = Some(m),
+                        Err(e) => warn!("unable to post intermission card to {}: {}", channel, e),
+                    }
+                }
+                Ok(PlaybackEvent::ItemStarted { item }) => {
+                    close_card(&ctx, current.take(), &format!("Intermission is over, now playing **{}**", item.name())).await;
+                }
+                Ok(PlaybackEvent::QueueFinished) | Ok(PlaybackEvent::ItemFinished { reason: FinishReason::Stopped, .. }) => {
+                    close_card(&ctx, current.take(), "Intermission is over, playback was stopped").await;
+                }
+                Ok(_) => (),
+                Err(RecvError::Lagged(skipped)) => warn!("intermission cards fell behind, skipped {} events", skipped),
+                Err(RecvError::Closed) => break,
+            },
+            Some(mci) = clicks => {
+                let allowed = mci.member.as_ref().and_then(|m| m.permissions).is_some_and(|p| p.administrator());
+                let response = match allowed {
+                    true => serenity::CreateInteractionResponse::Acknowledge,
+                    false => serenity::CreateInteractionResponse::Message(
+                        serenity::CreateInteractionResponseMessage::new().content("Only admins can end the intermission early").ephemeral(true)
+                    ),
+                };
+                if let Err(e) = mci.create_response(&ctx, response).await {
+                    warn!("unable to respond to intermission button: {}", e);
+                }
+                if !allowed {
+                    continue
+                }
+                info!("{} ended the intermission early", mci.user.name);
+                // the card gets closed once the item started event comes in
+                if let Err(e) = pipeline.start_playback().await {
+                    warn!("unable to start the next item after the intermission: {}", e);
+                }
+            }
+        }
+    }
+}
+
+async fn close_card(ctx: &serenity::Context, card: Option<Message>, content: &str) {
+    if let Some(mut msg) = card {
+        if let Err(e) = msg.edit(ctx, EditMessage::new().content(content).components(vec![])).await {
+            warn!("unable to update intermission card: {}", e);
+        }
+    }
+}
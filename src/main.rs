@@ -1,16 +1,53 @@
-use embyclient::{EmbyClient};
+use announcements::AnnounceConfig;
+use artwork_cache::{ArtworkCache, ArtworkConfig};
+use command_registration::CommandRegistry;
+use embyclient::{EmbyIdentity, EmbyServers, ServerConfig};
+use file_browser::FileBrowser;
+use game_schedule::ScheduleOverrides;
+use guild_config::GuildConfig;
+use play_history::PlayHistory;
 use k8s_openapi::api::apps::v1::Deployment;
 use kube::{Api, Client as KubeClient};
 use poise::{samples::HelpConfiguration, serenity_prelude::{self as serenity, CreateSelectMenuKind, CreateSelectMenuOption}, FrameworkError};
 use std::{fmt, sync::Arc};
 use tracing::{info, error};
 use tracing_subscriber;
-use tokio::{signal::unix::{signal, SignalKind}, sync::{Mutex, MutexGuard}};
+use songbird::SerenityInit;
+use tokio::signal::unix::{signal, SignalKind};
 mod gstreamer;
 mod embyclient;
+mod play_queue_actor;
+mod playback_events;
 use gstreamer::PlayQueue;
+use play_queue_actor::PlayQueueHandle;
+use presence::PresenceManager;
+use resume_state::ResumeState;
+use state_store::StateStore;
 mod video_commands;
 mod gameserver;
+mod source_resolver;
+mod file_browser;
+mod artwork_cache;
+mod webhooks;
+mod state_store;
+mod guild_config;
+mod guild_commands;
+mod presence;
+mod presence_commands;
+mod command_registration;
+mod trim_analysis;
+mod media_inspect;
+mod announcements;
+mod play_history;
+mod health_reports;
+mod voice_output;
+mod resume_state;
+mod queue_store;
+mod stage;
+mod game_schedule;
+mod intermission;
+mod stream_alerts;
+mod parental_rating;
 extern crate gstreamer as gst;
 
 #[derive(Debug, poise::Modal)]
@@ -18,6 +55,7 @@ extern crate gstreamer as gst;
 struct ShowSearch {
     show_name: String,
     search_type: String,
+    genre: Option<String>,
 }
 
 // Define a custom error type
@@ -64,21 +102,45 @@ fn bot_error(msg: &str) -> Error {
 impl std::error::Error for BotError {}
 
 struct Data {
-    video_pipeline: Arc<Mutex<PlayQueue>>,
-    emby_client: Arc<EmbyClient>,
+    video_pipeline: PlayQueueHandle,
+    emby_servers: EmbyServers,
+    file_browser: Arc<FileBrowser>,
+    artwork_cache: ArtworkCache,
+    guild_config: GuildConfig,
+    presence: PresenceManager,
+    command_registry: CommandRegistry,
+    history: PlayHistory,
+    schedule_overrides: ScheduleOverrides,
+    resume: ResumeState,
 } // User data, which is stored and accessible in all command invocations
 impl Data {
-    pub async fn load(_ctx: &serenity::Context, video_pipeline: Arc<Mutex<PlayQueue>>, emby_client: EmbyClient) -> Self {
-        Self {
+    pub async fn load(_ctx: &serenity::Context, video_pipeline: PlayQueueHandle, emby_servers: EmbyServers, file_browser: FileBrowser, artwork_cache: ArtworkCache, state_store: StateStore, resume: ResumeState) -> Result<Self, Error> {
+        Ok(Self {
             video_pipeline: video_pipeline,
-            emby_client: Arc::new(emby_client),
-        }
+            emby_servers,
+            file_browser: Arc::new(file_browser),
+            artwork_cache,
+            guild_config: GuildConfig::load(state_store.clone()).await?,
+            presence: PresenceManager::load(state_store.clone()).await?,
+            command_registry: CommandRegistry::load(state_store.clone()).await?,
+            history: PlayHistory::load(state_store.clone()).await?,
+            schedule_overrides: ScheduleOverrides::load(state_store).await?,
+            resume,
+        })
     }
 
     fn clone(&self) -> Data {
         Data {
-            video_pipeline: Arc::clone(&self.video_pipeline),
-            emby_client: Arc::clone(&self.emby_client),
+            video_pipeline: self.video_pipeline.clone(),
+            emby_servers: self.emby_servers.clone(),
+            file_browser: Arc::clone(&self.file_browser),
+            artwork_cache: self.artwork_cache.clone(),
+            guild_config: self.guild_config.clone(),
+            presence: self.presence.clone(),
+            command_registry: self.command_registry.clone(),
+            history: self.history.clone(),
+            schedule_overrides: self.schedule_overrides.clone(),
+            resume: self.resume.clone(),
         }
     }
     async fn get_kube_client(&self) -> Result<KubeClient, Error> {
@@ -98,8 +160,8 @@ impl Data {
         Ok(api_client)
     }
 
-    async fn get_pipeline_ref(&self) -> MutexGuard<'_, PlayQueue> {
-        self.video_pipeline.lock().await
+    fn get_pipeline_ref(&self) -> &PlayQueueHandle {
+        &self.video_pipeline
     }
 }
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -186,40 +248,59 @@ async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
 #[tokio::main]
 async fn main() {
     let default_rtmp_address = "rtmp://localhost:7788/live/livestream";
-    let default_emby_url = "http://localhost:8096";
 
     let token = std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN");
-    let emby_api_token = std::env::var("EMBY_API_TOKEN").expect("missing EMBY_API_TOKEN");
-    let emby_api_address = std::env::var("EMBY_API_URL").unwrap_or(default_emby_url.to_string());
-    let rtmp_dst_address = std::env::var("RTMP_URI").unwrap_or(default_rtmp_address.to_string());
+    let emby_server_configs = ServerConfig::from_env().expect("invalid media server settings");
+    // OUTPUT_URI can point at srt://, udp://, a file or an .m3u8 playlist, RTMP_URI is kept for existing setups.
+    // HLS_OUTPUT_DIR is a shortcut for serving the stream to browsers straight from a directory
+    let output_address = std::env::var("OUTPUT_URI").or(std::env::var("RTMP_URI")).ok();
+    let output_target = match (output_address, std::env::var("HLS_OUTPUT_DIR")) {
+        (Some(address), _) => gstreamer::OutputTarget::parse(&address).expect("invalid OUTPUT_URI"),
+        (None, Ok(dir)) if !dir.is_empty() => gstreamer::OutputTarget::hls_dir(&dir),
+        _ => gstreamer::OutputTarget::parse(default_rtmp_address).expect("invalid default output"),
+    };
 
     let intents = serenity::GatewayIntents::non_privileged();
-    // Bind the string to a variable so it isn't dropped immediately
-    let guild_ids_str = std::env::var("DISCORD_SERVER_IDS").unwrap_or_else(|_| "1206408803118088202".to_string());
     let commands = vec![
         help(), 
         register(),
         rusto_register(),
         gameserver::rusto_gameadmin(),
         video_commands::rusto_video(),
+        guild_commands::rusto_guilds(),
+        guild_commands::rusto_channels(),
+        guild_commands::rusto_rating(),
+        presence_commands::rusto_presence(),
     ];
-    let play_queue = PlayQueue::new(&rtmp_dst_address).unwrap();
-    let shared_play_queue = Arc::new(Mutex::new(play_queue));
-    let main_playqueue = Arc::clone(&shared_play_queue.clone());
-    let eos_watch_playqueue = Arc::clone(&shared_play_queue.clone());
-    let eos_thread = tokio::spawn(async move {
-        PlayQueue::add_eos_watch(&eos_watch_playqueue).await;
-    });
-    let emby_client = EmbyClient::new(emby_api_address, emby_api_token).await.unwrap();
+    let source_resolver = source_resolver::SourceResolver::new(source_resolver::ResolverConfig::from_env());
+    let emby_resolver = source_resolver.clone();
+    let trimmer = trim_analysis::TrimAnalyser::new(trim_analysis::TrimConfig::from_env());
+    let encoder_config = gstreamer::EncoderConfig::from_env().expect("invalid encoder settings");
+    let play_queue = PlayQueue::new(&output_target, &encoder_config, gstreamer::FallbackConfig::from_env(), gstreamer::QueueLimits::from_env(), gstreamer::IntermissionConfig::from_env(), gstreamer::TransitionConfig::from_env(), gstreamer::TitleOverlayConfig::from_env(), gstreamer::ClockOverlayConfig::from_env(), gstreamer::BumperConfig::from_env(), gstreamer::StandbySource::from_env(), gstreamer::RecoveryConfig::from_env(), source_resolver, trimmer).unwrap();
+    let (play_queue_handle, play_queue_tasks) = play_queue_actor::spawn(play_queue, play_queue_actor::WatchdogConfig::from_env());
+    tokio::spawn(playback_events::log_events(play_queue_handle.subscribe()));
+    tokio::spawn(webhooks::run_webhooks(webhooks::WebhookConfig::from_env(), play_queue_handle.subscribe()));
+    let main_playqueue = play_queue_handle.clone();
     tracing_subscriber::fmt::init();
-
-    let guild_ids: Vec<_> = guild_ids_str.split(",")
-        .map(|f| {
-            f.parse::<u64>()
-             .map(serenity::GuildId::new)
-             .expect("invalid guild id")
-        })
-        .collect();
+    let state_store = StateStore::from_env();
+    let emby_identity = EmbyIdentity::load(&state_store).await.expect("unable to load the emby device identity");
+    let resume_state = ResumeState::load(state_store.clone()).await.expect("unable to load the resume position");
+    tokio::spawn(resume_state::run_resume_saver(resume_state.clone(), play_queue_handle.clone()));
+    let shutdown_resume_state = resume_state.clone();
+    let emby_servers = EmbyServers::connect(emby_server_configs, emby_identity).await.unwrap();
+    emby_resolver.set_emby_servers(emby_servers.clone());
+    tokio::spawn(queue_store::run_queue_store(state_store.clone(), play_queue_handle.clone(), emby_servers.clone()));
+    let registration_servers = emby_servers.clone();
+    tokio::spawn(async move {
+        for server in registration_servers.iter() {
+            if let Err(e) = server.register_device().await {
+                error!("unable to register device with {}: {}", server.name(), e);
+            }
+        }
+    });
+    let file_browser = FileBrowser::from_env();
+    let artwork_cache = ArtworkCache::new(ArtworkConfig::from_env());
+    let force_registration = std::env::var("FORCE_COMMAND_REGISTRATION").map(|v| v == "1" || v == "true").unwrap_or(false);
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
@@ -240,25 +321,34 @@ async fn main() {
 					);
                 })
             },
+            command_check: Some(|ctx| Box::pin(guild_commands::check_channel(ctx))),
             on_error: |error| Box::pin(on_error(error)),
             ..Default::default()
         })
-        .setup(|ctx, _ready, framework| {
+        .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
                 info!("Registering {} commands", &framework.options().commands.len());
-                for guild_id in guild_ids {
-                    info!("Registering in guild id {guild_id}");
-                    poise::builtins::register_in_guild(ctx, &framework.options().commands, guild_id).await?;
+                let data = Data::load(ctx, main_playqueue, emby_servers, file_browser, artwork_cache, state_store, resume_state).await?;
+                for guild_id in data.guild_config.guild_ids().await {
+                    data.command_registry.register_guild(ctx, &framework.options().commands, guild_id, force_registration).await?;
                 }
-                let empty_commands = vec![help()];
-                poise::builtins::register_globally(ctx, &empty_commands).await?;
-                Ok(Data::load(ctx, main_playqueue, emby_client).await)
+                data.command_registry.register_global(ctx, &framework.options().commands, force_registration).await?;
+                tokio::spawn(presence::run_presence(ctx.clone(), data.presence.clone(), data.video_pipeline.clone()));
+                tokio::spawn(health_reports::run_health_reports(ctx.http.clone(), health_reports::HealthReportConfig::from_env(), data.video_pipeline.clone()));
+                tokio::spawn(intermission::run_intermission_cards(ctx.clone(), intermission::IntermissionCardConfig::from_env(), data.video_pipeline.clone()));
+                tokio::spawn(stream_alerts::run_stream_alerts(ctx.http.clone(), stream_alerts::AlertConfig::from_env(), data.video_pipeline.clone()));
+                tokio::spawn(stage::run_stage(ctx.http.clone(), stage::StageConfig::from_env(), data.video_pipeline.subscribe()));
+                tokio::spawn(play_history::run_history_recorder(data.history.clone(), data.video_pipeline.subscribe()));
+                tokio::spawn(announcements::run_announcements(ctx.http.clone(), AnnounceConfig::from_env(), data.history.clone(), data.video_pipeline.subscribe()));
+                tokio::spawn(game_schedule::run_schedule(ctx.http.clone(), game_schedule::ScheduleConfig::from_env(), data.schedule_overrides.clone()));
+                Ok(data)
             })
         })
         .build();
 
     let mut client = serenity::ClientBuilder::new(token, intents)
         .framework(framework)
+        .register_songbird()
         .await
         .expect("error creating serenity client");
     let mut ctrl_c = signal(SignalKind::interrupt()).expect("failed to listen for interrupt");
@@ -271,9 +361,10 @@ async fn main() {
         _ = sig_quit.recv() => println!("Received SIGQUIT, shutting down..."),
     };
     client.shard_manager.shutdown_all().await;
-    eos_thread.abort();
-    match shared_play_queue.clone().lock().await.stop_playback().await {
+    shutdown_resume_state.shutdown(&play_queue_handle).await;
+    match play_queue_handle.stop_playback().await {
         Ok(_) => (),
         Err(e) => error!("error stopping pipeline {}", e)
     }
+    play_queue_tasks.abort();
 }
\ No newline at end of file
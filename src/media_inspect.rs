@@ -0,0 +1,148 @@
+extern crate gstreamer_pbutils as gst_pbutils;
+
+use anyhow::{Error, anyhow};
+use gst::prelude::*;
+use gst_pbutils::{prelude::*, Discoverer, DiscovererAudioInfo, DiscovererResult, DiscovererSubtitleInfo, DiscovererVideoInfo};
+use url::Url;
+
+use std::time::Duration;
+
+use crate::gstreamer::HttpSourceOptions;
+
+const DISCOVER_TIMEOUT_SECS: u64 = 20;
+
+#[derive(Clone, Debug)]
+pub(crate) struct VideoStream {
+    pub(crate) codec: String,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) framerate: f64,
+    pub(crate) interlaced: bool,
+    pub(crate) bitrate: u32,
+    pub(crate) remuxable: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct AudioStream {
+    pub(crate) codec: String,
+    pub(crate) channels: u32,
+    pub(crate) sample_rate: u32,
+    pub(crate) language: Option<String>,
+    pub(crate) bitrate: u32,
+    pub(crate) remuxable: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct MediaInfo {
+    pub(crate) container: Option<String>,
+    pub(crate) duration: Option<Duration>,
+    pub(crate) seekable: bool,
+    pub(crate) live: bool,
+    pub(crate) video: Vec<VideoStream>,
+    pub(crate) audio: Vec<AudioStream>,
+    pub(crate) subtitles: Vec<Option<String>>,
+}
+
+impl MediaInfo {
+    // why the default tracks can't go into flv as they are, none when they can
+    pub fn remux_blocker(&self) -> Option<String> {
+        let video = match self.video.first() {
+            Some(v) => v,
+            None => return Some("there is no video track".to_string()),
+        };
+        if !video.remuxable {
+            return Some(format!("{} video has to be re-encoded", video.codec))
+        }
+        match self.audio.first() {
+            Some(a) if !a.remuxable => Some(format!("{} audio has to be re-encoded", a.codec)),
+            Some(_) => None,
+            None => Some("there is no audio track".to_string()),
+        }
+    }
+}
+
+// run the discoverer against a uri. blocks for up to DISCOVER_TIMEOUT_SECS so it runs on the blocking pool
+pub(crate) async fn inspect(uri: Url, http: HttpSourceOptions) -> Result<MediaInfo, Error> {
+    tokio::task::spawn_blocking(move || discover(&uri, http)).await?
+}
+
+fn discover(uri: &Url, http: HttpSourceOptions) -> Result<MediaInfo, Error> {
+    gst::init()?;
+    let discoverer = Discoverer::new(gst::ClockTime::from_seconds(DISCOVER_TIMEOUT_SECS))?;
+    discoverer.connect_source_setup(move |_, source| http.apply(source));
+    let info = discoverer.discover_uri(uri.as_str())?;
+    match info.result() {
+        DiscovererResult::Ok => (),
+        DiscovererResult::MissingPlugins => {
+            let missing = info.missing_elements_installer_details().join(", ");
+            return Err(anyhow!("missing gstreamer plugins: {}", missing))
+        }
+        DiscovererResult::Timeout => return Err(anyhow!("timed out after {}s", DISCOVER_TIMEOUT_SECS)),
+        result => return Err(anyhow!("discoverer failed: {:?}", result)),
+    }
+
+    let container = info.container_streams().first()
+        .and_then(|c| c.caps())
+        .map(|caps| codec_description(&caps));
+    let video = info.video_streams().iter()
+        .filter_map(|s| s.downcast_ref::<DiscovererVideoInfo>())
+        .map(|s| {
+            let framerate = s.framerate();
+            VideoStream {
+                codec: s.caps().map(|c| codec_description(&c)).unwrap_or("unknown".to_string()),
+                width: s.width(),
+                height: s.height(),
+                framerate: if framerate.denom() > 0 { framerate.numer() as f64 / framerate.denom() as f64 } else { 0.0 },
+                interlaced: s.is_interlaced(),
+                bitrate: s.bitrate(),
+                remuxable: s.caps().is_some_and(|c| remuxable(&c)),
+            }
+        })
+        .collect();
+    let audio = info.audio_streams().iter()
+        .filter_map(|s| s.downcast_ref::<DiscovererAudioInfo>())
+        .map(|s| AudioStream {
+            codec: s.caps().map(|c| codec_description(&c)).unwrap_or("unknown".to_string()),
+            channels: s.channels(),
+            sample_rate: s.sample_rate(),
+            language: s.language().map(|l| l.to_string()),
+            bitrate: s.bitrate(),
+            remuxable: s.caps().is_some_and(|c| remuxable(&c)),
+        })
+        .collect();
+    let subtitles = info.subtitle_streams().iter()
+        .filter_map(|s| s.downcast_ref::<DiscovererSubtitleInfo>())
+        .map(|s| s.language().map(|l| l.to_string()))
+        .collect();
+
+    Ok(MediaInfo {
+        container,
+        duration: info.duration().map(|d| Duration::from_nanos(d.nseconds())),
+        seekable: info.is_seekable(),
+        live: info.is_live(),
+        video,
+        audio,
+        subtitles,
+    })
+}
+
+// h.264, mp3 and aac without adts headers go into flv without a decoder or encoder in between
+fn remuxable(caps: &gst::Caps) -> bool {
+    let s = match caps.structure(0) {
+        Some(s) => s,
+        None => return false,
+    };
+    match s.name().as_str() {
+        "video/x-h264" => true,
+        "audio/mpeg" => match s.get::<i32>("mpegversion") {
+            Ok(1) => s.get::<i32>("layer").is_ok_and(|l| l == 3),
+            Ok(2) | Ok(4) => !matches!(s.get::<&str>("stream-format"), Ok(f) if f != "raw"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn codec_description(caps: &gst::Caps) -> String {
+    gst_pbutils::pb_utils_get_codec_description(caps).to_string()
+}
@@ -0,0 +1,71 @@
+use anyhow::{Error, anyhow};
+use derive_more::{Display, Error};
+use serde::{Deserialize, Serialize};
+
+// the age a rating is meant for. the us movie and tv ratings are known by name, anything else
+// is read from the age in it (FSK-16, DE-12, 12A, ...). None for unrated or unknown ratings
+pub(crate) fn rating_age(rating: &str) -> Option<u32> {
+    let upper = rating.trim().to_ascii_uppercase();
+    // emby sometimes puts the country in front
+    let rating = upper.strip_prefix("US-").unwrap_or(&upper);
+    let age = match rating {
+        "G" | "TV-Y" | "TV-G" | "U" | "APPROVED" => 0,
+        "TV-Y7" | "TV-Y7-FV" => 7,
+        "PG" | "TV-PG" => 10,
+        "PG-13" => 13,
+        "TV-14" => 14,
+        "R" | "TV-MA" => 17,
+        "NC-17" | "X" | "XXX" => 18,
+        _ => rating.split(|c: char| !c.is_ascii_digit())
+            .rfind(|d| !d.is_empty())
+            .and_then(|d| d.parse().ok())
+            .filter(|age| *age <= 21)?,
+    };
+    Some(age)
+}
+
+// the highest rating a guild allows emby items to have
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct ParentalLimit {
+    pub(crate) max_rating: String,
+    // items without a rating we can read are turned down unless this is set
+    #[serde(default)]
+    pub(crate) allow_unrated: bool,
+}
+
+impl ParentalLimit {
+    pub fn new(max_rating: &str, allow_unrated: bool) -> Result<Self, Error> {
+        if rating_age(max_rating).is_none() {
+            return Err(anyhow!("unknown rating {}, use something like PG-13, TV-14 or an age like 12", max_rating))
+        }
+        Ok(ParentalLimit { max_rating: max_rating.trim().to_string(), allow_unrated })
+    }
+
+    pub fn allows(&self, rating: Option<&str>) -> bool {
+        let max_age = rating_age(&self.max_rating).unwrap_or_default();
+        match rating.and_then(rating_age) {
+            Some(age) => age <= max_age,
+            None => self.allow_unrated,
+        }
+    }
+
+    pub fn check(&self, name: &str, rating: Option<&str>) -> Result<(), RatingBlocked> {
+        if self.allows(rating) {
+            return Ok(())
+        }
+        Err(RatingBlocked {
+            name: name.to_string(),
+            rating: rating.unwrap_or("unrated").to_string(),
+            max_rating: self.max_rating.clone(),
+        })
+    }
+}
+
+// an item turned down because of its rating, the message is meant for whoever tried to add it
+#[derive(Debug, Display, Error)]
+#[display(fmt = "{} is rated {}, this server only allows up to {}", name, rating, max_rating)]
+pub(crate) struct RatingBlocked {
+    name: String,
+    rating: String,
+    max_rating: String,
+}
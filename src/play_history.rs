@@ -0,0 +1,127 @@
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast::{self, error::RecvError}, Mutex as TokioMutex};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use std::{collections::{BTreeMap, HashMap, VecDeque}, sync::Arc};
+
+use crate::{gstreamer::QueueItem, playback_events::{FinishReason, PlaybackEvent}, state_store::StateStore};
+
+const HISTORY_FILE: &str = "history.json";
+// oldest plays get dropped past this
+const MAX_PLAYS: usize = 2000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub(crate) struct ItemStats {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) skips: u32,
+    #[serde(default)]
+    pub(crate) last_skip_reason: Option<String>,
+}
+
+// one item that was on stream, from when it started until it left the player
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct PlayRecord {
+    pub(crate) name: String,
+    pub(crate) uri: String,
+    #[serde(default)]
+    pub(crate) emby_item_id: Option<String>,
+    #[serde(default)]
+    pub(crate) requested_by: Option<String>,
+    pub(crate) started_at: DateTime<Utc>,
+    pub(crate) finished_at: DateTime<Utc>,
+    pub(crate) reason: FinishReason,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct HistoryFile {
+    // keyed by the item uri since that is the same every time an item gets queued
+    #[serde(default)]
+    items: BTreeMap<String, ItemStats>,
+    // oldest first
+    #[serde(default)]
+    plays: VecDeque<PlayRecord>,
+}
+
+#[derive(Clone)]
+pub(crate) struct PlayHistory {
+    store: StateStore,
+    history: Arc<TokioMutex<HistoryFile>>,
+}
+
+impl PlayHistory {
+    pub async fn load(store: StateStore) -> Result<Self, Error> {
+        let history = store.load::<HistoryFile>(HISTORY_FILE).await?;
+        Ok(PlayHistory {
+            store,
+            history: Arc::new(TokioMutex::new(history)),
+        })
+    }
+
+    // returns how often the item has been skipped including this time
+    pub async fn record_skip(&self, item: &QueueItem, reason: Option<String>) -> Result<u32, Error> {
+        let mut history = self.history.lock().await;
+        let stats = history.items.entry(item.uri().to_string()).or_default();
+        stats.name = item.name();
+        stats.skips += 1;
+        if reason.is_some() {
+            stats.last_skip_reason = reason;
+        }
+        let skips = stats.skips;
+        self.store.save(HISTORY_FILE, &*history).await?;
+        Ok(skips)
+    }
+
+    pub async fn record_play(&self, record: PlayRecord) -> Result<(), Error> {
+        let mut history = self.history.lock().await;
+        history.plays.push_back(record);
+        while history.plays.len() > MAX_PLAYS {
+            history.plays.pop_front();
+        }
+        self.store.save(HISTORY_FILE, &*history).await
+    }
+
+    // newest first, skipping the first `offset` plays. also returns how many plays there are in total
+    pub async fn plays(&self, offset: usize, limit: usize) -> (Vec<PlayRecord>, usize) {
+        let history = self.history.lock().await;
+        (history.plays.iter().rev().skip(offset).take(limit).cloned().collect(), history.plays.len())
+    }
+}
+
+// writes every item that was on stream to the history once it is done
+pub(crate) async fn run_history_recorder(history: PlayHistory, mut receiver: broadcast::Receiver<PlaybackEvent>) {
+    // items that never started (dropped while loading) have no entry here and aren't recorded
+    let mut started: HashMap<Uuid, DateTime<Utc>> = HashMap::new();
+    loop {
+        match receiver.recv().await {
+            // bumpers aren't something anyone asked to watch
+            Ok(PlaybackEvent::ItemStarted { item }) if !item.is_bumper() => {
+                started.insert(item.id(), Utc::now());
+            }
+            Ok(PlaybackEvent::ItemFinished { item, reason, .. }) => {
+                let started_at = match started.remove(&item.id()) {
+                    Some(s) => s,
+                    None => continue,
+                };
+                let record = PlayRecord {
+                    name: item.name(),
+                    uri: item.uri().to_string(),
+                    emby_item_id: item.watched_mark().map(|m| m.item_id),
+                    requested_by: item.requested_by().map(|r| r.name),
+                    started_at,
+                    finished_at: Utc::now(),
+                    reason,
+                };
+                if let Err(e) = history.record_play(record).await {
+                    error!("unable to record play of {}: {}", item.name(), e);
+                }
+            }
+            Ok(_) => (),
+            Err(RecvError::Lagged(skipped)) => warn!("history recorder fell behind, skipped {} events", skipped),
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
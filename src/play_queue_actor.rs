@@ -0,0 +1,832 @@
+use anyhow::{Error, anyhow};
+use gst::{prelude::*, MessageView};
+use poise::serenity_prelude::futures::StreamExt;
+use tokio::{sync::{broadcast::{self, error::RecvError}, mpsc, oneshot, watch}, task::JoinHandle};
+use tracing::{error, info, warn};
+use url::Url;
+use uuid::Uuid;
+
+use std::time::{Duration, Instant};
+
+use crate::{gstreamer::{describe_missing_plugin, GAPLESS_SWITCH_MESSAGE, is_output_message, is_unsupported_media_error, message_source_name, sample_to_png, select_streams, BusLog, BusLogEntry, Chapter, ChapterJump, ChapterList, Intermission, OutputTarget, PipelineStats, PlayQueue, QueueItem, QueueItemOptions, QueuePosition, RepeatMode, SeekResult, StopFn, StreamHealth, StreamSelection, StreamStats, TitleOverlay, TrackInfo}, playback_events::{FinishReason, PlaybackEvent, PlaybackEvents}, source_resolver::SourceResolver, trim_analysis::TrimAnalyser, video_commands::format_timestamp};
+
+const MAILBOX_SIZE: usize = 32;
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+// how often the faders get a new level during a transition
+const FADE_STEP: Duration = Duration::from_millis(40);
+const CLOCK_STEP: Duration = Duration::from_secs(1);
+// how often the prefetcher looks whether what is up next still has a usable url
+const PREFETCH_INTERVAL: Duration = Duration::from_secs(10);
+// resolving earlier than this risks the url or token expiring before the item comes up
+const PREFETCH_LEAD: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+pub(crate) struct WatchdogConfig {
+    // None disables the watchdog
+    stall_timeout: Option<Duration>,
+}
+
+impl WatchdogConfig {
+    pub fn from_env() -> Self {
+        let stall_secs = std::env::var("WATCHDOG_STALL_SECS").ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(30);
+        WatchdogConfig {
+            stall_timeout: if stall_secs == 0 { None } else { Some(Duration::from_secs(stall_secs)) },
+        }
+    }
+}
+
+type Responder<T> = oneshot::Sender<T>;
+
+// everything the play queue can be asked to do. each request carries a oneshot to send the result back on
+pub(crate) enum PlayQueueMessage {
+    EnqueueItem { item: Box<QueueItem>, position: QueuePosition, respond_to: Responder<Result<QueueItem, Error>> },
+    Shuffle { respond_to: Responder<usize> },
+    Clear { respond_to: Responder<usize> },
+    MoveItem { id: Uuid, new_index: usize, respond_to: Responder<Result<usize, Error>> },
+    RemoveUri { id: Uuid, respond_to: Responder<Result<(), Error>> },
+    GetQueueItems { respond_to: Responder<Vec<QueueItem>> },
+    GetCurrentItem { respond_to: Responder<Option<QueueItem>> },
+    GetIntermission { respond_to: Responder<Option<Intermission>> },
+    GetRepeatMode { respond_to: Responder<RepeatMode> },
+    SetRepeatMode { mode: RepeatMode, respond_to: Responder<()> },
+    StartPlayback { respond_to: Responder<Result<Option<QueueItem>, Error>> },
+    StopPlayback { respond_to: Responder<Result<(), Error>> },
+    PausePlayback { respond_to: Responder<Result<(), Error>> },
+    SkipVideo { reason: Option<String>, respond_to: Responder<Result<(), Error>> },
+    SeekVideo { seek_seconds: i64, respond_to: Responder<Result<SeekResult, Error>> },
+    GetChapters { respond_to: Responder<(Vec<Chapter>, Option<usize>)> },
+    SeekChapter { jump: ChapterJump, respond_to: Responder<Result<(usize, Chapter, SeekResult), Error>> },
+    PipelineDot { respond_to: Responder<String> },
+    LastFrame { respond_to: Responder<Result<gst::Sample, Error>> },
+    RecentBusMessages { respond_to: Responder<Vec<BusLogEntry>> },
+    GetStreamHealth { respond_to: Responder<StreamHealth> },
+    GetPipelineStats { respond_to: Responder<PipelineStats> },
+    GetTracks { respond_to: Responder<Vec<TrackInfo>> },
+    SetAudioTrack { index: usize, respond_to: Responder<Result<TrackInfo, Error>> },
+    ListOutputs { respond_to: Responder<Vec<(String, String)>> },
+    AddOutput { target: OutputTarget, respond_to: Responder<Result<String, Error>> },
+    RemoveOutput { name: String, respond_to: Responder<Result<(), Error>> },
+    AttachAudioTap { respond_to: Responder<Result<std::sync::mpsc::Receiver<Vec<u8>>, Error>> },
+    DetachAudioTap { respond_to: Responder<Result<(), Error>> },
+    ListSubtitleTracks { respond_to: Responder<(Vec<TrackInfo>, bool)> },
+    SetSubtitleTrack { index: Option<usize>, respond_to: Responder<Result<Option<TrackInfo>, Error>> },
+    ShowTitle { respond_to: Responder<Result<(), Error>> },
+    ShowClock { shown: Option<bool>, respond_to: Responder<Result<bool, Error>> },
+    // sent by the bus watcher and timers rather than commands so nobody waits on them
+    EndOfItem,
+    GaplessSwitch { item_id: Uuid },
+    NextItemResolved,
+    PipelineError { unsupported_media: bool, reason: String },
+    OutputError { reason: String },
+    ReconnectOutput,
+    ReconnectLive { item_id: Uuid },
+    EndIntermission { id: Uuid },
+    HideTitle { id: Uuid },
+    CheckProgress { stall_timeout: Duration },
+}
+
+// cheap to clone handle that commands use to talk to the play queue task
+#[derive(Clone)]
+pub(crate) struct PlayQueueHandle {
+    sender: mpsc::Sender<PlayQueueMessage>,
+    resolver: SourceResolver,
+    trimmer: TrimAnalyser,
+    events: PlaybackEvents,
+    // what is queued up, updated whenever it changes
+    queued: watch::Receiver<Vec<QueueItem>>,
+}
+
+pub(crate) struct PlayQueueTasks {
+    actor: JoinHandle<()>,
+    bus_watch: JoinHandle<()>,
+    watchdog: Option<JoinHandle<()>>,
+    transitions: Option<JoinHandle<()>>,
+    clock: Option<JoinHandle<()>>,
+    prefetch: JoinHandle<()>,
+}
+
+impl PlayQueueTasks {
+    pub fn abort(&self) {
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.abort();
+        }
+        if let Some(transitions) = &self.transitions {
+            transitions.abort();
+        }
+        if let Some(clock) = &self.clock {
+            clock.abort();
+        }
+        self.prefetch.abort();
+        self.bus_watch.abort();
+        self.actor.abort();
+    }
+}
+
+pub(crate) fn spawn(play_queue: PlayQueue, watchdog_config: WatchdogConfig) -> (PlayQueueHandle, PlayQueueTasks) {
+    let (sender, receiver) = mpsc::channel(MAILBOX_SIZE);
+    let (queue_watch, queued) = watch::channel(play_queue.get_queue_items());
+    let handle = PlayQueueHandle {
+        sender: sender.clone(),
+        resolver: play_queue.resolver(),
+        trimmer: play_queue.trimmer(),
+        events: play_queue.events(),
+        queued,
+    };
+    let pipeline = play_queue.pipeline();
+    let bus_log = play_queue.bus_log();
+    let stats = play_queue.stats();
+    let stream_selection = play_queue.stream_selection();
+    let toc_chapters = play_queue.toc_chapters();
+    let transitions = play_queue.transition().duration.map(|d| tokio::spawn(run_transitions(pipeline.clone(), d, play_queue.events().subscribe())));
+    let clock = play_queue.clock_config().enabled().then(|| tokio::spawn(run_clock(pipeline.clone(), play_queue.events().subscribe())));
+    let prefetch = tokio::spawn(run_prefetch(handle.clone(), pipeline.clone()));
+    let actor = tokio::spawn(run_actor(play_queue, receiver, sender, queue_watch));
+    let bus_watch = tokio::spawn(watch_bus(pipeline, bus_log, stats, stream_selection, toc_chapters, handle.clone()));
+    let watchdog = watchdog_config.stall_timeout.map(|stall_timeout| tokio::spawn(run_watchdog(handle.clone(), stall_timeout)));
+    (handle, PlayQueueTasks { actor, bus_watch, watchdog, transitions, clock, prefetch })
+}
+
+enum FadeState {
+    // nothing is playing, the standby source and intermissions show at full level
+    Idle,
+    // stays dark until the next item started
+    Between,
+    Playing(Instant),
+}
+
+// fades out as the item runs out and back in once the next one started
+async fn run_transitions(pipeline: gst::Pipeline, duration: Duration, mut events: broadcast::Receiver<PlaybackEvent>) {
+    let faders = [("fade_video", "alpha"), ("fade_audio", "volume")].into_iter()
+        .filter_map(|(name, property)| Some((pipeline.by_name(name)?.sink_pads().into_iter().next()?, property)))
+        .collect::<Vec<(gst::Pad, &str)>>();
+    info!("fading between items over {}ms", duration.as_millis());
+    let mut interval = tokio::time::interval(FADE_STEP);
+    let mut state = FadeState::Idle;
+    let mut level = 1.0;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            event = events.recv() => {
+                match event {
+                    Ok(PlaybackEvent::ItemStarted { .. }) => state = FadeState::Playing(Instant::now()),
+                    Ok(PlaybackEvent::ItemFinished { reason: FinishReason::Stopped, .. }) | Ok(PlaybackEvent::QueueFinished) | Ok(PlaybackEvent::IntermissionStarted { .. }) => state = FadeState::Idle,
+                    Ok(PlaybackEvent::ItemFinished { .. }) => state = FadeState::Between,
+                    Ok(_) => (),
+                    Err(RecvError::Lagged(skipped)) => warn!("transitions fell behind, skipped {} events", skipped),
+                    Err(RecvError::Closed) => break,
+                }
+                continue
+            }
+        }
+        let target = match state {
+            FadeState::Idle => 1.0,
+            FadeState::Between => 0.0,
+            FadeState::Playing(started) => fade_level(&pipeline, duration, started.elapsed()),
+        };
+        if target != level {
+            level = target;
+            for (pad, property) in &faders {
+                pad.set_property(property, level);
+            }
+        }
+    }
+}
+
+// 0 is black and silent. items without a known duration never fade out
+fn fade_level(pipeline: &gst::Pipeline, duration: Duration, since_start: Duration) -> f64 {
+    let fade_in = since_start.as_secs_f64() / duration.as_secs_f64();
+    let fade_out = remaining_time(pipeline).map(|r| r.as_secs_f64() / duration.as_secs_f64()).unwrap_or(1.0);
+    fade_in.min(fade_out).clamp(0.0, 1.0)
+}
+
+// how much is left of the item the source is playing, None when it has no known length
+fn remaining_time(pipeline: &gst::Pipeline) -> Option<Duration> {
+    let src = pipeline.by_name("src")?;
+    let position = src.query_position::<gst::ClockTime>()?;
+    let length = src.query_duration::<gst::ClockTime>().filter(|d| d.nseconds() > 0)?;
+    Some(Duration::from_nanos(length.nseconds().saturating_sub(position.nseconds())))
+}
+
+// keeps the clock overlay text in step with the current item, blank while nothing is playing
+async fn run_clock(pipeline: gst::Pipeline, mut events: broadcast::Receiver<PlaybackEvent>) {
+    let overlay = match pipeline.by_name("clock_overlay") {
+        Some(o) => o,
+        None => return,
+    };
+    let mut interval = tokio::time::interval(CLOCK_STEP);
+    let mut playing = false;
+    let mut text = String::new();
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            event = events.recv() => {
+                match event {
+                    Ok(PlaybackEvent::ItemStarted { .. }) => playing = true,
+                    Ok(PlaybackEvent::ItemFinished { .. }) | Ok(PlaybackEvent::QueueFinished) | Ok(PlaybackEvent::IntermissionStarted { .. }) => playing = false,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => warn!("clock overlay fell behind, skipped {} events", skipped),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+        let current = match playing && !overlay.property::<bool>("silent") {
+            true => clock_text(&pipeline),
+            false => String::new(),
+        };
+        if current != text {
+            overlay.set_property("text", &current);
+            text = current;
+        }
+    }
+}
+
+// live items and anything else without a length only get the elapsed time
+fn clock_text(pipeline: &gst::Pipeline) -> String {
+    let src = match pipeline.by_name("src") {
+        Some(s) => s,
+        None => return String::new(),
+    };
+    let position = match src.query_position::<gst::ClockTime>() {
+        Some(p) => Duration::from_nanos(p.nseconds()),
+        None => return String::new(),
+    };
+    match src.query_duration::<gst::ClockTime>().filter(|d| d.nseconds() > 0).map(|d| Duration::from_nanos(d.nseconds())) {
+        Some(length) => format!("{} / {}  -{}", format_timestamp(position), format_timestamp(length), format_timestamp(length.saturating_sub(position))),
+        None => format_timestamp(position),
+    }
+}
+
+async fn run_watchdog(handle: PlayQueueHandle, stall_timeout: Duration) {
+    info!("starting playback watchdog with a {}s stall timeout", stall_timeout.as_secs());
+    let mut interval = tokio::time::interval(WATCHDOG_INTERVAL);
+    loop {
+        interval.tick().await;
+        handle.notify(PlayQueueMessage::CheckProgress { stall_timeout }).await;
+    }
+}
+
+// resolves whatever is up next before the actor needs it, yt-dlp and media servers can take a while to answer.
+// only done close to the end of the current item or during an intermission so the url is still good when it
+// comes up. failures and expired urls are tried again on the next round
+async fn run_prefetch(handle: PlayQueueHandle, pipeline: gst::Pipeline) {
+    let mut queued = handle.watch_queue();
+    let mut events = handle.subscribe();
+    let mut interval = tokio::time::interval(PREFETCH_INTERVAL);
+    let mut playing = false;
+    let mut intermission = false;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => (),
+            changed = queued.changed() => {
+                if changed.is_err() {
+                    break
+                }
+            }
+            event = events.recv() => {
+                match event {
+                    Ok(PlaybackEvent::ItemStarted { .. }) => (playing, intermission) = (true, false),
+                    Ok(PlaybackEvent::IntermissionStarted { .. }) => (playing, intermission) = (false, true),
+                    Ok(PlaybackEvent::ItemFinished { .. }) | Ok(PlaybackEvent::QueueFinished) => (playing, intermission) = (false, false),
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => warn!("prefetcher fell behind, skipped {} events", skipped),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+        let next = match queued.borrow_and_update().first() {
+            Some(i) => i.clone(),
+            None => continue,
+        };
+        // live items and items without a length never get close to their end, they start the slow way
+        let due = intermission || (playing && remaining_time(&pipeline).is_some_and(|r| r <= PREFETCH_LEAD));
+        if !due {
+            continue
+        }
+        if handle.resolver.cached_playable_uri(&next.uri()).is_some() {
+            continue
+        }
+        match handle.resolver.playable_uri(&next.uri()).await {
+            Ok(_) => handle.notify(PlayQueueMessage::NextItemResolved).await,
+            Err(e) => warn!("unable to resolve {} ahead of time: {}", next.name(), e),
+        }
+    }
+}
+
+async fn run_actor(mut play_queue: PlayQueue, mut receiver: mpsc::Receiver<PlayQueueMessage>, sender: mpsc::Sender<PlayQueueMessage>, queue_watch: watch::Sender<Vec<QueueItem>>) {
+    // the intermission and title a timer is already running for
+    let mut scheduled_intermission: Option<Uuid> = None;
+    let mut scheduled_title: Option<Uuid> = None;
+    while let Some(msg) = receiver.recv().await {
+        match msg {
+            PlayQueueMessage::EnqueueItem { item, position, respond_to } => {
+                let _ = respond_to.send(play_queue.enqueue_item(*item, position));
+            }
+            PlayQueueMessage::MoveItem { id, new_index, respond_to } => {
+                let _ = respond_to.send(play_queue.move_item(&id, new_index));
+            }
+            PlayQueueMessage::Clear { respond_to } => {
+                let _ = respond_to.send(play_queue.clear());
+            }
+            PlayQueueMessage::Shuffle { respond_to } => {
+                let _ = respond_to.send(play_queue.shuffle());
+            }
+            PlayQueueMessage::RemoveUri { id, respond_to } => {
+                let _ = respond_to.send(play_queue.remove_uri(&id));
+            }
+            PlayQueueMessage::GetQueueItems { respond_to } => {
+                let _ = respond_to.send(play_queue.get_queue_items());
+            }
+            PlayQueueMessage::GetRepeatMode { respond_to } => {
+                let _ = respond_to.send(play_queue.repeat_mode());
+            }
+            PlayQueueMessage::SetRepeatMode { mode, respond_to } => {
+                play_queue.set_repeat_mode(mode);
+                let _ = respond_to.send(());
+            }
+            PlayQueueMessage::GetCurrentItem { respond_to } => {
+                let _ = respond_to.send(play_queue.get_current_item());
+            }
+            PlayQueueMessage::GetIntermission { respond_to } => {
+                let _ = respond_to.send(play_queue.intermission());
+            }
+            PlayQueueMessage::StartPlayback { respond_to } => {
+                let _ = respond_to.send(play_queue.start_playback().await);
+            }
+            PlayQueueMessage::StopPlayback { respond_to } => {
+                let _ = respond_to.send(play_queue.stop_playback().await);
+            }
+            PlayQueueMessage::PausePlayback { respond_to } => {
+                let _ = respond_to.send(play_queue.pause_playback().await);
+            }
+            PlayQueueMessage::SkipVideo { reason, respond_to } => {
+                let _ = respond_to.send(play_queue.skip_video(reason).await);
+            }
+            PlayQueueMessage::SeekVideo { seek_seconds, respond_to } => {
+                let _ = respond_to.send(play_queue.seek_video(seek_seconds).await);
+            }
+            PlayQueueMessage::GetChapters { respond_to } => {
+                let chapters = play_queue.chapters();
+                let current = play_queue.current_chapter(&chapters);
+                let _ = respond_to.send((chapters, current));
+            }
+            PlayQueueMessage::SeekChapter { jump, respond_to } => {
+                let _ = respond_to.send(play_queue.seek_chapter(jump).await);
+            }
+            PlayQueueMessage::PipelineDot { respond_to } => {
+                let _ = respond_to.send(play_queue.pipeline_dot());
+            }
+            PlayQueueMessage::LastFrame { respond_to } => {
+                let _ = respond_to.send(play_queue.last_frame());
+            }
+            PlayQueueMessage::RecentBusMessages { respond_to } => {
+                let _ = respond_to.send(play_queue.recent_bus_messages());
+            }
+            PlayQueueMessage::GetStreamHealth { respond_to } => {
+                let _ = respond_to.send(play_queue.stream_health());
+            }
+            PlayQueueMessage::GetPipelineStats { respond_to } => {
+                let _ = respond_to.send(play_queue.pipeline_stats());
+            }
+            PlayQueueMessage::GetTracks { respond_to } => {
+                let _ = respond_to.send(play_queue.stream_selection().tracks());
+            }
+            PlayQueueMessage::SetAudioTrack { index, respond_to } => {
+                let _ = respond_to.send(play_queue.set_audio_track(index));
+            }
+            PlayQueueMessage::ListOutputs { respond_to } => {
+                let _ = respond_to.send(play_queue.outputs());
+            }
+            PlayQueueMessage::AddOutput { target, respond_to } => {
+                let _ = respond_to.send(play_queue.add_output(target));
+            }
+            PlayQueueMessage::RemoveOutput { name, respond_to } => {
+                let _ = respond_to.send(play_queue.remove_output(&name));
+            }
+            PlayQueueMessage::AttachAudioTap { respond_to } => {
+                let _ = respond_to.send(play_queue.attach_audio_tap());
+            }
+            PlayQueueMessage::DetachAudioTap { respond_to } => {
+                let _ = respond_to.send(play_queue.detach_audio_tap());
+            }
+            PlayQueueMessage::ListSubtitleTracks { respond_to } => {
+                let _ = respond_to.send((play_queue.list_subtitle_tracks(), play_queue.subtitles_enabled()));
+            }
+            PlayQueueMessage::SetSubtitleTrack { index, respond_to } => {
+                let _ = respond_to.send(play_queue.set_subtitle_track(index));
+            }
+            PlayQueueMessage::ShowTitle { respond_to } => {
+                let _ = respond_to.send(play_queue.show_title());
+            }
+            PlayQueueMessage::ShowClock { shown, respond_to } => {
+                let _ = respond_to.send(play_queue.show_clock(shown));
+            }
+            PlayQueueMessage::HideTitle { id } => {
+                play_queue.hide_title(&id);
+            }
+            PlayQueueMessage::EndOfItem => {
+                let result = play_queue.handle_end_of_item().await;
+                schedule_reconnect(&play_queue, &sender, result);
+            }
+            PlayQueueMessage::GaplessSwitch { item_id } => {
+                if let Err(e) = play_queue.handle_gapless_switch(&item_id).await {
+                    error!("error moving on to the next item: {}", e);
+                }
+            }
+            PlayQueueMessage::NextItemResolved => {
+                // prepare_next_item below picks it up
+            }
+            PlayQueueMessage::PipelineError { unsupported_media, reason } => {
+                let result = play_queue.handle_pipeline_error(unsupported_media, reason).await;
+                schedule_reconnect(&play_queue, &sender, result);
+            }
+            PlayQueueMessage::OutputError { reason } => {
+                match play_queue.handle_output_error(reason) {
+                    Ok(Some(delay)) => {
+                        let sender = sender.clone();
+                        tokio::spawn(async move {
+                            tokio::time::sleep(delay).await;
+                            if sender.send(PlayQueueMessage::ReconnectOutput).await.is_err() {
+                                warn!("play queue went away before the output could reconnect");
+                            }
+                        });
+                    }
+                    Ok(None) => (),
+                    Err(e) => error!("error stopping the pipeline after the output failed: {}", e),
+                }
+            }
+            PlayQueueMessage::ReconnectOutput => {
+                if let Err(e) = play_queue.reconnect_output().await {
+                    error!("error reconnecting the output: {}", e);
+                    // counts as another failure so the backoff keeps going
+                    handle_failed_reconnect(&sender, e.to_string());
+                }
+            }
+            PlayQueueMessage::ReconnectLive { item_id } => {
+                if let Err(e) = play_queue.reconnect_current_item(&item_id).await {
+                    error!("error reconnecting live item: {}", e);
+                    // a failed reconnect counts as another drop so it either retries later or gives up
+                    let result = play_queue.handle_end_of_item().await;
+                    schedule_reconnect(&play_queue, &sender, result);
+                }
+            }
+            PlayQueueMessage::EndIntermission { id } => {
+                if let Err(e) = play_queue.end_intermission(&id).await {
+                    error!("error starting the next item after the intermission: {}", e);
+                }
+            }
+            PlayQueueMessage::CheckProgress { stall_timeout } => {
+                if let Err(e) = play_queue.check_progress(stall_timeout).await {
+                    error!("watchdog failed to recover stalled item: {}", e);
+                }
+            }
+        }
+        play_queue.prepare_next_item();
+        if let Some(intermission) = play_queue.intermission().filter(|i| scheduled_intermission != Some(i.id)) {
+            scheduled_intermission = Some(intermission.id);
+            schedule_intermission_end(&sender, intermission);
+        }
+        if let Some(title) = play_queue.title_overlay().filter(|t| scheduled_title != Some(t.id)) {
+            scheduled_title = Some(title.id);
+            schedule_title_hide(&sender, title);
+        }
+        queue_watch.send_if_modified(|queued| {
+            let current = play_queue.get_queue_items();
+            if queued.iter().map(|i| i.id()).eq(current.iter().map(|i| i.id())) {
+                return false
+            }
+            *queued = current;
+            true
+        });
+    }
+    info!("play queue actor stopped");
+}
+
+fn handle_failed_reconnect(sender: &mpsc::Sender<PlayQueueMessage>, reason: String) {
+    let sender = sender.clone();
+    tokio::spawn(async move {
+        if sender.send(PlayQueueMessage::OutputError { reason }).await.is_err() {
+            warn!("play queue went away while reconnecting the output");
+        }
+    });
+}
+
+fn schedule_intermission_end(sender: &mpsc::Sender<PlayQueueMessage>, intermission: Intermission) {
+    let sender = sender.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(intermission.remaining()).await;
+        if sender.send(PlayQueueMessage::EndIntermission { id: intermission.id }).await.is_err() {
+            warn!("play queue went away during the intermission");
+        }
+    });
+}
+
+fn schedule_title_hide(sender: &mpsc::Sender<PlayQueueMessage>, title: TitleOverlay) {
+    let sender = sender.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(title.remaining()).await;
+        if sender.send(PlayQueueMessage::HideTitle { id: title.id }).await.is_err() {
+            warn!("play queue went away while showing the title");
+        }
+    });
+}
+
+// reconnects wait on a timer task so the actor keeps serving commands in the meantime
+fn schedule_reconnect(play_queue: &PlayQueue, sender: &mpsc::Sender<PlayQueueMessage>, result: Result<Option<Duration>, Error>) {
+    match result {
+        Ok(Some(delay)) => {
+            let item_id = match play_queue.get_current_item() {
+                Some(i) => i.id(),
+                None => return,
+            };
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                if sender.send(PlayQueueMessage::ReconnectLive { item_id }).await.is_err() {
+                    warn!("play queue went away before live item could reconnect");
+                }
+            });
+        }
+        Ok(None) => (),
+        Err(e) => error!("{}", e),
+    }
+}
+
+async fn watch_bus(pipeline: gst::Pipeline, bus_log: BusLog, stats: StreamStats, stream_selection: StreamSelection, toc_chapters: ChapterList, handle: PlayQueueHandle) {
+    let bus = pipeline.bus().unwrap();
+    let mut messages = bus.stream();
+    // the missing-plugin element message arrives before the error so keep it around for a useful skip message
+    let mut last_missing_plugin: Option<String> = None;
+
+    while let Some(msg) = messages.next().await {
+        match msg.view() {
+            MessageView::Eos(..) => {
+                handle.notify(PlayQueueMessage::EndOfItem).await;
+            },
+            MessageView::Error(err) => {
+                let src_name = message_source_name(&msg);
+                error!(source = %src_name, debug = ?err.debug(), "gstreamer error: {}", err.error());
+                bus_log.push("error", src_name, err.error().to_string());
+                let unsupported_media = is_unsupported_media_error(&err.error());
+                let reason = match unsupported_media {
+                    true => last_missing_plugin.take().unwrap_or(err.error().to_string()),
+                    false => err.error().to_string(),
+                };
+                if is_output_message(&msg) {
+                    handle.notify(PlayQueueMessage::OutputError { reason: err.error().to_string() }).await;
+                    continue
+                }
+                handle.notify(PlayQueueMessage::PipelineError { unsupported_media, reason }).await;
+            },
+            MessageView::Application(a) => {
+                let item_id = a.structure()
+                    .filter(|s| s.name() == GAPLESS_SWITCH_MESSAGE)
+                    .and_then(|s| s.get::<String>("item").ok())
+                    .and_then(|id| Uuid::parse_str(&id).ok());
+                if let Some(item_id) = item_id {
+                    handle.notify(PlayQueueMessage::GaplessSwitch { item_id }).await;
+                }
+            },
+            MessageView::Warning(w) => {
+                let src_name = message_source_name(&msg);
+                warn!(source = %src_name, debug = ?w.debug(), "gstreamer warning: {}", w.error());
+                bus_log.push("warning", src_name, w.error().to_string());
+            },
+            MessageView::Info(i) => {
+                let src_name = message_source_name(&msg);
+                info!(source = %src_name, debug = ?i.debug(), "gstreamer info: {}", i.error());
+                bus_log.push("info", src_name, i.error().to_string());
+            },
+            MessageView::StreamCollection(c) => {
+                let stream_ids = stream_selection.default_selection(c.stream_collection());
+                if let Err(e) = select_streams(&pipeline, &stream_ids) {
+                    warn!("{}", e);
+                }
+            },
+            MessageView::StreamsSelected(s) => {
+                let stream_ids = s.streams().iter().filter_map(|stream| stream.stream_id()).map(|id| id.to_string()).collect();
+                stream_selection.set_selected(stream_ids);
+            },
+            MessageView::Toc(t) => {
+                let (toc, _) = t.toc();
+                toc_chapters.update(&toc);
+            },
+            MessageView::Qos(..) => {
+                // elements post qos when they throw away late buffers
+                stats.record_dropped_frame();
+            },
+            MessageView::Buffering(b) => {
+                let previous = stats.record_buffering(b.percent());
+                if (previous < 100) != (b.percent() < 100) {
+                    let state = if b.percent() < 100 { format!("buffering {}%", b.percent()) } else { "done buffering".to_string() };
+                    info!("source is {}", state);
+                    bus_log.push("buffering", message_source_name(&msg), state);
+                }
+            },
+            MessageView::Element(e) => {
+                if let Some(structure) = e.structure().filter(|s| s.name() == "missing-plugin") {
+                    let src_name = message_source_name(&msg);
+                    let description = describe_missing_plugin(structure);
+                    error!(source = %src_name, "{}", description);
+                    bus_log.push("missing-plugin", src_name, description.clone());
+                    last_missing_plugin = Some(description);
+                } else if let Some(structure) = e.structure() {
+                    let src_name = message_source_name(&msg);
+                    info!(source = %src_name, structure = %structure.name(), "gstreamer element message: {}", structure);
+                    bus_log.push("element", src_name, structure.to_string());
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+impl PlayQueueHandle {
+    async fn request<T>(&self, make_msg: impl FnOnce(Responder<T>) -> PlayQueueMessage) -> Result<T, Error> {
+        let (respond_to, response) = oneshot::channel();
+        self.sender.send(make_msg(respond_to)).await.map_err(|_| anyhow!("play queue is not running"))?;
+        response.await.map_err(|_| anyhow!("play queue dropped the request"))
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PlaybackEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn watch_queue(&self) -> watch::Receiver<Vec<QueueItem>> {
+        self.queued.clone()
+    }
+
+    async fn notify(&self, msg: PlayQueueMessage) {
+        if self.sender.send(msg).await.is_err() {
+            error!("play queue is not running");
+        }
+    }
+
+    pub async fn add_uri(&self, uri: String, display_name: String, stop_fn: Option<StopFn>, options: QueueItemOptions, position: QueuePosition) -> Result<QueueItem, Error> {
+        // resolving can shell out to yt-dlp so do it here instead of inside the actor
+        let item = PlayQueue::prepare_item(&self.resolver, uri, display_name, stop_fn, options).await?;
+        self.trimmer.schedule(&item.uri());
+        self.request(|respond_to| PlayQueueMessage::EnqueueItem { item: Box::new(item), position, respond_to }).await?
+    }
+
+    // builds an item the same way add_uri does without queueing it
+    pub async fn prepare_item(&self, uri: String, display_name: String) -> Result<QueueItem, Error> {
+        PlayQueue::prepare_item(&self.resolver, uri, display_name, None, QueueItemOptions::default()).await
+    }
+
+    pub async fn playable_uri(&self, uri: &Url) -> Result<Url, Error> {
+        self.resolver.playable_uri(uri).await
+    }
+
+    // the actor only looks in the resolver cache, so fill it for the item that is about to start
+    async fn resolve_next(&self) {
+        let next = self.queued.borrow().first().cloned();
+        if let Some(next) = next {
+            if let Err(e) = self.resolver.playable_uri(&next.uri()).await {
+                warn!("unable to resolve {}: {}", next.name(), e);
+            }
+        }
+    }
+
+    pub async fn remove_uri(&self, id: &Uuid) -> Result<(), Error> {
+        let id = *id;
+        self.request(|respond_to| PlayQueueMessage::RemoveUri { id, respond_to }).await?
+    }
+
+    // returns the index the item ended up at
+    pub async fn move_item(&self, id: Uuid, new_index: usize) -> Result<usize, Error> {
+        self.request(|respond_to| PlayQueueMessage::MoveItem { id, new_index, respond_to }).await?
+    }
+
+    // returns how many items were removed
+    pub async fn clear(&self) -> Result<usize, Error> {
+        self.request(|respond_to| PlayQueueMessage::Clear { respond_to }).await
+    }
+
+    // returns how many items got shuffled
+    pub async fn shuffle(&self) -> Result<usize, Error> {
+        self.request(|respond_to| PlayQueueMessage::Shuffle { respond_to }).await
+    }
+
+    pub async fn get_queue_items(&self) -> Result<Vec<QueueItem>, Error> {
+        self.request(|respond_to| PlayQueueMessage::GetQueueItems { respond_to }).await
+    }
+
+    pub async fn get_current_item(&self) -> Result<Option<QueueItem>, Error> {
+        self.request(|respond_to| PlayQueueMessage::GetCurrentItem { respond_to }).await
+    }
+
+    pub async fn intermission(&self) -> Result<Option<Intermission>, Error> {
+        self.request(|respond_to| PlayQueueMessage::GetIntermission { respond_to }).await
+    }
+
+    pub async fn repeat_mode(&self) -> Result<RepeatMode, Error> {
+        self.request(|respond_to| PlayQueueMessage::GetRepeatMode { respond_to }).await
+    }
+
+    pub async fn set_repeat_mode(&self, mode: RepeatMode) -> Result<(), Error> {
+        self.request(|respond_to| PlayQueueMessage::SetRepeatMode { mode, respond_to }).await
+    }
+
+    pub async fn start_playback(&self) -> Result<Option<QueueItem>, Error> {
+        self.resolve_next().await;
+        self.request(|respond_to| PlayQueueMessage::StartPlayback { respond_to }).await?
+    }
+
+    pub async fn stop_playback(&self) -> Result<(), Error> {
+        self.request(|respond_to| PlayQueueMessage::StopPlayback { respond_to }).await?
+    }
+
+    pub async fn pause_playback(&self) -> Result<(), Error> {
+        self.request(|respond_to| PlayQueueMessage::PausePlayback { respond_to }).await?
+    }
+
+    pub async fn skip_video(&self, reason: Option<String>) -> Result<(), Error> {
+        self.resolve_next().await;
+        self.request(|respond_to| PlayQueueMessage::SkipVideo { reason, respond_to }).await?
+    }
+
+    pub async fn seek_video(&self, seek_seconds: i64) -> Result<SeekResult, Error> {
+        self.request(|respond_to| PlayQueueMessage::SeekVideo { seek_seconds, respond_to }).await?
+    }
+
+    // chapters of the current item and the one that is playing
+    pub async fn chapters(&self) -> Result<(Vec<Chapter>, Option<usize>), Error> {
+        self.request(|respond_to| PlayQueueMessage::GetChapters { respond_to }).await
+    }
+
+    pub async fn seek_chapter(&self, jump: ChapterJump) -> Result<(usize, Chapter, SeekResult), Error> {
+        self.request(|respond_to| PlayQueueMessage::SeekChapter { jump, respond_to }).await?
+    }
+
+    pub async fn pipeline_dot(&self) -> Result<String, Error> {
+        self.request(|respond_to| PlayQueueMessage::PipelineDot { respond_to }).await
+    }
+
+    // png of the frame that is on the stream right now
+    pub async fn snapshot(&self) -> Result<Vec<u8>, Error> {
+        let sample = self.request(|respond_to| PlayQueueMessage::LastFrame { respond_to }).await??;
+        tokio::task::spawn_blocking(move || sample_to_png(&sample)).await?
+    }
+
+    pub async fn recent_bus_messages(&self) -> Result<Vec<BusLogEntry>, Error> {
+        self.request(|respond_to| PlayQueueMessage::RecentBusMessages { respond_to }).await
+    }
+
+    // puts the name of the current item on the video for a few seconds
+    pub async fn show_title(&self) -> Result<(), Error> {
+        self.request(|respond_to| PlayQueueMessage::ShowTitle { respond_to }).await?
+    }
+
+    // returns whether the clock is on the video now
+    pub async fn show_clock(&self, shown: Option<bool>) -> Result<bool, Error> {
+        self.request(|respond_to| PlayQueueMessage::ShowClock { shown, respond_to }).await?
+    }
+
+    pub async fn stream_health(&self) -> Result<StreamHealth, Error> {
+        self.request(|respond_to| PlayQueueMessage::GetStreamHealth { respond_to }).await
+    }
+
+    pub async fn pipeline_stats(&self) -> Result<PipelineStats, Error> {
+        self.request(|respond_to| PlayQueueMessage::GetPipelineStats { respond_to }).await
+    }
+
+    pub async fn tracks(&self) -> Result<Vec<TrackInfo>, Error> {
+        self.request(|respond_to| PlayQueueMessage::GetTracks { respond_to }).await
+    }
+
+    pub async fn set_audio_track(&self, index: usize) -> Result<TrackInfo, Error> {
+        self.request(|respond_to| PlayQueueMessage::SetAudioTrack { index, respond_to }).await?
+    }
+
+    // name and description of every output, the main one first
+    pub async fn outputs(&self) -> Result<Vec<(String, String)>, Error> {
+        self.request(|respond_to| PlayQueueMessage::ListOutputs { respond_to }).await
+    }
+
+    pub async fn add_output(&self, target: OutputTarget) -> Result<String, Error> {
+        self.request(|respond_to| PlayQueueMessage::AddOutput { target, respond_to }).await?
+    }
+
+    pub async fn remove_output(&self, name: String) -> Result<(), Error> {
+        self.request(|respond_to| PlayQueueMessage::RemoveOutput { name, respond_to }).await?
+    }
+
+    pub async fn attach_audio_tap(&self) -> Result<std::sync::mpsc::Receiver<Vec<u8>>, Error> {
+        self.request(|respond_to| PlayQueueMessage::AttachAudioTap { respond_to }).await?
+    }
+
+    pub async fn detach_audio_tap(&self) -> Result<(), Error> {
+        self.request(|respond_to| PlayQueueMessage::DetachAudioTap { respond_to }).await?
+    }
+
+    // the tracks and whether subtitles are showing at all
+    pub async fn list_subtitle_tracks(&self) -> Result<(Vec<TrackInfo>, bool), Error> {
+        self.request(|respond_to| PlayQueueMessage::ListSubtitleTracks { respond_to }).await
+    }
+
+    pub async fn set_subtitle_track(&self, index: Option<usize>) -> Result<Option<TrackInfo>, Error> {
+        self.request(|respond_to| PlayQueueMessage::SetSubtitleTrack { index, respond_to }).await?
+    }
+}
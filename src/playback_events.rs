@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+use std::time::Duration;
+
+use crate::gstreamer::{Intermission, QueueItem};
+
+const EVENT_BUFFER_SIZE: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum StallAction {
+    Restarted,
+    Skipped,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FinishReason {
+    // played through to the end
+    Completed,
+    Skipped,
+    Stopped,
+    // unplayable or wedged and dropped by the queue
+    Failed,
+}
+
+#[derive(Clone)]
+pub(crate) enum PlaybackEvent {
+    // the item was handed to the pipeline and is now what's on stream
+    ItemStarted { item: QueueItem },
+    // the pipeline said it was playing but the position stopped moving
+    Stalled { item: QueueItem, stalled_for: Duration, action: StallAction },
+    // the item left the player for whatever reason. detail is a human readable why (skip reason, error) and position is where it was
+    ItemFinished { item: QueueItem, reason: FinishReason, detail: Option<String>, position: Option<Duration> },
+    // the last item finished and there was nothing left to play
+    QueueFinished,
+    // an item finished and the next one waits for the gap to be over
+    IntermissionStarted { intermission: Intermission },
+}
+
+// fan out of playback events to whoever is interested (announcements, webhooks, presence, ...)
+#[derive(Clone)]
+pub(crate) struct PlaybackEvents(broadcast::Sender<PlaybackEvent>);
+
+impl PlaybackEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+        PlaybackEvents(sender)
+    }
+
+    pub fn emit(&self, event: PlaybackEvent) {
+        // sending only fails when nobody is subscribed which is fine
+        let _ = self.0.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PlaybackEvent> {
+        self.0.subscribe()
+    }
+}
+
+// writes every playback event to the log. runs until the sender side goes away
+pub(crate) async fn log_events(mut receiver: broadcast::Receiver<PlaybackEvent>) {
+    loop {
+        match receiver.recv().await {
+            Ok(PlaybackEvent::ItemStarted { item }) => {
+                info!(item_id = %item.id(), uri = %item.uri(), "started {}", item.name());
+            }
+            Ok(PlaybackEvent::QueueFinished) => info!("queue finished"),
+            Ok(PlaybackEvent::IntermissionStarted { intermission }) => {
+                info!(item_id = %intermission.next.id(), "intermission, {} starts in {}s", intermission.next.name(), intermission.remaining().as_secs());
+            }
+            Ok(PlaybackEvent::Stalled { item, stalled_for, action }) => {
+                warn!(item_id = %item.id(), uri = %item.uri(), "{} stalled for {}s, {:?}", item.name(), stalled_for.as_secs(), action);
+            }
+            Ok(PlaybackEvent::ItemFinished { item, reason, detail, position }) => {
+                info!(item_id = %item.id(), uri = %item.uri(), detail = detail.as_deref().unwrap_or(""), "{} finished ({:?}) at {}s", item.name(), reason, position.unwrap_or_default().as_secs());
+            }
+            Err(RecvError::Lagged(skipped)) => warn!("playback event log fell behind, skipped {} events", skipped),
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
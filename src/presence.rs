@@ -0,0 +1,120 @@
+use anyhow::{Error, anyhow};
+use poise::serenity_prelude::{self as serenity, ActivityData};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex as TokioMutex;
+use tracing::{info, warn};
+
+use std::{sync::Arc, time::Duration};
+
+use crate::{play_queue_actor::PlayQueueHandle, state_store::StateStore};
+
+const PRESENCE_FILE: &str = "presence.json";
+const MIN_INTERVAL_SECS: u64 = 15;
+// discord cuts custom statuses off at 128 characters
+const MAX_STATUS_LEN: usize = 128;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct PresenceSettings {
+    #[serde(default)]
+    pub(crate) messages: Vec<String>,
+    #[serde(default = "default_interval_secs")]
+    pub(crate) interval_secs: u64,
+}
+
+fn default_interval_secs() -> u64 {
+    60
+}
+
+impl Default for PresenceSettings {
+    fn default() -> Self {
+        PresenceSettings {
+            messages: vec![],
+            interval_secs: default_interval_secs(),
+        }
+    }
+}
+
+// rotates the bot status through the configured messages. while something is playing it gets a slot in the rotation
+#[derive(Clone)]
+pub(crate) struct PresenceManager {
+    store: StateStore,
+    settings: Arc<TokioMutex<PresenceSettings>>,
+}
+
+impl PresenceManager {
+    pub async fn load(store: StateStore) -> Result<Self, Error> {
+        let settings = store.load::<PresenceSettings>(PRESENCE_FILE).await?;
+        Ok(PresenceManager {
+            store,
+            settings: Arc::new(TokioMutex::new(settings)),
+        })
+    }
+
+    pub async fn settings(&self) -> PresenceSettings {
+        self.settings.lock().await.clone()
+    }
+
+    pub async fn add_message(&self, message: String) -> Result<(), Error> {
+        if message.is_empty() || message.chars().count() > MAX_STATUS_LEN {
+            return Err(anyhow!("status messages must be between 1 and {} characters", MAX_STATUS_LEN))
+        }
+        let mut settings = self.settings.lock().await;
+        settings.messages.push(message);
+        self.store.save(PRESENCE_FILE, &*settings).await
+    }
+
+    // index is 1 based to match what list shows
+    pub async fn remove_message(&self, index: usize) -> Result<String, Error> {
+        let mut settings = self.settings.lock().await;
+        if index == 0 || index > settings.messages.len() {
+            return Err(anyhow!("no status message number {}", index))
+        }
+        let removed = settings.messages.remove(index - 1);
+        self.store.save(PRESENCE_FILE, &*settings).await?;
+        Ok(removed)
+    }
+
+    pub async fn set_interval(&self, interval_secs: u64) -> Result<(), Error> {
+        if interval_secs < MIN_INTERVAL_SECS {
+            return Err(anyhow!("the interval has to be at least {}s, discord rate limits presence updates", MIN_INTERVAL_SECS))
+        }
+        let mut settings = self.settings.lock().await;
+        settings.interval_secs = interval_secs;
+        self.store.save(PRESENCE_FILE, &*settings).await
+    }
+}
+
+pub(crate) async fn run_presence(ctx: serenity::Context, presence: PresenceManager, play_queue: PlayQueueHandle) {
+    info!("starting presence rotation");
+    let mut position = 0;
+    let mut last_activity: Option<String> = None;
+    loop {
+        let settings = presence.settings().await;
+        let now_playing = match play_queue.get_current_item().await {
+            Ok(item) => item.map(|i| i.name().chars().take(MAX_STATUS_LEN).collect::<String>()),
+            Err(e) => {
+                warn!("unable to get current item for presence: {}", e);
+                None
+            }
+        };
+        // the now playing item is one extra slot at the end of the rotation
+        let slots = settings.messages.len() + now_playing.is_some() as usize;
+        let activity = if slots == 0 {
+            None
+        } else {
+            position %= slots;
+            match settings.messages.get(position) {
+                Some(message) => Some(ActivityData::custom(message.clone())),
+                None => now_playing.map(ActivityData::watching),
+            }
+        };
+        position += 1;
+        let activity_key = activity.as_ref().map(|a| format!("{:?}", a));
+        // skip no-op updates, they still count against the gateway rate limit
+        if activity_key != last_activity {
+            ctx.set_activity(activity);
+            last_activity = activity_key;
+        }
+        tokio::time::sleep(Duration::from_secs(settings.interval_secs.max(MIN_INTERVAL_SECS))).await;
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{Context, Error};
+use poise::CreateReply;
+
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR", subcommands("list", "add", "remove", "interval"), subcommand_required)]
+pub(crate) async fn rusto_presence(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// show the status messages the bot rotates through
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let settings = ctx.data().presence.settings().await;
+    let mut message = format!("rotating every {}s, the current video is shown while something plays\n", settings.interval_secs);
+    if settings.messages.is_empty() {
+        message.push_str("no status messages configured");
+    } else {
+        message.push_str(&settings.messages.iter().enumerate().map(|(i, m)| format!("{}. {}", i + 1, m)).collect::<Vec<String>>().join("\n"));
+    }
+    ctx.send(CreateReply::default().content(message).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// add a status message to the rotation
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "status text"] message: String,
+) -> Result<(), Error> {
+    ctx.data().presence.add_message(message.trim().to_string()).await?;
+    ctx.send(CreateReply::default().content("added status message").ephemeral(true)).await?;
+    Ok(())
+}
+
+/// remove a status message from the rotation
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "number of the message from list"] number: usize,
+) -> Result<(), Error> {
+    let removed = ctx.data().presence.remove_message(number).await?;
+    ctx.send(CreateReply::default().content(format!("removed `{}`", removed)).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// change how often the status changes
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn interval(
+    ctx: Context<'_>,
+    #[description = "seconds between status changes"] seconds: u64,
+) -> Result<(), Error> {
+    ctx.data().presence.set_interval(seconds).await?;
+    ctx.send(CreateReply::default().content(format!("status now changes every {}s", seconds)).ephemeral(true)).await?;
+    Ok(())
+}
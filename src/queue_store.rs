@@ -0,0 +1,184 @@
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use url::Url;
+
+use std::time::Duration;
+
+use crate::{embyclient::{EmbySearch, EmbyServers}, gstreamer::{Chapter, EmbyItemRef, QueueItem, QueueItemOptions, QueuePosition, Requester, WatchedMark}, play_queue_actor::PlayQueueHandle, state_store::StateStore};
+
+const QUEUE_FILE: &str = "queue.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct SavedChapter {
+    title: Option<String>,
+    start_ms: u64,
+}
+
+// a queued item as it goes to disk, also used for the resume point. http headers and credentials are left out on purpose
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct SavedItem {
+    pub(crate) uri: String,
+    pub(crate) name: String,
+    #[serde(default)]
+    requested_by: Option<String>,
+    #[serde(default)]
+    requested_by_id: Option<u64>,
+    #[serde(default)]
+    live: bool,
+    #[serde(default)]
+    subtitles: Option<String>,
+    #[serde(default)]
+    max_framerate: Option<u32>,
+    #[serde(default)]
+    webhook: Option<String>,
+    #[serde(default)]
+    chapters: Vec<SavedChapter>,
+    // emby user and item to mark as watched once it has played
+    #[serde(default)]
+    emby_user_id: Option<String>,
+    #[serde(default)]
+    emby_item_id: Option<String>,
+    // media server the item came from, missing means the default one
+    #[serde(default)]
+    emby_server: Option<String>,
+    #[serde(default)]
+    copy: bool,
+}
+
+impl SavedItem {
+    pub(crate) fn from_item(item: &QueueItem) -> Self {
+        let watched_mark = item.watched_mark();
+        let emby_item = item.emby_item();
+        SavedItem {
+            uri: item.uri().to_string(),
+            name: item.name(),
+            requested_by: item.requested_by().map(|r| r.name),
+            requested_by_id: item.requested_by().map(|r| r.id),
+            live: item.is_live(),
+            subtitles: item.subtitles().map(|s| s.to_string()),
+            max_framerate: item.max_framerate(),
+            webhook: item.webhook().map(|w| w.to_string()),
+            chapters: item.chapters().iter()
+                .map(|c| SavedChapter { title: c.title.clone(), start_ms: c.start.as_millis() as u64 })
+                .collect(),
+            emby_user_id: watched_mark.as_ref().map(|m| m.user_id.clone()),
+            emby_item_id: emby_item.as_ref().map(|i| i.item_id.clone()).or(watched_mark.as_ref().map(|m| m.item_id.clone())),
+            emby_server: match emby_item {
+                Some(i) => i.server,
+                None => watched_mark.and_then(|m| m.server),
+            },
+            copy: item.copy(),
+        }
+    }
+
+    // the stop callback can't be saved, this is what it gets rebuilt from
+    pub(crate) fn watched_mark(&self) -> Option<WatchedMark> {
+        match (&self.emby_user_id, &self.emby_item_id) {
+            (Some(user_id), Some(item_id)) => Some(WatchedMark { server: self.emby_server.clone(), user_id: user_id.clone(), item_id: item_id.clone() }),
+            _ => None,
+        }
+    }
+
+    fn emby_item(&self) -> Option<EmbyItemRef> {
+        self.emby_item_id.as_ref().map(|item_id| EmbyItemRef { server: self.emby_server.clone(), item_id: item_id.clone() })
+    }
+
+    fn requester(&self) -> Option<Requester> {
+        match (self.requested_by_id, &self.requested_by) {
+            (Some(id), Some(name)) => Some(Requester { id, name: name.clone() }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn queue_options(&self) -> QueueItemOptions {
+        QueueItemOptions {
+            live: Some(self.live),
+            webhook: self.webhook.as_deref().and_then(|w| Url::parse(w).ok()),
+            max_framerate: self.max_framerate,
+            subtitles: self.subtitles.clone(),
+            chapters: self.chapters.iter()
+                .map(|c| Chapter { title: c.title.clone(), start: Duration::from_millis(c.start_ms) })
+                .collect(),
+            requested_by: self.requester(),
+            watched_mark: self.watched_mark(),
+            emby_item: self.emby_item(),
+            copy: self.copy,
+            ..QueueItemOptions::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct QueueFile {
+    #[serde(default)]
+    items: Vec<SavedItem>,
+}
+
+// the queue as json, the same format that is kept in the state dir
+pub(crate) fn export_queue(items: &[QueueItem]) -> Result<Vec<u8>, Error> {
+    Ok(serde_json::to_vec_pretty(&QueueFile { items: items.iter().map(SavedItem::from_item).collect() })?)
+}
+
+// adds an exported queue to the end of the current one, optionally on behalf of someone else.
+// returns how many items made it in and the errors for the rest
+pub(crate) async fn import_queue(data: &[u8], pipeline: &PlayQueueHandle, emby_servers: &EmbyServers, requester: Option<Requester>) -> Result<(usize, Vec<String>), Error> {
+    let mut saved: QueueFile = serde_json::from_slice(data)?;
+    if let Some(requester) = requester {
+        for item in saved.items.iter_mut() {
+            item.requested_by = Some(requester.name.clone());
+            item.requested_by_id = Some(requester.id);
+        }
+    }
+    Ok(restore_items(saved.items, pipeline, emby_servers).await)
+}
+
+async fn restore_items(items: Vec<SavedItem>, pipeline: &PlayQueueHandle, emby_servers: &EmbyServers) -> (usize, Vec<String>) {
+    let mut added = 0;
+    let mut errors = vec![];
+    for item in items {
+        let stop_fn = match item.watched_mark() {
+            Some(mark) => match emby_servers.by_name(mark.server.as_deref()) {
+                Some(server) => Some(server.user_stop_fn(mark.user_id, mark.item_id).await),
+                None => {
+                    warn!("media server {:?} of {} is no longer configured, it won't be marked as watched", mark.server, item.name);
+                    None
+                }
+            },
+            None => None,
+        };
+        match pipeline.add_uri(item.uri.clone(), item.name.clone(), stop_fn, item.queue_options(), QueuePosition::Last).await {
+            Ok(_) => added += 1,
+            Err(e) => {
+                warn!("unable to restore {} to the queue: {}", item.name, e);
+                errors.push(format!("{}: {}", item.name, e));
+            }
+        }
+    }
+    (added, errors)
+}
+
+// puts back whatever was queued before the restart, then keeps the saved queue in line with the real one
+pub(crate) async fn run_queue_store(store: StateStore, pipeline: PlayQueueHandle, emby_servers: EmbyServers) {
+    let saved = match store.load::<QueueFile>(QUEUE_FILE).await {
+        Ok(saved) => saved,
+        Err(e) => {
+            warn!("unable to load the saved queue, starting with an empty one: {}", e);
+            QueueFile::default()
+        }
+    };
+    if !saved.items.is_empty() {
+        info!("restoring {} queued items", saved.items.len());
+    }
+    restore_items(saved.items, &pipeline, &emby_servers).await;
+    let mut queued = pipeline.watch_queue();
+    loop {
+        let items = queued.borrow_and_update().iter().map(SavedItem::from_item).collect();
+        if let Err(e) = store.save(QUEUE_FILE, &QueueFile { items }).await {
+            warn!("unable to save the queue: {}", e);
+        }
+        if queued.changed().await.is_err() {
+            break
+        }
+    }
+}
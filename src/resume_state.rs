@@ -0,0 +1,130 @@
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast::error::RecvError, Mutex as TokioMutex};
+use tracing::{info, warn};
+
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
+
+use crate::{gstreamer::{QueueItem, QueueItemOptions}, play_queue_actor::PlayQueueHandle, playback_events::PlaybackEvent, queue_store::SavedItem, state_store::StateStore};
+
+const RESUME_FILE: &str = "resume.json";
+const SAVE_INTERVAL: Duration = Duration::from_secs(15);
+// not worth offering to resume something that had only just started
+const MIN_RESUME_POSITION: Duration = Duration::from_secs(30);
+
+// what was playing and how far in, the item is kept the same way as in the saved queue
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct ResumePoint {
+    #[serde(flatten)]
+    pub(crate) item: SavedItem,
+    position_secs: u64,
+}
+
+impl ResumePoint {
+    fn from_item(item: &QueueItem, position: Duration) -> Self {
+        ResumePoint {
+            item: SavedItem::from_item(item),
+            position_secs: position.as_secs(),
+        }
+    }
+
+    pub fn position(&self) -> Duration {
+        Duration::from_secs(self.position_secs)
+    }
+
+    // options to queue the item again, starting at the saved position or from the beginning
+    pub fn queue_options(&self, resume: bool) -> QueueItemOptions {
+        QueueItemOptions {
+            start_at: if resume { Some(self.position()) } else { None },
+            ..self.item.queue_options()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct ResumeFile {
+    #[serde(default)]
+    current: Option<ResumePoint>,
+}
+
+#[derive(Clone)]
+pub(crate) struct ResumeState {
+    store: StateStore,
+    // what was playing when the bot last went down, offered once on the next play
+    pending: Arc<TokioMutex<Option<ResumePoint>>>,
+    // set on shutdown so stopping the pipeline doesn't wipe the position we just saved
+    frozen: Arc<AtomicBool>,
+}
+
+impl ResumeState {
+    pub async fn load(store: StateStore) -> Result<Self, Error> {
+        let saved = store.load::<ResumeFile>(RESUME_FILE).await?;
+        if let Some(point) = &saved.current {
+            info!("{} was interrupted at {}s", point.item.name, point.position_secs);
+        }
+        Ok(ResumeState {
+            store,
+            pending: Arc::new(TokioMutex::new(saved.current)),
+            frozen: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub async fn pending(&self) -> Option<ResumePoint> {
+        self.pending.lock().await.clone()
+    }
+
+    pub async fn take_pending(&self) -> Option<ResumePoint> {
+        self.pending.lock().await.take()
+    }
+
+    async fn save(&self, current: Option<ResumePoint>) -> Result<(), Error> {
+        if self.frozen.load(Ordering::Relaxed) {
+            return Ok(())
+        }
+        self.store.save(RESUME_FILE, &ResumeFile { current }).await
+    }
+
+    // remember the latest position one last time before the pipeline gets stopped for good
+    pub async fn shutdown(&self, pipeline: &PlayQueueHandle) {
+        if let Some(point) = current_point(pipeline).await {
+            if let Err(e) = self.save(Some(point)).await {
+                warn!("unable to save the resume position: {}", e);
+            }
+        }
+        self.frozen.store(true, Ordering::Relaxed);
+    }
+}
+
+async fn current_point(pipeline: &PlayQueueHandle) -> Option<ResumePoint> {
+    let health = pipeline.stream_health().await.ok()?;
+    match (health.item, health.position) {
+        (Some(item), Some(position)) if health.playing && !item.is_live() && position >= MIN_RESUME_POSITION => Some(ResumePoint::from_item(&item, position)),
+        _ => None,
+    }
+}
+
+// keeps the saved position current while something plays and forgets it once the item is done
+pub(crate) async fn run_resume_saver(state: ResumeState, pipeline: PlayQueueHandle) {
+    let mut events = pipeline.subscribe();
+    let mut interval = tokio::time::interval(SAVE_INTERVAL);
+    loop {
+        let current = tokio::select! {
+            _ = interval.tick() => match current_point(&pipeline).await {
+                Some(point) => Some(point),
+                None => continue,
+            },
+            event = events.recv() => match event {
+                Ok(PlaybackEvent::ItemFinished { .. }) | Ok(PlaybackEvent::QueueFinished) => None,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("resume saver fell behind, skipped {} events", skipped);
+                    continue
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if let Err(e) = state.save(current).await {
+            warn!("unable to save the resume position: {}", e);
+        }
+    }
+}
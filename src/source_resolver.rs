@@ -0,0 +1,261 @@
+use anyhow::{Error, anyhow};
+use serde::Deserialize;
+use tokio::process::Command;
+use tracing::{info, warn};
+use url::Url;
+
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::embyclient::{EmbySearch, EmbyServers, PlayMode};
+
+const WEB_VIDEO_HOSTS: [&str; 10] = [
+    "youtube.com",
+    "www.youtube.com",
+    "m.youtube.com",
+    "music.youtube.com",
+    "youtu.be",
+    "vimeo.com",
+    "player.vimeo.com",
+    "twitch.tv",
+    "www.twitch.tv",
+    "m.twitch.tv",
+];
+
+// schemes that only ever carry live content
+const LIVE_SCHEMES: [&str; 5] = ["rtmp", "rtmps", "rtsp", "srt", "udp"];
+
+// emby items are queued as emby://{server}/{item id}?user={user id}. the stream url carries the access token
+// so it is only asked for right before playback, nothing that gets logged or saved has it
+pub(crate) const EMBY_SCHEME: &str = "emby";
+// long enough that the gapless handover and the actual start share one transcode session
+const EMBY_URL_TTL: Duration = Duration::from_secs(900);
+
+// query params that hold credentials, taken out before a url is logged or handed out
+const CREDENTIAL_PARAMS: [&str; 5] = ["api_key", "apikey", "x-emby-token", "access_token", "token"];
+
+// refresh a bit before the url actually expires so we never hand gstreamer a url that dies mid-preroll
+const EXPIRY_MARGIN: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Debug)]
+pub(crate) struct ResolverConfig {
+    ytdlp_path: String,
+    format: String,
+    default_ttl: Duration,
+    timeout: Duration,
+}
+
+impl ResolverConfig {
+    pub fn from_env() -> Self {
+        let default_ttl = std::env::var("YTDLP_URL_TTL_SECS").ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(3600);
+        let timeout = std::env::var("YTDLP_TIMEOUT_SECS").ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(60);
+        ResolverConfig {
+            ytdlp_path: std::env::var("YTDLP_PATH").unwrap_or("yt-dlp".to_string()),
+            // uridecodebin can only take a single url so prefer formats with both audio and video muxed together
+            format: std::env::var("YTDLP_FORMAT").unwrap_or("best[vcodec!=none][acodec!=none]/best".to_string()),
+            default_ttl: Duration::from_secs(default_ttl),
+            timeout: Duration::from_secs(timeout),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ResolvedSource {
+    pub(crate) title: Option<String>,
+    pub(crate) media_uri: Url,
+    pub(crate) is_live: bool,
+    expires_at: SystemTime,
+}
+
+impl ResolvedSource {
+    fn is_expired(&self) -> bool {
+        SystemTime::now() + EXPIRY_MARGIN >= self.expires_at
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct YtDlpInfo {
+    title: Option<String>,
+    url: Option<String>,
+    #[serde(default)]
+    is_live: Option<bool>,
+}
+
+#[derive(Clone)]
+pub(crate) struct SourceResolver {
+    config: ResolverConfig,
+    cache: Arc<Mutex<HashMap<String, ResolvedSource>>>,
+    // connected after the play queue is up, see set_emby_servers
+    emby: Arc<Mutex<Option<EmbyServers>>>,
+}
+
+impl SourceResolver {
+    pub fn new(config: ResolverConfig) -> Self {
+        SourceResolver {
+            config,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            emby: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn set_emby_servers(&self, servers: EmbyServers) {
+        *self.emby.lock().unwrap() = Some(servers);
+    }
+
+    pub fn emby_uri(server: &str, item_id: &str, user_id: Option<&str>) -> Result<Url, Error> {
+        let mut uri = Url::parse(&format!("{}://{}/{}", EMBY_SCHEME, server, item_id))?;
+        if let Some(user_id) = user_id {
+            uri.query_pairs_mut().append_pair("user", user_id);
+        }
+        Ok(uri)
+    }
+
+    // the same url without tokens, api keys or a user and password in it
+    pub fn without_credentials(uri: &Url) -> Url {
+        let mut clean = uri.clone();
+        // only fails for urls that can't have a user in the first place
+        let _ = clean.set_username("");
+        let _ = clean.set_password(None);
+        if !uri.query_pairs().any(|(k, _)| CREDENTIAL_PARAMS.contains(&k.to_ascii_lowercase().as_str())) {
+            return clean
+        }
+        let kept: Vec<(String, String)> = uri.query_pairs()
+            .filter(|(k, _)| !CREDENTIAL_PARAMS.contains(&k.to_ascii_lowercase().as_str()))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        clean.set_query(None);
+        if !kept.is_empty() {
+            clean.query_pairs_mut().extend_pairs(kept);
+        }
+        clean
+    }
+
+    pub fn is_web_video(uri: &Url) -> bool {
+        match uri.host_str() {
+            Some(host) => WEB_VIDEO_HOSTS.contains(&host),
+            None => false,
+        }
+    }
+
+    pub fn is_live_uri(uri: &Url) -> bool {
+        LIVE_SCHEMES.contains(&uri.scheme())
+    }
+
+    // drop any cached resolution so the next lookup asks yt-dlp again. live playlists rotate urls on reconnect
+    pub fn invalidate(&self, uri: &Url) {
+        self.cache.lock().unwrap().remove(uri.as_str());
+    }
+
+    // resolve a web video page into a direct media url. results are cached until the media url expires
+    pub async fn resolve(&self, uri: &Url) -> Result<ResolvedSource, Error> {
+        if let Some(cached) = self.cache.lock().unwrap().get(uri.as_str()) {
+            if !cached.is_expired() {
+                return Ok(cached.clone())
+            }
+            info!("resolved url for {} has expired, refreshing", uri);
+        }
+        let resolved = self.run_ytdlp(uri).await?;
+        self.cache.lock().unwrap().insert(uri.to_string(), resolved.clone());
+        Ok(resolved)
+    }
+
+    // returns the uri gstreamer should actually play. non web uris are passed through untouched
+    pub async fn playable_uri(&self, uri: &Url) -> Result<Url, Error> {
+        if uri.scheme() == EMBY_SCHEME {
+            return self.resolve_emby(uri).await
+        }
+        if !Self::is_web_video(uri) {
+            return Ok(uri.clone())
+        }
+        Ok(self.resolve(uri).await?.media_uri)
+    }
+
+    // what playable_uri would hand back without running yt-dlp or asking a media server. None until it was resolved
+    pub fn cached_playable_uri(&self, uri: &Url) -> Option<Url> {
+        if uri.scheme() != EMBY_SCHEME && !Self::is_web_video(uri) {
+            return Some(uri.clone())
+        }
+        self.cache.lock().unwrap().get(uri.as_str())
+            .filter(|cached| !cached.is_expired())
+            .map(|cached| cached.media_uri.clone())
+    }
+
+    // until when the cached url for uri can still be handed out. None for uris that don't expire
+    pub fn fresh_until(&self, uri: &Url) -> Option<SystemTime> {
+        self.cache.lock().unwrap().get(uri.as_str()).map(|cached| cached.expires_at - EXPIRY_MARGIN)
+    }
+
+    // asks the media server for a stream of the item, with the token it uses right now
+    async fn resolve_emby(&self, uri: &Url) -> Result<Url, Error> {
+        if let Some(cached) = self.cache.lock().unwrap().get(uri.as_str()) {
+            if !cached.is_expired() {
+                return Ok(cached.media_uri.clone())
+            }
+        }
+        let servers = self.emby.lock().unwrap().clone().ok_or_else(|| anyhow!("media servers are not connected yet"))?;
+        let server = uri.host_str().unwrap_or_default();
+        let emby_client = servers.by_name(Some(server)).ok_or_else(|| anyhow!("media server {} is not configured", server))?;
+        let item_id = uri.path().trim_start_matches('/');
+        let user_id = uri.query_pairs().find(|(k, _)| k == "user").map(|(_, v)| v.to_string());
+        // path mode only gets here when the file wasn't mounted, emby transcodes it then
+        let media_uri = match emby_client.play_mode() {
+            PlayMode::Stream => emby_client.get_direct_stream(item_id).await?,
+            PlayMode::Path | PlayMode::Transcode => emby_client.get_hls_stream(item_id, user_id.as_deref()).await?,
+        };
+        self.cache.lock().unwrap().insert(uri.to_string(), ResolvedSource {
+            title: None,
+            media_uri: media_uri.clone(),
+            is_live: false,
+            expires_at: SystemTime::now() + EMBY_URL_TTL,
+        });
+        Ok(media_uri)
+    }
+
+    async fn run_ytdlp(&self, uri: &Url) -> Result<ResolvedSource, Error> {
+        info!("resolving {} with {}", uri, self.config.ytdlp_path);
+        // a stuck yt-dlp would block the command and the queue actor, kill_on_drop reaps it once the timeout fires
+        let output = Command::new(&self.config.ytdlp_path)
+            .arg("--no-playlist")
+            .arg("--dump-single-json")
+            .arg("-f")
+            .arg(&self.config.format)
+            .arg(uri.as_str())
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(self.config.timeout, output).await
+            .map_err(|_| anyhow!("yt-dlp did not resolve {} within {}s", uri, self.config.timeout.as_secs()))?
+            .map_err(|e| anyhow!("unable to run {}: {}", self.config.ytdlp_path, e))?;
+        if !output.status.success() {
+            return Err(anyhow!("yt-dlp failed to resolve {}: {}", uri, String::from_utf8_lossy(&output.stderr).trim()))
+        }
+        let info = serde_json::from_slice::<YtDlpInfo>(&output.stdout)
+            .map_err(|e| anyhow!("unable to parse yt-dlp output for {}: {}", uri, e))?;
+        let media_url = match info.url {
+            Some(u) => u,
+            None => return Err(anyhow!("yt-dlp returned no single media url for {}. try a different YTDLP_FORMAT", uri)),
+        };
+        let media_uri = Url::parse(&media_url)?;
+        let expires_at = media_url_expiry(&media_uri).unwrap_or_else(|| SystemTime::now() + self.config.default_ttl);
+        Ok(ResolvedSource {
+            title: info.title,
+            media_uri,
+            is_live: info.is_live.unwrap_or(false),
+            expires_at,
+        })
+    }
+}
+
+// googlevideo urls carry their expiry as a unix timestamp in the `expire` query param
+fn media_url_expiry(media_uri: &Url) -> Option<SystemTime> {
+    let expire = media_uri.query_pairs().find(|(k, _)| k == "expire")?.1;
+    match expire.parse::<u64>() {
+        Ok(ts) => Some(UNIX_EPOCH + Duration::from_secs(ts)),
+        Err(e) => {
+            warn!("unable to parse expire param {}: {}", expire, e);
+            None
+        }
+    }
+}
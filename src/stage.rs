@@ -0,0 +1,77 @@
+use poise::serenity_prelude::{self as serenity, ChannelId, CreateStageInstance, EditStageInstance};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn};
+
+use std::sync::Arc;
+
+use crate::playback_events::{FinishReason, PlaybackEvent};
+
+// discord rejects longer stage topics
+const MAX_TOPIC_LEN: usize = 120;
+
+#[derive(Clone, Debug)]
+pub(crate) struct StageConfig {
+    // stage channel that gets opened for watch parties, nothing happens without it
+    channel: Option<ChannelId>,
+    // ping everyone following the server when the stage opens
+    notify: bool,
+}
+
+impl StageConfig {
+    pub fn from_env() -> Self {
+        StageConfig {
+            channel: std::env::var("STAGE_CHANNEL_ID").ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|id| *id != 0)
+                .map(ChannelId::new),
+            notify: std::env::var("STAGE_NOTIFY").map(|v| v == "1" || v == "true").unwrap_or(false),
+        }
+    }
+}
+
+// opens the stage when something starts playing, keeps the topic on the current item and closes it once playback is over
+pub(crate) async fn run_stage(http: Arc<serenity::Http>, config: StageConfig, mut receiver: broadcast::Receiver<PlaybackEvent>) {
+    let channel = match config.channel {
+        Some(c) => c,
+        None => return,
+    };
+    info!("announcing playback on stage {}", channel);
+    let mut open = false;
+    loop {
+        match receiver.recv().await {
+            Ok(PlaybackEvent::ItemStarted { item }) if !item.is_bumper() => {
+                let topic: String = format!("Watching {}", item.name()).chars().take(MAX_TOPIC_LEN).collect();
+                if open {
+                    match channel.edit_stage_instance(&*http, EditStageInstance::new().topic(topic.clone())).await {
+                        Ok(_) => continue,
+                        // someone closed the stage by hand, open it again below
+                        Err(e) => warn!("unable to update stage topic, reopening it: {}", e),
+                    }
+                }
+                let stage = CreateStageInstance::new(topic).send_start_notification(config.notify);
+                match channel.create_stage_instance(&*http, stage).await {
+                    Ok(_) => open = true,
+                    Err(e) => {
+                        warn!("unable to open stage {}: {}", channel, e);
+                        open = false;
+                    }
+                }
+            }
+            Ok(PlaybackEvent::QueueFinished) | Ok(PlaybackEvent::ItemFinished { reason: FinishReason::Stopped, .. }) => {
+                if !open {
+                    continue
+                }
+                if let Err(e) = channel.delete_stage_instance(&*http).await {
+                    warn!("unable to close stage {}: {}", channel, e);
+                }
+                open = false;
+            }
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("stage announcements fell behind, skipped {} events", skipped);
+                continue
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
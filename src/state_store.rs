@@ -0,0 +1,48 @@
+use anyhow::{Error, anyhow};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::info;
+
+use std::{io::ErrorKind, path::PathBuf};
+
+// small json files on disk for settings that can change at runtime and need to survive a restart
+#[derive(Clone, Debug)]
+pub(crate) struct StateStore {
+    dir: PathBuf,
+}
+
+impl StateStore {
+    pub fn from_env() -> Self {
+        StateStore {
+            dir: std::env::var("STATE_DIR").map(PathBuf::from).unwrap_or(PathBuf::from("state")),
+        }
+    }
+
+    // returns the default value when nothing has been saved yet
+    pub async fn load<T: DeserializeOwned + Default>(&self, name: &str) -> Result<T, Error> {
+        let path = self.dir.join(name);
+        match tokio::fs::read(&path).await {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| anyhow!("unable to parse {}: {}", path.display(), e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                info!("no saved state at {}, starting fresh", path.display());
+                Ok(T::default())
+            }
+            Err(e) => Err(anyhow!("unable to read {}: {}", path.display(), e)),
+        }
+    }
+
+    // whether anything was saved under name yet, even if it is empty
+    pub async fn exists(&self, name: &str) -> Result<bool, Error> {
+        let path = self.dir.join(name);
+        tokio::fs::try_exists(&path).await.map_err(|e| anyhow!("unable to read {}: {}", path.display(), e))
+    }
+
+    pub async fn save<T: Serialize>(&self, name: &str, value: &T) -> Result<(), Error> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.dir.join(name);
+        // write next to the real file and rename so a crash mid-write never leaves half a file behind
+        let tmp_path = self.dir.join(format!("{}.tmp", name));
+        tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(value)?).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use poise::serenity_prelude::{self as serenity, ChannelId, CreateMessage};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use std::{collections::HashMap, sync::Arc, time::{Duration, Instant}};
+
+use crate::{gstreamer::StreamHealth, play_queue_actor::PlayQueueHandle, playback_events::{PlaybackEvent, StallAction}};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+pub(crate) struct AlertConfig {
+    // alerts only go out when a channel is set
+    channel: Option<ChannelId>,
+    // buffering longer than this gets reported
+    buffering: Duration,
+    // how far playback may fall behind the wall clock before it counts as the encoder not keeping up
+    max_lag: Duration,
+    // qos drops per check before it counts as the encoder not keeping up
+    max_dropped_frames: u64,
+    // the same kind of alert is posted at most once per cooldown
+    cooldown: Duration,
+}
+
+impl AlertConfig {
+    pub fn from_env() -> Self {
+        let secs = |name: &str, default: u64| Duration::from_secs(std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default));
+        AlertConfig {
+            channel: std::env::var("ALERT_CHANNEL_ID").ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|id| *id != 0)
+                .map(ChannelId::new),
+            buffering: secs("ALERT_BUFFERING_SECS", 15),
+            max_lag: secs("ALERT_LAG_SECS", 5),
+            max_dropped_frames: std::env::var("ALERT_DROPPED_FRAMES").ok().and_then(|v| v.parse().ok()).unwrap_or(50),
+            cooldown: secs("ALERT_COOLDOWN_SECS", 300),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum AlertKind {
+    Stalled,
+    Buffering,
+    FallingBehind,
+}
+
+// what the last health check saw, to compare the next one against
+struct Sample {
+    taken: Instant,
+    item: Option<Uuid>,
+    position: Option<Duration>,
+    dropped_frames: u64,
+}
+
+#[derive(Default)]
+struct Watch {
+    last: Option<Sample>,
+    buffering_since: Option<Instant>,
+    // how much playback fell behind the wall clock on the current item
+    lag: Duration,
+}
+
+impl Watch {
+    // returns the problems found in this sample
+    fn check(&mut self, config: &AlertConfig, health: &StreamHealth) -> Vec<(AlertKind, String)> {
+        let now = Instant::now();
+        let item = health.item.as_ref().map(|i| i.id());
+        let mut alerts = vec![];
+        if !health.playing || item.is_none() {
+            *self = Watch::default();
+            return alerts
+        }
+        let name = health.item.as_ref().map(|i| i.name()).unwrap_or_default();
+        if health.buffering_percent < 100 {
+            let since = *self.buffering_since.get_or_insert(now);
+            if now.duration_since(since) >= config.buffering {
+                alerts.push((AlertKind::Buffering, format!("**{}** has been buffering for {}s ({}%)", name, now.duration_since(since).as_secs(), health.buffering_percent)));
+            }
+        } else {
+            self.buffering_since = None;
+        }
+        if let Some(last) = self.last.as_ref().filter(|l| l.item == item) {
+            if let (Some(before), Some(position)) = (last.position, health.position) {
+                let wall = now.duration_since(last.taken);
+                match position.checked_sub(before) {
+                    // a seek forward makes the position jump ahead of the clock, start measuring over
+                    Some(moved) if moved > wall + CHECK_INTERVAL => self.lag = Duration::ZERO,
+                    Some(moved) => self.lag += wall.saturating_sub(moved),
+                    // seeked back
+                    None => self.lag = Duration::ZERO,
+                }
+            }
+            let dropped = health.dropped_frames.saturating_sub(last.dropped_frames);
+            if self.lag >= config.max_lag {
+                alerts.push((AlertKind::FallingBehind, format!("**{}** is {}s behind real time, the encoder can't keep up", name, self.lag.as_secs())));
+                self.lag = Duration::ZERO;
+            } else if dropped >= config.max_dropped_frames {
+                alerts.push((AlertKind::FallingBehind, format!("**{}** dropped {} frames in {}s, the encoder can't keep up", name, dropped, CHECK_INTERVAL.as_secs())));
+            }
+        } else {
+            self.lag = Duration::ZERO;
+        }
+        self.last = Some(Sample { taken: now, item, position: health.position, dropped_frames: health.dropped_frames });
+        alerts
+    }
+}
+
+// keeps an eye on the stream and tells the alert channel when it stalls, buffers for too long or falls behind
+pub(crate) async fn run_stream_alerts(http: Arc<serenity::Http>, config: AlertConfig, pipeline: PlayQueueHandle) {
+    let channel = match config.channel {
+        Some(c) => c,
+        None => return,
+    };
+    info!("posting stream alerts to {}", channel);
+    let mut events = pipeline.subscribe();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    let mut watch = Watch::default();
+    let mut last_sent: HashMap<AlertKind, Instant> = HashMap::new();
+    loop {
+        let alerts = tokio::select! {
+            _ = interval.tick() => match pipeline.stream_health().await {
+                Ok(health) => watch.check(&config, &health),
+                Err(e) => {
+                    warn!("unable to get stream health: {}", e);
+                    continue
+                }
+            },
+            event = events.recv() => match event {
+                Ok(PlaybackEvent::Stalled { item, stalled_for, action }) => {
+                    let action = match action {
+                        StallAction::Restarted => "restarted it",
+                        StallAction::Skipped => "skipped it",
+                    };
+                    vec![(AlertKind::Stalled, format!("**{}** stopped moving for {}s, {}", item.name(), stalled_for.as_secs(), action))]
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("stream alerts fell behind, skipped {} events", skipped);
+                    continue
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+        for (kind, alert) in alerts {
+            if last_sent.get(&kind).is_some_and(|t| t.elapsed() < config.cooldown) {
+                continue
+            }
+            last_sent.insert(kind, Instant::now());
+            warn!("stream alert: {}", alert);
+            if let Err(e) = channel.send_message(&http, CreateMessage::new().content(format!("\u{1F6A8} {}", alert))).await {
+                warn!("unable to post stream alert to {}: {}", channel, e);
+            }
+        }
+    }
+}
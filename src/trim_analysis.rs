@@ -0,0 +1,257 @@
+use anyhow::{Error, anyhow};
+use gst::{glib, prelude::*};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+use url::Url;
+
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::Duration};
+
+const LEVEL_INTERVAL: Duration = Duration::from_millis(500);
+const PREROLL_TIMEOUT_SECS: u64 = 30;
+// how long a single window may take to decode before we give up on the file
+const SCAN_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Debug)]
+pub(crate) struct TrimConfig {
+    enabled: bool,
+    // how much of the start and the end of a file gets looked at
+    window: Duration,
+    // dead air shorter than this is left alone
+    min_trim: Duration,
+    black_luma: f64,
+    silence_db: f64,
+}
+
+impl TrimConfig {
+    pub fn from_env() -> Self {
+        let env_or = |name: &str, default: f64| std::env::var(name).ok().and_then(|v| v.parse::<f64>().ok()).unwrap_or(default);
+        TrimConfig {
+            enabled: std::env::var("AUTO_TRIM").map(|v| v == "1" || v == "true").unwrap_or(false),
+            window: Duration::from_secs_f64(env_or("AUTO_TRIM_WINDOW_SECS", 120.0)),
+            min_trim: Duration::from_secs_f64(env_or("AUTO_TRIM_MIN_SECS", 3.0)),
+            black_luma: env_or("AUTO_TRIM_BLACK_LUMA", 0.08),
+            silence_db: env_or("AUTO_TRIM_SILENCE_DB", -50.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct TrimOffsets {
+    pub(crate) start: Option<Duration>,
+    pub(crate) end: Option<Duration>,
+}
+
+impl TrimOffsets {
+    pub fn is_empty(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    start: Duration,
+    end: Duration,
+    // not black for video, not silent for audio
+    content: bool,
+}
+
+// finds leading and trailing black+silence in local files in the background so playback can skip past it
+#[derive(Clone)]
+pub(crate) struct TrimAnalyser {
+    config: TrimConfig,
+    // None while the analysis is still running
+    results: Arc<Mutex<HashMap<String, Option<TrimOffsets>>>>,
+    // decoding is expensive so only one file is analysed at a time
+    permits: Arc<Semaphore>,
+}
+
+impl TrimAnalyser {
+    pub fn new(config: TrimConfig) -> Self {
+        TrimAnalyser {
+            config,
+            results: Arc::new(Mutex::new(HashMap::new())),
+            permits: Arc::new(Semaphore::new(1)),
+        }
+    }
+
+    // start analysing a queued item. only local files are looked at, ripped files are where the dead air lives
+    pub fn schedule(&self, uri: &Url) {
+        if !self.config.enabled || uri.scheme() != "file" {
+            return
+        }
+        {
+            let mut results = self.results.lock().unwrap();
+            if results.contains_key(uri.as_str()) {
+                return
+            }
+            results.insert(uri.to_string(), None);
+        }
+        let analyser = self.clone();
+        let uri = uri.clone();
+        tokio::spawn(async move {
+            let _permit = analyser.permits.acquire().await;
+            let config = analyser.config.clone();
+            let analysis_uri = uri.clone();
+            let offsets = match tokio::task::spawn_blocking(move || analyse(&config, &analysis_uri)).await {
+                Ok(Ok(offsets)) => {
+                    info!("trim analysis for {}: {:?}", uri, offsets);
+                    offsets
+                }
+                Ok(Err(e)) => {
+                    warn!("trim analysis for {} failed: {}", uri, e);
+                    TrimOffsets::default()
+                }
+                Err(e) => {
+                    warn!("trim analysis for {} panicked: {}", uri, e);
+                    TrimOffsets::default()
+                }
+            };
+            analyser.results.lock().unwrap().insert(uri.to_string(), Some(offsets));
+        });
+    }
+
+    // offsets for a finished analysis. items that come up before their analysis is done just play untrimmed
+    pub fn offsets(&self, uri: &Url) -> Option<TrimOffsets> {
+        self.results.lock().unwrap().get(uri.as_str()).copied().flatten().filter(|o| !o.is_empty())
+    }
+}
+
+fn analyse(config: &TrimConfig, uri: &Url) -> Result<TrimOffsets, Error> {
+    for factory in ["videoanalyse", "level"] {
+        if gst::ElementFactory::find(factory).is_none() {
+            return Err(anyhow!("{} element is missing, install the gstreamer plugin that provides it", factory))
+        }
+    }
+    let pipeline = analysis_pipeline(uri)?;
+    let result = scan_edges(config, &pipeline);
+    let _ = pipeline.set_state(gst::State::Null);
+    result
+}
+
+fn scan_edges(config: &TrimConfig, pipeline: &gst::Pipeline) -> Result<TrimOffsets, Error> {
+    pipeline.set_state(gst::State::Paused)?;
+    let (state_result, _, _) = pipeline.state(gst::ClockTime::from_seconds(PREROLL_TIMEOUT_SECS));
+    state_result.map_err(|e| anyhow!("analysis pipeline failed to preroll: {}", e))?;
+    let duration = pipeline.query_duration::<gst::ClockTime>()
+        .map(|d| Duration::from_nanos(d.nseconds()))
+        .ok_or_else(|| anyhow!("unable to get the duration"))?;
+
+    let (lead_samples, tail_samples) = if duration <= config.window * 2 {
+        let samples = scan_window(config, pipeline, Duration::ZERO, duration)?;
+        (samples.clone(), samples)
+    } else {
+        (scan_window(config, pipeline, Duration::ZERO, config.window)?, scan_window(config, pipeline, duration - config.window, duration)?)
+    };
+
+    // nothing but dead air in a window means something odd is going on, leave the file alone
+    let start = lead_samples.iter().filter(|s| s.content).map(|s| s.start).min()
+        .filter(|s| *s >= config.min_trim);
+    let end = tail_samples.iter().filter(|s| s.content).map(|s| s.end).max()
+        .filter(|e| duration.saturating_sub(*e) >= config.min_trim);
+    let end = match (start, end) {
+        (Some(s), Some(e)) if e <= s => None,
+        (_, e) => e,
+    };
+    Ok(TrimOffsets { start, end })
+}
+
+fn scan_window(config: &TrimConfig, pipeline: &gst::Pipeline, start: Duration, stop: Duration) -> Result<Vec<Sample>, Error> {
+    let bus = pipeline.bus().ok_or_else(|| anyhow!("analysis pipeline has no bus"))?;
+    pipeline.seek(
+        1.0,
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+        gst::SeekType::Set,
+        Some(gst::ClockTime::from_nseconds(start.as_nanos() as u64)),
+        gst::SeekType::Set,
+        Some(gst::ClockTime::from_nseconds(stop.as_nanos() as u64)),
+    )?;
+    pipeline.set_state(gst::State::Playing)?;
+    let mut video = vec![];
+    let mut audio = vec![];
+    loop {
+        let msg = match bus.timed_pop(gst::ClockTime::from_nseconds(SCAN_TIMEOUT.as_nanos() as u64)) {
+            Some(m) => m,
+            None => return Err(anyhow!("timed out analysing {}s-{}s", start.as_secs(), stop.as_secs())),
+        };
+        match msg.view() {
+            gst::MessageView::Eos(..) => break,
+            gst::MessageView::Error(err) => return Err(anyhow!("analysis failed: {}", err.error())),
+            gst::MessageView::Element(element) => {
+                let structure = match element.structure() {
+                    Some(s) => s,
+                    None => continue,
+                };
+                let stream_time = match structure.get::<u64>("stream-time") {
+                    Ok(t) => Duration::from_nanos(t),
+                    Err(_) => continue,
+                };
+                let sample_duration = structure.get::<u64>("duration").map(Duration::from_nanos).unwrap_or_default();
+                match structure.name().as_str() {
+                    "GstVideoAnalyse" => {
+                        let luma = structure.get::<f64>("luma-average").unwrap_or(1.0);
+                        video.push((stream_time, stream_time + sample_duration, luma));
+                    }
+                    "level" => {
+                        let loudest = structure.get::<glib::ValueArray>("rms")
+                            .map(|rms| rms.iter().filter_map(|v| v.get::<f64>().ok()).fold(f64::NEG_INFINITY, f64::max))
+                            .unwrap_or(0.0);
+                        audio.push((stream_time, stream_time + sample_duration, loudest));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    pipeline.set_state(gst::State::Paused)?;
+    Ok(classify(config, video, audio))
+}
+
+// a stretch only counts as dead when the picture is black and the sound is silent at the same time
+fn classify(config: &TrimConfig, video: Vec<(Duration, Duration, f64)>, audio: Vec<(Duration, Duration, f64)>) -> Vec<Sample> {
+    video.into_iter()
+        .map(|(start, end, luma)| Sample { start, end, content: luma >= config.black_luma })
+        .chain(audio.into_iter().map(|(start, end, rms)| Sample { start, end, content: rms >= config.silence_db }))
+        .collect()
+}
+
+// uridecodebin with a small analysis branch per decoded stream. branches are only added for streams that exist so preroll works for audio only files
+fn analysis_pipeline(uri: &Url) -> Result<gst::Pipeline, Error> {
+    let pipeline = gst::Pipeline::new();
+    let decodebin = gst::ElementFactory::make("uridecodebin").property("uri", uri.as_str()).build()?;
+    pipeline.add(&decodebin)?;
+    let pipeline_weak = pipeline.downgrade();
+    decodebin.connect_pad_added(move |_, src_pad| {
+        let pipeline = match pipeline_weak.upgrade() {
+            Some(p) => p,
+            None => return,
+        };
+        let caps = src_pad.current_caps().unwrap_or_else(|| src_pad.query_caps(None));
+        let media_type = caps.structure(0).map(|s| s.name().to_string()).unwrap_or_default();
+        let description = if media_type.starts_with("video/x-raw") {
+            "queue ! videoconvert ! videoscale ! video/x-raw,width=160 ! videoanalyse ! fakesink sync=false".to_string()
+        } else if media_type.starts_with("audio/x-raw") {
+            format!("queue ! audioconvert ! level interval={} ! fakesink sync=false", LEVEL_INTERVAL.as_nanos())
+        } else {
+            "fakesink sync=false".to_string()
+        };
+        let branch = match gst::parse::bin_from_description(&description, true) {
+            Ok(b) => b,
+            Err(e) => {
+                warn!("unable to build analysis branch for {}: {}", media_type, e);
+                return
+            }
+        };
+        if let Err(e) = pipeline.add(&branch) {
+            warn!("unable to add analysis branch for {}: {}", media_type, e);
+            return
+        }
+        let _ = branch.sync_state_with_parent();
+        if let Some(sink_pad) = branch.static_pad("sink") {
+            if let Err(e) = src_pad.link(&sink_pad) {
+                warn!("unable to link analysis branch for {}: {}", media_type, e);
+            }
+        }
+    });
+    Ok(pipeline)
+}
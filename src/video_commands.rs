@@ -1,30 +1,73 @@
-use crate::{bot_error, embyclient::{EmbyClient, EmbyItemData, EmbySearch, SearchItemType}, gstreamer::PlayQueue, BotError, Context, EmbySearchResult, Error, ShowSearch};
+use crate::{bot_error, play_history::PlayHistory, queue_store, embyclient::{EmbyClient, EmbyItemData, EmbySearch, EmbyServers, PlayMode, SearchItemType}, file_browser::BrowseEntry, media_inspect::{self, MediaInfo}, parental_rating::{ParentalLimit, RatingBlocked}, gstreamer::{BusLogEntry, Chapter, ChapterJump, EmbyItemRef, HttpSourceOptions, OutputTarget, PipelineStats, QueueItem, QueueItemOptions, QueuePosition, QueueLimitError, RepeatMode, Requester, SeekResult, TrackInfo, TrackKind, WatchedMark}, play_queue_actor::PlayQueueHandle, playback_events::PlaybackEvent, resume_state::ResumePoint, source_resolver::SourceResolver, voice_output::PipelineAudio, BotError, Context, EmbySearchResult, Error, ShowSearch};
 
 use paginate::Pages;
-use poise::{serenity_prelude::{self as serenity, ComponentInteractionDataKind, CreateActionRow, CreateAttachment, CreateSelectMenuKind, CreateSelectMenuOption}, CreateReply, Modal};
+use rand::seq::SliceRandom;
+use poise::{futures_util::StreamExt, serenity_prelude::{self as serenity, Attachment, ComponentInteractionDataKind, CreateActionRow, CreateAttachment, CreateSelectMenuKind, CreateSelectMenuOption}, CreateReply, Modal};
 use strum::IntoEnumIterator;
 use uuid::Uuid;
-use std::str::FromStr;
+use std::{path::PathBuf, process::Stdio, str::FromStr, time::Duration};
+use tokio::{io::AsyncWriteExt, sync::broadcast};
 use tracing::{info, error, warn};
+use url::Url;
 
 
-#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR", subcommands("add", "play", "pause", "stop", "skip", "list_series", "list_movies", "player", "seek"), subcommand_required)]
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR", subcommands("add", "play", "pause", "stop", "skip", "list_series", "list_movies", "player", "seek", "browse", "dump_graph", "diagnostics", "inspect", "quick", "tracks", "audio", "subtitles", "chapters", "repeat", "move_item", "clear", "random", "export_queue", "import_queue", "history", "output", "stats", "snapshot", "clock", "recent", "mark_watched", "mark_unwatched", "refresh_library"), subcommand_required)]
 pub(crate) async fn rusto_video(_: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
 
 #[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+#[allow(clippy::too_many_arguments)]
 async fn add(
     ctx: Context<'_>,
-    #[description = "path to a video to play"] url: String,
+    #[description = "path or url (including youtube/vimeo/twitch links) of a video to play"] url: String,
+    #[description = "treat the url as a live stream (auto detected for rtmp/srt/twitch)"] live: Option<bool>,
+    #[description = "extra http headers separated by | e.g. `Referer: https://x | Cookie: a=b`"] headers: Option<String>,
+    #[description = "http basic auth username"] username: Option<String>,
+    #[description = "http basic auth password"] password: Option<String>,
+    #[description = "url that gets a json POST when this item finishes playing"] webhook: Option<String>,
+    #[description = "frame rate cap for this item, e.g. 60 for sports"] #[min = 1] #[max = 120] max_fps: Option<u32>,
+    #[description = "path or url of an .srt/.ass subtitle file"] subtitles: Option<String>,
+    #[description = "play it right after the current item instead of at the end of the queue"] play_next: Option<bool>,
+    #[description = "remux h.264 + aac/mp3 sources without re-encoding, transcodes anything else"] copy: Option<bool>,
 ) -> Result<(), Error> {
-    let mut pipeline_ref = ctx.data().get_pipeline_ref().await;
-    match &pipeline_ref.add_uri(url.clone(), url.clone().split("/").last().unwrap().to_string(), None) {
+    let headers = match headers {
+        Some(h) => parse_http_headers(&h)?,
+        None => vec![],
+    };
+    let webhook = match webhook {
+        Some(w) => Some(parse_webhook_url(&w)?),
+        None => None,
+    };
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let options = QueueItemOptions {
+        live,
+        http: HttpSourceOptions {
+            headers,
+            username,
+            password,
+        },
+        webhook,
+        max_framerate: max_fps,
+        subtitles,
+        chapters: vec![],
+        start_at: None,
+        requested_by: Some(Requester { id: ctx.author().id.get(), name: ctx.author().name.clone() }),
+        watched_mark: None,
+        emby_item: None,
+        copy: copy.unwrap_or(false),
+    };
+    let position = if play_next.unwrap_or(false) { QueuePosition::Next } else { QueuePosition::Last };
+    match &pipeline_ref.add_uri(url.clone(), url.clone().split("/").last().unwrap().to_string(), None, options, position).await {
         Ok(_) => {
             ctx.say("queued video").await?;
             Ok(())
         },
+        Err(e) if e.downcast_ref::<QueueLimitError>().is_some() => {
+            ctx.say(format!("Can't queue that, {}", e)).await?;
+            Ok(())
+        },
         Err(e) => {
             let err_msg = format!("error setting the source uri: {}", e);
             ctx.say(err_msg.clone()).await?;
@@ -34,11 +77,35 @@ async fn add(
     }
 }
 
+fn parse_webhook_url(webhook: &str) -> Result<Url, Error> {
+    let url = Url::parse(webhook.trim()).map_err(|e| bot_error(&format!("invalid webhook url {}: {}", webhook, e)))?;
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        s => Err(bot_error(&format!("webhook urls must be http or https, not {}", s))),
+    }
+}
+
+fn parse_http_headers(headers: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut parsed = vec![];
+    for header in headers.split('|').map(|h| h.trim()).filter(|h| !h.is_empty()) {
+        match header.split_once(':') {
+            Some((name, value)) if !name.trim().is_empty() => parsed.push((name.trim().to_string(), value.trim().to_string())),
+            _ => return Err(bot_error(format!("invalid header `{}`. expected `Name: value`", header).as_str())),
+        }
+    }
+    Ok(parsed)
+}
+
 #[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
 async fn play(
     ctx: Context<'_>,
 ) -> Result<(), Error> {
-    let mut pipeline_ref = ctx.data().get_pipeline_ref().await;
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    if pipeline_ref.get_current_item().await?.is_none() {
+        if let Some(point) = ctx.data().resume.pending().await {
+            offer_resume(ctx, pipeline_ref, point).await?;
+        }
+    }
     match &pipeline_ref.start_playback().await {
         Ok(_) => {
             ctx.say("played video").await?;
@@ -53,112 +120,1315 @@ async fn play(
     }
 }
 
-#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
-async fn stop(
-    ctx: Context<'_>,
-) -> Result<(), Error> {
-    let mut pipeline_ref = ctx.data().get_pipeline_ref().await;
-    match &pipeline_ref.stop_playback().await {
-        Ok(_) => {
-            ctx.say("stopped video").await?;
-            Ok(())
-        },
-        Err(e) => {
-            let err_msg = format!("error setting stopping video: {}", e);
-            ctx.say(err_msg.clone()).await?;
-            error!(err_msg);
-            Err(bot_error(err_msg.as_str()))
-        }
+/// move a queued item so it plays sooner or later
+#[poise::command(slash_command, rename = "move", default_member_permissions = "ADMINISTRATOR")]
+async fn move_item(
+    ctx: Context<'_>,
+    #[description = "queue position of the item, 1 is up next"] #[min = 1] from: usize,
+    #[description = "queue position to move it to"] #[min = 1] to: usize,
+) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let items = pipeline_ref.get_queue_items().await?;
+    let item = match items.get(from - 1) {
+        Some(i) => i,
+        None => return Err(bot_error(&format!("there is no item {} in the queue, it has {} items", from, items.len()))),
+    };
+    let new_index = pipeline_ref.move_item(item.id(), to - 1).await?;
+    ctx.say(format!("Moved **{}** to position {}", item.name(), new_index + 1)).await?;
+    Ok(())
+}
+
+/// remove everything that is queued, the current item keeps playing
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let queued = pipeline_ref.get_queue_items().await?.len();
+    if queued == 0 {
+        ctx.send(CreateReply::default().content("The queue is already empty").ephemeral(true)).await?;
+        return Ok(())
+    }
+    let interaction_prefix = ctx.id();
+    let buttons = vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(format!("{interaction_prefix}_confirm_clear"))
+            .style(serenity::ButtonStyle::Danger)
+            .label(format!("remove {} items", queued))
+            .emoji('\u{1F5D1}'),
+        serenity::CreateButton::new(format!("{interaction_prefix}_cancel_clear"))
+            .style(serenity::ButtonStyle::Secondary)
+            .label("cancel"),
+    ])];
+    let reply = ctx.send(
+        CreateReply::default()
+            .content(format!("Clear all {} queued items?", queued))
+            .components(buttons)
+            .ephemeral(true)
+    ).await?;
+    let choice = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(60))
+        .filter(move |mci| mci.data.custom_id.starts_with(&interaction_prefix.to_string()))
+        .await;
+    if let Some(mci) = &choice {
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+    }
+    let response = match choice {
+        Some(mci) if mci.data.custom_id.ends_with("_confirm_clear") => match pipeline_ref.clear().await {
+            Ok(count) => {
+                info!("{} cleared {} queued items", ctx.author().name, count);
+                format!("Removed {} queued items", count)
+            }
+            Err(e) => format!("Error clearing the queue {}", e),
+        },
+        _ => "Left the queue as it is".to_string(),
+    };
+    reply.edit(ctx, CreateReply::default().content(response).components(vec![])).await?;
+    Ok(())
+}
+
+/// what has been played, newest first
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn history(
+    ctx: Context<'_>,
+    #[description = "page to start on"] #[min = 1] page: Option<usize>,
+) -> Result<(), Error> {
+    let history = &ctx.data().history;
+    let mut page = page.unwrap_or(1).max(1) - 1;
+    let interaction_prefix = ctx.id();
+    let (content, pages) = format_history_page(history, page).await;
+    let reply = ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(history_buttons(interaction_prefix, page, pages))
+    ).await?;
+    while let Some(mci) = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(300))
+        .filter(move |mci| mci.data.custom_id.starts_with(&interaction_prefix.to_string()))
+        .await
+    {
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+        if mci.data.custom_id.ends_with("_history_newer") {
+            page = page.saturating_sub(1);
+        } else if mci.data.custom_id.ends_with("_history_older") {
+            page += 1;
+        }
+        let (content, pages) = format_history_page(history, page).await;
+        page = page.min(pages.saturating_sub(1));
+        reply.edit(ctx, CreateReply::default().content(content).components(history_buttons(interaction_prefix, page, pages))).await?;
+    }
+    reply.edit(ctx, CreateReply::default().components(vec![])).await?;
+    Ok(())
+}
+
+// the page of plays and the number of pages there are
+async fn format_history_page(history: &PlayHistory, page: usize) -> (String, usize) {
+    let (plays, total) = history.plays(page * HISTORY_PAGE_SIZE, HISTORY_PAGE_SIZE).await;
+    let pages = total.div_ceil(HISTORY_PAGE_SIZE).max(1);
+    if plays.is_empty() {
+        return (match total {
+            0 => "Nothing has been played yet".to_string(),
+            _ => format!("Page {} is past the end of the history, there are {} pages", page + 1, pages),
+        }, pages)
+    }
+    let lines = plays.iter().map(|p| {
+        let played_for = (p.finished_at - p.started_at).to_std().unwrap_or_default();
+        let mut line = format!("<t:{}:f> **{}** {} ({:?})", p.started_at.timestamp(), p.name, format_timestamp(played_for), p.reason);
+        if let Some(user) = &p.requested_by {
+            line.push_str(&format!(" queued by {}", user));
+        }
+        line
+    }).collect::<Vec<String>>();
+    (format!("{}\npage {} of {}", lines.join("\n"), page + 1, pages), pages)
+}
+
+fn history_buttons(prefix: u64, page: usize, pages: usize) -> Vec<CreateActionRow> {
+    vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(format!("{prefix}_history_newer"))
+            .style(serenity::ButtonStyle::Secondary)
+            .label("newer")
+            .emoji('\u{25C0}')
+            .disabled(page == 0),
+        serenity::CreateButton::new(format!("{prefix}_history_older"))
+            .style(serenity::ButtonStyle::Secondary)
+            .label("older")
+            .emoji('\u{25B6}')
+            .disabled(page + 1 >= pages),
+    ])]
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+enum RepeatChoice {
+    #[name = "off"]
+    Off,
+    #[name = "one"]
+    One,
+    #[name = "queue"]
+    Queue,
+}
+
+/// repeat the current item or the whole queue, cycles through the modes without a choice
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn repeat(
+    ctx: Context<'_>,
+    #[description = "what to repeat"] mode: Option<RepeatChoice>,
+) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let mode = match mode {
+        Some(RepeatChoice::Off) => RepeatMode::Off,
+        Some(RepeatChoice::One) => RepeatMode::One,
+        Some(RepeatChoice::Queue) => RepeatMode::Queue,
+        None => pipeline_ref.repeat_mode().await?.next(),
+    };
+    pipeline_ref.set_repeat_mode(mode).await?;
+    ctx.say(format!("Repeat is now {}", mode.label())).await?;
+    Ok(())
+}
+
+/// show elapsed and remaining time on the stream, toggles without a choice
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn clock(
+    ctx: Context<'_>,
+    #[description = "whether the clock is shown"] shown: Option<bool>,
+) -> Result<(), Error> {
+    let response = match ctx.data().get_pipeline_ref().show_clock(shown).await {
+        Ok(true) => "The clock is now shown on the stream".to_string(),
+        Ok(false) => "The clock is now hidden".to_string(),
+        Err(e) => format!("Unable to change the clock: {}", e),
+    };
+    ctx.say(response).await?;
+    Ok(())
+}
+
+// the bot went down in the middle of something, ask whether to pick it up again. only offered once
+async fn offer_resume(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, point: ResumePoint) -> Result<(), Error> {
+    let interaction_prefix = ctx.id();
+    let buttons = vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(format!("{interaction_prefix}_resume"))
+            .style(serenity::ButtonStyle::Primary)
+            .label(format!("resume at {}", format_timestamp(point.position())))
+            .emoji('\u{23EF}'),
+        serenity::CreateButton::new(format!("{interaction_prefix}_restart"))
+            .style(serenity::ButtonStyle::Secondary)
+            .label("from the start")
+            .emoji('\u{23EE}'),
+        serenity::CreateButton::new(format!("{interaction_prefix}_dismiss"))
+            .style(serenity::ButtonStyle::Secondary)
+            .label("skip it")
+            .emoji('\u{23ED}'),
+    ])];
+    let reply = ctx.send(
+        CreateReply::default()
+            .content(format!("**{}** was interrupted at {} when the bot restarted", point.item.name, format_timestamp(point.position())))
+            .components(buttons)
+            .ephemeral(true)
+    ).await?;
+    let choice = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(60))
+        .filter(move |mci| mci.data.custom_id.starts_with(&interaction_prefix.to_string()))
+        .await;
+    ctx.data().resume.take_pending().await;
+    let resume = match &choice {
+        Some(mci) if mci.data.custom_id.ends_with("_resume") => Some(true),
+        Some(mci) if mci.data.custom_id.ends_with("_restart") => Some(false),
+        _ => None,
+    };
+    if let Some(mci) = &choice {
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+    }
+    let stop_fn = match point.item.watched_mark().filter(|_| resume.is_some()) {
+        Some(mark) => match ctx.data().emby_servers.by_name(mark.server.as_deref()) {
+            Some(server) => Some(server.user_stop_fn(mark.user_id, mark.item_id).await),
+            None => {
+                warn!("media server {:?} of {} is no longer configured, it won't be marked as watched", mark.server, point.item.name);
+                None
+            }
+        },
+        None => None,
+    };
+    let response = match resume {
+        Some(resume) => match pipeline_ref.add_uri(point.item.uri.clone(), point.item.name.clone(), stop_fn, point.queue_options(resume), QueuePosition::Next).await {
+            Ok(i) if resume => format!("Resuming **{}** at {}", i.name(), format_timestamp(point.position())),
+            Ok(i) => format!("Playing **{}** from the start", i.name()),
+            Err(e) => format!("Error queueing {} again {}", point.item.name, e),
+        },
+        None => format!("Not resuming **{}**", point.item.name),
+    };
+    reply.edit(ctx, CreateReply::default().content(response).components(vec![])).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn stop(
+    ctx: Context<'_>,
+) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    match &pipeline_ref.stop_playback().await {
+        Ok(_) => {
+            ctx.say("stopped video").await?;
+            Ok(())
+        },
+        Err(e) => {
+            let err_msg = format!("error setting stopping video: {}", e);
+            ctx.say(err_msg.clone()).await?;
+            error!(err_msg);
+            Err(bot_error(err_msg.as_str()))
+        }
+    }
+}
+
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn pause(
+    ctx: Context<'_>,
+) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    match &pipeline_ref.pause_playback().await {
+        Ok(_) => {
+            ctx.say("paused current video").await?;
+            Ok(())
+        },
+        Err(e) => {
+            let err_msg = format!("error pausing video: {}", e);
+            ctx.say(err_msg.clone()).await?;
+            error!(err_msg);
+            Err(bot_error(err_msg.as_str()))
+        }
+    }
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Skip video"]
+struct SkipReason {
+    #[placeholder = "wrong audio track"]
+    #[max_length = 200]
+    reason: Option<String>,
+}
+
+// reasons end up in the announcement channel so they carry who skipped
+fn skip_reason(reason: Option<String>, user: &str) -> String {
+    match reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty()) {
+        Some(r) => format!("{} (skipped by {})", r, user),
+        None => format!("skipped by {}", user),
+    }
+}
+
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn skip(
+    ctx: Context<'_>,
+    #[description = "why it was skipped, shows up in the announcement"] reason: Option<String>,
+) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let reason = skip_reason(reason, &ctx.author().name);
+    match &pipeline_ref.skip_video(Some(reason.clone())).await {
+        Ok(_) => {
+            ctx.say(format!("skipped video: {}", reason)).await?;
+            Ok(())
+        },
+        Err(e) => {
+            let err_msg = format!("error skipping video: {}", e);
+            ctx.say(err_msg.clone()).await?;
+            error!(err_msg);
+            Err(bot_error(err_msg.as_str()))
+        }
+    }
+}
+
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn seek(
+    ctx: Context<'_>,
+    seek_seconds: i64,
+) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    match &pipeline_ref.seek_video(seek_seconds).await {
+        Ok(result) => {
+            ctx.say(format_seek_result(result)).await?;
+            Ok(())
+        },
+        Err(e) => {
+            let err_msg = format!("error seeking video: {}", e);
+            ctx.say(err_msg.clone()).await?;
+            error!(err_msg);
+            Err(bot_error(err_msg.as_str()))
+        }
+    }
+}
+
+fn format_seek_result(result: &SeekResult) -> String {
+    let mut message = format!("seeked to {}", format_timestamp(result.position));
+    if let Some(remaining) = result.remaining() {
+        message.push_str(&format!(", {} remaining", format_timestamp(remaining)));
+    }
+    if result.clamped {
+        message.push_str(" (clamped to the bounds of the video)");
+    }
+    message
+}
+
+pub(crate) fn format_timestamp(timestamp: Duration) -> String {
+    let secs = timestamp.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// show codecs, resolution and tracks of a queue item or url
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn inspect(
+    ctx: Context<'_>,
+    #[description = "`current`, a queue position (1 is up next) or a path/url"] target: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let target = target.trim();
+    let item = if target == "current" {
+        pipeline_ref.get_current_item().await?.ok_or_else(|| bot_error("nothing is playing"))?
+    } else if let Ok(position) = target.parse::<usize>() {
+        let items = pipeline_ref.get_queue_items().await?;
+        match position.checked_sub(1).and_then(|p| items.get(p)) {
+            Some(i) => i.clone(),
+            None => return Err(bot_error(&format!("there is no item {} in the queue, it has {} items", position, items.len()))),
+        }
+    } else {
+        pipeline_ref.prepare_item(target.to_string(), target.rsplit('/').next().unwrap_or(target).to_string()).await?
+    };
+    let play_uri = pipeline_ref.playable_uri(&item.uri()).await?;
+    match media_inspect::inspect(play_uri, item.http_options()).await {
+        Ok(info) => {
+            ctx.say(format_media_info(&item.name(), &info)).await?;
+            Ok(())
+        }
+        Err(e) => {
+            let err_msg = format!("unable to inspect {}: {}", item.name(), e);
+            ctx.say(err_msg.clone()).await?;
+            error!(err_msg);
+            Err(bot_error(&err_msg))
+        }
+    }
+}
+
+fn format_media_info(name: &str, info: &MediaInfo) -> String {
+    let mut lines = vec![format!("**{}**", name)];
+    let duration = match info.duration {
+        Some(d) if !info.live => format_timestamp(d),
+        _ => "live".to_string(),
+    };
+    lines.push(format!("container: {}, duration: {}, seekable: {}", info.container.clone().unwrap_or("none".to_string()), duration, info.seekable));
+    if info.video.is_empty() {
+        lines.push("no video tracks".to_string());
+    }
+    for (i, v) in info.video.iter().enumerate() {
+        lines.push(format!(
+            "video {}: {} {}x{} @ {:.2}fps{}{}",
+            i + 1, v.codec, v.width, v.height, v.framerate,
+            if v.interlaced { " interlaced" } else { "" },
+            if v.bitrate > 0 { format!(" {}kbit/s", v.bitrate / 1000) } else { "".to_string() },
+        ));
+    }
+    if info.audio.is_empty() {
+        lines.push("no audio tracks".to_string());
+    }
+    for (i, a) in info.audio.iter().enumerate() {
+        lines.push(format!(
+            "audio {}: {} {}ch {}Hz{}{}",
+            i + 1, a.codec, a.channels, a.sample_rate,
+            a.language.as_ref().map(|l| format!(" [{}]", l)).unwrap_or_default(),
+            if a.bitrate > 0 { format!(" {}kbit/s", a.bitrate / 1000) } else { "".to_string() },
+        ));
+    }
+    for (i, language) in info.subtitles.iter().enumerate() {
+        lines.push(format!("subtitle {}: {}", i + 1, language.clone().unwrap_or("unknown language".to_string())));
+    }
+    lines.push(match info.remux_blocker() {
+        Some(reason) => format!("copy: no, {}", reason),
+        None => "copy: yes, can be remuxed to flv without re-encoding".to_string(),
+    });
+    let mut message = lines.join("\n");
+    truncate_on_char_boundary(&mut message, 2000);
+    message
+}
+
+/// dump the current pipeline graph for debugging
+#[poise::command(slash_command, owners_only, hide_in_help)]
+async fn dump_graph(
+    ctx: Context<'_>,
+) -> Result<(), Error> {
+    let dot_data = ctx.data().get_pipeline_ref().pipeline_dot().await?;
+    let mut reply = CreateReply::default().ephemeral(true).attachment(CreateAttachment::bytes(dot_data.as_bytes(), "pipeline.dot"));
+    // rendering is best effort since graphviz is usually not installed in the container
+    match render_dot(&dot_data).await {
+        Ok(png) => reply = reply.attachment(CreateAttachment::bytes(png, "pipeline.png")),
+        Err(e) => info!("not rendering pipeline graph: {}", e),
+    }
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// queue a random episode of a series
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn random(
+    ctx: Context<'_>,
+    #[description = "name of the series"] series: String,
+    #[description = "emby user the episode gets marked as watched for"] user: Option<String>,
+    #[description = "only pick episodes the user hasn't watched yet"] unwatched: Option<bool>,
+) -> Result<(), Error> {
+    let unwatched = unwatched.unwrap_or(false);
+    if unwatched && user.is_none() {
+        ctx.say("pick a user to only get unwatched episodes").await?;
+        return Ok(())
+    }
+    // an exact name match on any server wins over the first result of the first server
+    let mut found = vec![];
+    for server in ctx.data().emby_servers.iter() {
+        found.extend(server.search_series(&series).await?.into_iter().map(|s| (server, s)));
+    }
+    let (emby_client, show) = match found.iter().find(|(_, s)| s.name.eq_ignore_ascii_case(series.trim())).or(found.first()) {
+        Some((server, s)) => (*server, s.clone()),
+        None => {
+            ctx.say(format!("no series found for {}", series)).await?;
+            return Ok(())
+        }
+    };
+    let current_user = match &user {
+        Some(name) => match emby_client.get_users().await?.into_iter().find(|u| u.name.eq_ignore_ascii_case(name.trim())) {
+            Some(u) => Some(u),
+            None => {
+                ctx.say(format!("no emby user called {} on {}", name, emby_client.name())).await?;
+                return Ok(())
+            }
+        },
+        None => None,
+    };
+    ctx.defer().await?;
+    let (_, episodes) = get_series_episodes(emby_client, &show.id, &current_user).await?;
+    let episodes = if unwatched { unwatched_episodes(episodes) } else { episodes };
+    let picked = episodes.choose(&mut rand::thread_rng()).cloned();
+    let episode = match picked {
+        Some(e) => e,
+        None => {
+            ctx.say(format!("no {}episodes left in {}", if unwatched { "unwatched " } else { "" }, show.name)).await?;
+            return Ok(())
+        }
+    };
+    let message = add_emby_item(ctx, ctx.data().get_pipeline_ref(), emby_client, &episode.id, &current_user, QueuePosition::Last).await?;
+    ctx.say(format!("{}: {}", show.name, message)).await?;
+    Ok(())
+}
+
+/// list what was added to emby lately and queue it with a click
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn recent(
+    ctx: Context<'_>,
+    #[description = "how many days back to look (default 7)"] days: Option<u32>,
+    #[description = "emby user whose libraries are listed and who gets the items marked as watched"] user: Option<String>,
+) -> Result<(), Error> {
+    let days = days.unwrap_or(RECENT_DAYS);
+    let emby_servers = ctx.data().emby_servers.clone();
+    ctx.defer().await?;
+    // the latest endpoint always needs a user, without one named the first user of each server is used to look
+    let mut users: Vec<Option<EmbyItemData>> = vec![];
+    let mut found: Vec<(usize, EmbyItemData)> = vec![];
+    for (index, server) in emby_servers.iter().enumerate() {
+        let server_users = server.get_users().await?;
+        let named = match &user {
+            Some(name) => server_users.iter().find(|u| u.name.eq_ignore_ascii_case(name.trim())).cloned(),
+            None => None,
+        };
+        // servers that don't know the named user are left out
+        let viewer = match &user {
+            Some(_) => named.clone(),
+            None => server_users.first().cloned(),
+        };
+        users.push(named);
+        if let Some(viewer) = viewer {
+            found.extend(get_recent_items(server, &viewer, days).await?.into_iter().map(|i| (index, i)));
+        }
+    }
+    if found.is_empty() {
+        ctx.say(format!("nothing was added in the last {} days", days)).await?;
+        return Ok(())
+    }
+    found.sort_by_key(|(_, i)| std::cmp::Reverse(i.created()));
+    found.truncate(25);
+    let interaction_prefix = ctx.id();
+    let menu = get_recent_menu(interaction_prefix.to_string(), &emby_servers, &found);
+    ctx.send(CreateReply::default().content(format!("{} items added in the last {} days", found.len(), days)).components(vec![menu])).await?;
+
+    while let Some(mci) = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(600))
+        .filter(move |mci| mci.data.custom_id.starts_with(&interaction_prefix.to_string()))
+        .await
+    {
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+        let selected = match &mci.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => values[0].clone(),
+            _ => continue,
+        };
+        let message = match parse_recent_value(&selected) {
+            Some((server, item_id)) => {
+                let current_user = users.get(server).cloned().flatten();
+                add_emby_item(ctx, ctx.data().get_pipeline_ref(), emby_servers.get(server)?, item_id, &current_user, QueuePosition::Last).await?
+            }
+            None => format!("unknown item {}", selected),
+        };
+        let mut msg = mci.message.clone();
+        msg.edit(ctx, serenity::EditMessage::new().content(message)).await?;
+    }
+    Ok(())
+}
+
+/// mark an emby movie or series as watched for a user
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn mark_watched(
+    ctx: Context<'_>,
+    #[description = "movie or series to search for"] item: String,
+    #[description = "emby user to mark it for"] user: String,
+) -> Result<(), Error> {
+    set_watched(ctx, &item, &user, true).await
+}
+
+/// mark an emby movie or series as not watched for a user
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn mark_unwatched(
+    ctx: Context<'_>,
+    #[description = "movie or series to search for"] item: String,
+    #[description = "emby user to mark it for"] user: String,
+) -> Result<(), Error> {
+    set_watched(ctx, &item, &user, false).await
+}
+
+// searches every server the user is on. a single match is marked right away, several are offered in a menu
+async fn set_watched(ctx: Context<'_>, item_name: &str, user_name: &str, played: bool) -> Result<(), Error> {
+    let emby_servers = ctx.data().emby_servers.clone();
+    ctx.defer().await?;
+    let mut users: Vec<Option<EmbyItemData>> = vec![];
+    let mut found: Vec<(usize, EmbyItemData)> = vec![];
+    for (index, server) in emby_servers.iter().enumerate() {
+        let user = server.get_users().await?.into_iter().find(|u| u.name.eq_ignore_ascii_case(user_name.trim()));
+        if user.is_some() {
+            found.extend(server.search_items(item_name, vec![SearchItemType::Series, SearchItemType::Movie], None).await?.into_iter().map(|i| (index, i)));
+        }
+        users.push(user);
+    }
+    if users.iter().all(|u| u.is_none()) {
+        ctx.say(format!("there is no emby user named {}", user_name)).await?;
+        return Ok(())
+    }
+    if let [(server, item)] = found.as_slice() {
+        let message = mark_played(emby_servers.get(*server)?, users[*server].as_ref(), item, played).await;
+        ctx.say(message).await?;
+        return Ok(())
+    }
+    if found.is_empty() {
+        ctx.say(format!("nothing found for {}", item_name)).await?;
+        return Ok(())
+    }
+    found.truncate(25);
+    let interaction_prefix = ctx.id();
+    let options = found.iter()
+        .map(|(server, item)| emby_servers.get(*server).map(|c| item_menu_option(&emby_servers, *server, c, item)))
+        .collect::<Result<Vec<CreateSelectMenuOption>, _>>()?;
+    let menu = serenity::CreateSelectMenu::new(format!("{}_mark_result", interaction_prefix), CreateSelectMenuKind::String { options }).placeholder("Pick what to mark");
+    ctx.send(CreateReply::default().content(format!("found {} matches for {}", found.len(), item_name)).components(vec![CreateActionRow::SelectMenu(menu)])).await?;
+
+    while let Some(mci) = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(300))
+        .filter(move |mci| mci.data.custom_id.starts_with(&interaction_prefix.to_string()))
+        .await
+    {
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+        let selected = match &mci.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => values[0].clone(),
+            _ => continue,
+        };
+        // menu values are {type}_{server}_{id}
+        let picked = selected.splitn(3, '_').collect::<Vec<&str>>();
+        let picked = match picked.as_slice() {
+            [_, server, id] => found.iter().find(|(s, i)| s.to_string() == *server && i.id == *id),
+            _ => None,
+        };
+        let message = match picked {
+            Some((server, item)) => mark_played(emby_servers.get(*server)?, users[*server].as_ref(), item, played).await,
+            None => format!("unknown item {}", selected),
+        };
+        let mut msg = mci.message.clone();
+        msg.edit(ctx, serenity::EditMessage::new().content(message)).await?;
+    }
+    Ok(())
+}
+
+async fn mark_played(emby_client: &EmbyClient, user: Option<&EmbyItemData>, item: &EmbyItemData, played: bool) -> String {
+    let state = if played { "watched" } else { "not watched" };
+    let user = match user {
+        Some(u) => u,
+        None => return format!("the user does not exist on the server of {}", item.name),
+    };
+    match emby_client.set_played(&user.id, &item.id, played).await {
+        Ok(_) => format!("marked {} as {} for {}", item.name, state, user.name),
+        Err(e) => {
+            error!("unable to mark {} as {} for {}: {}", item.id, state, user.name, e);
+            format!("could not mark {} as {}: {}", item.name, state, e)
+        }
+    }
+}
+
+/// rescan emby libraries, e.g. right after new episodes were copied to the share
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn refresh_library(
+    ctx: Context<'_>,
+    #[description = "library to scan e.g. TV Shows, every library when left empty"] library: Option<String>,
+) -> Result<(), Error> {
+    let emby_servers = ctx.data().emby_servers.clone();
+    ctx.defer().await?;
+    let mut started: Vec<&EmbyClient> = vec![];
+    let mut known = vec![];
+    // one server being down shouldn't stop the others from scanning
+    let mut failed = vec![];
+    for server in emby_servers.iter() {
+        let library_id = match &library {
+            Some(name) => {
+                let libraries = match server.get_libraries().await {
+                    Ok(l) => l,
+                    Err(e) => {
+                        warn!("unable to list the libraries of {}: {}", server.name(), e);
+                        failed.push(format!("{}: {}", server.name(), e));
+                        continue
+                    }
+                };
+                match libraries.iter().find(|l| l.name.eq_ignore_ascii_case(name.trim())) {
+                    Some(l) => Some(l.id.clone()),
+                    None => {
+                        known.extend(libraries.iter().map(|l| emby_servers.label(server, &l.name)));
+                        continue
+                    }
+                }
+            }
+            None => None,
+        };
+        if let Err(e) = server.refresh_library(library_id.as_deref()).await {
+            warn!("unable to refresh the libraries of {}: {}", server.name(), e);
+            failed.push(format!("{}: {}", server.name(), e));
+            continue
+        }
+        started.push(server);
+    }
+    let failures = match failed.is_empty() {
+        true => String::new(),
+        false => format!("\nfailed on {}", failed.join(", ")),
+    };
+    if started.is_empty() && known.is_empty() && !failed.is_empty() {
+        ctx.say(format!("unable to refresh any library{}", failures)).await?;
+        return Ok(())
+    }
+    let name = match library {
+        Some(name) if started.is_empty() => {
+            ctx.say(format!("there is no library named {}, pick one of {}{}", name, known.join(", "), failures)).await?;
+            return Ok(())
+        }
+        // a single library is refreshed outside of the scan task so there is no progress to follow,
+        // the listings stay uncached for a while so new items show up once emby has them
+        Some(name) => {
+            for server in &started {
+                server.pause_cache(LIBRARY_REFRESH_UNCACHED);
+            }
+            ctx.say(format!("refreshing {}, new items show up as emby finds them{}", name, failures)).await?;
+            return Ok(())
+        }
+        None => "all libraries",
+    };
+    ctx.say(format!("refreshing {}{}", name, failures)).await?;
+    // scans can outlast the interaction token, so progress goes into a plain channel message
+    let mut status = ctx.channel_id().send_message(ctx.http(), serenity::CreateMessage::new().content(format!("waiting on the scan of {}", name))).await?;
+    let started_at = std::time::Instant::now();
+    loop {
+        // give the scan task a moment to start before the first look
+        tokio::time::sleep(LIBRARY_SCAN_POLL).await;
+        let mut running = vec![];
+        for server in &started {
+            match server.get_library_scan().await {
+                Ok(Some(progress)) => running.push(emby_servers.label(server, &format!("{:.0}%", progress))),
+                Ok(None) => (),
+                Err(e) => warn!("unable to check the library scan of {}: {}", server.name(), e),
+            }
+        }
+        if running.is_empty() {
+            break
+        }
+        if started_at.elapsed() >= LIBRARY_SCAN_TIMEOUT {
+            // whatever was scanned so far should still show up
+            for server in &started {
+                server.pause_cache(LIBRARY_REFRESH_UNCACHED);
+            }
+            edit_scan_progress(ctx, &mut status, format!("still refreshing {} after {} minutes, stopped following it ({}){}", name, LIBRARY_SCAN_TIMEOUT.as_secs() / 60, running.join(", "), failures)).await;
+            return Ok(())
+        }
+        edit_scan_progress(ctx, &mut status, format!("refreshing {}: {}{}", name, running.join(", "), failures)).await;
+    }
+    // the cached listings don't have the new items yet
+    for server in &started {
+        server.invalidate_cache();
+    }
+    edit_scan_progress(ctx, &mut status, format!("refreshed {} in {}{}", name, format_timestamp(started_at.elapsed()), failures)).await;
+    Ok(())
+}
+
+// a failed edit only loses the progress display, the cache still has to be dropped afterwards
+async fn edit_scan_progress(ctx: Context<'_>, status: &mut serenity::Message, content: String) {
+    if let Err(e) = status.edit(ctx.http(), serenity::EditMessage::new().content(content)).await {
+        warn!("unable to update the library refresh progress: {}", e);
+    }
+}
+
+/// attach the pending queue as json so it can be imported again later
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn export_queue(ctx: Context<'_>) -> Result<(), Error> {
+    let items = ctx.data().get_pipeline_ref().get_queue_items().await?;
+    if items.is_empty() {
+        ctx.say("the queue is empty, nothing to export").await?;
+        return Ok(())
+    }
+    let data = queue_store::export_queue(&items)?;
+    ctx.send(CreateReply::default()
+        .content(format!("exported {} queued items", items.len()))
+        .attachment(CreateAttachment::bytes(data, "queue.json"))
+    ).await?;
+    Ok(())
+}
+
+/// add the items of an exported queue to the end of the queue
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn import_queue(
+    ctx: Context<'_>,
+    #[description = "queue.json from export_queue"] file: Attachment,
+) -> Result<(), Error> {
+    if file.size > MAX_QUEUE_IMPORT_BYTES {
+        return Err(bot_error(&format!("{} is {} bytes, the limit is {}", file.filename, file.size, MAX_QUEUE_IMPORT_BYTES)))
+    }
+    ctx.defer().await?;
+    let data = file.download().await?;
+    let requester = Requester { id: ctx.author().id.get(), name: ctx.author().name.clone() };
+    let (added, errors) = match queue_store::import_queue(&data, ctx.data().get_pipeline_ref(), &ctx.data().emby_servers, Some(requester)).await {
+        Ok(r) => r,
+        Err(e) => return Err(bot_error(&format!("{} is not an exported queue: {}", file.filename, e))),
+    };
+    let message = match errors.len() {
+        0 => format!("imported {} items", added),
+        // keep the reply within the discord message limit
+        n => format!("imported {} items, {} could not be added:\n{}", added, n, errors.iter().take(10).cloned().collect::<Vec<String>>().join("\n")),
+    };
+    ctx.say(message).await?;
+    Ok(())
+}
+
+/// manage where the stream is sent
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR", subcommands("output_list", "output_add", "output_remove", "output_voice", "output_leave"), subcommand_required)]
+async fn output(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// list the outputs the stream is sent to
+#[poise::command(slash_command, rename = "list", default_member_permissions = "ADMINISTRATOR")]
+async fn output_list(ctx: Context<'_>) -> Result<(), Error> {
+    let outputs = ctx.data().get_pipeline_ref().outputs().await?;
+    let lines = outputs.iter().map(|(name, label)| format!("**{}** {}", name, label)).collect::<Vec<String>>();
+    ctx.send(CreateReply::default().content(format!("Outputs:\n{}", lines.join("\n"))).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// also send the stream to an rtmp/srt url or record it to a file
+#[poise::command(slash_command, rename = "add", default_member_permissions = "ADMINISTRATOR")]
+async fn output_add(
+    ctx: Context<'_>,
+    #[description = "rtmp://, srt:// or udp:// url, or a file path"] uri: String,
+) -> Result<(), Error> {
+    let target = OutputTarget::parse(&uri).map_err(|e| bot_error(&format!("invalid output {}: {}", uri, e)))?;
+    let label = target.label();
+    let response = match ctx.data().get_pipeline_ref().add_output(target).await {
+        Ok(name) => format!("Sending the stream to {} as **{}**", label, name),
+        Err(e) => format!("Error adding output {}", e),
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// stop sending the stream to an added output
+#[poise::command(slash_command, rename = "remove", default_member_permissions = "ADMINISTRATOR")]
+async fn output_remove(
+    ctx: Context<'_>,
+    #[description = "output name from the list"] name: String,
+) -> Result<(), Error> {
+    let response = match ctx.data().get_pipeline_ref().remove_output(name.clone()).await {
+        Ok(_) => format!("Removed output **{}**", name),
+        Err(e) => format!("Error removing output {}", e),
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// play the audio of the stream in a voice channel, bots can't go live with video
+#[poise::command(slash_command, guild_only, rename = "voice", default_member_permissions = "ADMINISTRATOR")]
+async fn output_voice(
+    ctx: Context<'_>,
+    #[description = "voice channel to join, defaults to the one you are in"]
+    #[channel_types("Voice", "Stage")]
+    channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or(bot_error("voice output only works in servers"))?;
+    let channel_id = match channel {
+        Some(c) => Some(c.id),
+        None => ctx.guild().and_then(|g| g.voice_states.get(&ctx.author().id).and_then(|v| v.channel_id)),
+    };
+    let channel_id = match channel_id {
+        Some(c) => c,
+        None => {
+            ctx.send(CreateReply::default().content("Join a voice channel or pick one").ephemeral(true)).await?;
+            return Ok(())
+        }
+    };
+    let manager = songbird::get(ctx.serenity_context()).await.ok_or(bot_error("voice client is not initialised"))?;
+    let call = match manager.join(guild_id, channel_id).await {
+        Ok(call) => call,
+        Err(e) => {
+            ctx.send(CreateReply::default().content(format!("Error joining <#{}> {}", channel_id, e)).ephemeral(true)).await?;
+            return Ok(())
+        }
+    };
+    let receiver = ctx.data().get_pipeline_ref().attach_audio_tap().await?;
+    call.lock().await.play_only_input(PipelineAudio::new(receiver).into_input());
+    info!("streaming audio to voice channel {} for {}", channel_id, ctx.author().name);
+    ctx.send(CreateReply::default().content(format!("Playing the stream audio in <#{}>", channel_id)).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// leave the voice channel the stream audio is playing in
+#[poise::command(slash_command, guild_only, rename = "leave", default_member_permissions = "ADMINISTRATOR")]
+async fn output_leave(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().ok_or(bot_error("voice output only works in servers"))?;
+    let manager = songbird::get(ctx.serenity_context()).await.ok_or(bot_error("voice client is not initialised"))?;
+    let response = match manager.remove(guild_id).await {
+        Ok(_) => "Left the voice channel".to_string(),
+        Err(e) => format!("Error leaving the voice channel {}", e),
+    };
+    ctx.data().get_pipeline_ref().detach_audio_tap().await?;
+    ctx.send(CreateReply::default().content(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// show encoder, queue and latency stats of the video pipeline
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn stats(
+    ctx: Context<'_>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    // rates need two samples
+    let before = pipeline_ref.pipeline_stats().await?;
+    tokio::time::sleep(STATS_SAMPLE_TIME).await;
+    let after = pipeline_ref.pipeline_stats().await?;
+    ctx.send(CreateReply::default().content(format_pipeline_stats(&before, &after)).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// post the frame that is on the stream right now
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn snapshot(
+    ctx: Context<'_>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let reply = match ctx.data().get_pipeline_ref().snapshot().await {
+        Ok(png) => CreateReply::default().attachment(CreateAttachment::bytes(png, "snapshot.png")),
+        Err(e) => CreateReply::default().content(format!("Unable to take a snapshot: {}", e)),
+    };
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// show recent warnings and messages from the video pipeline
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn diagnostics(
+    ctx: Context<'_>,
+    #[description = "only show messages of this level (error, warning, info, element)"] level: Option<String>,
+    #[description = "how many messages to show"] count: Option<usize>,
+) -> Result<(), Error> {
+    let entries = ctx.data().get_pipeline_ref().recent_bus_messages().await?;
+    let response = format_bus_messages(&entries, level.as_deref(), count.unwrap_or(15).min(50));
+    ctx.send(CreateReply::default().content(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// list the chapters of the current video or jump to one
+#[poise::command(slash_command)]
+async fn chapters(
+    ctx: Context<'_>,
+    #[description = "chapter number to jump to"] #[min = 1] jump: Option<usize>,
+) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    if let Some(jump) = jump {
+        let response = format_chapter_jump(pipeline_ref.seek_chapter(ChapterJump::Index(jump - 1)).await);
+        ctx.send(CreateReply::default().content(response).ephemeral(true)).await?;
+        return Ok(())
+    }
+    let item = match pipeline_ref.get_current_item().await? {
+        Some(i) => i,
+        None => {
+            ctx.send(CreateReply::default().content("Nothing is playing right now").ephemeral(true)).await?;
+            return Ok(())
+        }
+    };
+    let (chapters, current) = pipeline_ref.chapters().await?;
+    let response = if chapters.is_empty() {
+        format!("**{}** has no chapters", item.name())
+    } else {
+        let lines = chapters.iter()
+            .enumerate()
+            .map(|(i, c)| match current == Some(i) {
+                true => format!("{} \u{25C0} playing", format_chapter(i + 1, c)),
+                false => format_chapter(i + 1, c),
+            })
+            .collect::<Vec<String>>();
+        let mut response = format!("Chapters of **{}**\n{}", item.name(), lines.join("\n"));
+        truncate_on_char_boundary(&mut response, 1990);
+        response
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// list the audio and subtitle tracks of the current video
+#[poise::command(slash_command)]
+async fn tracks(ctx: Context<'_>) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let item = match pipeline_ref.get_current_item().await? {
+        Some(i) => i,
+        None => {
+            ctx.send(CreateReply::default().content("Nothing is playing right now").ephemeral(true)).await?;
+            return Ok(())
+        }
+    };
+    let tracks = pipeline_ref.tracks().await?;
+    let response = if tracks.is_empty() {
+        format!("No track information for **{}** yet", item.name())
+    } else {
+        let section = |kind: TrackKind, heading: &str| {
+            let lines = tracks.iter()
+                .filter(|t| t.kind == kind)
+                .enumerate()
+                .map(|(i, t)| format_track(i + 1, t))
+                .collect::<Vec<String>>();
+            if lines.is_empty() {
+                format!("**{}**: none", heading)
+            } else {
+                format!("**{}**\n{}", heading, lines.join("\n"))
+            }
+        };
+        let mut response = format!("Tracks of **{}**\n{}\n{}", item.name(), section(TrackKind::Audio, "Audio"), section(TrackKind::Subtitle, "Subtitles"));
+        truncate_on_char_boundary(&mut response, 1990);
+        response
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// switch the audio track of the current video, lists them without a track
+#[poise::command(slash_command)]
+async fn audio(
+    ctx: Context<'_>,
+    #[description = "track number from the list"] #[min = 1] track: Option<usize>,
+) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let response = match track {
+        Some(track) => match pipeline_ref.set_audio_track(track - 1).await {
+            Ok(info) => format!("Switched audio to {}", format_track(track, &info)),
+            Err(e) => format!("Error switching audio {}", e),
+        },
+        None => {
+            let lines = pipeline_ref.tracks().await?.iter()
+                .filter(|t| t.kind == TrackKind::Audio)
+                .enumerate()
+                .map(|(i, t)| format_track(i + 1, t))
+                .collect::<Vec<String>>();
+            if lines.is_empty() {
+                "No audio tracks to pick from".to_string()
+            } else {
+                format!("**Audio**\n{}", lines.join("\n"))
+            }
+        }
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(track.is_none())).await?;
+    Ok(())
+}
+
+/// pick or turn off subtitles of the current video, lists them without a track
+#[poise::command(slash_command)]
+async fn subtitles(
+    ctx: Context<'_>,
+    #[description = "track number from the list, 0 turns subtitles off"] track: Option<usize>,
+) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let response = match track {
+        Some(track) => format_subtitle_change(track, pipeline_ref.set_subtitle_track(track.checked_sub(1)).await),
+        None => {
+            let (tracks, enabled) = pipeline_ref.list_subtitle_tracks().await?;
+            let lines = tracks.iter()
+                .enumerate()
+                .map(|(i, t)| format_track(i + 1, t))
+                .collect::<Vec<String>>();
+            if lines.is_empty() {
+                "No subtitle tracks to pick from".to_string()
+            } else {
+                format!("**Subtitles**{}\n{}", if enabled { "" } else { " (off)" }, lines.join("\n"))
+            }
+        }
+    };
+    ctx.send(CreateReply::default().content(response).ephemeral(track.is_none())).await?;
+    Ok(())
+}
+
+// track is the number shown to users where 0 means off
+fn format_subtitle_change(track: usize, result: Result<Option<TrackInfo>, anyhow::Error>) -> String {
+    match result {
+        Ok(Some(info)) => format!("Switched subtitles to {}", format_track(track, &info)),
+        Ok(None) => "Subtitles off".to_string(),
+        Err(e) => format!("Error switching subtitles {}", e),
+    }
+}
+
+async fn get_subtitle_selector(pipeline_ref: &PlayQueueHandle, prefix: &str) -> Vec<CreateActionRow> {
+    let (tracks, enabled) = pipeline_ref.list_subtitle_tracks().await.unwrap_or_default();
+    let mut options = vec![CreateSelectMenuOption::new("Off", "0").default_selection(!enabled)];
+    options.extend(tracks.iter()
+        .enumerate()
+        .map(|(i, t)| {
+            // select menu labels are capped at 100 characters
+            let mut label = format_track(i + 1, t);
+            truncate_on_char_boundary(&mut label, 100);
+            CreateSelectMenuOption::new(label, (i + 1).to_string())
+        }));
+    vec![serenity::CreateActionRow::SelectMenu(
+        serenity::CreateSelectMenu::new(
+            format!("{}_subtitle_select", prefix),
+            serenity::CreateSelectMenuKind::String { options }
+        ).placeholder(format!("{} Subtitle Tracks", tracks.len())))]
+}
+
+async fn get_audio_selector(pipeline_ref: &PlayQueueHandle, prefix: &str) -> Vec<CreateActionRow> {
+    let mut options: Vec<CreateSelectMenuOption> = pipeline_ref.tracks().await.unwrap_or_default().iter()
+        .filter(|t| t.kind == TrackKind::Audio)
+        .enumerate()
+        .map(|(i, t)| {
+            // select menu labels are capped at 100 characters
+            let mut label = format_track(i + 1, t);
+            truncate_on_char_boundary(&mut label, 100);
+            CreateSelectMenuOption::new(label, i.to_string())
+        })
+        .collect();
+    let num_tracks = options.len();
+    if num_tracks == 0 {
+        options = vec![CreateSelectMenuOption::new("No audio tracks!", "empty")];
+    }
+    vec![serenity::CreateActionRow::SelectMenu(
+        serenity::CreateSelectMenu::new(
+            format!("{}_audio_select", prefix),
+            serenity::CreateSelectMenuKind::String { options }
+        ).placeholder(format!("{} Audio Tracks", num_tracks)))]
+}
+
+fn format_chapter(index: usize, chapter: &Chapter) -> String {
+    format!("{}. {} {}", index, format_timestamp(chapter.start), chapter.title.as_deref().unwrap_or("untitled"))
+}
+
+fn format_chapter_jump(result: Result<(usize, Chapter, SeekResult), anyhow::Error>) -> String {
+    match result {
+        Ok((index, chapter, result)) => format!("Jumped to chapter {}, {}", format_chapter(index + 1, &chapter), format_seek_result(&result)),
+        Err(e) => format!("Error changing chapter {}", e),
+    }
+}
+
+fn format_track(index: usize, track: &TrackInfo) -> String {
+    let mut line = format!("{}. {}", index, track.language.as_deref().unwrap_or("unknown language"));
+    if let Some(title) = &track.title {
+        line.push_str(&format!(" - {}", title));
+    }
+    if let Some(codec) = &track.codec {
+        line.push_str(&format!(" ({})", codec));
+    }
+    if track.selected {
+        line.push_str(" \u{25C0} playing");
+    }
+    line
+}
+
+fn format_pipeline_stats(before: &PipelineStats, after: &PipelineStats) -> String {
+    let secs = STATS_SAMPLE_TIME.as_secs_f64();
+    let mut lines = vec![
+        format!("**state**: {}", after.state),
+        format!("**encoder**: {} at {} kbps target, {}", after.encoder, after.target_bitrate, after.framerate.map(|f| format!("{} fps", f)).unwrap_or("unknown frame rate".to_string())),
+        format!("**output bitrate**: {:.0} kbps", after.bytes_sent.saturating_sub(before.bytes_sent) as f64 * 8.0 / 1000.0 / secs),
+        format!("**frames encoded**: {} ({:.1} fps)", after.frames_encoded, after.frames_encoded.saturating_sub(before.frames_encoded) as f64 / secs),
+        format!("**frames dropped**: {} ({} in the last {}s)", after.dropped_frames, after.dropped_frames.saturating_sub(before.dropped_frames), STATS_SAMPLE_TIME.as_secs()),
+    ];
+    lines.push(match &after.latency {
+        Some(l) => format!("**latency**: {}ms min, {} max{}",
+            l.min.as_millis(),
+            l.max.map(|m| format!("{}ms", m.as_millis())).unwrap_or("no".to_string()),
+            if l.live { ", live" } else { "" }),
+        None => "**latency**: unknown".to_string(),
+    });
+    lines.push("**queues**:".to_string());
+    for q in &after.queues {
+        lines.push(format!("`{}` {}ms of {}ms, {} buffers, {} KiB", q.name, q.time.as_millis(), q.max_time.as_millis(), q.buffers, q.bytes / 1024));
+    }
+    let mut response = lines.join("\n");
+    truncate_on_char_boundary(&mut response, 1990);
+    response
+}
+
+fn format_bus_messages(entries: &[BusLogEntry], level: Option<&str>, count: usize) -> String {
+    let lines = entries.iter()
+        .rev()
+        .filter(|e| level.map(|l| e.level.eq_ignore_ascii_case(l)).unwrap_or(true))
+        .take(count)
+        .map(|e| {
+            let mut message = e.message.clone();
+            truncate_on_char_boundary(&mut message, 150);
+            format!("`{}s ago` **{}** {}: {}", e.received.elapsed().as_secs(), e.level, e.source, message)
+        })
+        .collect::<Vec<String>>();
+    if lines.is_empty() {
+        return "No recent pipeline messages".to_string()
+    }
+    let mut response = format!("Recent pipeline messages:\n{}", lines.join("\n"));
+    // discord messages are capped at 2000 characters
+    truncate_on_char_boundary(&mut response, 1990);
+    response
+}
+
+// String::truncate panics when the cut lands inside a multi byte character
+fn truncate_on_char_boundary(text: &mut String, max_len: usize) {
+    if text.len() <= max_len {
+        return
     }
+    let mut cut = max_len;
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    text.truncate(cut);
 }
 
-#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
-async fn pause(
-    ctx: Context<'_>,
-) -> Result<(), Error> {
-    let mut pipeline_ref = ctx.data().get_pipeline_ref().await;
-    match &pipeline_ref.pause_playback().await {
-        Ok(_) => {
-            ctx.say("paused current video").await?;
-            Ok(())
-        },
-        Err(e) => {
-            let err_msg = format!("error pausing video: {}", e);
-            ctx.say(err_msg.clone()).await?;
-            error!(err_msg);
-            Err(bot_error(err_msg.as_str()))
-        }
+async fn render_dot(dot_data: &str) -> Result<Vec<u8>, Error> {
+    let mut child = tokio::process::Command::new("dot")
+        .arg("-Tpng")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(dot_data.as_bytes()).await?;
     }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(bot_error(format!("dot exited with {}", output.status).as_str()))
+    }
+    Ok(output.stdout)
 }
 
-#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
-async fn skip(
-    ctx: Context<'_>,
-) -> Result<(), Error> {
-    let mut pipeline_ref = ctx.data().get_pipeline_ref().await;
-    match &pipeline_ref.skip_video().await {
-        Ok(_) => {
-            ctx.say("skipped video").await?;
-            Ok(())
-        },
-        Err(e) => {
-            let err_msg = format!("error skipping video: {}", e);
-            ctx.say(err_msg.clone()).await?;
-            error!(err_msg);
-            Err(bot_error(err_msg.as_str()))
+const MAX_QUEUE_IMPORT_BYTES: u32 = 1024 * 1024;
+const HISTORY_PAGE_SIZE: usize = 10;
+// how long the stats command watches the counters to work out rates
+const STATS_SAMPLE_TIME: Duration = Duration::from_secs(2);
+
+// discord allows at most 5 action rows per message
+const MAX_COMPONENT_ROWS: usize = 5;
+// transport row plus the row of the selected control group
+const CONTROL_ROWS: usize = 2;
+// most episodes a single "add whole series" puts in the queue
+const SERIES_QUEUE_CAP: usize = 100;
+// how far back recently added looks unless told otherwise
+const RECENT_DAYS: u32 = 7;
+// how many search results get their poster shown, discord allows up to 10 embeds on a message
+const SEARCH_POSTERS: usize = 5;
+// longer synopses get cut off so the card stays readable
+const MAX_OVERVIEW_LEN: usize = 600;
+// how often a running library scan is checked on and when to stop watching it
+const LIBRARY_SCAN_POLL: Duration = Duration::from_secs(5);
+const LIBRARY_SCAN_TIMEOUT: Duration = Duration::from_secs(1800);
+// how long listings stay uncached after a refresh whose end can't be followed
+const LIBRARY_REFRESH_UNCACHED: Duration = Duration::from_secs(600);
+// newest items asked for per server, older ones than the cutoff are dropped afterwards
+const RECENT_FETCH_LIMIT: usize = 100;
+
+// the player only shows one group of secondary controls at a time so result menus always have room
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum ControlGroup {
+    #[default]
+    Library,
+    Seek,
+    Tracks,
+}
+
+impl ControlGroup {
+    fn next(self) -> Self {
+        match self {
+            ControlGroup::Library => ControlGroup::Seek,
+            ControlGroup::Seek => ControlGroup::Tracks,
+            ControlGroup::Tracks => ControlGroup::Library,
         }
     }
-}
 
-#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
-async fn seek(
-    ctx: Context<'_>,
-    seek_seconds: i64,
-) -> Result<(), Error> {
-    let mut pipeline_ref = ctx.data().get_pipeline_ref().await;
-    match &pipeline_ref.seek_video(seek_seconds).await {
-        Ok(pos) => {
-            ctx.say(format!("seeked {}s to {}s", seek_seconds, pos)).await?;
-            Ok(())
-        },
-        Err(e) => {
-            let err_msg = format!("error seeking video: {}", e);
-            ctx.say(err_msg.clone()).await?;
-            error!(err_msg);
-            Err(bot_error(err_msg.as_str()))
+    fn label(self) -> &'static str {
+        match self {
+            ControlGroup::Library => "library",
+            ControlGroup::Seek => "seek",
+            ControlGroup::Tracks => "tracks",
         }
     }
 }
 
-async fn get_buttons(interaction_prefix: String, user: &Option<EmbyItemData>, result_box: Option<Vec<CreateActionRow>>) -> Vec<CreateActionRow> {
+async fn get_buttons(interaction_prefix: String, user: &Option<EmbyItemData>, controls: ControlGroup, queue_position: QueuePosition, result_box: Option<Vec<CreateActionRow>>) -> Vec<CreateActionRow> {
     let user_button_label = match user {
         Some(u) => format!("User: {}", u.name),
         None => "User: (None)".to_string(),
     };
-    let result_box = match result_box {
+    let mut result_box = match result_box {
         Some(rb) => rb,
         None => vec![],
     };
-    vec![
-        serenity::CreateActionRow::Buttons(vec![
-            serenity::CreateButton::new(format!("{interaction_prefix}_play"))
-                .style(serenity::ButtonStyle::Primary)
-                .label("play")
-                .emoji('\u{25B6}'),
-            serenity::CreateButton::new(format!("{interaction_prefix}_pause"))
-                .style(serenity::ButtonStyle::Primary)
-                .label("pause")
-                .emoji('\u{23F8}'),
-            serenity::CreateButton::new(format!("{interaction_prefix}_stop"))
-                .style(serenity::ButtonStyle::Primary)
-                .label("stop")
-                .emoji('\u{23F9}'),
-            serenity::CreateButton::new(format!("{interaction_prefix}_skip"))
-                .style(serenity::ButtonStyle::Primary)
-                .label("skip")
-                .emoji('\u{23ED}'),
-        ]),
-        serenity::CreateActionRow::Buttons(vec![
+    if result_box.len() > MAX_COMPONENT_ROWS - CONTROL_ROWS {
+        warn!("dropping {} result rows to stay within the discord component limit", result_box.len() - (MAX_COMPONENT_ROWS - CONTROL_ROWS));
+        result_box.truncate(MAX_COMPONENT_ROWS - CONTROL_ROWS);
+    }
+    let transport_row = serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(format!("{interaction_prefix}_play"))
+            .style(serenity::ButtonStyle::Primary)
+            .label("play")
+            .emoji('\u{25B6}'),
+        serenity::CreateButton::new(format!("{interaction_prefix}_pause"))
+            .style(serenity::ButtonStyle::Primary)
+            .label("pause")
+            .emoji('\u{23F8}'),
+        serenity::CreateButton::new(format!("{interaction_prefix}_stop"))
+            .style(serenity::ButtonStyle::Primary)
+            .label("stop")
+            .emoji('\u{23F9}'),
+        serenity::CreateButton::new(format!("{interaction_prefix}_skip"))
+            .style(serenity::ButtonStyle::Primary)
+            .label("skip")
+            .emoji('\u{23ED}'),
+        serenity::CreateButton::new(format!("{interaction_prefix}_controls"))
+            .style(serenity::ButtonStyle::Secondary)
+            .label(format!("{} controls", controls.next().label()))
+            .emoji('\u{1F39B}'),
+    ]);
+    let group_row = match controls {
+        ControlGroup::Library => serenity::CreateActionRow::Buttons(vec![
             serenity::CreateButton::new(format!("{interaction_prefix}_search"))
                 .style(serenity::ButtonStyle::Primary)
                 .label("search")
@@ -175,8 +1445,15 @@ async fn get_buttons(interaction_prefix: String, user: &Option<EmbyItemData>, re
                 .style(serenity::ButtonStyle::Primary)
                 .label(user_button_label)
                 .emoji('\u{1F9D4}'),
+            serenity::CreateButton::new(format!("{interaction_prefix}_queue_position"))
+                .style(serenity::ButtonStyle::Secondary)
+                .label(match queue_position {
+                    QueuePosition::Last => "adding to the end",
+                    QueuePosition::Next => "adding to play next",
+                })
+                .emoji('\u{2795}'),
         ]),
-        serenity::CreateActionRow::Buttons(vec![
+        ControlGroup::Seek => serenity::CreateActionRow::Buttons(vec![
             serenity::CreateButton::new(format!("{interaction_prefix}_seek_minus_300"))
                 .style(serenity::ButtonStyle::Primary)
                 .label("-5m")
@@ -198,15 +1475,41 @@ async fn get_buttons(interaction_prefix: String, user: &Option<EmbyItemData>, re
                 .label("+15m")
                 .emoji('\u{23E9}'),
         ]),
-    ].iter().chain(result_box.iter()).cloned().collect()
+        ControlGroup::Tracks => serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(format!("{interaction_prefix}_audio_tracks"))
+                .style(serenity::ButtonStyle::Primary)
+                .label("audio")
+                .emoji('\u{1F50A}'),
+            serenity::CreateButton::new(format!("{interaction_prefix}_subtitle_tracks"))
+                .style(serenity::ButtonStyle::Primary)
+                .label("subtitles")
+                .emoji('\u{1F4AC}'),
+            serenity::CreateButton::new(format!("{interaction_prefix}_chapter_previous"))
+                .style(serenity::ButtonStyle::Primary)
+                .label("previous chapter")
+                .emoji('\u{23EE}'),
+            serenity::CreateButton::new(format!("{interaction_prefix}_chapter_next"))
+                .style(serenity::ButtonStyle::Primary)
+                .label("next chapter")
+                .emoji('\u{23ED}'),
+            serenity::CreateButton::new(format!("{interaction_prefix}_show_title"))
+                .style(serenity::ButtonStyle::Secondary)
+                .label("show title")
+                .emoji('\u{1F3F7}'),
+        ]),
+    };
+    vec![transport_row, group_row].into_iter().chain(result_box).collect()
 }
 
 #[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
 async fn list_series(
     ctx: Context<'_>,
 ) -> Result<(), Error> {
-    let emby_client = ctx.data().emby_client.as_ref();
-    let series_list = emby_client.get_all_series().await?.iter().map(|f| f.name.clone()).collect::<Vec<String>>().join("\n");
+    let mut series = vec![];
+    for server in ctx.data().emby_servers.iter() {
+        series.extend(server.get_all_series().await?.iter().map(|f| ctx.data().emby_servers.label(server, &f.name)));
+    }
+    let series_list = series.join("\n");
     let attachment_name = "all_series.csv";
     let attachment_logs = CreateAttachment::bytes(series_list.as_bytes(), attachment_name);
     ctx.send(CreateReply::default().attachment(attachment_logs)).await?;
@@ -217,8 +1520,11 @@ async fn list_series(
 async fn list_movies(
     ctx: Context<'_>,
 ) -> Result<(), Error> {
-    let emby_client = ctx.data().emby_client.as_ref();
-    let series_list = emby_client.get_all_movies().await?.iter().map(|f| f.name.clone()).collect::<Vec<String>>().join("\n");
+    let mut movies = vec![];
+    for server in ctx.data().emby_servers.iter() {
+        movies.extend(server.get_all_movies().await?.iter().map(|f| ctx.data().emby_servers.label(server, &f.name)));
+    }
+    let series_list = movies.join("\n");
     let attachment_name = "all_series.csv";
     let attachment_logs = CreateAttachment::bytes(series_list.as_bytes(), attachment_name);
     ctx.send(CreateReply::default().attachment(attachment_logs)).await?;
@@ -230,13 +1536,24 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
     // using ctx.id here prevents issues with multiple bot instances
     let interaction_prefix = ctx.id();
     let mut current_user = None;
+    // media server the selected user belongs to
+    let mut user_server = 0;
+    // media server of the series or movie picked last, seasons and episodes come from it too
+    let mut server_context = 0;
     // current identifier to be used between iteractions
     let mut id_context: Option<String> = None;
+    // series picked last, for queueing all of it
+    let mut series_context: Option<String> = None;
+    // collection picked last, for queueing all of its movies
+    let mut collection_context: Option<String> = None;
+    let mut controls = ControlGroup::default();
+    // where picked episodes and movies go
+    let mut queue_position = QueuePosition::default();
 
     let reply = {
         CreateReply::default()
             .content("I want to watch something \u{1F346}")
-            .components(get_buttons(interaction_prefix.to_string(), &current_user, None).await)
+            .components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, None).await)
     };
 
     ctx.send(reply).await?;
@@ -244,7 +1561,7 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
     while let Some(mci) = serenity::ComponentInteractionCollector::new(ctx)
         .author_id(ctx.author().id)
         .channel_id(ctx.channel_id())
-        .timeout(std::time::Duration::from_secs(3600))
+        .timeout(Duration::from_secs(3600))
         .filter(move |mci| mci.data.custom_id.starts_with(&interaction_prefix.clone().to_string()))
         .await
     {
@@ -253,13 +1570,13 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
         }
         let mut send_final = true;
         let mut msg = mci.message.clone();
-        let mut pipeline_ref = ctx.data().get_pipeline_ref().await;
+        let pipeline_ref = ctx.data().get_pipeline_ref();
         if mci.data.custom_id.ends_with("play") {
             match &pipeline_ref.start_playback().await {
                 Ok(_v) => {
                     msg.edit(
                         ctx,
-                        serenity::EditMessage::new().content(get_now_playing(&pipeline_ref).await)
+                        serenity::EditMessage::new().content(get_now_playing(pipeline_ref).await)
                     ).await?;
                 },
                 Err(e) => {
@@ -273,8 +1590,31 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
         if mci.data.custom_id.ends_with("now_playing") {
             msg.edit(
                 ctx,
-                serenity::EditMessage::new().content(get_now_playing(&pipeline_ref).await)
+                serenity::EditMessage::new().content(get_now_playing(pipeline_ref).await)
             ).await?;
+            // the player message gets edited by every button so the card goes out on its own
+            if let Some((embed, poster)) = now_playing_card(ctx, pipeline_ref).await {
+                let mut reply = CreateReply::default().embed(embed).ephemeral(true);
+                if let Some(poster) = poster {
+                    reply = reply.attachment(poster);
+                }
+                // the heart only shows up once a user of the server it plays from is selected
+                if let Ok((_, item, _)) = playing_for_user(ctx, pipeline_ref, &current_user, user_server).await {
+                    let favorite = item.user_data.is_some_and(|u| u.is_favorite);
+                    reply = reply.components(vec![CreateActionRow::Buttons(vec![favorite_button(interaction_prefix.to_string(), favorite)])]);
+                }
+                ctx.send(reply).await?;
+            }
+        }
+        if mci.data.custom_id.ends_with("_favorite") {
+            send_final = false;
+            let (message, favorite) = toggle_favorite(ctx, pipeline_ref, &current_user, user_server).await;
+            // the button sits on an ephemeral card which can only be changed through the interaction
+            let mut update = serenity::CreateInteractionResponseMessage::new().content(message);
+            if let Some(favorite) = favorite {
+                update = update.components(vec![CreateActionRow::Buttons(vec![favorite_button(interaction_prefix.to_string(), favorite)])]);
+            }
+            mci.create_response(ctx, serenity::CreateInteractionResponse::UpdateMessage(update)).await?;
         }
         if mci.data.custom_id.ends_with("pause") {
             match &pipeline_ref.pause_playback().await {
@@ -309,14 +1649,27 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             }
         }
         if mci.data.custom_id.ends_with("skip") {
-            match &pipeline_ref.skip_video().await {
-                Ok(_) => {
+            // the modal answers the interaction so it must not be acknowledged again
+            send_final = false;
+            let input = poise::execute_modal_on_component_interaction::<SkipReason>(ctx, mci.clone(), None, Some(std::time::Duration::from_secs(30))).await?;
+            let result = match input {
+                Some(input) => Some(pipeline_ref.skip_video(Some(skip_reason(input.reason, &mci.user.name))).await),
+                None => None,
+            };
+            match &result {
+                None => {
+                    msg.edit(
+                        ctx,
+                        serenity::EditMessage::new().content("Skip cancelled")
+                    ).await?;
+                },
+                Some(Ok(_)) => {
                     msg.edit(
                         ctx,
                         serenity::EditMessage::new().content(format!("Video Skipped"))
                     ).await?;
                 },
-                Err(e) => {
+                Some(Err(e)) => {
                     msg.edit(
                         ctx,
                         serenity::EditMessage::new().content(format!("Error Skipped {}", e))
@@ -363,8 +1716,8 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
 
             if numeric_parts != 0 {
                 let response = match pipeline_ref.seek_video(seek_amount).await {
-                    Ok(dst_ts) => {
-                        format!("seeked to {}s", dst_ts)
+                    Ok(result) => {
+                        format_seek_result(&result)
                     }
                     Err(e) => {
                         format!("Error seeking {}", e)
@@ -377,13 +1730,108 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             }
 
         }
+        if mci.data.custom_id.ends_with("_chapter_previous") || mci.data.custom_id.ends_with("_chapter_next") {
+            let jump = match mci.data.custom_id.ends_with("_chapter_next") {
+                true => ChapterJump::Next,
+                false => ChapterJump::Previous,
+            };
+            let response = format_chapter_jump(pipeline_ref.seek_chapter(jump).await);
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(response)
+            ).await?;
+        }
+        if mci.data.custom_id.ends_with("_show_title") {
+            let response = match pipeline_ref.show_title().await {
+                Ok(_) => "Showing the title on the stream".to_string(),
+                Err(e) => format!("Unable to show the title: {}", e),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(response)
+            ).await?;
+        }
+        if mci.data.custom_id.ends_with("_controls") {
+            controls = controls.next();
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, None).await)
+            ).await?;
+        }
+        if mci.data.custom_id.ends_with("_shuffle") {
+            let response = match pipeline_ref.shuffle().await {
+                Ok(0) => "Nothing queued to shuffle".to_string(),
+                Ok(count) => format!("Shuffled {} queued items", count),
+                Err(e) => format!("Error shuffling {}", e),
+            };
+            let result_box = get_queue_selector(pipeline_ref, interaction_prefix.to_string().as_str()).await;
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(response).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
+            ).await?;
+        }
+        if mci.data.custom_id.ends_with("_queue_position") {
+            queue_position = match queue_position {
+                QueuePosition::Last => QueuePosition::Next,
+                QueuePosition::Next => QueuePosition::Last,
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, None).await)
+            ).await?;
+        }
         if mci.data.custom_id.ends_with("show_queue") {
-            let result_box = get_queue_selector(&pipeline_ref, interaction_prefix.to_string().as_str()).await;
+            let result_box = get_queue_selector(pipeline_ref, interaction_prefix.to_string().as_str()).await;
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
+            ).await?;
+        }
+
+        if mci.data.custom_id.ends_with("_audio_tracks") {
+            let result_box = get_audio_selector(pipeline_ref, interaction_prefix.to_string().as_str()).await;
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
+            ).await?;
+        }
+        if mci.data.custom_id.ends_with("_audio_select") {
+            let selected = match &mci.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => values[0].parse::<usize>().ok(),
+                _ => None,
+            };
+            if let Some(index) = selected {
+                let response = match pipeline_ref.set_audio_track(index).await {
+                    Ok(track) => format!("Switched audio to {}", format_track(index + 1, &track)),
+                    Err(e) => format!("Error switching audio {}", e),
+                };
+                msg.edit(
+                    ctx,
+                    serenity::EditMessage::new().content(response).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, None).await)
+                ).await?;
+            }
+        }
+
+        if mci.data.custom_id.ends_with("_subtitle_tracks") {
+            let result_box = get_subtitle_selector(pipeline_ref, interaction_prefix.to_string().as_str()).await;
             msg.edit(
                 ctx,
-                serenity::EditMessage::new().components(get_buttons(interaction_prefix.to_string(), &current_user, Some(result_box)).await)
+                serenity::EditMessage::new().components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
             ).await?;
         }
+        if mci.data.custom_id.ends_with("_subtitle_select") {
+            let selected = match &mci.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => values[0].parse::<usize>().ok(),
+                _ => None,
+            };
+            if let Some(track) = selected {
+                let response = format_subtitle_change(track, pipeline_ref.set_subtitle_track(track.checked_sub(1)).await);
+                msg.edit(
+                    ctx,
+                    serenity::EditMessage::new().content(response).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, None).await)
+                ).await?;
+            }
+        }
 
         // handle click on queue item to remove
         if mci.data.custom_id.ends_with("queue_list") {
@@ -401,11 +1849,11 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
                     ctx,
                     serenity::EditMessage::new().content(format!("Removing item {}", queue_item))
                 ).await?;
-                pipeline_ref.remove_uri(&Uuid::from_str(queue_item).unwrap())?;
-                let result_box = get_queue_selector(&pipeline_ref, interaction_prefix.to_string().as_str()).await;
+                pipeline_ref.remove_uri(&Uuid::from_str(queue_item).unwrap()).await?;
+                let result_box = get_queue_selector(pipeline_ref, interaction_prefix.to_string().as_str()).await;
                 msg.edit(
                     ctx,
-                    serenity::EditMessage::new().components(get_buttons(interaction_prefix.to_string(), &current_user, Some(result_box)).await)
+                    serenity::EditMessage::new().components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
                 ).await?;
             }
         }
@@ -424,10 +1872,14 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
                 Some(p) => p.clone(),
                 None => "unknown".to_string(),
             };
-            let result_id = match parts.get(1) {
+            let result_server = parts.get(1).and_then(|p| p.parse::<usize>().ok()).unwrap_or_default();
+            let result_id = match parts.get(2) {
                 Some(p) => p.clone(),
                 None => "unknown".to_string(),
             };
+            let emby_client = ctx.data().emby_servers.get(result_server)?;
+            server_context = result_server;
+            let result_user = user_on_server(&current_user, user_server, server_context);
             msg.edit(
                 ctx,
                 serenity::EditMessage::new().content(format!("Got {} {}", result_type, result_id))
@@ -436,12 +1888,34 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             let mut message: String = "No results found".to_string();
             match result_type.as_str() {
                 "series" => {
-                    match get_seasons(ctx.data().emby_client.as_ref(), &result_id).await {
+                    match get_seasons(emby_client, &result_id).await {
                         Ok(seasons) => {
                             result_box.push(
                                 serenity::CreateActionRow::SelectMenu(serenity::CreateSelectMenu::new(format!("{}_season_result", interaction_prefix), seasons.to_menu()).placeholder(format!("{} Seasons", seasons.result_items))),
                             );
+                            result_box.push(
+                                serenity::CreateActionRow::Buttons(vec![
+                                    serenity::CreateButton::new(format!("{}_add_series", interaction_prefix))
+                                        .style(serenity::ButtonStyle::Primary)
+                                        .label("Add whole series")
+                                        .emoji('\u{1F4FA}'),
+                                    serenity::CreateButton::new(format!("{}_add_next_up", interaction_prefix))
+                                        .style(serenity::ButtonStyle::Primary)
+                                        .label("Queue next up")
+                                        .emoji('\u{23ED}')
+                                        .disabled(result_user.is_none()),
+                                ]),
+                            );
+                            series_context = Some(result_id.clone());
                             message = format!("Found {} Seasons", seasons.result_items);
+                            match emby_client.get_item_info(&result_id, &None).await {
+                                Ok(series) => {
+                                    if let Err(e) = send_item_card(ctx, emby_client, &series, &message).await {
+                                        warn!("unable to send details of {}: {}", result_id, e);
+                                    }
+                                }
+                                Err(e) => warn!("unable to get details of {}: {}", result_id, e),
+                            }
                         }
                         Err(e) => {
                             message = format!("Error getting seasons: {}", e);
@@ -449,7 +1923,33 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
                     }
                 }
                 "movie" => {
-                    message = add_emby_item(ctx, &mut pipeline_ref, &result_id, &current_user).await?
+                    message = add_emby_item(ctx, pipeline_ref, emby_client, &result_id, &result_user, queue_position).await?
+                }
+                // the movies (or shows) of a collection go into the same menu so picking one works like a search result
+                "boxset" => {
+                    match get_collection(&ctx.data().emby_servers, result_server, &result_id).await {
+                        Ok(items) if items.result_items == 0 => {
+                            message = "this collection is empty".to_string();
+                        }
+                        Ok(items) => {
+                            result_box.push(
+                                serenity::CreateActionRow::SelectMenu(serenity::CreateSelectMenu::new(format!("{}_first_item_result", interaction_prefix), items.to_menu()).placeholder(format!("{} in this collection", items.result_items))),
+                            );
+                            result_box.push(
+                                serenity::CreateActionRow::Buttons(vec![
+                                    serenity::CreateButton::new(format!("{}_add_collection", interaction_prefix))
+                                        .style(serenity::ButtonStyle::Primary)
+                                        .label("Add all movies")
+                                        .emoji('\u{1F4DA}'),
+                                ]),
+                            );
+                            collection_context = Some(result_id.clone());
+                            message = items.to_msg(Some("items in this collection"));
+                        }
+                        Err(e) => {
+                            message = format!("Error getting collection: {}", e);
+                        }
+                    }
                 }
                 v => {
                     message = format!("unknown item {}", v)
@@ -457,7 +1957,7 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             }
             msg.edit(
                 ctx,
-                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, Some(result_box)).await)
+                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
             ).await?;
         }
 
@@ -474,11 +1974,11 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
                 ctx,
                 serenity::EditMessage::new().content(format!("Got Season {}", season_id))
             ).await?;
-            let (result_box, message) = handle_episode_search(interaction_prefix.to_string(), season_id, &current_user, ctx, 1).await;
+            let (result_box, message) = handle_episode_search(interaction_prefix.to_string(), season_id, &user_on_server(&current_user, user_server, server_context), ctx, server_context, 1).await;
             id_context = Some(season_id.to_string());
             msg.edit(
                 ctx,
-                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, Some(result_box)).await)
+                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
             ).await?;
         }
 
@@ -500,10 +2000,10 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
                         let page_num: u32 = p.parse().expect("unable to parse page number");
                         match id_context.clone() {
                             Some(season_id) => {
-                                let (result_box, message) = handle_episode_search(interaction_prefix.to_string(), season_id.as_str(), &current_user, ctx, page_num).await;
+                                let (result_box, message) = handle_episode_search(interaction_prefix.to_string(), season_id.as_str(), &user_on_server(&current_user, user_server, server_context), ctx, server_context, page_num).await;
                                 msg.edit(
                                     ctx,
-                                    serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, Some(result_box)).await)
+                                    serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
                                 ).await?;
                             },
                             None => {
@@ -526,7 +2026,7 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
                     }
                 }
             } else {
-                let message = add_emby_item(ctx, &mut pipeline_ref, episode_id, &current_user).await?;
+                let message = add_emby_item(ctx, pipeline_ref, ctx.data().emby_servers.get(server_context)?, episode_id, &user_on_server(&current_user, user_server, server_context), queue_position).await?;
                 msg.edit(
                     ctx,
                     serenity::EditMessage::new().content(message)
@@ -534,6 +2034,55 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             }
         }
 
+        // queue every season of the series that is currently being browsed
+        if mci.data.custom_id.ends_with("_add_series") {
+            let message = match series_context.clone() {
+                Some(series_id) => add_emby_series(ctx, pipeline_ref, ctx.data().emby_servers.get(server_context)?, &series_id, &user_on_server(&current_user, user_server, server_context), queue_position).await?,
+                None => "no series selected".to_string(),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message)
+            ).await?;
+        }
+
+        // queue the next unwatched episode of the series for the selected user
+        if mci.data.custom_id.ends_with("_add_next_up") {
+            let message = match (series_context.clone(), user_on_server(&current_user, user_server, server_context)) {
+                (Some(series_id), Some(user)) => add_next_up(ctx, pipeline_ref, ctx.data().emby_servers.get(server_context)?, &series_id, &user, queue_position).await?,
+                (None, _) => "no series selected".to_string(),
+                (_, None) => "select a user first, next up depends on what they watched".to_string(),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message)
+            ).await?;
+        }
+
+        // queue every movie of the collection that is currently being browsed
+        if mci.data.custom_id.ends_with("_add_collection") {
+            let message = match collection_context.clone() {
+                Some(collection_id) => add_emby_collection(ctx, pipeline_ref, ctx.data().emby_servers.get(server_context)?, &collection_id, &user_on_server(&current_user, user_server, server_context), queue_position).await?,
+                None => "no collection selected".to_string(),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message)
+            ).await?;
+        }
+
+        // queue the whole season that is currently being browsed
+        if mci.data.custom_id.ends_with("_add_season") {
+            let message = match id_context.clone() {
+                Some(season_id) => add_emby_season(ctx, pipeline_ref, ctx.data().emby_servers.get(server_context)?, &season_id, &user_on_server(&current_user, user_server, server_context), queue_position).await?,
+                None => "no season selected".to_string(),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message)
+            ).await?;
+        }
+
         // handle result from clicking on select user
         if mci.data.custom_id.ends_with("select_user") {
             msg.edit(
@@ -542,7 +2091,7 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             ).await?;
             let mut result_box: Vec<CreateActionRow> = vec![];
             let mut message: String = "No results found".to_string();
-            match get_users(ctx.data().emby_client.as_ref()).await {
+            match get_users(&ctx.data().emby_servers).await {
                 Ok(seasons) => {
                     result_box.push(
                         serenity::CreateActionRow::SelectMenu(serenity::CreateSelectMenu::new(format!("{}_user_list_result", interaction_prefix), seasons.to_menu()).placeholder(format!("{} Users", seasons.result_items))),
@@ -557,7 +2106,7 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             }
             msg.edit(
                 ctx,
-                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, Some(result_box)).await)
+                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
             ).await?;
         }
 
@@ -572,15 +2121,157 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             };
             let mut message: String = "No results found".to_string();
             let user_name = "";
-            if user_id == "None" {
-                current_user = None;
-            } else {
-                current_user = Some(ctx.data().emby_client.as_ref().get_user_by_id(user_id.to_string()).await?);
+            // picked users look like {server}_{id}
+            match user_id.split_once("_") {
+                Some((server, id)) => {
+                    user_server = server.parse().unwrap_or_default();
+                    current_user = Some(ctx.data().emby_servers.get(user_server)?.get_user_by_id(id.to_string()).await?);
+                }
+                None => current_user = None,
             };
             message = format!("Set user to {}", user_name);
+            let result_box = match current_user {
+                Some(_) => vec![serenity::CreateActionRow::Buttons(vec![
+                    serenity::CreateButton::new(format!("{}_continue_watching", interaction_prefix))
+                        .style(serenity::ButtonStyle::Primary)
+                        .label("Continue watching")
+                        .emoji('\u{23EF}'),
+                    serenity::CreateButton::new(format!("{}_playlists", interaction_prefix))
+                        .style(serenity::ButtonStyle::Primary)
+                        .label("Playlists")
+                        .emoji('\u{1F3B6}'),
+                    serenity::CreateButton::new(format!("{}_recently_added", interaction_prefix))
+                        .style(serenity::ButtonStyle::Primary)
+                        .label("Recently added")
+                        .emoji('\u{1F195}'),
+                ])],
+                None => vec![],
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
+            ).await?;
+        }
+
+        // list what the selected user is in the middle of
+        if mci.data.custom_id.ends_with("_continue_watching") {
+            let mut result_box: Vec<CreateActionRow> = vec![];
+            let message = match &current_user {
+                Some(user) => match get_resume_items(ctx.data().emby_servers.get(user_server)?, user).await {
+                    Ok(items) if items.result_items == 0 => format!("{} has nothing in progress", user.name),
+                    Ok(items) => {
+                        result_box.push(
+                            serenity::CreateActionRow::SelectMenu(serenity::CreateSelectMenu::new(format!("{}_continue_result", interaction_prefix), items.to_menu()).placeholder("Continue watching")),
+                        );
+                        items.to_msg(Some("items in progress"))
+                    }
+                    Err(e) => format!("Error getting items in progress: {}", e),
+                },
+                None => "select a user first".to_string(),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
+            ).await?;
+        }
+
+        // what was added lately to the libraries of the selected user
+        if mci.data.custom_id.ends_with("_recently_added") {
+            let mut result_box: Vec<CreateActionRow> = vec![];
+            let message = match &current_user {
+                Some(user) => match get_recent_items(ctx.data().emby_servers.get(user_server)?, user, RECENT_DAYS).await {
+                    Ok(items) if items.is_empty() => format!("nothing was added in the last {} days", RECENT_DAYS),
+                    Ok(items) => {
+                        let found: Vec<(usize, EmbyItemData)> = items.into_iter().take(25).map(|i| (user_server, i)).collect();
+                        result_box.push(get_recent_menu(interaction_prefix.to_string(), &ctx.data().emby_servers, &found));
+                        format!("{} items added in the last {} days", found.len(), RECENT_DAYS)
+                    }
+                    Err(e) => format!("Error getting recently added items: {}", e),
+                },
+                None => "select a user first".to_string(),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
+            ).await?;
+        }
+
+        if mci.data.custom_id.ends_with("_recent_result") {
+            let selected = match &mci.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => values[0].clone(),
+                _ => {
+                    warn!("got an unknown selection kind on recently added");
+                    "unknown".to_string()
+                }
+            };
+            let message = match parse_recent_value(&selected) {
+                Some((server, item_id)) => {
+                    server_context = server;
+                    add_emby_item(ctx, pipeline_ref, ctx.data().emby_servers.get(server)?, item_id, &user_on_server(&current_user, user_server, server), queue_position).await?
+                }
+                None => format!("unknown item {}", selected),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message)
+            ).await?;
+        }
+
+        // list the selected user's playlists
+        if mci.data.custom_id.ends_with("_playlists") {
+            let mut result_box: Vec<CreateActionRow> = vec![];
+            let message = match &current_user {
+                Some(user) => match get_playlists(ctx.data().emby_servers.get(user_server)?, user).await {
+                    Ok(playlists) if playlists.result_items == 0 => format!("{} has no playlists", user.name),
+                    Ok(playlists) => {
+                        result_box.push(
+                            serenity::CreateActionRow::SelectMenu(serenity::CreateSelectMenu::new(format!("{}_playlist_result", interaction_prefix), playlists.to_menu()).placeholder("Playlists")),
+                        );
+                        playlists.to_msg(Some("playlists"))
+                    }
+                    Err(e) => format!("Error getting playlists: {}", e),
+                },
+                None => "select a user first".to_string(),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
+            ).await?;
+        }
+
+        // queue a whole playlist in its order
+        if mci.data.custom_id.ends_with("_playlist_result") {
+            let playlist_id = match &mci.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => &values[0],
+                _ => {
+                    warn!("got an unknown selection kind on playlists");
+                    "unknown"
+                }
+            };
+            let message = match &current_user {
+                Some(user) => add_emby_playlist(ctx, pipeline_ref, ctx.data().emby_servers.get(user_server)?, playlist_id, user, queue_position).await?,
+                None => "select a user first".to_string(),
+            };
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(message)
+            ).await?;
+        }
+
+        // queue an in progress item, the resume prompt comes from add_emby_item
+        if mci.data.custom_id.ends_with("_continue_result") {
+            let item_id = match &mci.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => &values[0],
+                _ => {
+                    warn!("got an unknown selection kind on continue watching");
+                    "unknown"
+                }
+            };
+            server_context = user_server;
+            let message = add_emby_item(ctx, pipeline_ref, ctx.data().emby_servers.get(user_server)?, item_id, &current_user, queue_position).await?;
             msg.edit(
                 ctx,
-                serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, None).await)
+                serenity::EditMessage::new().content(message)
             ).await?;
         }
 
@@ -593,6 +2284,7 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             let default_input = ShowSearch {
                 search_type: SearchItemType::iter().map(|i| i.to_string()).collect::<Vec<String>>().join(","),
                 show_name: "".to_string(),
+                genre: None,
             };
             let data = poise::execute_modal_on_component_interaction::<ShowSearch>(ctx, mci.clone(), Some(default_input), Some(std::time::Duration::from_secs(30))).await;
             let mut result_box: Vec<CreateActionRow> = vec![];
@@ -609,8 +2301,12 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
                                     Err(e) => error!("invalid search item type {}: {}", s_type, e)
                                 }
                             }
-                            match get_items(ctx.data().emby_client.as_ref(), &user_search.show_name, search_types).await {
-                                Ok(list) => {
+                            let genre = user_search.genre.as_deref().map(|g| g.trim()).filter(|g| !g.is_empty());
+                            match get_items(&ctx.data().emby_servers, &user_search.show_name, search_types, genre, parental_limit(ctx).await.as_ref()).await {
+                                Ok((list, top_items)) => {
+                                    if let Err(e) = send_search_posters(ctx, &top_items).await {
+                                        warn!("unable to show search result posters: {}", e);
+                                    }
                                     if list.result_items == 0 {
                                         let empty_result = CreateSelectMenuKind::String { options: vec![CreateSelectMenuOption::new("No Results found!", "empty")] };
                                         result_box.push(
@@ -633,7 +2329,7 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
                     }
                     msg.edit(
                         ctx,
-                        serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, Some(result_box)).await)
+                        serenity::EditMessage::new().content(message).components(get_buttons(interaction_prefix.to_string(), &current_user, controls, queue_position, Some(result_box)).await)
                     ).await?;
                 },
                 Err(e) => {
@@ -645,80 +2341,751 @@ pub async fn player(ctx: Context<'_>) -> Result<(), Error> {
             };
         }
 
-        if send_final {
-            mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+        if send_final {
+            mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// browse the media directories for files that are not in emby, or emby movies by genre
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn browse(
+    ctx: Context<'_>,
+    #[description = "browse emby movies of this genre instead, e.g. horror"] genre: Option<String>,
+) -> Result<(), Error> {
+    if let Some(genre) = genre {
+        return browse_genre(ctx, &genre).await
+    }
+    let file_browser = ctx.data().file_browser.clone();
+    if !file_browser.is_configured() {
+        ctx.say("no browse directories configured. set BROWSE_ROOTS to enable browsing").await?;
+        return Ok(())
+    }
+    let interaction_prefix = ctx.id();
+    // None means we are showing the list of roots
+    let mut current_dir: Option<PathBuf> = None;
+    let mut entries = file_browser.roots();
+    let mut page_num: u32 = 1;
+
+    let reply = {
+        CreateReply::default()
+            .content("Pick a folder")
+            .components(get_browse_components(interaction_prefix.to_string(), &entries, page_num, current_dir.is_some())?)
+    };
+    ctx.send(reply).await?;
+
+    while let Some(mci) = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(std::time::Duration::from_secs(600))
+        .filter(move |mci| mci.data.custom_id.starts_with(&interaction_prefix.clone().to_string()))
+        .await
+    {
+        let mut msg = mci.message.clone();
+        let mut message: Option<String> = None;
+        if mci.data.custom_id.ends_with("browse_up") {
+            current_dir = current_dir.and_then(|d| file_browser.parent_of(&d));
+            page_num = 1;
+        }
+        if mci.data.custom_id.ends_with("browse_result") {
+            let selected = match &mci.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => values[0].clone(),
+                k => {
+                    warn!("got an unknown selection kind on browse {:#?}", k);
+                    "unknown".to_string()
+                }
+            };
+            if let Some(p) = selected.strip_prefix("page_") {
+                page_num = p.parse().unwrap_or(1);
+            } else if let Some(entry) = selected.strip_prefix("entry_").and_then(|i| i.parse::<usize>().ok()).and_then(|i| entries.get(i)) {
+                if entry.is_dir {
+                    current_dir = Some(entry.path.clone());
+                    page_num = 1;
+                } else if !file_browser.is_allowed(&entry.path) {
+                    message = Some(format!("{} is outside of the browse directories", entry.name));
+                } else {
+                    let pipeline_ref = ctx.data().get_pipeline_ref();
+                    let options = QueueItemOptions { requested_by: Some(Requester { id: ctx.author().id.get(), name: ctx.author().name.clone() }), ..QueueItemOptions::default() };
+                    message = Some(match pipeline_ref.add_uri(entry.path.to_string_lossy().to_string(), entry.name.clone(), None, options, QueuePosition::Last).await {
+                        Ok(i) => format!("added {} to queue", i.name()),
+                        Err(e) if e.downcast_ref::<QueueLimitError>().is_some() => format!("Can't queue {}, {}", entry.name, e),
+                        Err(e) => {
+                            error!("error adding {} to queue: {}", entry.path.display(), e);
+                            format!("error adding {} to queue: {}", entry.name, e)
+                        }
+                    });
+                }
+            }
+        }
+        entries = match &current_dir {
+            Some(d) => match file_browser.list_dir(d).await {
+                Ok(e) => e,
+                Err(e) => {
+                    message = Some(format!("error listing {}: {}", d.display(), e));
+                    current_dir = None;
+                    file_browser.roots()
+                }
+            },
+            None => file_browser.roots(),
+        };
+        let location = match &current_dir {
+            Some(d) => d.display().to_string(),
+            None => "Pick a folder".to_string(),
+        };
+        let content = match message {
+            Some(m) => format!("{}\n{}", location, m),
+            None => location,
+        };
+        msg.edit(
+            ctx,
+            serenity::EditMessage::new().content(content).components(get_browse_components(interaction_prefix.to_string(), &entries, page_num, current_dir.is_some())?)
+        ).await?;
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+    }
+    Ok(())
+}
+
+// a random handful of movies from the genre across all servers, offered for picking or for letting the dice decide
+async fn browse_genre(ctx: Context<'_>, genre: &str) -> Result<(), Error> {
+    let emby_servers = ctx.data().emby_servers.clone();
+    // the genre the way the servers spell it
+    let mut known = vec![];
+    for server in emby_servers.iter() {
+        known.extend(server.get_genres().await?.into_iter().map(|g| g.name));
+    }
+    let genre = match known.iter().find(|g| g.eq_ignore_ascii_case(genre.trim())) {
+        Some(g) => g.clone(),
+        None => {
+            known.sort();
+            known.dedup();
+            let mut message = format!("no genre called {}, try one of: {}", genre, known.join(", "));
+            truncate_on_char_boundary(&mut message, 2000);
+            ctx.say(message).await?;
+            return Ok(())
         }
+    };
+    let interaction_prefix = ctx.id();
+    let mut picks = get_genre_picks(&emby_servers, &genre).await?;
+    if picks.is_empty() {
+        ctx.say(format!("there are no {} movies", genre)).await?;
+        return Ok(())
     }
+    ctx.send(
+        CreateReply::default()
+            .content(format!("Some {} movies", genre))
+            .components(get_genre_components(interaction_prefix.to_string(), &emby_servers, &picks))
+    ).await?;
 
+    while let Some(mci) = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(600))
+        .filter(move |mci| mci.data.custom_id.starts_with(&interaction_prefix.to_string()))
+        .await
+    {
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+        let mut msg = mci.message.clone();
+        let pipeline_ref = ctx.data().get_pipeline_ref();
+        let mut message = format!("Some {} movies", genre);
+        if mci.data.custom_id.ends_with("_genre_shuffle") {
+            picks = get_genre_picks(&emby_servers, &genre).await?;
+        }
+        let picked = if mci.data.custom_id.ends_with("_genre_random") {
+            picks.choose(&mut rand::thread_rng()).cloned()
+        } else if mci.data.custom_id.ends_with("_genre_result") {
+            let selected = match &mci.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => values[0].clone(),
+                _ => "unknown".to_string(),
+            };
+            picks.iter().find(|(server, item)| selected == format!("movie_{}_{}", server, item.id)).cloned()
+        } else {
+            None
+        };
+        if let Some((server, item)) = picked {
+            let added = add_emby_item(ctx, pipeline_ref, emby_servers.get(server)?, &item.id, &None, QueuePosition::Last).await?;
+            message = format!("{}\n{}", message, added);
+        }
+        msg.edit(
+            ctx,
+            serenity::EditMessage::new().content(message).components(get_genre_components(interaction_prefix.to_string(), &emby_servers, &picks))
+        ).await?;
+    }
     Ok(())
 }
 
-async fn add_emby_item(ctx: Context<'_>, pipeline_ref: &mut PlayQueue, item_id: &str, current_user: &Option<EmbyItemData>) -> Result<String, Error> {
-    let mut message = "nothing".to_string();
-    let episode_info = ctx.data().emby_client.as_ref().get_item_info(item_id).await?;
-    let episode_path = match episode_info.clone().path {
+async fn get_genre_picks(emby_servers: &EmbyServers, genre: &str) -> Result<Vec<(usize, EmbyItemData)>, Error> {
+    let mut picks = vec![];
+    for (index, server) in emby_servers.iter().enumerate() {
+        picks.extend(server.get_random_in_genre(genre, SearchItemType::Movie, 25).await?.into_iter().map(|i| (index, i)));
+    }
+    // mix the servers together, a select menu holds at most 25 options
+    picks.shuffle(&mut rand::thread_rng());
+    picks.truncate(25);
+    Ok(picks)
+}
+
+fn get_genre_components(interaction_prefix: String, emby_servers: &EmbyServers, picks: &[(usize, EmbyItemData)]) -> Vec<CreateActionRow> {
+    let mut rows = vec![];
+    let options: Vec<CreateSelectMenuOption> = picks.iter()
+        .filter_map(|(server, item)| emby_servers.get(*server).ok().map(|client| item_menu_option(emby_servers, *server, client, item)))
+        .collect();
+    if !options.is_empty() {
+        rows.push(serenity::CreateActionRow::SelectMenu(serenity::CreateSelectMenu::new(format!("{}_genre_result", interaction_prefix), CreateSelectMenuKind::String { options }).placeholder("Pick a movie")));
+    }
+    rows.push(serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(format!("{}_genre_random", interaction_prefix))
+            .style(serenity::ButtonStyle::Primary)
+            .label("Surprise me")
+            .emoji('\u{1F3B2}'),
+        serenity::CreateButton::new(format!("{}_genre_shuffle", interaction_prefix))
+            .style(serenity::ButtonStyle::Secondary)
+            .label("Show others")
+            .emoji('\u{1F500}'),
+    ]));
+    rows
+}
+
+fn get_browse_components(interaction_prefix: String, entries: &[BrowseEntry], page_number: u32, show_up: bool) -> Result<Vec<CreateActionRow>, Error> {
+    let mut menu_options: Vec<CreateSelectMenuOption> = entries
+      .iter()
+      .enumerate()
+      .map(|(idx, entry)| {
+        let icon = if entry.is_dir { '\u{1F4C1}' } else { '\u{1F39E}' };
+        let mut label = format!("{} {}", icon, entry.name);
+        truncate_on_char_boundary(&mut label, 64);
+        CreateSelectMenuOption::new(label, format!("entry_{}", idx))
+      })
+      .collect();
+    if menu_options.is_empty() {
+        menu_options.push(CreateSelectMenuOption::new("Nothing playable here", "empty"));
+    }
+    let menu_item_count = menu_options.len();
+    let paged_result = paginate_result(EmbySearchResult { result_menu_option: menu_options, result_items: menu_item_count }, page_number)?;
+    let mut components = vec![];
+    if show_up {
+        components.push(serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(format!("{interaction_prefix}_browse_up"))
+                .style(serenity::ButtonStyle::Primary)
+                .label("up")
+                .emoji('\u{2B06}'),
+        ]));
+    }
+    components.push(
+        serenity::CreateActionRow::SelectMenu(serenity::CreateSelectMenu::new(format!("{}_browse_result", interaction_prefix), paged_result.to_menu()).placeholder(format!("{} entries", entries.len()))),
+    );
+    Ok(components)
+}
+
+async fn add_emby_item(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, emby_client: &EmbyClient, item_id: &str, current_user: &Option<EmbyItemData>, position: QueuePosition) -> Result<String, Error> {
+    let episode_info = emby_client.get_item_info(item_id, current_user).await?;
+    if let Err(e) = check_rating(ctx, emby_client, &episode_info).await {
+        return Ok(format!("Can't queue {}", e))
+    }
+    let start_at = match episode_info.user_data.as_ref().and_then(|u| u.position()) {
+        Some(saved) => offer_emby_resume(ctx, &episode_info, saved).await?,
+        None => None,
+    };
+    let message = match enqueue_emby_item(ctx, pipeline_ref, emby_client, &episode_info, current_user, start_at, position).await {
+        Ok(i) => {
+            let message = match start_at {
+                Some(start_at) => format!("added {} to queue, resuming at {}", i.name(), format_timestamp(start_at)),
+                None => format!("added {} to queue", i.name()),
+            };
+            if let Err(e) = send_item_card(ctx, emby_client, &episode_info, &message).await {
+                warn!("unable to send artwork for {}: {}", episode_info.id, e);
+            }
+            message
+        }
+        Err(e) if e.downcast_ref::<QueueLimitError>().is_some() => format!("Can't queue {}, {}", episode_info.name, e),
+        Err(e) => {
+            let message = format!("error adding {} to queue: {}", episode_info.name, e);
+            error!(message);
+            message
+        }
+    };
+    Ok(message)
+}
+
+// the selected user stopped part way through the item in emby, ask whether to start there. only the answer of whoever queued it counts
+async fn offer_emby_resume(ctx: Context<'_>, item: &EmbyItemData, saved: Duration) -> Result<Option<Duration>, Error> {
+    let interaction_prefix = format!("{}_{}", ctx.id(), item.id);
+    let buttons = vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(format!("{interaction_prefix}_emby_resume"))
+            .style(serenity::ButtonStyle::Primary)
+            .label(format!("resume from {}", format_timestamp(saved)))
+            .emoji('\u{23EF}'),
+        serenity::CreateButton::new(format!("{interaction_prefix}_emby_restart"))
+            .style(serenity::ButtonStyle::Secondary)
+            .label("from the start")
+            .emoji('\u{23EE}'),
+    ])];
+    let reply = ctx.send(
+        CreateReply::default()
+            .content(format!("**{}** was left at {}", item.name, format_timestamp(saved)))
+            .components(buttons)
+            .ephemeral(true)
+    ).await?;
+    let choice = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(30))
+        .filter(move |mci| mci.data.custom_id.starts_with(&interaction_prefix))
+        .await;
+    if let Some(mci) = &choice {
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+    }
+    // no answer plays it from the start
+    let start_at = choice.filter(|mci| mci.data.custom_id.ends_with("_emby_resume")).map(|_| saved);
+    let response = match start_at {
+        Some(_) => format!("Resuming **{}** from {}", item.name, format_timestamp(saved)),
+        None => format!("Playing **{}** from the start", item.name),
+    };
+    reply.edit(ctx, CreateReply::default().content(response).components(vec![])).await?;
+    Ok(start_at)
+}
+
+async fn enqueue_emby_item(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, emby_client: &EmbyClient, episode_info: &EmbyItemData, current_user: &Option<EmbyItemData>, start_at: Option<Duration>, position: QueuePosition) -> Result<QueueItem, Error> {
+    check_rating(ctx, emby_client, episode_info).await?;
+    let local_path = match emby_client.play_mode() {
+        PlayMode::Path => episode_info.path.as_deref().and_then(|p| emby_client.local_path(p)),
+        PlayMode::Stream | PlayMode::Transcode => None,
+    };
+    // fall back to letting emby transcode when the file isn't mounted here
+    let episode_path = match local_path {
         Some(path) => path,
-        None => "".to_string(),
+        None => {
+            if emby_client.play_mode() == PlayMode::Path {
+                warn!("path {:?} for {} is not usable, asking emby for a transcoded stream", episode_info.path, episode_info.id);
+            }
+            // the direct or transcoded stream is only asked for when the item comes up, see SourceResolver::emby_uri
+            let user_id = current_user.as_ref().map(|u| u.id.as_str());
+            SourceResolver::emby_uri(emby_client.name(), &episode_info.id, user_id)?.to_string()
+        }
+    };
+    info!("Got episode {}", episode_info.id);
+    let stop_fn = match &current_user {
+        Some(u) => Some(emby_client.user_stop_fn(u.id.clone(), episode_info.id.clone()).await),
+        None => None,
+    };
+    let options = QueueItemOptions {
+        chapters: episode_info.chapters.iter()
+            .map(|c| Chapter { title: c.name.clone().or(c.marker_type.clone()), start: c.start() })
+            .collect(),
+        requested_by: Some(Requester { id: ctx.author().id.get(), name: ctx.author().name.clone() }),
+        start_at,
+        watched_mark: current_user.as_ref().map(|u| WatchedMark { server: Some(emby_client.name().to_string()), user_id: u.id.clone(), item_id: episode_info.id.clone() }),
+        emby_item: Some(EmbyItemRef { server: Some(emby_client.name().to_string()), item_id: episode_info.id.clone() }),
+        ..QueueItemOptions::default()
     };
-    if episode_path.is_empty() {
-        message = format!("could not find episode info for {}", episode_info.name);
-        error!(message)
+    let name = ctx.data().emby_servers.label(emby_client, &generate_episode_name(episode_info.clone()));
+    Ok(pipeline_ref.add_uri(episode_path, name, stop_fn, options, position).await?)
+}
+
+async fn add_next_up(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, emby_client: &EmbyClient, series_id: &str, user: &EmbyItemData, position: QueuePosition) -> Result<String, Error> {
+    match emby_client.get_next_up(series_id, user).await? {
+        Some(episode) => add_emby_item(ctx, pipeline_ref, emby_client, &episode.id, &Some(user.clone()), position).await,
+        None => Ok(format!("{} has no episodes left to watch in this series", user.name)),
+    }
+}
+
+// queues a playlist's entries in playlist order, folders and other things that can't be played are left out
+async fn add_emby_playlist(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, emby_client: &EmbyClient, playlist_id: &str, user: &EmbyItemData, position: QueuePosition) -> Result<String, Error> {
+    let entries: Vec<EmbyItemData> = emby_client.get_playlist_items(playlist_id, user).await?
+        .into_iter()
+        .filter(|i| matches!(i.item_type.as_deref(), Some("Movie" | "Episode" | "Video" | "MusicVideo" | "Audio")))
+        .collect();
+    if entries.is_empty() {
+        return Ok("there is nothing playable in this playlist".to_string())
+    }
+    Ok(enqueue_emby_episodes(ctx, pipeline_ref, emby_client, entries, "playlist items", &Some(user.clone()), position).await)
+}
+
+// queues the movies of a collection in release order, shows in it are left out
+async fn add_emby_collection(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, emby_client: &EmbyClient, collection_id: &str, current_user: &Option<EmbyItemData>, position: QueuePosition) -> Result<String, Error> {
+    let movies: Vec<EmbyItemData> = emby_client.get_collection_items(collection_id).await?
+        .into_iter()
+        .filter(|i| i.item_type.as_deref() == Some("Movie"))
+        .collect();
+    if movies.is_empty() {
+        return Ok("there are no movies in this collection".to_string())
+    }
+    Ok(enqueue_emby_episodes(ctx, pipeline_ref, emby_client, movies, "movies", current_user, position).await)
+}
+
+// queues every episode of a season in order. with a user selected the ones they already watched are skipped
+async fn add_emby_season(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, emby_client: &EmbyClient, season_id: &str, current_user: &Option<EmbyItemData>, position: QueuePosition) -> Result<String, Error> {
+    let episodes = unwatched_episodes(emby_client.get_episodes_for_season(season_id, current_user).await?);
+    if episodes.is_empty() {
+        return Ok("no unwatched episodes left in this season".to_string())
+    }
+    Ok(enqueue_emby_episodes(ctx, pipeline_ref, emby_client, episodes, "episodes", current_user, position).await)
+}
+
+// queues the whole show season by season, specials are left out and at most SERIES_QUEUE_CAP episodes go in
+async fn add_emby_series(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, emby_client: &EmbyClient, series_id: &str, current_user: &Option<EmbyItemData>, position: QueuePosition) -> Result<String, Error> {
+    let (seasons, episodes) = get_series_episodes(emby_client, series_id, current_user).await?;
+    let mut episodes = unwatched_episodes(episodes);
+    if episodes.is_empty() {
+        return Ok("no unwatched episodes left in this series".to_string())
+    }
+    let available = episodes.len();
+    episodes.truncate(SERIES_QUEUE_CAP);
+    let message = enqueue_emby_episodes(ctx, pipeline_ref, emby_client, episodes, "episodes", current_user, position).await;
+    if available > SERIES_QUEUE_CAP {
+        Ok(format!("{} from {} seasons, only the first {} of {} episodes were queued", message, seasons, SERIES_QUEUE_CAP, available))
     } else {
-        info!("Got episode {}", episode_path);
-        let episode_path = episode_path.replace("/mnt/storage", "/mnt/zfspool/storage");
-        let stop_fn = match &current_user {
-            Some(u) => Some(ctx.data().emby_client.as_ref().user_stop_fn(u.id.clone(), episode_info.id.clone()).await),
-            None => None,
-        };
-        match pipeline_ref.add_uri(episode_path.to_string(), generate_episode_name(episode_info.clone()), stop_fn) {
-            Ok(i) => {
-                message = format!("added {} to queue", i.name());
+        Ok(format!("{} from {} seasons", message, seasons))
+    }
+}
+
+// every episode of the regular seasons in broadcast order, along with how many seasons there were
+async fn get_series_episodes(emby_client: &EmbyClient, series_id: &str, current_user: &Option<EmbyItemData>) -> Result<(usize, Vec<EmbyItemData>), Error> {
+    let mut seasons: Vec<(u32, EmbyItemData)> = emby_client.get_seasons_for_series(series_id).await?
+        .into_iter()
+        .map(|s| (s.episode_num.as_deref().and_then(|n| n.parse().ok()).unwrap_or(0), s))
+        .filter(|(index, _)| *index > 0)
+        .collect();
+    seasons.sort_by_key(|(index, _)| *index);
+    let mut episodes = vec![];
+    for (_, season) in &seasons {
+        episodes.extend(emby_client.get_episodes_for_season(&season.id, current_user).await?);
+    }
+    Ok((seasons.len(), episodes))
+}
+
+// user data is only there when the episodes were fetched for a user, without one nothing counts as watched
+fn unwatched_episodes(episodes: Vec<EmbyItemData>) -> Vec<EmbyItemData> {
+    episodes.into_iter()
+        .filter(|e| !e.user_data.as_ref().is_some_and(|u| u.played))
+        .collect()
+}
+
+// adds the episodes (or movies) in the given order and sums up how that went
+async fn enqueue_emby_episodes(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, emby_client: &EmbyClient, episodes: Vec<EmbyItemData>, noun: &str, current_user: &Option<EmbyItemData>, position: QueuePosition) -> String {
+    let total = episodes.len();
+    let mut added = 0;
+    let mut failed = vec![];
+    let mut blocked = 0;
+    for episode in episodes {
+        match enqueue_emby_item(ctx, pipeline_ref, emby_client, &episode, current_user, None, position).await {
+            Ok(item) => {
+                // playing next puts every episode at the front, move it behind the ones added before it
+                if position == QueuePosition::Next && added > 0 {
+                    if let Err(e) = pipeline_ref.move_item(item.id(), added).await {
+                        warn!("unable to move {} behind the episodes before it: {}", item.name(), e);
+                    }
+                }
+                added += 1
+            }
+            Err(e) if e.downcast_ref::<QueueLimitError>().is_some() => {
+                return format!("added {} of {} {} to queue, stopped because {}", added, total, noun, e)
+            }
+            Err(e) if e.downcast_ref::<RatingBlocked>().is_some() => {
+                info!("not adding {}: {}", episode.name, e);
+                blocked += 1;
             }
             Err(e) => {
-                message = format!("error adding {} to queue: {}", episode_path, e);
-                error!(message)
+                error!("error adding {} to queue: {}", episode.name, e);
+                failed.push(episode.name);
             }
-            
-        };
+        }
+    }
+    let message = if failed.is_empty() && blocked == 0 {
+        format!("added {} {} to queue", added, noun)
+    } else if failed.is_empty() {
+        format!("added {} of {} {} to queue", added, total, noun)
+    } else {
+        format!("added {} of {} {} to queue, could not add {}", added, total, noun, failed.join(", "))
     };
-    Ok(message.to_string())
+    match blocked {
+        0 => message,
+        _ => format!("{}, skipped {} rated too high for this server", message, blocked),
+    }
 }
 
-async fn get_items(emby_client: &EmbyClient, item_name: &str, item_types: Vec<SearchItemType>) -> Result<EmbySearchResult, Error> {
-    let series_result = if item_name == "all" {
-        match emby_client.get_all_series().await {
-            Ok(d) => Ok(d),
-            Err(e) => Err(Box::new(BotError::new(e.to_string().as_str())))
-        }?
-    } else {
-        match emby_client.search_items(item_name, item_types).await {
-            Ok(d) => Ok(d),
-            Err(e) => Err(Box::new(BotError::new(e.to_string().as_str())))
-        }?
+// the rating limit of the guild the command came from, there is none in dms
+async fn parental_limit(ctx: Context<'_>) -> Option<ParentalLimit> {
+    ctx.data().guild_config.parental_limit(ctx.guild_id()?).await
+}
+
+// episodes without a rating of their own go by the rating of their series
+async fn check_rating(ctx: Context<'_>, emby_client: &EmbyClient, item: &EmbyItemData) -> Result<(), RatingBlocked> {
+    let limit = match parental_limit(ctx).await {
+        Some(l) => l,
+        None => return Ok(()),
+    };
+    let rating = match (&item.official_rating, &item.series_id) {
+        (Some(rating), _) => Some(rating.clone()),
+        (None, Some(series_id)) => match emby_client.get_official_rating(series_id).await {
+            Ok(rating) => rating,
+            Err(e) => {
+                warn!("unable to get the rating of series {}: {}", series_id, e);
+                None
+            }
+        },
+        (None, None) => None,
+    };
+    limit.check(&item.name, rating.as_deref())
+}
+
+// ephemeral card with the item artwork and details so whoever picked it can see they got the right thing
+async fn send_item_card(ctx: Context<'_>, emby_client: &EmbyClient, item: &EmbyItemData, message: &str) -> Result<(), Error> {
+    let title = ctx.data().emby_servers.label(emby_client, &generate_episode_name(item.clone()));
+    let (embed, poster) = with_poster(ctx, emby_client, &item.id, item_embed(title, item), "artwork.jpg").await;
+    let mut reply = CreateReply::default().content(message).embed(embed).ephemeral(true);
+    if let Some(poster) = poster {
+        reply = reply.attachment(poster);
+    }
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+// synopsis, runtime, rating and premiere date of an emby item, whatever of it the server knows
+fn item_embed(title: String, item: &EmbyItemData) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::new().title(title);
+    if let Some(overview) = item.overview.as_ref().filter(|o| !o.trim().is_empty()) {
+        let mut overview = overview.clone();
+        if overview.len() > MAX_OVERVIEW_LEN {
+            truncate_on_char_boundary(&mut overview, MAX_OVERVIEW_LEN);
+            overview.push('\u{2026}');
+        }
+        embed = embed.description(overview);
+    }
+    if let Some(runtime) = item.runtime() {
+        let minutes = runtime.as_secs() / 60;
+        let runtime = match minutes {
+            0..=59 => format!("{}m", minutes.max(1)),
+            _ => format!("{}h {}m", minutes / 60, minutes % 60),
+        };
+        embed = embed.field("Runtime", runtime, true);
+    }
+    if let Some(rating) = item.community_rating {
+        embed = embed.field("Rating", format!("\u{2B50} {:.1}", rating), true);
+    }
+    if let Some(premiered) = item.premiered() {
+        embed = embed.field("Premiered", premiered.format("%Y-%m-%d").to_string(), true);
+    }
+    embed
+}
+
+// puts the primary image of an emby item on the embed as its thumbnail, the attachment has to go out with it
+async fn with_poster(ctx: Context<'_>, emby_client: &EmbyClient, item_id: &str, embed: serenity::CreateEmbed, filename: &str) -> (serenity::CreateEmbed, Option<CreateAttachment>) {
+    match ctx.data().artwork_cache.get(emby_client, item_id, "Primary").await {
+        Ok(artwork) => (embed.thumbnail(format!("attachment://{}", filename)), Some(CreateAttachment::bytes(artwork, filename))),
+        Err(e) => {
+            warn!("no poster for {}: {}", item_id, e);
+            (embed, None)
+        }
+    }
+}
+
+// posters of the top search results, the ones without artwork are left out
+async fn send_search_posters(ctx: Context<'_>, items: &[(&EmbyClient, EmbyItemData)]) -> Result<(), Error> {
+    let mut reply = CreateReply::default().ephemeral(true);
+    let mut found = false;
+    for (index, (emby_client, item)) in items.iter().enumerate() {
+        let title = ctx.data().emby_servers.label(emby_client, &item.name);
+        let embed = serenity::CreateEmbed::new().title(title);
+        if let (embed, Some(poster)) = with_poster(ctx, emby_client, &item.id, embed, &format!("poster{}.jpg", index)).await {
+            reply = reply.embed(embed).attachment(poster);
+            found = true;
+        }
+    }
+    if found {
+        ctx.send(reply).await?;
+    }
+    Ok(())
+}
+
+// details and poster of the emby item that is playing, None for anything else
+async fn now_playing_card(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle) -> Option<(serenity::CreateEmbed, Option<CreateAttachment>)> {
+    let item = pipeline_ref.get_current_item().await.ok().flatten()?;
+    let emby_item = item.emby_item()?;
+    let emby_client = ctx.data().emby_servers.by_name(emby_item.server.as_deref())?;
+    let embed = match emby_client.get_item_info(&emby_item.item_id, &None).await {
+        Ok(info) => item_embed(item.name(), &info),
+        Err(e) => {
+            warn!("unable to get details of {}: {}", emby_item.item_id, e);
+            serenity::CreateEmbed::new().title(item.name())
+        }
     };
+    Some(with_poster(ctx, emby_client, &emby_item.item_id, embed, "artwork.jpg").await)
+}
+
+// swaps the card on a now playing message, the old one is taken off when the current item has none
+async fn with_now_playing_card(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, edit: serenity::EditMessage) -> serenity::EditMessage {
+    match now_playing_card(ctx, pipeline_ref).await {
+        Some((embed, Some(poster))) => edit.embed(embed).new_attachment(poster),
+        Some((embed, None)) => edit.embed(embed).remove_all_attachments(),
+        None => edit.embeds(vec![]).remove_all_attachments(),
+    }
+}
+
+// searches every server, the menu values carry the index of the server the item came from.
+// the top results come back as well to show their posters
+async fn get_items<'a>(emby_servers: &'a EmbyServers, item_name: &str, item_types: Vec<SearchItemType>, genre: Option<&str>, limit: Option<&ParentalLimit>) -> Result<(EmbySearchResult, Vec<(&'a EmbyClient, EmbyItemData)>), Error> {
+    let mut series_result = vec![];
+    for (index, emby_client) in emby_servers.iter().enumerate() {
+        let found = if item_name == "all" && genre.is_none() {
+            match emby_client.get_all_series().await {
+                Ok(d) => Ok(d),
+                Err(e) => Err(Box::new(BotError::new(e.to_string().as_str())))
+            }?
+        } else {
+            // "all" with a genre lists the whole genre
+            let item_name = if item_name == "all" { "" } else { item_name };
+            match emby_client.search_items(item_name, item_types.clone(), genre).await {
+                Ok(d) => Ok(d),
+                Err(e) => Err(Box::new(BotError::new(e.to_string().as_str())))
+            }?
+        };
+        series_result.extend(found.into_iter()
+            .filter(|f| limit.is_none_or(|l| l.allows(f.official_rating.as_deref())))
+            .map(|f| (index, emby_client, f)));
+    }
     let menu_options: Vec<CreateSelectMenuOption> = series_result
       .iter()
-      .map(|series| {
-        let item_type = series.item_type.clone().unwrap_or("Unknown".to_string());
-        let (label_prefix, value_prefix) = match item_type.as_str() {
-            "Movie" => ("\u{1F4FD}", "movie"),
-            "Series" => ("\u{1F4FA}", "series"),
-            _ => ("unknown: ", "unknown"),
-        };
-        CreateSelectMenuOption::new(format!("{}: {}", label_prefix, series.name.as_str()), format!("{}_{}", value_prefix, series.id.to_string()))
-      })
+      .map(|(index, emby_client, series)| item_menu_option(emby_servers, *index, emby_client, series))
       .collect();
     let menu_item_count = menu_options.len();
     info!("found {} series", menu_item_count.clone());
+    let top_items = series_result.into_iter()
+      .take(SEARCH_POSTERS)
+      .map(|(_, emby_client, item)| (emby_client, item))
+      .collect();
+    Ok( (EmbySearchResult { result_menu_option: menu_options, result_items: menu_item_count}, top_items) )
+}
+
+// users only exist on their own server, anything from another one gets queued without a user
+fn user_on_server(user: &Option<EmbyItemData>, user_server: usize, server: usize) -> Option<EmbyItemData> {
+    user.clone().filter(|_| user_server == server)
+}
+
+// the emby item that is playing, fetched for the selected user so it comes with their favorite and played state.
+// the error says why there is none
+async fn playing_for_user<'a>(ctx: Context<'a>, pipeline_ref: &PlayQueueHandle, user: &Option<EmbyItemData>, user_server: usize) -> Result<(&'a EmbyClient, EmbyItemData, EmbyItemData), String> {
+    let playing = pipeline_ref.get_current_item().await.ok().flatten().ok_or_else(|| "nothing is playing".to_string())?;
+    let emby_item = playing.emby_item().ok_or_else(|| format!("{} is not from emby", playing.name()))?;
+    let server = ctx.data().emby_servers.index_of(emby_item.server.as_deref()).ok_or_else(|| format!("the media server of {} is not configured anymore", playing.name()))?;
+    let user = user_on_server(user, user_server, server).ok_or_else(|| "select a user of the server it plays from first".to_string())?;
+    let emby_client = ctx.data().emby_servers.get(server).map_err(|e| e.to_string())?;
+    let item = emby_client.get_item_info(&emby_item.item_id, &Some(user.clone())).await
+        .map_err(|e| format!("unable to look up {}: {}", playing.name(), e))?;
+    Ok((emby_client, item, user))
+}
+
+// flips the favorite flag of the playing emby item for the selected user, returns the new flag when that worked
+async fn toggle_favorite(ctx: Context<'_>, pipeline_ref: &PlayQueueHandle, user: &Option<EmbyItemData>, user_server: usize) -> (String, Option<bool>) {
+    let (emby_client, item, user) = match playing_for_user(ctx, pipeline_ref, user, user_server).await {
+        Ok(p) => p,
+        Err(message) => return (message, None),
+    };
+    let favorite = !item.user_data.as_ref().is_some_and(|u| u.is_favorite);
+    let name = generate_episode_name(item.clone());
+    match emby_client.set_favorite(&user.id, &item.id, favorite).await {
+        Ok(_) if favorite => (format!("added {} to the favorites of {}", name, user.name), Some(true)),
+        Ok(_) => (format!("removed {} from the favorites of {}", name, user.name), Some(false)),
+        Err(e) => {
+            error!("unable to update favorite {} for {}: {}", item.id, user.name, e);
+            (format!("could not update the favorites of {}: {}", user.name, e), None)
+        }
+    }
+}
+
+fn favorite_button(interaction_prefix: String, favorite: bool) -> serenity::CreateButton {
+    let button = serenity::CreateButton::new(format!("{}_favorite", interaction_prefix)).style(serenity::ButtonStyle::Secondary);
+    match favorite {
+        true => button.label("Unfavorite").emoji('\u{2764}'),
+        false => button.label("Favorite").emoji('\u{1F90D}'),
+    }
+}
+
+// newest first, limited to what was added within the last days
+async fn get_recent_items(emby_client: &EmbyClient, user: &EmbyItemData, days: u32) -> Result<Vec<EmbyItemData>, Error> {
+    let since = chrono::Utc::now() - chrono::Duration::days(days as i64);
+    let mut items: Vec<EmbyItemData> = emby_client.get_latest(user, RECENT_FETCH_LIMIT).await?
+        .into_iter()
+        .filter(|i| i.created().is_some_and(|c| c >= since))
+        .collect();
+    items.sort_by_key(|i| std::cmp::Reverse(i.created()));
+    Ok(items)
+}
+
+// values are {server}_{id}
+fn get_recent_menu(interaction_prefix: String, emby_servers: &EmbyServers, items: &[(usize, EmbyItemData)]) -> CreateActionRow {
+    let options = items.iter()
+        .map(|(server, item)| {
+            let name = match &item.series_name {
+                Some(series) => format!("{} {}", series, generate_episode_name(item.clone())),
+                None => generate_episode_name(item.clone()),
+            };
+            let added = item.created().map(|c| c.format("%b %d").to_string()).unwrap_or_default();
+            let mut label = format!("{} (added {})", name, added);
+            if let Ok(client) = emby_servers.get(*server) {
+                label = emby_servers.label(client, &label);
+            }
+            // discord caps option labels at 100 characters
+            truncate_on_char_boundary(&mut label, 100);
+            CreateSelectMenuOption::new(label, format!("{}_{}", server, item.id))
+        })
+        .collect();
+    serenity::CreateActionRow::SelectMenu(serenity::CreateSelectMenu::new(format!("{}_recent_result", interaction_prefix), CreateSelectMenuKind::String { options }).placeholder("Recently added"))
+}
+
+fn parse_recent_value(value: &str) -> Option<(usize, &str)> {
+    let (server, id) = value.split_once('_')?;
+    Some((server.parse().ok()?, id))
+}
+
+async fn get_playlists(emby_client: &EmbyClient, user: &EmbyItemData) -> Result<EmbySearchResult, Error> {
+    let mut menu_options: Vec<CreateSelectMenuOption> = emby_client.get_playlists(user).await?
+      .iter()
+      .map(|playlist| CreateSelectMenuOption::new(playlist.name.as_str(), playlist.id.as_str()))
+      .collect();
+    menu_options.truncate(25);
+    let menu_item_count = menu_options.len();
+    Ok( EmbySearchResult { result_menu_option: menu_options, result_items: menu_item_count} )
+}
+
+// menu entry for a search result or collection item, the value is {type}_{server}_{id}
+fn item_menu_option(emby_servers: &EmbyServers, server: usize, emby_client: &EmbyClient, item: &EmbyItemData) -> CreateSelectMenuOption {
+    let item_type = item.item_type.clone().unwrap_or("Unknown".to_string());
+    let (label_prefix, value_prefix) = match item_type.as_str() {
+        "Movie" => ("\u{1F4FD}", "movie"),
+        "Series" => ("\u{1F4FA}", "series"),
+        "BoxSet" => ("\u{1F4DA}", "boxset"),
+        _ => ("unknown: ", "unknown"),
+    };
+    let label = emby_servers.label(emby_client, &format!("{}: {}", label_prefix, item.name.as_str()));
+    CreateSelectMenuOption::new(label, format!("{}_{}_{}", value_prefix, server, item.id))
+}
+
+async fn get_collection(emby_servers: &EmbyServers, server: usize, collection_id: &str) -> Result<EmbySearchResult, Error> {
+    let emby_client = emby_servers.get(server)?;
+    let mut menu_options: Vec<CreateSelectMenuOption> = emby_client.get_collection_items(collection_id).await?
+      .iter()
+      .map(|item| item_menu_option(emby_servers, server, emby_client, item))
+      .collect();
+    // a select menu holds at most 25 options
+    if menu_options.len() > 25 {
+        warn!("collection {} has {} items, only listing the first 25", collection_id, menu_options.len());
+        menu_options.truncate(25);
+    }
+    let menu_item_count = menu_options.len();
     Ok( EmbySearchResult { result_menu_option: menu_options, result_items: menu_item_count} )
 }
 
-async fn get_users(emby_client: &EmbyClient) -> Result<EmbySearchResult, Error> {
-    let users = emby_client.get_users().await?;
+async fn get_users(emby_servers: &EmbyServers) -> Result<EmbySearchResult, Error> {
+    let mut users = vec![];
+    for (index, emby_client) in emby_servers.iter().enumerate() {
+        users.extend(emby_client.get_users().await?.into_iter().map(|u| (index, emby_client, u)));
+    }
     let menu_options: Vec<CreateSelectMenuOption> = users
       .iter()
-      .map(|user| {
-        CreateSelectMenuOption::new(user.name.to_string(), user.id.to_string())
+      .map(|(index, emby_client, user)| {
+        CreateSelectMenuOption::new(emby_servers.label(emby_client, &user.name), format!("{}_{}", index, user.id))
       })
       .collect();
     let menu_options: Vec<CreateSelectMenuOption> = vec![CreateSelectMenuOption::new("None", "None")].iter().chain(menu_options.iter()).cloned().collect();
@@ -727,12 +3094,159 @@ async fn get_users(emby_client: &EmbyClient) -> Result<EmbySearchResult, Error>
     Ok( EmbySearchResult { result_menu_option: menu_options, result_items: menu_item_count} )
 }
 
-async fn get_now_playing(pipeline_ref: &PlayQueue) -> String {
-    match pipeline_ref.get_current_item() {
+async fn get_resume_items(emby_client: &EmbyClient, user: &EmbyItemData) -> Result<EmbySearchResult, Error> {
+    let items = emby_client.get_resume_items(user).await?;
+    let menu_options: Vec<CreateSelectMenuOption> = items
+      .iter()
+      .map(|item| {
+        let position = item.user_data.as_ref().and_then(|u| u.position()).map(format_timestamp).unwrap_or_default();
+        let mut label = format!("{} ({})", generate_episode_name(item.clone()), position);
+        // discord caps option labels at 100 characters
+        truncate_on_char_boundary(&mut label, 100);
+        CreateSelectMenuOption::new(label, item.id.as_str())
+      })
+      .collect();
+    let menu_item_count = menu_options.len();
+    Ok( EmbySearchResult { result_menu_option: menu_options, result_items: menu_item_count} )
+}
+
+// reaction and the action it triggers on the quick controls message
+const QUICK_CONTROLS: [(&str, QuickAction); 3] = [
+    ("\u{23EF}\u{FE0F}", QuickAction::PlayPause),
+    ("\u{23ED}\u{FE0F}", QuickAction::Skip),
+    ("\u{23F9}\u{FE0F}", QuickAction::Stop),
+];
+
+#[derive(Clone, Copy, Debug)]
+enum QuickAction {
+    PlayPause,
+    Skip,
+    Stop,
+}
+
+/// now playing message controlled with reactions, lighter than the full player
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+async fn quick(ctx: Context<'_>) -> Result<(), Error> {
+    let pipeline_ref = ctx.data().get_pipeline_ref();
+    let mut reply = CreateReply::default().content(get_now_playing(pipeline_ref).await);
+    if let Some((embed, poster)) = now_playing_card(ctx, pipeline_ref).await {
+        reply = reply.embed(embed);
+        if let Some(poster) = poster {
+            reply = reply.attachment(poster);
+        }
+    }
+    let reply = ctx.send(reply).await?;
+    let mut msg = reply.into_message().await?;
+    for (emoji, _) in QUICK_CONTROLS {
+        msg.react(ctx, serenity::ReactionType::Unicode(emoji.to_string())).await?;
+    }
+    let bot_id = ctx.framework().bot_id;
+    let mut reactions = msg.await_reactions(ctx)
+        .timeout(Duration::from_secs(3600))
+        .stream();
+    // keep the message on the current item while it is up
+    let mut events = pipeline_ref.subscribe();
+    loop {
+        tokio::select! {
+            reaction = reactions.next() => {
+                let reaction = match reaction {
+                    Some(r) => r,
+                    None => break,
+                };
+                let user_id = match reaction.user_id {
+                    Some(u) if u != bot_id => u,
+                    _ => continue,
+                };
+                let action = QUICK_CONTROLS.iter()
+                    .find(|(emoji, _)| reaction.emoji.unicode_eq(emoji))
+                    .map(|(_, action)| *action);
+                // take the reaction back off so the same button can be used again
+                if let Err(e) = reaction.delete(ctx).await {
+                    warn!("unable to remove quick control reaction: {}", e);
+                }
+                let action = match action {
+                    Some(a) => a,
+                    None => continue,
+                };
+                if !can_use_quick_controls(ctx, user_id).await {
+                    info!("ignoring {:?} from unauthorized user {}", action, user_id);
+                    continue
+                }
+                let result = match action {
+                    QuickAction::PlayPause => match pipeline_ref.pause_playback().await {
+                        Ok(_) => Ok(()),
+                        // pausing fails when nothing is playing so start instead
+                        Err(_) => pipeline_ref.start_playback().await.map(|_| ()),
+                    },
+                    QuickAction::Skip => {
+                        let user_name = user_id.to_user(ctx).await.map(|u| u.name).unwrap_or(user_id.to_string());
+                        pipeline_ref.skip_video(Some(skip_reason(None, &user_name))).await
+                    }
+                    QuickAction::Stop => pipeline_ref.stop_playback().await,
+                };
+                let content = match result {
+                    Ok(_) => get_now_playing(pipeline_ref).await,
+                    Err(e) => format!("{}\nerror: {}", get_now_playing(pipeline_ref).await, e),
+                };
+                msg.edit(ctx, with_now_playing_card(ctx, pipeline_ref, serenity::EditMessage::new().content(content)).await).await?;
+            }
+            event = events.recv() => {
+                match event {
+                    Ok(PlaybackEvent::ItemStarted { .. }) | Ok(PlaybackEvent::QueueFinished) => {
+                        let edit = serenity::EditMessage::new().content(get_now_playing(pipeline_ref).await);
+                        msg.edit(ctx, with_now_playing_card(ctx, pipeline_ref, edit).await).await?;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                    _ => (),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// whoever opened the controls, bot owners and server admins
+async fn can_use_quick_controls(ctx: Context<'_>, user_id: serenity::UserId) -> bool {
+    if user_id == ctx.author().id || ctx.framework().options().owners.contains(&user_id) {
+        return true
+    }
+    let guild_id = match ctx.guild_id() {
+        Some(g) => g,
+        None => return false,
+    };
+    let member = match guild_id.member(ctx, user_id).await {
+        Ok(m) => m,
+        Err(_) => return false,
+    };
+    ctx.guild().map(|g| g.member_permissions(&member).administrator()).unwrap_or(false)
+}
+
+async fn get_now_playing(pipeline_ref: &PlayQueueHandle) -> String {
+    let now_playing = match pipeline_ref.get_current_item().await.unwrap_or_default() {
         Some(i) => {
-            i.name()
+            let name = if i.is_live() {
+                format!("{} LIVE - {}", '\u{1F534}', i.name())
+            } else {
+                i.name()
+            };
+            // the picture freezes while the source refills its buffer so say why
+            match pipeline_ref.stream_health().await {
+                Ok(h) if h.buffering_percent < 100 => format!("{} \u{23F3} buffering {}%", name, h.buffering_percent),
+                _ => name,
+            }
+        }
+        None => match pipeline_ref.intermission().await.unwrap_or_default() {
+            Some(i) => {
+                let ends_at = i.ends_at.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+                format!("\u{2615} Intermission, {} starts <t:{}:R>. play starts it now", i.next.name(), ends_at)
+            }
+            None => "No item playing".to_string(),
         }
-        None => "No item playing".to_string()
+    };
+    match pipeline_ref.repeat_mode().await.unwrap_or_default() {
+        RepeatMode::Off => now_playing,
+        RepeatMode::One => format!("{} \u{1F502} {}", now_playing, RepeatMode::One.label()),
+        RepeatMode::Queue => format!("{} \u{1F501} {}", now_playing, RepeatMode::Queue.label()),
     }
 }
 
@@ -762,10 +3276,11 @@ fn generate_episode_name(episode: EmbyItemData) -> String {
         }
         None => "".to_string(),
     };
-    if episode.item_type.unwrap_or("Unknown".to_string()) == "Movie" {
-        format!("{}Movie - {}", watched_icon, episode.name)
-    } else {
-        format!("{}S{}E{} - {}", watched_icon, episode.season_num.as_ref().unwrap(), episode.episode_num.as_ref().unwrap(), episode.name)
+    match (episode.item_type.as_deref(), &episode.season_num, &episode.episode_num) {
+        (Some("Movie"), _, _) => format!("{}Movie - {}", watched_icon, episode.name),
+        (_, Some(season), Some(number)) => format!("{}S{}E{} - {}", watched_icon, season, number, episode.name),
+        // playlists can hold videos and music that aren't part of a show
+        _ => format!("{}{}", watched_icon, episode.name),
     }
 }
 
@@ -807,15 +3322,27 @@ fn paginate_result(search_result: EmbySearchResult, page_number: u32) -> Result<
     }
 }
 
-async fn handle_episode_search(interaction_prefix: String, season_id: &str, current_user: &Option<EmbyItemData>, ctx: Context<'_>, page_number: u32) -> (Vec<CreateActionRow>, String) {
+async fn handle_episode_search(interaction_prefix: String, season_id: &str, current_user: &Option<EmbyItemData>, ctx: Context<'_>, server: usize, page_number: u32) -> (Vec<CreateActionRow>, String) {
     let mut message: String = "no result found".to_string();
     let mut result_box: Vec<CreateActionRow> = vec![];
-    match get_episodes(ctx.data().emby_client.as_ref(), season_id, &current_user).await {
+    let episodes = match ctx.data().emby_servers.get(server) {
+        Ok(emby_client) => get_episodes(emby_client, season_id, &current_user).await,
+        Err(e) => Err(e.into()),
+    };
+    match episodes {
         Ok(episodes) => {
             let paged_result = paginate_result(episodes, page_number).expect("Unable to paginate result");
             result_box.push(
                 serenity::CreateActionRow::SelectMenu(serenity::CreateSelectMenu::new(format!("{}_episodes_result", interaction_prefix), paged_result.to_menu()).placeholder(format!("{} Series Episodes", paged_result.result_items))),
             );
+            result_box.push(
+                serenity::CreateActionRow::Buttons(vec![
+                    serenity::CreateButton::new(format!("{}_add_season", interaction_prefix))
+                        .style(serenity::ButtonStyle::Primary)
+                        .label("Add all episodes")
+                        .emoji('\u{1F4FA}'),
+                ]),
+            );
             message = paged_result.to_msg(Some("episodes"));
         }
         Err(e) => {
@@ -850,8 +3377,8 @@ async fn get_episodes(emby_client: &EmbyClient, season_id: &str, current_user: &
     Ok( EmbySearchResult { result_menu_option: menu_options, result_items: menu_item_count} )
 }
 
-async fn get_queue_selector(pipeline_ref: &PlayQueue, prefix: &str) -> Vec<CreateActionRow> {
-    let mut queue_items: Vec<CreateSelectMenuOption> = pipeline_ref.get_queue_items().iter()
+async fn get_queue_selector(pipeline_ref: &PlayQueueHandle, prefix: &str) -> Vec<CreateActionRow> {
+    let mut queue_items: Vec<CreateSelectMenuOption> = pipeline_ref.get_queue_items().await.unwrap_or_default().iter()
       .map(|item| {
         CreateSelectMenuOption::new(item.name(), item.id())
       })
@@ -860,10 +3387,18 @@ async fn get_queue_selector(pipeline_ref: &PlayQueue, prefix: &str) -> Vec<Creat
     if num_items == 0 {
         queue_items = vec![CreateSelectMenuOption::new("No items in queue!", "empty")];
     }
-    let result_box = vec![serenity::CreateActionRow::SelectMenu(
-        serenity::CreateSelectMenu::new(
-            format!("{}_queue_list", prefix),
-            serenity::CreateSelectMenuKind::String { options: queue_items }
-        ).placeholder(format!("{} Queue Items", num_items)))];
+    let result_box = vec![
+        serenity::CreateActionRow::SelectMenu(
+            serenity::CreateSelectMenu::new(
+                format!("{}_queue_list", prefix),
+                serenity::CreateSelectMenuKind::String { options: queue_items }
+            ).placeholder(format!("{} Queue Items", num_items))),
+        serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(format!("{prefix}_shuffle"))
+                .style(serenity::ButtonStyle::Primary)
+                .label("shuffle")
+                .emoji('\u{1F500}'),
+        ]),
+    ];
     result_box
 }
\ No newline at end of file
@@ -0,0 +1,68 @@
+use songbird::input::{core::io::MediaSource, Input, RawAdapter};
+
+use std::{io::{self, Read, Seek, SeekFrom}, sync::{mpsc::{Receiver, TryRecvError}, Mutex}};
+
+const SAMPLE_RATE: u32 = 48000;
+const CHANNELS: u32 = 2;
+// one interleaved stereo f32 frame
+const FRAME_BYTES: usize = 8;
+
+// feeds the audio tap of the pipeline to songbird. gaps are filled with silence so
+// the call doesn't stop while the pipeline is switching items or seeking
+pub(crate) struct PipelineAudio {
+    // songbird wants sources to be Sync
+    receiver: Mutex<Receiver<Vec<u8>>>,
+    pending: Vec<u8>,
+    offset: usize,
+}
+
+impl PipelineAudio {
+    pub fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        PipelineAudio { receiver: Mutex::new(receiver), pending: Vec::new(), offset: 0 }
+    }
+
+    pub fn into_input(self) -> Input {
+        RawAdapter::new(self, SAMPLE_RATE, CHANNELS).into()
+    }
+}
+
+impl Read for PipelineAudio {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset >= self.pending.len() {
+            let receiver = self.receiver.get_mut().map_err(|_| io::Error::other("audio receiver is poisoned"))?;
+            match receiver.try_recv() {
+                Ok(chunk) => {
+                    self.pending = chunk;
+                    self.offset = 0;
+                }
+                Err(TryRecvError::Empty) => {
+                    let len = (buf.len() / FRAME_BYTES * FRAME_BYTES).max(buf.len().min(FRAME_BYTES));
+                    buf[..len].fill(0);
+                    return Ok(len)
+                }
+                // tap was detached
+                Err(TryRecvError::Disconnected) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.pending.len() - self.offset);
+        buf[..len].copy_from_slice(&self.pending[self.offset..self.offset + len]);
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+impl Seek for PipelineAudio {
+    fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "pipeline audio can't be seeked"))
+    }
+}
+
+impl MediaSource for PipelineAudio {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
@@ -0,0 +1,95 @@
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{error, info, warn};
+use url::Url;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{playback_events::{FinishReason, PlaybackEvent}, source_resolver::SourceResolver};
+
+#[derive(Clone, Debug)]
+pub(crate) struct WebhookConfig {
+    // used for items that were queued without their own webhook
+    default_url: Option<Url>,
+    timeout: Duration,
+}
+
+impl WebhookConfig {
+    pub fn from_env() -> Self {
+        let default_url = std::env::var("ON_COMPLETE_WEBHOOK_URL").ok()
+            .filter(|v| !v.is_empty())
+            .and_then(|v| match Url::parse(&v) {
+                Ok(u) => Some(u),
+                Err(e) => {
+                    warn!("ignoring invalid ON_COMPLETE_WEBHOOK_URL {}: {}", v, e);
+                    None
+                }
+            });
+        let timeout_secs = std::env::var("WEBHOOK_TIMEOUT_SECS").ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(10);
+        WebhookConfig {
+            default_url,
+            timeout: Duration::from_secs(timeout_secs),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ItemFinishedPayload {
+    event: &'static str,
+    item_id: String,
+    name: String,
+    uri: String,
+    live: bool,
+    reason: FinishReason,
+    detail: Option<String>,
+    position_secs: Option<u64>,
+    finished_at: u64,
+}
+
+// posts a json payload for every finished item that has a webhook (or when a default is configured)
+pub(crate) async fn run_webhooks(config: WebhookConfig, mut receiver: broadcast::Receiver<PlaybackEvent>) {
+    let client = reqwest::Client::builder()
+        .timeout(config.timeout)
+        .build()
+        .unwrap_or_default();
+    loop {
+        let (item, reason, detail, position) = match receiver.recv().await {
+            Ok(PlaybackEvent::ItemFinished { item, reason, detail, position }) => (item, reason, detail, position),
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("webhook sender fell behind, {} events were not delivered", skipped);
+                continue
+            }
+            Err(RecvError::Closed) => break,
+        };
+        let webhook_url = match item.webhook().or(config.default_url.clone()) {
+            Some(u) => u,
+            None => continue,
+        };
+        let payload = ItemFinishedPayload {
+            event: "item_finished",
+            item_id: item.id().to_string(),
+            name: item.name(),
+            // the receiver doesn't need the credentials the item was played with
+            uri: SourceResolver::without_credentials(&item.uri()).to_string(),
+            live: item.is_live(),
+            reason,
+            detail,
+            position_secs: position.map(|p| p.as_secs()),
+            finished_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        };
+        // a slow endpoint shouldn't delay the next notification
+        let client = client.clone();
+        // webhook urls often carry a token so only the host ends up in the logs
+        let target = webhook_url.host_str().unwrap_or("<unknown>").to_string();
+        tokio::spawn(async move {
+            match client.post(webhook_url).json(&payload).send().await {
+                Ok(resp) if resp.status().is_success() => info!("notified {} that {} finished", target, payload.name),
+                Ok(resp) => warn!("webhook on {} returned {} for {}", target, resp.status(), payload.name),
+                Err(e) => error!("unable to call webhook on {}: {}", target, e.without_url()),
+            }
+        });
+    }
+}